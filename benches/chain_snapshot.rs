@@ -0,0 +1,37 @@
+#![feature(test)]
+
+extern crate test;
+
+use blockchain::block::Block;
+use blockchain::testkit::{get_genesis_block, get_signed_transaction};
+use test::Bencher;
+
+fn get_blockchain(length: usize, tx_count: usize) -> Vec<Block> {
+    let transaction = get_signed_transaction();
+    (1..length).fold(vec![get_genesis_block()], |mut blockchain, index| {
+        let previous = blockchain.last().unwrap();
+        let block = Block::new(
+            index,
+            format!("{:064x}", index),
+            previous.hash.clone(),
+            previous.timestamp + 1,
+            vec![transaction.clone(); tx_count],
+            0,
+            0,
+        );
+        blockchain.push(block);
+        blockchain
+    })
+}
+
+#[bench]
+fn bench_snapshot_blockchain(b: &mut Bencher) {
+    let blockchain = get_blockchain(1000, 100);
+    b.iter(|| blockchain.to_vec());
+}
+
+#[bench]
+fn bench_serialize_blockchain(b: &mut Bencher) {
+    let blockchain = get_blockchain(100, 100);
+    b.iter(|| serde_json::to_string(&blockchain).unwrap());
+}
@@ -0,0 +1,66 @@
+#ifndef BLOCKCHAIN_H
+#define BLOCKCHAIN_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * Get code of the last error on this thread, 0 if the last call succeeded.
+ */
+uintptr_t blockchain_last_error_code(void);
+
+/*
+ * Release a string returned by this library.
+ *
+ * # Safety
+ * `s` must be null or returned by this library and not released yet.
+ */
+void blockchain_string_free(char *s);
+
+/*
+ * Create a wallet key of `key_type`, `secp256k1` or `ed25519`, and return its hex private key.
+ *
+ * # Safety
+ * `key_type` must be null or a NUL terminated string.
+ */
+char *blockchain_wallet_create(const char *key_type);
+
+/*
+ * Derive the address of a hex or WIF private key.
+ *
+ * # Safety
+ * `private_key` must be null or a NUL terminated string.
+ */
+char *blockchain_address_derive(const char *private_key);
+
+/*
+ * Build a transaction sending `amount` to `receiver_address` from `unspent_tx_outs_json`,
+ * a JSON array of unspent tx outs, sign it with `private_key` and return it as JSON.
+ *
+ * # Safety
+ * Every string must be null or NUL terminated.
+ */
+char *blockchain_transaction_create(const char *receiver_address,
+                                    uintptr_t amount,
+                                    const char *private_key,
+                                    const char *unspent_tx_outs_json,
+                                    uintptr_t dust_limit);
+
+/*
+ * Verify JSON `block_json` follows `previous_block_json` and its transactions spend `unspent_tx_outs_json`,
+ * a JSON array of the unspent tx outs after the previous block, returning 1 if valid, 0 if not and -1 on error.
+ *
+ * Only the previous block is known, so transactions or names repeating ones earlier in the chain are not checked.
+ *
+ * # Safety
+ * Every string must be null or NUL terminated.
+ */
+int blockchain_block_verify(const char *block_json,
+                            const char *previous_block_json,
+                            const char *unspent_tx_outs_json);
+
+#endif /* BLOCKCHAIN_H */
@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+use crate::chain_params::{get_chain_params, Network};
+
+/// Version of blocks and transactions before any rule activated.
+pub const INITIAL_VERSION: u32 = 1;
+
+/// Consensus rule rolled out after the initial protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    Locktime,
+    Multisig,
+    UtxoCommitment,
+    CoinbaseHeight,
+    Sighash,
+    TxRoot,
+}
+
+/// Heights a rule is enforced from on each network and block version signaling it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Activation {
+    /// Activated rule
+    pub rule: Rule,
+
+    /// First block height enforcing the rule on the main network, none while it is not scheduled
+    pub height: Option<usize>,
+
+    /// First block height enforcing the rule on regtest, none while it is not scheduled
+    pub regtest_height: Option<usize>,
+
+    /// Least version of blocks from the activation height
+    pub version: u32,
+}
+
+impl Activation {
+    /// Get first block height enforcing the rule on network, none while it is not scheduled
+    pub fn get_height(&self, network: Network) -> Option<usize> {
+        match network {
+            Network::Main => self.height,
+            Network::Regtest => self.regtest_height,
+        }
+    }
+}
+
+/// Activation table, rules without height keep existing chains valid until they are scheduled. Regtest enforces
+/// every rule from the first block after genesis.
+pub const ACTIVATIONS: [Activation; 6] = [
+    Activation { rule: Rule::Locktime, height: None, regtest_height: Some(1), version: 2 },
+    Activation { rule: Rule::Multisig, height: None, regtest_height: Some(1), version: 3 },
+    Activation { rule: Rule::UtxoCommitment, height: None, regtest_height: Some(1), version: 4 },
+    Activation { rule: Rule::CoinbaseHeight, height: None, regtest_height: Some(1), version: 5 },
+    Activation { rule: Rule::Sighash, height: None, regtest_height: Some(1), version: 6 },
+    Activation { rule: Rule::TxRoot, height: None, regtest_height: Some(1), version: 7 },
+];
+
+/// Get flag a rule is enforced at block height on this network.
+pub fn get_is_active(rule: Rule, height: usize) -> bool {
+    get_is_active_in(&ACTIVATIONS, get_chain_params().network, rule, height)
+}
+
+/// Get version of a block generated at height on this network.
+pub fn get_block_version(height: usize) -> u32 {
+    get_block_version_in(&ACTIVATIONS, get_chain_params().network, height)
+}
+
+fn get_is_active_in(activations: &[Activation], network: Network, rule: Rule, height: usize) -> bool {
+    activations.iter()
+        .any(|activation| activation.rule == rule && activation.get_height(network).map_or(false, |active| active <= height))
+}
+
+fn get_block_version_in(activations: &[Activation], network: Network, height: usize) -> u32 {
+    activations.iter()
+        .filter(|activation| activation.get_height(network).map_or(false, |active| active <= height))
+        .map(|activation| activation.version)
+        .fold(INITIAL_VERSION, u32::max)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain_params::{with_chain_params, REGTEST};
+    use super::*;
+
+    #[test]
+    fn test_activations() {
+        assert!(!get_is_active(Rule::Locktime, usize::MAX));
+        assert_eq!(get_block_version(usize::MAX), INITIAL_VERSION);
+
+        let activations = [
+            Activation { rule: Rule::Locktime, height: Some(10), regtest_height: Some(1), version: 2 },
+            Activation { rule: Rule::Multisig, height: Some(20), regtest_height: Some(1), version: 3 },
+            Activation { rule: Rule::UtxoCommitment, height: None, regtest_height: None, version: 4 },
+        ];
+        assert!(!get_is_active_in(&activations, Network::Main, Rule::Locktime, 9));
+        assert!(get_is_active_in(&activations, Network::Main, Rule::Locktime, 10));
+        assert!(!get_is_active_in(&activations, Network::Main, Rule::Multisig, 19));
+        assert!(!get_is_active_in(&activations, Network::Main, Rule::UtxoCommitment, usize::MAX));
+        assert!(get_is_active_in(&activations, Network::Regtest, Rule::Multisig, 1));
+        assert!(!get_is_active_in(&activations, Network::Regtest, Rule::UtxoCommitment, usize::MAX));
+
+        assert_eq!(get_block_version_in(&activations, Network::Main, 0), INITIAL_VERSION);
+        assert_eq!(get_block_version_in(&activations, Network::Main, 15), 2);
+        assert_eq!(get_block_version_in(&activations, Network::Main, 20), 3);
+        assert_eq!(get_block_version_in(&activations, Network::Regtest, 1), 3);
+    }
+
+    #[test]
+    fn test_regtest_activations() {
+        with_chain_params(REGTEST, || {
+            assert_eq!(get_block_version(0), INITIAL_VERSION);
+            assert!(!get_is_active(Rule::CoinbaseHeight, 0));
+            assert!(ACTIVATIONS.iter().all(|activation| get_is_active(activation.rule, 1)));
+            assert_eq!(get_block_version(1), 7);
+        });
+        assert!(!get_is_active(Rule::CoinbaseHeight, 1));
+    }
+}
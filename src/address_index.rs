@@ -0,0 +1,449 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use serde::Serialize;
+
+use crate::{Block, UnspentTxOut};
+use crate::dto::serialize_model;
+use crate::price_feed::FiatAmount;
+use crate::transaction::TxOut;
+
+const DISTRIBUTION_BUCKETS: usize = 8;
+
+/// Balance of an address.
+#[derive(Debug, Serialize)]
+pub struct AddressBalance {
+    /// Address of owner
+    pub address: String,
+
+    /// Sum of unspent outputs
+    pub balance: usize,
+}
+
+/// Histogram bucket of balances in `[min, max)`.
+#[derive(Debug, Serialize)]
+pub struct DistributionBucket {
+    /// Inclusive lower bound
+    pub min: usize,
+
+    /// Exclusive upper bound, none for the last bucket
+    pub max: Option<usize>,
+
+    /// Count of addresses in bucket
+    pub count: usize,
+
+    /// Sum of balances in bucket
+    pub balance: usize,
+}
+
+/// Unspent outputs, balance and transaction count of an address.
+#[derive(Debug, Serialize)]
+pub struct AddressSummary {
+    pub address: String,
+    pub balance: usize,
+
+    /// Count of confirmed transactions spending from or paying to the address
+    pub tx_count: usize,
+
+    /// Balance of every asset held
+    pub assets: BTreeMap<String, usize>,
+    #[serde(serialize_with = "serialize_model")]
+    pub unspent_tx_outs: Vec<UnspentTxOut>,
+
+    /// Local label of the address, never on chain
+    pub label: Option<String>,
+
+    /// Fiat equivalent of balance, when a price feed is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatAmount>,
+}
+
+/// Where an output of the chain was spent.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Spend {
+    /// Id of the spending transaction
+    pub tx_id: String,
+
+    /// Index of the block confirming it
+    pub block_index: usize,
+    pub block_hash: String,
+}
+
+/// Status of an outpoint on the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutpointStatus {
+    Unspent,
+    Spent,
+
+    /// No output of the chain has the outpoint
+    Unknown,
+}
+
+/// Outpoint looked up with its unspent output or where it was spent.
+#[derive(Debug, Serialize)]
+pub struct OutpointLookup {
+    pub tx_out_id: String,
+    pub tx_out_index: usize,
+    pub status: OutpointStatus,
+
+    /// Output with its address and amount, while unspent
+    #[serde(serialize_with = "serialize_model", skip_serializing_if = "Option::is_none")]
+    pub unspent_tx_out: Option<UnspentTxOut>,
+
+    /// Spend of the output, once spent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spend: Option<Spend>,
+}
+
+/// Secondary index from address to unspent outputs, balance and transaction count, maintained from the chain.
+///
+/// Balances count coins only, assets are summed separately per address.
+#[derive(Debug, Default)]
+pub struct AddressIndex {
+    outputs: HashMap<(String, usize), UnspentTxOut>,
+    address_outputs: HashMap<String, BTreeSet<(String, usize)>>,
+    balances: HashMap<String, usize>,
+    asset_balances: HashMap<String, BTreeMap<String, usize>>,
+    tx_counts: HashMap<String, usize>,
+    spends: HashMap<(String, usize), Spend>,
+}
+
+impl AddressIndex {
+    /// Returns an index built from blockchain
+    pub fn new(blockchain: &[Block]) -> AddressIndex {
+        let mut index = AddressIndex::default();
+        index.rebuild(blockchain);
+        index
+    }
+
+    /// Rebuild whole index, used when the chain is replaced.
+    pub fn rebuild(&mut self, blockchain: &[Block]) {
+        self.outputs.clear();
+        self.address_outputs.clear();
+        self.balances.clear();
+        self.asset_balances.clear();
+        self.tx_counts.clear();
+        self.spends.clear();
+        for block in blockchain {
+            self.apply_block(block);
+        }
+    }
+
+    /// Rebuild whole index from the unspent tx outs of a utxo snapshot and the blocks after it, used when the chain is
+    /// replaced by one fast-synced from headers without data. Transactions and spends before the snapshot are not
+    /// counted.
+    pub fn rebuild_from_snapshot(&mut self, unspent_tx_outs: &[UnspentTxOut], blocks: &[Block]) {
+        self.rebuild(&[]);
+        for unspent_tx_out in unspent_tx_outs {
+            let tx_out = TxOut {
+                script: unspent_tx_out.script.clone(),
+                asset: unspent_tx_out.asset.clone(),
+                ..TxOut::new(unspent_tx_out.address.clone(), unspent_tx_out.amount)
+            };
+            self.insert(unspent_tx_out.tx_out_id.clone(), unspent_tx_out.tx_out_index, &tx_out);
+        }
+        for block in blocks {
+            self.apply_block(block);
+        }
+    }
+
+    /// Apply spent and created outputs of a block appended to the chain.
+    pub fn apply_block(&mut self, block: &Block) {
+        let mut tx_addresses: Vec<HashSet<String>> = vec![HashSet::new(); block.data.len()];
+        for (tx, addresses) in block.data.iter().zip(tx_addresses.iter_mut()) {
+            for tx_in in &tx.tx_ins {
+                if let Some(address) = self.remove(&tx_in.tx_out_id, tx_in.tx_out_index) {
+                    addresses.insert(address);
+                    self.spends.insert((tx_in.tx_out_id.clone(), tx_in.tx_out_index), Spend {
+                        tx_id: tx.id.clone(),
+                        block_index: block.index,
+                        block_hash: block.hash.clone(),
+                    });
+                }
+            }
+        }
+        for (tx, addresses) in block.data.iter().zip(tx_addresses.iter_mut()) {
+            for (index, tx_out) in tx.tx_outs.iter().enumerate().filter(|(_, tx_out)| !tx_out.get_is_unspendable()) {
+                self.insert(tx.id.clone(), index, tx_out);
+                addresses.insert(tx_out.address.clone());
+            }
+        }
+        for address in tx_addresses.into_iter().flatten() {
+            *self.tx_counts.entry(address).or_insert(0) += 1;
+        }
+    }
+
+    /// Get balance of address.
+    pub fn get_balance(&self, address: &str) -> usize {
+        *self.balances.get(address).unwrap_or(&0)
+    }
+
+    /// Get balance of every asset held by address.
+    pub fn get_asset_balances(&self, address: &str) -> BTreeMap<String, usize> {
+        self.asset_balances.get(address).cloned().unwrap_or_default()
+    }
+
+    /// Get count of confirmed transactions of address.
+    pub fn get_tx_count(&self, address: &str) -> usize {
+        *self.tx_counts.get(address).unwrap_or(&0)
+    }
+
+    /// Get unspent outputs of address, ordered by outpoint.
+    pub fn get_unspent_tx_outs(&self, address: &str) -> Vec<UnspentTxOut> {
+        self.address_outputs
+            .get(address)
+            .map(|outpoints| {
+                outpoints
+                    .iter()
+                    .map(|outpoint| self.outputs[outpoint].clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get status of outpoint, with its output while unspent or its spend once spent.
+    pub fn lookup(&self, tx_out_id: &str, tx_out_index: usize) -> OutpointLookup {
+        let outpoint = (tx_out_id.to_string(), tx_out_index);
+        let unspent_tx_out = self.outputs.get(&outpoint).cloned();
+        let spend = self.spends.get(&outpoint).cloned();
+        let status = match (&unspent_tx_out, &spend) {
+            (Some(_), _) => OutpointStatus::Unspent,
+            (None, Some(_)) => OutpointStatus::Spent,
+            (None, None) => OutpointStatus::Unknown,
+        };
+        OutpointLookup { tx_out_id: outpoint.0, tx_out_index, status, unspent_tx_out, spend }
+    }
+
+    /// Get summary of address.
+    pub fn get_summary(&self, address: &str) -> AddressSummary {
+        AddressSummary {
+            address: address.to_string(),
+            balance: self.get_balance(address),
+            tx_count: self.get_tx_count(address),
+            assets: self.get_asset_balances(address),
+            unspent_tx_outs: self.get_unspent_tx_outs(address),
+            label: None,
+            fiat: None,
+        }
+    }
+
+    /// Get count of addresses holding a balance.
+    pub fn get_address_count(&self) -> usize {
+        self.balances.len()
+    }
+
+    /// Get sum of all balances.
+    pub fn get_total_balance(&self) -> usize {
+        self.balances.values().sum()
+    }
+
+    /// Get top addresses by balance, ties ordered by address.
+    pub fn get_richest(&self, limit: usize) -> Vec<AddressBalance> {
+        let mut balances: Vec<(&String, &usize)> = self.balances.iter().collect();
+        balances.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        balances
+            .into_iter()
+            .take(limit)
+            .map(|(address, balance)| AddressBalance { address: address.clone(), balance: *balance })
+            .collect()
+    }
+
+    /// Get histogram of balances in power of ten buckets.
+    pub fn get_distribution(&self) -> Vec<DistributionBucket> {
+        let mut buckets: Vec<DistributionBucket> = (0..DISTRIBUTION_BUCKETS)
+            .map(|i| DistributionBucket {
+                min: if i == 0 { 0 } else { 10_usize.pow(i as u32) },
+                max: if i + 1 == DISTRIBUTION_BUCKETS { None } else { Some(10_usize.pow(i as u32 + 1)) },
+                count: 0,
+                balance: 0,
+            })
+            .collect();
+
+        for balance in self.balances.values() {
+            let bucket = buckets
+                .iter_mut()
+                .find(|bucket| bucket.max.map_or(true, |max| *balance < max))
+                .unwrap();
+            bucket.count += 1;
+            bucket.balance += balance;
+        }
+        buckets
+    }
+
+    fn insert(&mut self, tx_out_id: String, tx_out_index: usize, tx_out: &TxOut) {
+        let address = tx_out.address.clone();
+        match &tx_out.asset {
+            Some(asset) => *self.asset_balances.entry(address.clone()).or_default().entry(asset.clone()).or_insert(0) += tx_out.amount,
+            None => *self.balances.entry(address.clone()).or_insert(0) += tx_out.amount,
+        }
+        self.address_outputs.entry(address.clone()).or_default().insert((tx_out_id.clone(), tx_out_index));
+        let unspent_tx_out = UnspentTxOut {
+            script: tx_out.script.clone(),
+            asset: tx_out.asset.clone(),
+            ..UnspentTxOut::new(tx_out_id.clone(), tx_out_index, address, tx_out.amount)
+        };
+        self.outputs.insert((tx_out_id, tx_out_index), unspent_tx_out);
+    }
+
+    /// Remove a spent output, returning its address
+    fn remove(&mut self, tx_out_id: &str, tx_out_index: usize) -> Option<String> {
+        let outpoint = (tx_out_id.to_string(), tx_out_index);
+        let UnspentTxOut { address, amount, asset, .. } = self.outputs.remove(&outpoint)?;
+        match asset {
+            Some(asset) => {
+                let assets = self.asset_balances.entry(address.clone()).or_default();
+                let balance = assets.entry(asset.clone()).or_insert(0);
+                *balance -= amount;
+                if *balance == 0 {
+                    assets.remove(&asset);
+                }
+                if assets.is_empty() {
+                    self.asset_balances.remove(&address);
+                }
+            }
+            None => {
+                let balance = self.balances.entry(address.clone()).or_insert(0);
+                *balance -= amount;
+                if *balance == 0 {
+                    self.balances.remove(&address);
+                }
+            }
+        }
+        if let Some(outpoints) = self.address_outputs.get_mut(&address) {
+            outpoints.remove(&outpoint);
+            if outpoints.is_empty() {
+                self.address_outputs.remove(&address);
+            }
+        }
+        Some(address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::transaction::{Transaction, TxIn, TxOut};
+    use super::*;
+
+    const SENDER: &'static str = "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b";
+    const RECEIVER: &'static str = "03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40";
+
+    fn get_block(index: usize, data: Vec<Transaction>) -> Block {
+        Block::new(
+            index,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
+            "".to_string(),
+            1465154705,
+            data,
+            0,
+            0,
+        )
+    }
+
+    fn get_coinbase(tx_outs: &Vec<TxOut>) -> Transaction {
+        Transaction::generate(&vec![TxIn::new("".to_string(), 0, "".to_string())], tx_outs)
+    }
+
+    #[test]
+    fn test_address_index_new() {
+        let coinbase = get_coinbase(&vec![
+            TxOut::new(SENDER.to_string(), 50),
+            TxOut::new(SENDER.to_string(), 50),
+            TxOut::new(RECEIVER.to_string(), 5),
+        ]);
+        let index = AddressIndex::new(&vec![get_block(0, vec![coinbase])]);
+        assert_eq!(index.get_address_count(), 2);
+        assert_eq!(index.get_total_balance(), 105);
+        assert_eq!(index.get_balance(SENDER), 100);
+        assert_eq!(index.get_tx_count(SENDER), 1);
+        assert_eq!(index.get_unspent_tx_outs(SENDER).len(), 2);
+
+        let richest = index.get_richest(1);
+        assert_eq!(richest.len(), 1);
+        assert_eq!(richest.get(0).unwrap().address, SENDER);
+
+        let distribution = index.get_distribution();
+        assert_eq!(distribution.len(), DISTRIBUTION_BUCKETS);
+        assert_eq!(distribution.get(0).unwrap().count, 1);
+        assert_eq!(distribution.get(2).unwrap().count, 1);
+        assert_eq!(distribution.get(2).unwrap().balance, 100);
+    }
+
+    #[test]
+    fn test_address_index_apply_block() {
+        let coinbase = get_coinbase(&vec![TxOut::new(SENDER.to_string(), 50)]);
+        let mut blockchain = vec![get_block(0, vec![coinbase.clone()])];
+        let mut index = AddressIndex::new(&blockchain);
+
+        let tx_ins = vec![TxIn::new(coinbase.id.clone(), 0, "".to_string())];
+        let tx_outs = vec![
+            TxOut::new(RECEIVER.to_string(), 30),
+            TxOut::new(SENDER.to_string(), 20),
+        ];
+        let transaction = Transaction::generate(&tx_ins, &tx_outs);
+        blockchain.push(get_block(1, vec![transaction.clone()]));
+        index.apply_block(&blockchain[1]);
+        assert_eq!(index.get_total_balance(), 50);
+        assert_eq!(index.get_balance(RECEIVER), 30);
+        assert_eq!(index.get_balance(SENDER), 20);
+        assert_eq!(index.get_tx_count(SENDER), 2);
+        assert_eq!(index.get_tx_count(RECEIVER), 1);
+
+        let summary = index.get_summary(SENDER);
+        assert_eq!(summary.unspent_tx_outs.len(), 1);
+        assert_eq!(summary.unspent_tx_outs[0].tx_out_id, transaction.id);
+        assert_eq!(summary.unspent_tx_outs[0].tx_out_index, 1);
+        assert_eq!(summary.unspent_tx_outs[0].amount, 20);
+
+        let gold = format!("{}:GOLD", SENDER);
+        let issuance = Transaction::generate(&vec![TxIn::new(transaction.id.clone(), 1, "".to_string())], &vec![
+            TxOut::new(SENDER.to_string(), 20),
+            TxOut::with_asset(RECEIVER.to_string(), 7, gold.clone()),
+        ]);
+        blockchain.push(get_block(2, vec![issuance]));
+        index.apply_block(&blockchain[2]);
+        assert_eq!(index.get_balance(RECEIVER), 30);
+        assert_eq!(index.get_asset_balances(RECEIVER).get(&gold), Some(&7));
+        assert!(index.get_summary(RECEIVER).unspent_tx_outs.iter().any(|u_tx_o| u_tx_o.asset == Some(gold.clone())));
+
+        let rebuilt = AddressIndex::new(&blockchain);
+        assert_eq!(rebuilt.get_tx_count(SENDER), 3);
+        assert_eq!(rebuilt.get_unspent_tx_outs(RECEIVER).len(), 2);
+        assert!(rebuilt.get_summary("unknown").unspent_tx_outs.is_empty());
+    }
+
+    #[test]
+    fn test_address_index_rebuild_from_snapshot() {
+        let coinbase = get_coinbase(&vec![TxOut::new(SENDER.to_string(), 50)]);
+        let unspent_tx_outs = vec![UnspentTxOut::new(coinbase.id.clone(), 0, SENDER.to_string(), 50)];
+        let transaction = Transaction::generate(&vec![TxIn::new(coinbase.id.clone(), 0, "".to_string())], &vec![
+            TxOut::new(RECEIVER.to_string(), 30),
+            TxOut::new(SENDER.to_string(), 20),
+        ]);
+
+        let mut index = AddressIndex::default();
+        index.rebuild_from_snapshot(&unspent_tx_outs, &vec![get_block(2, vec![transaction])]);
+        assert_eq!(index.get_total_balance(), 50);
+        assert_eq!(index.get_balance(RECEIVER), 30);
+        assert_eq!(index.get_balance(SENDER), 20);
+        assert_eq!(index.lookup(&coinbase.id, 0).status, OutpointStatus::Spent);
+    }
+
+    #[test]
+    fn test_address_index_lookup() {
+        let coinbase = get_coinbase(&vec![TxOut::new(SENDER.to_string(), 50), TxOut::new(SENDER.to_string(), 10)]);
+        let transaction = Transaction::generate(&vec![TxIn::new(coinbase.id.clone(), 0, "".to_string())], &vec![TxOut::new(RECEIVER.to_string(), 50)]);
+        let blockchain = vec![get_block(0, vec![coinbase.clone()]), get_block(1, vec![transaction.clone()])];
+        let index = AddressIndex::new(&blockchain);
+
+        let spent = index.lookup(&coinbase.id, 0);
+        assert_eq!(spent.status, OutpointStatus::Spent);
+        assert!(spent.unspent_tx_out.is_none());
+        assert_eq!(spent.spend, Some(Spend { tx_id: transaction.id.clone(), block_index: 1, block_hash: blockchain[1].hash.clone() }));
+
+        let unspent = index.lookup(&coinbase.id, 1);
+        assert_eq!(unspent.status, OutpointStatus::Unspent);
+        assert_eq!(unspent.unspent_tx_out.map(|u_tx_o| (u_tx_o.address, u_tx_o.amount)), Some((SENDER.to_string(), 10)));
+
+        assert_eq!(index.lookup(&coinbase.id, 2).status, OutpointStatus::Unknown);
+        assert_eq!(index.lookup("", 0).status, OutpointStatus::Unknown);
+    }
+}
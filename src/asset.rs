@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use crate::transaction::{find_unspent_tx_out, Transaction, UnspentTxOut};
+
+/// Separator of issuer address and symbol in an asset id.
+pub const ASSET_SEPARATOR: char = ':';
+
+/// Longest symbol of an asset.
+pub const MAX_SYMBOL_LENGTH: usize = 12;
+
+/// Get id of the asset a key issues under symbol.
+pub fn get_asset_id(issuer: &str, symbol: &str) -> String {
+    format!("{}{}{}", issuer, ASSET_SEPARATOR, symbol)
+}
+
+/// Get address of the key allowed to create supply of an asset.
+pub fn get_asset_issuer(asset_id: &str) -> Option<&str> {
+    asset_id.split_once(ASSET_SEPARATOR).map(|(issuer, _)| issuer)
+}
+
+/// Get asset id is an issuer address with an uppercase alphanumeric symbol.
+pub fn get_is_valid_asset_id(asset_id: &str) -> bool {
+    match asset_id.split_once(ASSET_SEPARATOR) {
+        Some((issuer, symbol)) => {
+            issuer.len() == 66 &&
+                (1..=MAX_SYMBOL_LENGTH).contains(&symbol.len()) &&
+                symbol.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Get amounts of a transaction per asset, coins keyed by none, none if a total overflows.
+fn get_amounts<'a, I>(mut amounts: I) -> Option<BTreeMap<Option<&'a str>, usize>>
+where
+    I: Iterator<Item = (Option<&'a str>, usize)>,
+{
+    amounts.try_fold(BTreeMap::new(), |mut totals, (asset, amount)| {
+        let total = totals.entry(asset).or_insert(0usize);
+        *total = total.checked_add(amount)?;
+        Some(totals)
+    })
+}
+
+/// Get every asset and the coins of a transaction are conserved.
+///
+/// Outputs of an asset may exceed its inputs only in an issuance, a transaction spending
+/// an output of the issuer so it is signed by the issuer key.
+pub fn get_is_conserved(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> bool {
+    let referenced_unspent_tx_outs: Vec<&UnspentTxOut> = transaction.tx_ins
+        .iter()
+        .filter_map(|tx_in| find_unspent_tx_out(&tx_in.tx_out_id, tx_in.tx_out_index, unspent_tx_outs))
+        .collect();
+    let inputs = get_amounts(referenced_unspent_tx_outs.iter().map(|u_tx_o| (u_tx_o.asset.as_deref(), u_tx_o.amount)));
+    let outputs = get_amounts(transaction.tx_outs.iter().map(|tx_out| (tx_out.asset.as_deref(), tx_out.amount)));
+    let (inputs, outputs) = match (inputs, outputs) {
+        (Some(inputs), Some(outputs)) => (inputs, outputs),
+        _ => return false,
+    };
+
+    let get_is_signed_by_issuer = |asset: &str| {
+        get_asset_issuer(asset).map_or(false, |issuer| {
+            referenced_unspent_tx_outs.iter().any(|u_tx_o| u_tx_o.address.eq(issuer) && u_tx_o.script.is_none())
+        })
+    };
+
+    inputs.keys().chain(outputs.keys()).all(|asset| {
+        let input = inputs.get(asset).copied().unwrap_or(0);
+        let output = outputs.get(asset).copied().unwrap_or(0);
+        match asset {
+            None => input == output,
+            Some(asset) => input == output || input < output && get_is_signed_by_issuer(asset),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{ALICE, BOB, FUNDING_TX_OUT_ID, RECEIVER_ADDRESS};
+    use crate::transaction::{TxIn, TxOut};
+    use super::*;
+
+    /// Get balance of every asset held by address, coins left out, as the address index serves it.
+    fn get_asset_balances(address: &str, unspent_tx_outs: &Vec<UnspentTxOut>) -> BTreeMap<String, usize> {
+        unspent_tx_outs
+            .iter()
+            .filter(|u_tx_o| u_tx_o.address.eq(address))
+            .filter_map(|u_tx_o| u_tx_o.asset.as_ref().map(|asset| (asset, u_tx_o.amount)))
+            .fold(BTreeMap::new(), |mut balances, (asset, amount)| {
+                *balances.entry(asset.clone()).or_insert(0) += amount;
+                balances
+            })
+    }
+
+    #[test]
+    fn test_get_is_valid_asset_id() {
+        let asset = get_asset_id(ALICE.address, "GOLD");
+        assert!(get_is_valid_asset_id(&asset));
+        assert_eq!(get_asset_issuer(&asset), Some(ALICE.address));
+        assert!(!get_is_valid_asset_id(&get_asset_id(ALICE.address, "gold")));
+        assert!(!get_is_valid_asset_id(&get_asset_id(ALICE.address, "")));
+        assert!(!get_is_valid_asset_id(&get_asset_id("issuer", "GOLD")));
+        assert!(!get_is_valid_asset_id(ALICE.address));
+    }
+
+    #[test]
+    fn test_get_is_conserved() {
+        let gold = get_asset_id(ALICE.address, "GOLD");
+        let unspent_tx_outs = vec![
+            UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, ALICE.address.to_string(), 50),
+            UnspentTxOut {
+                asset: Some(gold.clone()),
+                ..UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 1, BOB.address.to_string(), 10)
+            },
+        ];
+        let coins = TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, "".to_string());
+        let tokens = TxIn::new(FUNDING_TX_OUT_ID.to_string(), 1, "".to_string());
+
+        let issuance = Transaction::generate(&vec![coins.clone()], &vec![
+            TxOut::new(ALICE.address.to_string(), 50),
+            TxOut::with_asset(ALICE.address.to_string(), 100, gold.clone()),
+        ]);
+        assert!(get_is_conserved(&issuance, &unspent_tx_outs));
+
+        let foreign_issuance = Transaction::generate(&vec![coins.clone()], &vec![
+            TxOut::new(ALICE.address.to_string(), 50),
+            TxOut::with_asset(ALICE.address.to_string(), 100, get_asset_id(BOB.address, "GOLD")),
+        ]);
+        assert!(!get_is_conserved(&foreign_issuance, &unspent_tx_outs));
+
+        let transfer = Transaction::generate(&vec![tokens.clone()], &vec![
+            TxOut::with_asset(RECEIVER_ADDRESS.to_string(), 4, gold.clone()),
+            TxOut::with_asset(BOB.address.to_string(), 6, gold.clone()),
+        ]);
+        assert!(get_is_conserved(&transfer, &unspent_tx_outs));
+
+        let inflation = Transaction::generate(&vec![tokens.clone()], &vec![TxOut::with_asset(RECEIVER_ADDRESS.to_string(), 11, gold.clone())]);
+        assert!(!get_is_conserved(&inflation, &unspent_tx_outs));
+
+        let conversion = Transaction::generate(&vec![tokens], &vec![TxOut::new(RECEIVER_ADDRESS.to_string(), 10)]);
+        assert!(!get_is_conserved(&conversion, &unspent_tx_outs));
+
+        let balances = get_asset_balances(BOB.address, &unspent_tx_outs);
+        assert_eq!(balances.get(&gold), Some(&10));
+        assert!(get_asset_balances(ALICE.address, &unspent_tx_outs).is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+use crate::{Block, Transaction, UnspentTxOut};
+use crate::transaction::{find_unspent_tx_out, get_block_reward, update_unspent_tx_outs};
+
+/// Coins of a chain against its emission schedule, recomputed from the blocks alone.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SupplyAudit {
+    /// Index of the latest block
+    pub height: usize,
+
+    /// Coins the emission schedule issues up to height
+    pub expected_issuance: usize,
+
+    /// Coins coinbase transactions paid
+    pub actual_issuance: usize,
+
+    /// Coins leaving the supply, fees and outputs which can never be spent
+    pub burned: usize,
+
+    /// Expected issuance minus burned coins
+    pub expected_supply: usize,
+
+    /// Coins of unspent tx outs
+    pub actual_supply: usize,
+
+    /// Actual minus expected supply, zero for a sound chain
+    pub discrepancy: i64,
+
+    /// Index of the first block creating or losing coins against the schedule, none for a sound chain
+    pub offending_height: Option<usize>,
+}
+
+/// Coins of outputs of transaction, spendable and unspendable
+fn get_coin_outputs(transaction: &Transaction) -> (usize, usize) {
+    transaction.tx_outs
+        .iter()
+        .filter(|tx_out| tx_out.asset.is_none())
+        .fold((0, 0), |(spendable, unspendable), tx_out| match tx_out.get_is_unspendable() {
+            true => (spendable, unspendable + tx_out.amount),
+            false => (spendable + tx_out.amount, unspendable),
+        })
+}
+
+/// Coins of unspent tx outs referenced by inputs of transaction
+fn get_coin_inputs(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> usize {
+    transaction.tx_ins
+        .iter()
+        .filter_map(|tx_in| find_unspent_tx_out(&tx_in.tx_out_id, tx_in.tx_out_index, unspent_tx_outs))
+        .filter(|unspent_tx_out| unspent_tx_out.asset.is_none())
+        .map(|unspent_tx_out| unspent_tx_out.amount)
+        .sum()
+}
+
+fn get_coin_total(unspent_tx_outs: &Vec<UnspentTxOut>) -> usize {
+    unspent_tx_outs.iter().filter(|unspent_tx_out| unspent_tx_out.asset.is_none()).map(|unspent_tx_out| unspent_tx_out.amount).sum()
+}
+
+/// Audit coins of blockchain, replaying its blocks without validating them.
+///
+/// A block offends if its coinbase pays other than the emission schedule, a transaction pays more coins than it spends,
+/// or unspent tx outs change by other than the coins it pays and spends.
+pub fn audit_supply(blockchain: &[Block]) -> SupplyAudit {
+    let mut unspent_tx_outs: Vec<UnspentTxOut> = vec![];
+    let mut expected_issuance = 0;
+    let mut actual_issuance = 0;
+    let mut burned = 0;
+    let mut offending_height = None;
+
+    for block in blockchain {
+        let reward = get_block_reward(block.index);
+        let (minted, minted_unspendable) = block.data.get(0).map_or((0, 0), get_coin_outputs);
+        expected_issuance += reward;
+        actual_issuance += minted + minted_unspendable;
+        burned += minted_unspendable;
+
+        let mut is_offending = minted + minted_unspendable != reward;
+        let mut spent = 0;
+        let mut paid = minted;
+        for transaction in block.data.iter().skip(1) {
+            let inputs = get_coin_inputs(transaction, &unspent_tx_outs);
+            let (spendable, unspendable) = get_coin_outputs(transaction);
+            is_offending |= spendable + unspendable > inputs;
+            burned += inputs.saturating_sub(spendable);
+            spent += inputs;
+            paid += spendable;
+        }
+
+        let total = get_coin_total(&unspent_tx_outs);
+        unspent_tx_outs = update_unspent_tx_outs(&block.data, &unspent_tx_outs);
+        is_offending |= get_coin_total(&unspent_tx_outs) as i64 - total as i64 != paid as i64 - spent as i64;
+        if is_offending && offending_height.is_none() {
+            offending_height = Some(block.index);
+        }
+    }
+
+    let expected_supply = expected_issuance.saturating_sub(burned);
+    let actual_supply = get_coin_total(&unspent_tx_outs);
+    SupplyAudit {
+        height: blockchain.last().map_or(0, |block| block.index),
+        expected_issuance,
+        actual_issuance,
+        burned,
+        expected_supply,
+        actual_supply,
+        discrepancy: actual_supply as i64 - expected_supply as i64,
+        offending_height,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::get_genesis_block;
+    use crate::constants::COINBASE_AMOUNT;
+    use crate::testkit::{BOB, RECEIVER_ADDRESS};
+    use crate::transaction::{get_coinbase_transaction, TxIn, TxOut};
+    use super::*;
+
+    #[test]
+    fn test_audit_supply() {
+        let genesis_block = get_genesis_block();
+        let coinbase_tx = get_coinbase_transaction(RECEIVER_ADDRESS, 1, None);
+        let block = Block::generate(&vec![coinbase_tx.clone()], &genesis_block, 0);
+        let spending_tx = Transaction::generate(
+            &vec![TxIn::new(coinbase_tx.id.clone(), 0, "".to_string())],
+            &vec![TxOut::new(BOB.address.to_string(), COINBASE_AMOUNT - 10)],
+        );
+        let next_block = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 2, None), spending_tx], &block, 0);
+
+        let audit = audit_supply(&vec![genesis_block.clone(), block.clone(), next_block]);
+        assert_eq!(audit.height, 2);
+        assert_eq!(audit.expected_issuance, 3 * COINBASE_AMOUNT);
+        assert_eq!(audit.actual_issuance, 3 * COINBASE_AMOUNT);
+        assert_eq!(audit.burned, 10);
+        assert_eq!(audit.actual_supply, 3 * COINBASE_AMOUNT - 10);
+        assert_eq!(audit.discrepancy, 0);
+        assert_eq!(audit.offending_height, None);
+
+        let mut inflated_tx = get_coinbase_transaction(RECEIVER_ADDRESS, 2, None);
+        inflated_tx.tx_outs = vec![TxOut::new(RECEIVER_ADDRESS.to_string(), COINBASE_AMOUNT + 1)];
+        let inflated_block = Block::generate(&vec![inflated_tx], &block, 0);
+        let audit = audit_supply(&vec![genesis_block, block, inflated_block]);
+        assert_eq!(audit.actual_issuance, 3 * COINBASE_AMOUNT + 1);
+        assert_eq!(audit.discrepancy, 1);
+        assert_eq!(audit.offending_height, Some(2));
+    }
+}
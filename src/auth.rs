@@ -0,0 +1,29 @@
+use rocket::{Outcome, State};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+
+pub const ADMIN_TOKEN_HEADER: &'static str = "X-Admin-Token";
+
+/// Token required by admin endpoints, admin endpoints are disabled without it.
+#[derive(Debug)]
+pub struct AdminToken(pub Option<String>);
+
+/// Guard for admin endpoints which checks `X-Admin-Token` header.
+pub struct Admin;
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Admin, ()> {
+        let admin_token = match request.guard::<State<AdminToken>>() {
+            Outcome::Success(admin_token) => admin_token,
+            _ => return Outcome::Failure((Status::InternalServerError, ())),
+        };
+
+        match (&admin_token.0, request.headers().get_one(ADMIN_TOKEN_HEADER)) {
+            (Some(expected), Some(actual)) if expected.eq(actual) => Outcome::Success(Admin),
+            (None, _) => Outcome::Failure((Status::Forbidden, ())),
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
@@ -1,22 +1,132 @@
 use std::mem;
-use sha2::{Sha256, Digest};
-use chrono::{Utc};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 
+use crate::activation::{get_block_version, get_is_active, Rule, INITIAL_VERSION};
+use crate::chain_params::{get_chain_params, ChainParams};
+use crate::codec::encode_block_content;
+use crate::consensus_rules::ConsensusRules;
+use crate::constants::MAX_COINBASE_EXTRA_DATA;
 use crate::errors::AppError;
-use crate::transaction::{get_coinbase_transaction, process_transactions, Transaction};
+use crate::names::{get_is_valid_names, NameRegistry};
+use crate::network_time::{Clock, NetworkClock};
+use crate::pow::{get_pow_algorithm, PowAlgorithm};
+use crate::transaction::{
+    get_coinbase_prefix, get_coinbase_transaction, process_transactions, BlockContext, Transaction, TxIn, TxOut,
+    COINBASE_HEIGHT_COMMITMENT,
+};
 use crate::transaction_pool::update_transaction_pool;
+use crate::tx_index::{get_is_valid_tx_ids, TxIndex};
+use crate::tx_root::get_tx_root_at;
 use crate::UnspentTxOut;
-use crate::utils::get_is_hash_matches_difficulty;
+use crate::utxo_commitment::get_is_valid_utxo_commitment;
+use crate::validation_cache::ValidationCache;
 use crate::wallet::{create_transaction, Wallet};
 
-const BLOCK_GENERATION_INTERVAL: usize = 10;
 const DIFFICULTY_ADJUSTMENT_INTERVAL: usize = 10;
-const TIMESTAMP_INTERVAL: usize = 60;
+/// Seconds a block timestamp may be behind its previous block or ahead of the network time.
+pub(crate) const TIMESTAMP_INTERVAL: usize = 60;
+
+/// Id of the transaction in genesis block.
+pub const GENESIS_TRANSACTION_ID: &'static str = "616efe31dd3ab261b1be7ee5872873de00afbcd5f12b94f80989ed5accca66a9";
+
+/// Hash of genesis block.
+pub const GENESIS_BLOCK_HASH: &'static str = "92b40e89629439d9173f38eda53cdec04ca5fc13fd21c913800edcfc6f42cfa7";
+
+/// Bytes of previous block hash leading coinbase extra data, so coinbase ids at the same height differ across forks.
+pub const COINBASE_FORK_COMMITMENT: usize = 8;
+
+/// Longest tag of a miner accepted in configuration, leaving room for the commitments to height and fork so it fits at
+/// every height.
+pub const MAX_MINER_TAG: usize = MAX_COINBASE_EXTRA_DATA - COINBASE_HEIGHT_COMMITMENT - COINBASE_FORK_COMMITMENT;
+
+/// Progress of a block being mined, shared with the thread mining it.
+#[derive(Debug, Default)]
+pub struct MiningProgress {
+    /// Count of hashes tried
+    pub hashes: AtomicUsize,
+
+    /// Flag to stop mining
+    pub cancelled: AtomicBool,
+
+    /// Cap of hashes tried per second, shared by the jobs of a miner
+    pub throttle: Arc<Throttle>,
+}
+
+/// Most hashes tried per second while mining, adjustable while a block is mined, unlimited when none.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    max_hashrate: AtomicUsize,
+}
+
+impl Throttle {
+    /// Returns a throttle capping hashes tried per second to max hashrate
+    pub fn new(max_hashrate: Option<usize>) -> Throttle {
+        let throttle = Throttle::default();
+        throttle.set_max_hashrate(max_hashrate);
+        throttle
+    }
+
+    /// Get most hashes tried per second, none when unlimited
+    pub fn get_max_hashrate(&self) -> Option<usize> {
+        match self.max_hashrate.load(Ordering::Relaxed) {
+            0 => None,
+            max_hashrate => Some(max_hashrate),
+        }
+    }
+
+    /// Set most hashes tried per second, none or zero lifting the cap
+    pub fn set_max_hashrate(&self, max_hashrate: Option<usize>) {
+        self.max_hashrate.store(max_hashrate.unwrap_or_default(), Ordering::Relaxed);
+    }
+}
+
+/// Hashes tried since the cap of a throttle was last seen changed, to sleep off hashes over the cap.
+struct ThrottleWindow {
+    max_hashrate: usize,
+    started_at: Instant,
+    hashes: u32,
+}
+
+impl ThrottleWindow {
+    /// Count a hash tried, sleeping until it is within the cap of throttle. The clock is only read once a cap is set,
+    /// leaving unthrottled mining to targets without one.
+    fn wait(window: &mut Option<ThrottleWindow>, throttle: &Throttle) {
+        let max_hashrate = match throttle.get_max_hashrate() {
+            Some(max_hashrate) => max_hashrate,
+            None => {
+                *window = None;
+                return;
+            }
+        };
+        let current = match window {
+            Some(current) if current.max_hashrate == max_hashrate && current.hashes < u32::MAX => current,
+            _ => window.insert(ThrottleWindow { max_hashrate, started_at: Instant::now(), hashes: 0 }),
+        };
+        current.hashes += 1;
+
+        let due = Duration::from_secs(1) * current.hashes / max_hashrate.min(u32::MAX as usize) as u32;
+        let elapsed = current.started_at.elapsed();
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        }
+    }
+}
 
 /// Block in blockchain has sequence, data, time, and so on.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Block {
+    /// Version of consensus rules, missing in blocks from before versioning
+    #[serde(default = "get_initial_version")]
+    pub version: u32,
+
+    /// Hash algorithm of proof of work
+    #[serde(default)]
+    pub pow: PowAlgorithm,
+
     /// Sequence in blockchain
     pub index: usize,
 
@@ -29,14 +139,27 @@ pub struct Block {
     /// Timestamp when created
     pub timestamp: usize,
 
-    /// Data in block
-    pub data: Vec<Transaction>,
+    /// Data in block, shared by clones so chain snapshots do not copy transactions
+    pub data: Arc<Vec<Transaction>>,
+
+    /// Root of the transactions of data once the rule is active, committed to by the hash in place of data so a
+    /// header is checked without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_root: Option<String>,
+
+    /// Commitment to unspent tx outs once the block is added, missing before the rule is active
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utxo_commitment: Option<String>,
 
     /// Difficulty to generate block
     pub difficulty: usize,
 
     /// Nonce to generate block
     pub nonce: usize,
+
+    /// Work of the chain up to the block, cached once computed as the hash commits to every block before it
+    #[serde(skip)]
+    pub(crate) chain_work: OnceLock<u128>,
 }
 
 impl Block {
@@ -51,39 +174,80 @@ impl Block {
         nonce: usize,
     ) -> Block {
         Block {
+            version: INITIAL_VERSION,
+            pow: PowAlgorithm::default(),
             index,
             hash,
             previous_hash,
             timestamp,
-            data,
+            data: Arc::new(data),
+            tx_root: None,
+            utxo_commitment: None,
             difficulty,
             nonce,
+            chain_work: OnceLock::new(),
         }
     }
 
-    /// Generate a block with data and previous block
+    /// Generate a block with data and previous block at the network time, never timestamped before it
     pub fn generate(data: &Vec<Transaction>, previous: &Block, difficulty: usize) -> Block {
+        Block::generate_with_clock(data, previous, difficulty, &NetworkClock)
+    }
+
+    /// Generate a block like `generate`, at the time of clock
+    pub fn generate_with_clock(data: &Vec<Transaction>, previous: &Block, difficulty: usize, clock: &dyn Clock) -> Block {
+        Block::generate_with_progress(data, previous, difficulty, None, &MiningProgress::default(), clock).unwrap()
+    }
+
+    /// Generate a block like `generate_with_clock` carrying utxo commitment, counting hashes tried in progress and
+    /// keeping to its throttle, none once progress is cancelled
+    pub fn generate_with_progress(
+        data: &Vec<Transaction>,
+        previous: &Block,
+        difficulty: usize,
+        utxo_commitment: Option<String>,
+        progress: &MiningProgress,
+        clock: &dyn Clock,
+    ) -> Option<Block> {
         let index = previous.index + 1;
-        let timestamp = Utc::now().timestamp() as usize;
+        let version = get_block_version(index);
+        let pow = get_pow_algorithm();
+        let timestamp = clock.now().max(previous.timestamp);
+        let tx_root = get_tx_root_at(index, data);
         let mut nonce = 0;
+        let mut window = None;
 
         loop {
-            let hash = calculate_hash(index, previous.hash.as_str(), timestamp, data, difficulty, nonce);
+            if progress.cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
 
-            if !get_is_hash_matches_difficulty(hash.as_str(), difficulty) {
+            let hash = calculate_hash(
+                version, pow, index, previous.hash.as_str(), timestamp, data, tx_root.as_deref(), utxo_commitment.as_deref(), difficulty, nonce,
+            );
+            progress.hashes.fetch_add(1, Ordering::Relaxed);
+            ThrottleWindow::wait(&mut window, &progress.throttle);
+
+            if !pow.get_hasher().get_is_hash_matches_difficulty(hash.as_str(), difficulty) {
                 nonce += 1;
                 continue;
             }
 
-            return Block::new(
-                index,
-                hash,
-                previous.hash.to_string(),
-                timestamp,
-                data.to_vec(),
-                difficulty,
-                nonce,
-            );
+            return Some(Block {
+                version,
+                pow,
+                tx_root,
+                utxo_commitment,
+                ..Block::new(
+                    index,
+                    hash,
+                    previous.hash.to_string(),
+                    timestamp,
+                    data.to_vec(),
+                    difficulty,
+                    nonce,
+                )
+            });
         }
     }
 
@@ -94,18 +258,16 @@ impl Block {
         Block::generate(data, latest, difficulty)
     }
 
-    /// Generate a block with coinbase transaction and previous block
-    pub fn generate_with_coinbase_transaction(blockchain: &Vec<Block>, transaction_pool: &Vec<Transaction>, wallet: &Wallet) -> Block {
-        let latest = get_latest_block(blockchain);
-        Block::generate_raw(
-            blockchain,
-            &vec![
-                get_coinbase_transaction(wallet.public_key.as_str(), latest.index + 1),
-            ]
-                .into_iter()
-                .chain(transaction_pool.clone())
-                .collect(),
-        )
+    /// Generate a block with coinbase transaction paying payout address and tagged with miner tag, and transaction pool,
+    /// leaving out pool transactions registering taken names
+    pub fn generate_with_coinbase_transaction(
+        blockchain: &Vec<Block>,
+        transaction_pool: &Vec<Transaction>,
+        payout_address: &str,
+        miner_tag: &str,
+    ) -> Block {
+        let name_registry = NameRegistry::new(blockchain);
+        Block::generate_raw(blockchain, &get_coinbase_template(blockchain, &name_registry, transaction_pool, payout_address, miner_tag))
     }
 
     /// Generate a block with transaction
@@ -115,16 +277,19 @@ impl Block {
         unspent_tx_outs: &Vec<UnspentTxOut>,
         receiver_address: &str,
         amount: usize,
+        dust_limit: usize,
+        miner_tag: &str,
     ) -> Result<Block, AppError> {
-        let latest = get_latest_block(blockchain);
-        let coinbase_tx = get_coinbase_transaction(wallet.public_key.as_str(), latest.index + 1);
-        let tx = create_transaction(receiver_address, amount, wallet, unspent_tx_outs)?;
-        Ok(Block::generate_raw(blockchain, &vec![coinbase_tx, tx]))
+        let data = get_transaction_template(blockchain, wallet, unspent_tx_outs, receiver_address, amount, dust_limit, miner_tag)?;
+        Ok(Block::generate_raw(blockchain, &data))
     }
 
-    /// Recalculate and return hash
+    /// Recalculate and return hash, from the tx root in place of data once it exists
     pub fn get_calculated_hash(&self) -> String {
-        calculate_hash(self.index, self.previous_hash.as_str(), self.timestamp, &self.data, self.difficulty, self.nonce)
+        calculate_hash(
+            self.version, self.pow, self.index, self.previous_hash.as_str(), self.timestamp, &self.data, self.tx_root.as_deref(),
+            self.utxo_commitment.as_deref(), self.difficulty, self.nonce,
+        )
     }
 
     /// Return structure is valid
@@ -132,89 +297,176 @@ impl Block {
         !self.hash.is_empty() && !self.previous_hash.is_empty()
     }
 
+    /// Return version signals every rule active at its height
+    pub fn get_is_valid_version(&self) -> bool {
+        self.version >= get_block_version(self.index)
+    }
+
     // Return hash is valid
     pub fn get_is_valid_hash(&self) -> bool {
+        self.get_is_valid_header_hash() && self.get_is_valid_tx_root()
+    }
+
+    /// Return hash is recalculated from the header and matches difficulty, without checking data against the tx root
+    pub fn get_is_valid_header_hash(&self) -> bool {
         if !self.get_calculated_hash().eq(&self.hash) {
             return false;
         }
 
-        if !get_is_hash_matches_difficulty(self.hash.as_str(), self.difficulty) {
+        if !self.pow.get_hasher().get_is_hash_matches_difficulty(self.hash.as_str(), self.difficulty) {
             return false;
         }
 
         true
     }
+
+    /// Return tx root is the root of data, and carried only once the rule is active
+    pub fn get_is_valid_tx_root(&self) -> bool {
+        self.tx_root == get_tx_root_at(self.index, &self.data)
+    }
 }
 
 impl PartialEq for Block {
     fn eq(&self, other: &Self) -> bool {
-        self.index == other.index &&
+        self.version == other.version &&
+            self.pow == other.pow &&
+            self.index == other.index &&
             self.hash == other.hash &&
             self.previous_hash == other.previous_hash &&
             self.timestamp == other.timestamp &&
-            self.data == other.data
+            self.data == other.data &&
+            self.tx_root == other.tx_root &&
+            self.utxo_commitment == other.utxo_commitment
     }
 }
 
 impl Clone for Block {
     fn clone(&self) -> Self {
         Self {
+            version: self.version,
+            pow: self.pow,
             index: self.index,
             hash: self.hash.clone(),
             previous_hash: self.previous_hash.clone(),
             timestamp: self.timestamp,
             data: self.data.clone(),
+            tx_root: self.tx_root.clone(),
+            utxo_commitment: self.utxo_commitment.clone(),
             difficulty: self.difficulty,
             nonce: self.nonce,
+            chain_work: self.chain_work.clone(),
         }
     }
 }
 
-fn calculate_hash(index: usize, previous_hash: &str, timestamp: usize, data: &Vec<Transaction>, difficulty: usize, nonce: usize) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(format!("{}{}{}{}{}{}", index, previous_hash, timestamp, serde_json::to_string(&data).unwrap(), difficulty, nonce).as_bytes());
-    format!("{:x}", hasher.finalize())
+fn get_initial_version() -> u32 {
+    INITIAL_VERSION
+}
+
+/// Calculate hash of canonical bytes of block properties with its proof of work algorithm.
+fn calculate_hash(
+    version: u32,
+    pow: PowAlgorithm,
+    index: usize,
+    previous_hash: &str,
+    timestamp: usize,
+    data: &Vec<Transaction>,
+    tx_root: Option<&str>,
+    utxo_commitment: Option<&str>,
+    difficulty: usize,
+    nonce: usize,
+) -> String {
+    let content = encode_block_content(version, pow, index, previous_hash, timestamp, data, tx_root, utxo_commitment, difficulty, nonce);
+    pow.get_hasher().hash(&content)
+}
+
+/// Get flag timestamp of new block is at most an interval before previous block and after the time of clock.
+fn get_is_valid_timestamp(new_block: &Block, previous_block: &Block, clock: &dyn Clock) -> bool {
+    previous_block.timestamp.checked_sub(TIMESTAMP_INTERVAL).map_or(true, |earliest| earliest < new_block.timestamp)
+        && new_block.timestamp.checked_sub(TIMESTAMP_INTERVAL).map_or(true, |latest| latest < clock.now())
 }
 
-fn get_is_valid_timestamp(new_block: &Block, previous_block: &Block) -> bool {
-    previous_block.timestamp - TIMESTAMP_INTERVAL < new_block.timestamp
-        && new_block.timestamp - TIMESTAMP_INTERVAL < Utc::now().timestamp() as usize
+pub(crate) fn get_is_valid_new_block(new_block: &Block, previous_block: &Block, clock: &dyn Clock) -> bool {
+    get_is_valid_new_header(new_block, previous_block, clock) && new_block.get_is_valid_tx_root()
 }
 
-fn get_is_valid_new_block(new_block: &Block, previous_block: &Block) -> bool {
+/// Get flag new block extends previous block like `get_is_valid_new_block`, recalculating its hash from the header
+/// without checking data it may not carry. Blocks before the tx root rule is active hash their data, so their header
+/// is only valid with it.
+pub(crate) fn get_is_valid_new_header(new_block: &Block, previous_block: &Block, clock: &dyn Clock) -> bool {
     return if !new_block.get_is_valid_structure() {
         false
     } else if previous_block.index + 1 != new_block.index {
         false
+    } else if !new_block.get_is_valid_version() {
+        false
+    } else if new_block.pow != get_pow_algorithm() {
+        false
     } else if previous_block.hash != new_block.previous_hash {
         false
-    } else if !get_is_valid_timestamp(new_block, previous_block) {
+    } else if !get_is_valid_timestamp(new_block, previous_block, clock) {
+        false
+    } else if new_block.tx_root.is_some() != get_is_active(Rule::TxRoot, new_block.index) {
         false
-    } else if !new_block.get_is_valid_hash() {
+    } else if !new_block.get_is_valid_header_hash() {
         false
     } else {
         true
     };
 }
 
-fn get_is_valid_chain(genesis_block: &Block, blockchain: &Vec<Block>) -> bool {
+fn get_is_valid_chain(genesis_block: &Block, blockchain: &Vec<Block>, clock: &dyn Clock) -> bool {
     if let Some(last) = blockchain.get(0) {
         if genesis_block != last {
             false
         } else if blockchain.len() == 1 {
             true
         } else {
-            blockchain.windows(2).all(|window| get_is_valid_new_block(&window[1], &window[0]))
+            blockchain.windows(2).all(|window| get_is_valid_new_block(&window[1], &window[0], clock))
         }
     } else {
         false
     }
 }
 
-fn get_accumulated_difficulty(blockchain: &Vec<Block>) -> i32 {
-    blockchain.into_iter()
-        .map(|block: &Block| block.difficulty)
-        .fold(0, |total: i32, difficulty: usize| total + 2_i32.pow(difficulty as u32))
+/// Get work of a block of difficulty, the hashes it takes on average.
+pub fn get_work(difficulty: usize) -> u128 {
+    1u128.checked_shl(difficulty as u32).unwrap_or(u128::MAX)
+}
+
+/// Get accumulated work of blockchain, resuming from the latest block caching it and caching it for the ones after.
+pub fn get_chain_work(blockchain: &[Block]) -> u128 {
+    let cached = blockchain.iter().rposition(|block| block.chain_work.get().is_some());
+    let (start, mut chain_work) = match cached {
+        Some(index) => (index + 1, blockchain[index].chain_work.get().copied().unwrap_or_default()),
+        None => (0, 0),
+    };
+    for block in &blockchain[start..] {
+        chain_work = chain_work.saturating_add(get_work(block.difficulty));
+        let _ = block.chain_work.set(chain_work);
+    }
+    chain_work
+}
+
+/// Get the first block every chain starts from.
+pub fn get_genesis_block() -> Block {
+    let genesis_transaction = Transaction::new(
+        GENESIS_TRANSACTION_ID.to_string(),
+        &vec![TxIn::new("".to_string(), 0, "".to_string())],
+        &vec![TxOut::new(
+            "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
+            50,
+        )]
+    );
+    Block::new(
+        0,
+        GENESIS_BLOCK_HASH.to_string(),
+        "".to_string(),
+        1655831820,
+        vec![genesis_transaction],
+        0,
+        0,
+    )
 }
 
 /// Get latest block from blockchain.
@@ -222,16 +474,140 @@ pub fn get_latest_block(blockchain: &Vec<Block>) -> &Block {
     blockchain.last().unwrap()
 }
 
-/// Add block to blockchain.
+/// Get extra data of coinbase transaction of block index on top of previous hash, the prefix due at the height, leading
+/// bytes of the hash and tag of the miner.
+pub fn get_coinbase_extra_data(block_index: usize, previous_hash: &str, miner_tag: &str) -> Vec<u8> {
+    let mut fork_commitment = hex::decode(previous_hash).unwrap_or_else(|_| previous_hash.as_bytes().to_vec());
+    fork_commitment.resize(COINBASE_FORK_COMMITMENT, 0);
+    let mut extra_data = get_coinbase_prefix(block_index);
+    extra_data.extend(fork_commitment);
+    let max_miner_tag = MAX_COINBASE_EXTRA_DATA - extra_data.len();
+    extra_data.extend(miner_tag.as_bytes().iter().take(max_miner_tag));
+    extra_data
+}
+
+/// Get data of a block on top of blockchain with coinbase transaction paying payout address and tagged with miner tag,
+/// and transaction pool, leaving out pool transactions registering names taken in name registry of blockchain.
+pub fn get_coinbase_template(
+    blockchain: &Vec<Block>,
+    name_registry: &NameRegistry,
+    transaction_pool: &Vec<Transaction>,
+    payout_address: &str,
+    miner_tag: &str,
+) -> Vec<Transaction> {
+    let latest = get_latest_block(blockchain);
+    let transaction_pool = name_registry.filter_transactions(transaction_pool);
+    let extra_data = get_coinbase_extra_data(latest.index + 1, &latest.hash, miner_tag);
+    vec![get_coinbase_transaction(payout_address, latest.index + 1, Some(&extra_data))]
+        .into_iter()
+        .chain(transaction_pool)
+        .collect()
+}
+
+/// Get data of a block on top of blockchain with coinbase transaction paying wallet and a transaction of wallet
+/// paying amount to receiver address.
+///
+/// # Errors
+/// If the transaction cannot be created, it returns one of the errors of `create_transaction`.
+pub fn get_transaction_template(
+    blockchain: &Vec<Block>,
+    wallet: &Wallet,
+    unspent_tx_outs: &Vec<UnspentTxOut>,
+    receiver_address: &str,
+    amount: usize,
+    dust_limit: usize,
+    miner_tag: &str,
+) -> Result<Vec<Transaction>, AppError> {
+    let latest = get_latest_block(blockchain);
+    let extra_data = get_coinbase_extra_data(latest.index + 1, &latest.hash, miner_tag);
+    let coinbase_tx = get_coinbase_transaction(wallet.public_key.as_str(), latest.index + 1, Some(&extra_data));
+    let tx = create_transaction(receiver_address, amount, wallet, unspent_tx_outs, dust_limit)?;
+    Ok(vec![coinbase_tx, tx])
+}
+
+/// Get tag of the miner in extra data of coinbase transaction, none if it is empty or not text.
+pub fn get_miner_tag(coinbase_tx: &Transaction) -> Option<String> {
+    let extra_data = hex::decode(coinbase_tx.extra_data.as_ref()?).ok()?;
+    let prefix = get_coinbase_prefix(coinbase_tx.tx_ins.first()?.tx_out_index);
+    let miner_tag = String::from_utf8(extra_data.get(prefix.len() + COINBASE_FORK_COMMITMENT..)?.to_vec()).ok()?;
+    Some(miner_tag).filter(|miner_tag| !miner_tag.is_empty())
+}
+
+/// Indexes of a chain checking blocks appended to it, applied block by block instead of replaying the chain.
+#[derive(Debug, Default)]
+pub struct ChainIndex {
+    /// Replay protection of the chain
+    pub tx_index: TxIndex,
+
+    /// Names registered on the chain
+    pub name_registry: NameRegistry,
+
+    /// Hash of the latest block applied, none before any
+    tip: Option<String>,
+}
+
+impl ChainIndex {
+    /// Returns indexes replaying blockchain
+    pub fn new(blockchain: &[Block]) -> ChainIndex {
+        let mut chain_index = ChainIndex::default();
+        for block in blockchain {
+            chain_index.apply_block(block);
+        }
+        chain_index
+    }
+
+    /// Record transactions and names of a block appended to the chain
+    pub fn apply_block(&mut self, block: &Block) {
+        self.tx_index.apply_block(block);
+        self.name_registry.apply_block(block);
+        self.tip = Some(block.hash.clone());
+    }
+
+    /// Update indexes to blockchain, applying only the blocks after the latest one applied if blockchain extends it,
+    /// or replaying blockchain if it forks off.
+    pub fn update(&mut self, blockchain: &[Block]) {
+        let applied = blockchain.iter().rposition(|block| self.tip.as_deref() == Some(block.hash.as_str()));
+        match applied {
+            Some(applied) => blockchain[applied + 1..].iter().for_each(|block| self.apply_block(block)),
+            None => *self = ChainIndex::new(blockchain),
+        }
+    }
+}
+
+/// Add block to blockchain indexed by chain index, validating its timestamp against clock and its transactions at
+/// its own timestamp. Chain index is updated with the block once it is added.
 ///
 /// # Errors
 /// If it is not valid compared to the previous block, it returns error 1000.
-pub fn add_block(blockchain: &mut Vec<Block>, unspent_tx_outs: &mut Vec<UnspentTxOut>, transaction_pool: &mut Vec<Transaction>, new_block: &Block) -> Result<(), AppError> {
-    if !get_is_valid_new_block(&new_block, get_latest_block(blockchain)) {
+/// If it has a spendable output below the dust limit of consensus rules, it returns error 1001.
+/// If it registers an invalid or taken name, it returns error 1002.
+/// If it does not carry the commitment to unspent tx outs due at its height, it returns error 1007.
+/// If it replays a transaction id or spent outpoint of the chain, it returns error 1009.
+pub fn add_block(
+    blockchain: &mut Vec<Block>,
+    chain_index: &mut ChainIndex,
+    unspent_tx_outs: &mut Vec<UnspentTxOut>,
+    transaction_pool: &mut Vec<Transaction>,
+    new_block: &Block,
+    validation_cache: &mut ValidationCache,
+    consensus_rules: &ConsensusRules,
+    clock: &dyn Clock,
+) -> Result<(), AppError> {
+    if !get_is_valid_new_block(&new_block, get_latest_block(blockchain), clock) {
         Err(AppError::new(1000))
+    } else if !consensus_rules.get_is_valid_transactions(&new_block.data) {
+        Err(AppError::new(1001))
+    } else if !chain_index.name_registry.get_is_valid_transactions(&new_block.data) {
+        Err(AppError::new(1002))
+    } else if !chain_index.tx_index.get_is_valid_transactions(&new_block.data) {
+        Err(AppError::new(1009))
     } else {
-        let processed_unspent_tx_outs = process_transactions(&new_block.data, unspent_tx_outs, new_block.index)?;
+        let processed_unspent_tx_outs = process_transactions(&new_block.data, unspent_tx_outs, BlockContext::of(new_block), validation_cache)?;
+        if !get_is_valid_utxo_commitment(new_block, &processed_unspent_tx_outs) {
+            return Err(AppError::new(1007));
+        }
         blockchain.push(new_block.clone());
+        chain_index.apply_block(new_block);
         let _ = mem::replace(&mut *unspent_tx_outs, processed_unspent_tx_outs);
         let updated_transaction_pool = update_transaction_pool(transaction_pool, unspent_tx_outs);
         let _ = mem::replace(&mut *transaction_pool, updated_transaction_pool);
@@ -240,19 +616,56 @@ pub fn add_block(blockchain: &mut Vec<Block>, unspent_tx_outs: &mut Vec<UnspentT
 }
 
 /// Get flag to replace blockchain.
-pub fn get_is_replace_chain(blockchain: &Vec<Block>, new_blockchain: &Vec<Block>) -> bool {
-    get_is_valid_chain(&blockchain[0], new_blockchain) && get_accumulated_difficulty(blockchain) < get_accumulated_difficulty(new_blockchain)
+pub fn get_is_replace_chain(blockchain: &Vec<Block>, new_blockchain: &Vec<Block>, consensus_rules: &ConsensusRules, clock: &dyn Clock) -> bool {
+    validate_chain(blockchain, new_blockchain, consensus_rules, clock).is_ok() && get_is_heavier_chain(blockchain, new_blockchain)
+}
+
+/// Validate new blockchain sharing the genesis block of blockchain under consensus rules, whether or not it is
+/// heavier, at the time of clock.
+///
+/// # Errors
+/// If a block is invalid, it returns error 1000, and if a name is registered invalid or taken, it returns error 1002.
+/// If a block has a spendable output below the dust limit of consensus rules, it returns error 1001.
+/// If a block replays a transaction id or spent outpoint before it, it returns error 1009.
+pub fn validate_chain(blockchain: &Vec<Block>, new_blockchain: &Vec<Block>, consensus_rules: &ConsensusRules, clock: &dyn Clock) -> Result<(), AppError> {
+    if !get_is_valid_chain(&blockchain[0], new_blockchain, clock) {
+        return Err(AppError::new(1000));
+    }
+    if !consensus_rules.get_is_valid_blocks(new_blockchain) {
+        return Err(AppError::new(1001));
+    }
+    if !get_is_valid_names(new_blockchain) {
+        return Err(AppError::new(1002));
+    }
+    if !get_is_valid_tx_ids(new_blockchain) {
+        return Err(AppError::new(1009));
+    }
+    Ok(())
+}
+
+/// Get flag new blockchain has more accumulated difficulty than blockchain.
+pub fn get_is_heavier_chain(blockchain: &[Block], new_blockchain: &[Block]) -> bool {
+    get_chain_work(blockchain) < get_chain_work(new_blockchain)
 }
 
-/// Get difficulty from blockchain.
+/// Get difficulty from blockchain, on the parameters of this network.
 pub fn get_difficulty(blockchain: &Vec<Block>) -> usize {
+    get_difficulty_with_params(blockchain, &get_chain_params())
+}
+
+/// Get difficulty from blockchain like `get_difficulty`, on params, always 0 on regtest.
+pub fn get_difficulty_with_params(blockchain: &Vec<Block>, params: &ChainParams) -> usize {
+    if params.get_is_regtest() {
+        return 0;
+    }
+
     let latest_block = get_latest_block(blockchain);
     if (latest_block.index % DIFFICULTY_ADJUSTMENT_INTERVAL) != 0 || latest_block.index == 0 {
         return latest_block.difficulty;
     }
 
     let prev_adjustment_block: &Block = blockchain.get(blockchain.len() - DIFFICULTY_ADJUSTMENT_INTERVAL).unwrap();
-    let time_expected = BLOCK_GENERATION_INTERVAL * DIFFICULTY_ADJUSTMENT_INTERVAL;
+    let time_expected = params.block_interval * DIFFICULTY_ADJUSTMENT_INTERVAL;
     let time_taken = latest_block.timestamp - prev_adjustment_block.timestamp;
 
     return if time_taken < time_expected / 2 {
@@ -265,42 +678,116 @@ pub fn get_difficulty(blockchain: &Vec<Block>) -> usize {
 }
 
 /// Get UnspentTxOut from blockchain.
+///
+/// # Errors
+/// If a block has invalid transactions, it returns one of the errors of `process_transactions`.
+/// If a block does not carry the commitment to unspent tx outs due at its height, it returns error 1007.
 pub fn get_unspent_tx_outs(blockchain: &Vec<Block>) -> Result<Vec<UnspentTxOut>, AppError> {
     let mut unspent_tx_outs = vec![];
-    blockchain.into_iter().for_each(|block| {
-        unspent_tx_outs = process_transactions(&block.data, &unspent_tx_outs, block.index).unwrap();
-    });
+    let mut validation_cache = ValidationCache::new(0);
+    for block in blockchain {
+        unspent_tx_outs = process_transactions(&block.data, &unspent_tx_outs, BlockContext::of(block), &mut validation_cache)?;
+        if !get_is_valid_utxo_commitment(block, &unspent_tx_outs) {
+            return Err(AppError::new(1007));
+        }
+    }
     Ok(unspent_tx_outs)
 }
 
 #[cfg(test)]
 mod test {
+    use chrono::Utc;
+    use crate::network_time::MockClock;
+    use crate::chain_params::{with_chain_params, Network, REGTEST};
     use crate::transaction::{TxIn, TxOut};
     use crate::constants::COINBASE_AMOUNT;
+    use crate::testkit::{get_block, get_wallet, BOB, RECEIVER_ADDRESS};
     use super::*;
 
     #[test]
     fn test_calculate_hash() {
         let hash = calculate_hash(
+            INITIAL_VERSION,
+            PowAlgorithm::Sha256,
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d",
             1465154705,
             &vec![],
+            None,
+            None,
             0,
             0,
         );
 
-        assert_eq!(hash, "12c7538225556354e750653f746fea1414b43fb09062f279162725d7748df7c9");
+        assert_eq!(hash, "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3");
 
         let hash = calculate_hash(
+            INITIAL_VERSION,
+            PowAlgorithm::Sha256,
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d",
             1465154705,
             &vec![Transaction::generate(&vec![], &vec![])],
+            None,
+            None,
+            0,
+            0,
+        );
+        assert_eq!(hash, "eec987be4dd05d206005e35a4a6b4ddea2df966c78d99b72c4807a355cc8691c");
+
+        let hash = calculate_hash(
+            2,
+            PowAlgorithm::Sha256,
+            0,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d",
+            1465154705,
+            &vec![],
+            None,
+            None,
+            0,
+            0,
+        );
+        assert_ne!(hash, "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3");
+
+        let hash = calculate_hash(
+            INITIAL_VERSION,
+            PowAlgorithm::Blake3,
+            0,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d",
+            1465154705,
+            &vec![],
+            None,
+            None,
             0,
             0,
         );
-        assert_eq!(hash, "e57a5313832eb6755a61a9ea87308ebfe04cb5aea378b3a0c0e2fba1051ceb1e");
+        assert_ne!(hash, "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3");
+        let content = encode_block_content(
+            INITIAL_VERSION,
+            PowAlgorithm::Blake3,
+            0,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d",
+            1465154705,
+            &vec![],
+            None,
+            None,
+            0,
+            0,
+        );
+        assert_eq!(hash, PowAlgorithm::Blake3.get_hasher().hash(&content));
+    }
+
+    #[test]
+    fn test_block_version() {
+        let mut genesis = serde_json::to_value(get_genesis_block()).unwrap();
+        genesis.as_object_mut().unwrap().remove("version");
+        let block: Block = serde_json::from_value(genesis).unwrap();
+        assert_eq!(block.version, INITIAL_VERSION);
+        assert!(block.get_is_valid_version());
+        assert!(block.get_is_valid_hash());
+
+        let block = Block { version: 0, ..get_genesis_block() };
+        assert!(!block.get_is_valid_version());
     }
 
     #[test]
@@ -315,12 +802,15 @@ mod test {
             0,
         );
         let data = vec![];
-        let next = Block::generate(&data, &previous, 0);
-        let timestamp = Utc::now().timestamp() as usize;
+        let clock = MockClock::new(1465154765);
+        let next = Block::generate_with_clock(&data, &previous, 0, &clock);
         assert_eq!(next.index, 1);
-        assert_eq!(next.timestamp, timestamp);
-        assert_eq!(next.hash, calculate_hash(1, previous.hash.as_str(), timestamp, &data, 0, 0));
-        assert_eq!(next.data, data);
+        assert_eq!(next.timestamp, 1465154765);
+        assert_eq!(next.hash, calculate_hash(INITIAL_VERSION, PowAlgorithm::Sha256, 1, previous.hash.as_str(), 1465154765, &data, None, None, 0, 0));
+        assert_eq!(*next.data, data);
+
+        clock.set(1465154700);
+        assert_eq!(Block::generate_with_clock(&data, &previous, 0, &clock).timestamp, previous.timestamp);
     }
 
     #[test]
@@ -340,16 +830,41 @@ mod test {
         let timestamp = Utc::now().timestamp() as usize;
         assert_eq!(next.index, 1);
         assert_eq!(next.timestamp, timestamp);
-        assert_eq!(next.hash, calculate_hash(1, previous.hash.as_str(), timestamp, &data, 0, 0));
-        assert_eq!(next.data, data);
+        assert_eq!(next.hash, calculate_hash(INITIAL_VERSION, PowAlgorithm::Sha256, 1, previous.hash.as_str(), timestamp, &data, None, None, 0, 0));
+        assert_eq!(*next.data, data);
+    }
+
+    #[test]
+    fn test_block_generate_with_progress() {
+        let genesis_block = get_genesis_block();
+        let progress = MiningProgress::default();
+        let block = Block::generate_with_progress(&vec![], &genesis_block, 0, None, &progress, &NetworkClock).unwrap();
+        assert_eq!(block.index, 1);
+        assert_eq!(progress.hashes.load(Ordering::Relaxed), 1);
+
+        progress.cancelled.store(true, Ordering::Relaxed);
+        assert!(Block::generate_with_progress(&vec![], &genesis_block, 0, None, &progress, &NetworkClock).is_none());
+    }
+
+    #[test]
+    fn test_block_generate_with_throttle() {
+        let genesis_block = get_genesis_block();
+        let progress = MiningProgress { throttle: Arc::new(Throttle::new(Some(100))), ..MiningProgress::default() };
+        assert_eq!(progress.throttle.get_max_hashrate(), Some(100));
+        let started_at = Instant::now();
+        let block = Block::generate_with_progress(&vec![], &genesis_block, 4, None, &progress, &NetworkClock).unwrap();
+        let hashes = progress.hashes.load(Ordering::Relaxed);
+        assert_eq!(hashes, block.nonce + 1);
+        assert!(started_at.elapsed() >= Duration::from_millis(10) * hashes as u32);
+
+        progress.throttle.set_max_hashrate(None);
+        assert_eq!(progress.throttle.get_max_hashrate(), None);
+        assert_eq!(Throttle::new(Some(0)).get_max_hashrate(), None);
     }
 
     #[test]
     fn test_block_generate_with_coinbase_transaction() {
-        let wallet = Wallet {
-            private_key: "eb35a95c6c1bcd1164e5f23629797131bd24aae3995b831be94c8e8fa37ee2d8".to_string(),
-            public_key: "03196c144d93ba0ca200221b507312a41c67eafb9b0d9b9348b286a693969b8192".to_string(),
-        };
+        let wallet = get_wallet(BOB);
         let previous = Block::new(
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
@@ -361,7 +876,7 @@ mod test {
         );
         let blockchain = vec![previous];
         let transaction_pool = vec![];
-        let block = Block::generate_with_coinbase_transaction(&blockchain, &transaction_pool, &wallet);
+        let block = Block::generate_with_coinbase_transaction(&blockchain, &transaction_pool, &wallet.public_key, "pool");
         let timestamp = Utc::now().timestamp() as usize;
         assert_eq!(block.index, 1);
         assert_eq!(block.timestamp, timestamp);
@@ -371,43 +886,70 @@ mod test {
         let tx_out = tx.tx_outs.get(0).unwrap();
         assert_eq!(tx_out.address, "03196c144d93ba0ca200221b507312a41c67eafb9b0d9b9348b286a693969b8192");
         assert_eq!(tx_out.amount, COINBASE_AMOUNT);
+        assert_eq!(tx.extra_data, Some("41cdda1f3f0f6bd2706f6f6c".to_string()));
+        assert_eq!(get_miner_tag(tx), Some("pool".to_string()));
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction_pool = vec![Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs)];
-        let block = Block::generate_with_coinbase_transaction(&blockchain, &transaction_pool, &wallet);
+        let transaction_pool = vec![Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs)];
+        let block = Block::generate_with_coinbase_transaction(&blockchain, &transaction_pool, RECEIVER_ADDRESS, "");
         assert_eq!(block.data.len(), 2);
+        assert_eq!(block.data[0].tx_outs[0].address, RECEIVER_ADDRESS);
+        assert_eq!(get_miner_tag(&block.data[0]), None);
+    }
+
+    #[test]
+    fn test_get_coinbase_extra_data() {
+        let genesis_block = get_genesis_block();
+        let fork = Block::generate(&vec![], &genesis_block, 0);
+        let extra_data = get_coinbase_extra_data(2, &genesis_block.hash, "pool");
+        assert_eq!(extra_data.len(), COINBASE_FORK_COMMITMENT + 4);
+        assert_ne!(
+            get_coinbase_transaction(RECEIVER_ADDRESS, 2, Some(&get_coinbase_extra_data(2, &fork.hash, ""))).id,
+            get_coinbase_transaction(RECEIVER_ADDRESS, 2, Some(&get_coinbase_extra_data(2, &genesis_block.hash, ""))).id,
+        );
+        let longest = get_coinbase_extra_data(2, "", &"a".repeat(MAX_COINBASE_EXTRA_DATA));
+        assert_eq!(longest.len(), MAX_COINBASE_EXTRA_DATA);
+        assert_eq!(longest[COINBASE_FORK_COMMITMENT..], *"a".repeat(MAX_COINBASE_EXTRA_DATA - COINBASE_FORK_COMMITMENT).as_bytes());
+
+        with_chain_params(REGTEST, || {
+            let longest = get_coinbase_extra_data(2, "", &"a".repeat(MAX_COINBASE_EXTRA_DATA));
+            assert_eq!(longest.len(), MAX_COINBASE_EXTRA_DATA);
+            assert_eq!(longest[..COINBASE_HEIGHT_COMMITMENT], 2u64.to_be_bytes());
+            assert_eq!(longest.len() - COINBASE_HEIGHT_COMMITMENT - COINBASE_FORK_COMMITMENT, MAX_MINER_TAG);
+
+            let extra_data = get_coinbase_extra_data(2, &genesis_block.hash, "pool");
+            let coinbase_tx = get_coinbase_transaction(RECEIVER_ADDRESS, 2, Some(&extra_data));
+            assert_eq!(get_miner_tag(&coinbase_tx), Some("pool".to_string()));
+        });
     }
 
     #[test]
     fn test_block_generate_with_transaction() {
-        let wallet = Wallet {
-            private_key: "eb35a95c6c1bcd1164e5f23629797131bd24aae3995b831be94c8e8fa37ee2d8".to_string(),
-            public_key: "03196c144d93ba0ca200221b507312a41c67eafb9b0d9b9348b286a693969b8192".to_string(),
-        };
+        let wallet = get_wallet(BOB);
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 wallet.public_key.to_string(),
                 50,
             ),
             UnspentTxOut::new(
-                "05f756fca4edb257e7ba26a4377246fcbef6de9e948886dad91355cdbfc32d9e".to_string(),
+                "815b62d7386b68071998931112d7c1ee9acc874c780a49388d2386517ad6109b".to_string(),
                 0,
                 wallet.public_key.to_string(),
                 50,
             ),
             UnspentTxOut::new(
-                "69202784cf6c645b87027eb1ccc0500609182f9f76f5be6e2fbe60bb1037b6ed".to_string(),
+                "a746ab045eb203907bdd6247c36a3fa4a3832b70366fce8ac7928f0bf2db7587".to_string(),
                 0,
                 wallet.public_key.to_string(),
                 50,
@@ -435,6 +977,8 @@ mod test {
             &unspent_tx_outs,
             "03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40",
             150,
+            1,
+            "",
         ).unwrap();
         let timestamp = Utc::now().timestamp() as usize;
         assert_eq!(block.index, 1);
@@ -462,7 +1006,7 @@ mod test {
             0,
             0,
         );
-        assert_eq!(block.get_calculated_hash(), calculate_hash(0, "", 1465154705, &vec![], 0, 0));
+        assert_eq!(block.get_calculated_hash(), calculate_hash(INITIAL_VERSION, PowAlgorithm::Sha256, 0, "", 1465154705, &vec![], None, None, 0, 0));
     }
 
     #[test]
@@ -505,7 +1049,7 @@ mod test {
     fn test_block_get_is_valid_hash() {
         let block = Block::new(
             0,
-            "12c7538225556354e750653f746fea1414b43fb09062f279162725d7748df7c9".to_string(),
+            "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3".to_string(),
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
             1465154705,
             vec![],
@@ -516,7 +1060,7 @@ mod test {
 
         let mut block = Block::new(
             0,
-            "12c7538225556354e750653f746fea1414b43fb09062f279162725d7748df7c9".to_string(),
+            "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3".to_string(),
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
             1465154705,
             vec![],
@@ -528,7 +1072,7 @@ mod test {
 
         let mut block = Block::new(
             0,
-            "12c7538225556354e750653f746fea1414b43fb09062f279162725d7748df7c9".to_string(),
+            "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3".to_string(),
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
             1465154705,
             vec![],
@@ -611,7 +1155,7 @@ mod test {
 
         let b = Block::new(
             0,
-            "e57a5313832eb6755a61a9ea87308ebfe04cb5aea378b3a0c0e2fba1051ceb1e".to_string(),
+            "eec987be4dd05d206005e35a4a6b4ddea2df966c78d99b72c4807a355cc8691c".to_string(),
             "".to_string(),
             1465154705,
             vec![Transaction::generate(&vec![], &vec![])],
@@ -642,21 +1186,28 @@ mod test {
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
             "".to_string(),
-            Utc::now().timestamp() as usize,
+            1465154705,
             vec![],
             0,
             0,
         );
-        let next = Block::generate(&vec![], &previous, 0);
-        assert!(get_is_valid_timestamp(&next, &previous));
-
-        let mut next = Block::generate(&vec![], &previous, 0);
-        next.timestamp = previous.timestamp + TIMESTAMP_INTERVAL + 1;
-        assert!(!get_is_valid_timestamp(&next, &previous));
-
-        let mut next = Block::generate(&vec![], &previous, 0);
-        next.timestamp = Utc::now().timestamp() as usize - TIMESTAMP_INTERVAL - 1;
-        assert!(!get_is_valid_timestamp(&next, &previous));
+        let clock = MockClock::new(previous.timestamp);
+        let next = Block::generate_with_clock(&vec![], &previous, 0, &clock);
+        assert!(get_is_valid_timestamp(&next, &previous, &clock));
+
+        let mut next = Block::generate_with_clock(&vec![], &previous, 0, &clock);
+        next.timestamp = previous.timestamp + TIMESTAMP_INTERVAL;
+        assert!(!get_is_valid_timestamp(&next, &previous, &clock));
+        clock.advance(1);
+        assert!(get_is_valid_timestamp(&next, &previous, &clock));
+
+        let mut next = Block::generate_with_clock(&vec![], &previous, 0, &clock);
+        next.timestamp = previous.timestamp - TIMESTAMP_INTERVAL;
+        assert!(!get_is_valid_timestamp(&next, &previous, &clock));
+
+        let early = Block { timestamp: 0, ..previous.clone() };
+        let next = Block { timestamp: 1, ..next };
+        assert!(get_is_valid_timestamp(&next, &early, &clock));
     }
 
     #[test]
@@ -671,27 +1222,27 @@ mod test {
             0,
         );
         let next = Block::generate(&vec![], &previous, 0);
-        assert!(get_is_valid_new_block(&next, &previous));
+        assert!(get_is_valid_new_block(&next, &previous, &NetworkClock));
 
         let mut next = Block::generate(&vec![], &previous, 0);
         next.index = 2;
-        assert!(!get_is_valid_new_block(&next, &previous));
+        assert!(!get_is_valid_new_block(&next, &previous, &NetworkClock));
 
         let mut next = Block::generate(&vec![], &previous, 0);
         next.previous_hash = "invalid".to_string();
-        assert!(!get_is_valid_new_block(&next, &previous));
+        assert!(!get_is_valid_new_block(&next, &previous, &NetworkClock));
 
         let mut next = Block::generate(&vec![], &previous, 0);
-        next.data = vec![Transaction::generate(&vec![], &vec![])];
-        assert!(!get_is_valid_new_block(&next, &previous));
+        next.data = Arc::new(vec![Transaction::generate(&vec![], &vec![])]);
+        assert!(!get_is_valid_new_block(&next, &previous, &NetworkClock));
 
         let mut next = Block::generate(&vec![], &previous, 0);
         next.timestamp = previous.timestamp + TIMESTAMP_INTERVAL + 1;
-        assert!(!get_is_valid_new_block(&next, &previous));
+        assert!(!get_is_valid_new_block(&next, &previous, &NetworkClock));
 
         let mut next = Block::generate(&vec![], &previous, 0);
         next.timestamp = previous.timestamp + TIMESTAMP_INTERVAL + 1;
-        assert!(!get_is_valid_new_block(&next, &previous));
+        assert!(!get_is_valid_new_block(&next, &previous, &NetworkClock));
     }
 
     #[test]
@@ -706,7 +1257,7 @@ mod test {
             0,
         );
         let blockchain = vec![genesis_block.clone()];
-        assert!(get_is_valid_chain(&genesis_block, &blockchain));
+        assert!(get_is_valid_chain(&genesis_block, &blockchain, &NetworkClock));
 
         let genesis_block = Block::new(
             0,
@@ -722,7 +1273,7 @@ mod test {
             genesis_block.clone(),
             next_block.clone(),
         ];
-        assert!(get_is_valid_chain(&genesis_block, &blockchain));
+        assert!(get_is_valid_chain(&genesis_block, &blockchain, &NetworkClock));
 
         let other_genesis_block = Block::new(
             1,
@@ -734,7 +1285,7 @@ mod test {
             0,
         );
         let blockchain = vec![genesis_block.clone()];
-        assert!(!get_is_valid_chain(&other_genesis_block, &blockchain));
+        assert!(!get_is_valid_chain(&other_genesis_block, &blockchain, &NetworkClock));
 
         let genesis_block = Block::new(
             0,
@@ -751,11 +1302,11 @@ mod test {
             genesis_block.clone(),
             next_block.clone(),
         ];
-        assert!(!get_is_valid_chain(&genesis_block, &blockchain));
+        assert!(!get_is_valid_chain(&genesis_block, &blockchain, &NetworkClock));
     }
 
     #[test]
-    fn test_get_accumulated_difficulty() {
+    fn test_get_chain_work() {
         let genesis_block = Block::new(
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
@@ -766,20 +1317,28 @@ mod test {
             0,
         );
         let blockchain = vec![genesis_block.clone()];
-        assert_eq!(get_accumulated_difficulty(&blockchain), 1);
+        assert_eq!(get_chain_work(&blockchain), 1);
 
         let blockchain = vec![
             genesis_block.clone(),
             Block::generate(&vec![], &genesis_block, 2),
         ];
-        assert_eq!(get_accumulated_difficulty(&blockchain), 5);
+        assert_eq!(get_chain_work(&blockchain), 5);
 
         let blockchain = vec![
             genesis_block.clone(),
             Block::generate(&vec![], &genesis_block, 2),
             Block::generate(&vec![], &genesis_block, 2),
         ];
-        assert_eq!(get_accumulated_difficulty(&blockchain), 9);
+        assert_eq!(get_chain_work(&blockchain), 9);
+        assert_eq!(blockchain[1].chain_work.get(), Some(&5));
+
+        // Work cached by a block is resumed from, so it still counts the blocks before it.
+        let mut blockchain = blockchain;
+        blockchain.push(Block::generate(&vec![], &blockchain[2], 3));
+        assert_eq!(get_chain_work(&blockchain[2..]), 17);
+        assert_eq!(blockchain[3].chain_work.get(), Some(&17));
+        assert_eq!(get_work(usize::MAX), u128::MAX);
     }
 
     #[test]
@@ -818,15 +1377,38 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         let mut unspent_tx_outs = vec![];
         let mut transaction_pool = vec![];
         let block = Block::generate_raw(&blockchain, &transactions);
-        assert!(add_block(&mut blockchain, &mut unspent_tx_outs, &mut transaction_pool, &block).is_ok());
+        let mut chain_index = ChainIndex::new(&blockchain);
+        let dust = add_block(&mut blockchain, &mut chain_index, &mut unspent_tx_outs, &mut transaction_pool, &block, &mut ValidationCache::default(), &ConsensusRules::new(Some(51)), &NetworkClock);
+        assert_eq!(dust.unwrap_err().code, 1001);
+        assert_eq!(blockchain.len(), 1);
+        assert!(add_block(&mut blockchain, &mut chain_index, &mut unspent_tx_outs, &mut transaction_pool, &block, &mut ValidationCache::default(), &ConsensusRules::default(), &NetworkClock).is_ok());
         assert_eq!(blockchain.len(), 2);
         assert_eq!(unspent_tx_outs.len(), 1);
         assert_eq!(transaction_pool.len(), 0);
+        assert!(chain_index.tx_index.contains(&transactions[0].id));
+    }
+
+    #[test]
+    fn test_chain_index_update() {
+        let coinbase = |index: usize| get_coinbase_transaction(RECEIVER_ADDRESS, index, None);
+        let get_hashed_block = |index: usize, hash: &str| Block { hash: hash.to_string(), ..get_block(index, vec![coinbase(index)]) };
+        let blockchain = vec![get_hashed_block(0, "a"), get_hashed_block(1, "b")];
+        let mut chain_index = ChainIndex::new(&blockchain[..1]);
+
+        chain_index.update(&blockchain);
+        assert!(chain_index.tx_index.contains(&coinbase(1).id));
+        chain_index.update(&blockchain);
+        assert!(chain_index.tx_index.get_is_valid_transactions(&[coinbase(2)]));
+
+        let fork = vec![get_hashed_block(0, "a"), get_hashed_block(2, "c")];
+        chain_index.update(&fork);
+        assert!(!chain_index.tx_index.contains(&coinbase(1).id));
+        assert!(chain_index.tx_index.contains(&coinbase(2).id));
     }
 
     #[test]
@@ -844,23 +1426,27 @@ mod test {
 
         let mut new_blockchain = blockchain.clone();
         new_blockchain.push(Block::generate(&vec![], previous, 0));
-        assert!(get_is_replace_chain(&blockchain, &new_blockchain));
+        assert!(get_is_replace_chain(&blockchain, &new_blockchain, &ConsensusRules::default(), &NetworkClock));
 
         let mut next = Block::generate(&vec![], previous, 0);
         next.hash = "invalid".to_string();
         let mut new_blockchain = blockchain.clone();
         new_blockchain.push(next);
-        assert!(!get_is_replace_chain(&blockchain, &new_blockchain));
+        assert!(!get_is_replace_chain(&blockchain, &new_blockchain, &ConsensusRules::default(), &NetworkClock));
+        assert_eq!(validate_chain(&blockchain, &new_blockchain, &ConsensusRules::default(), &NetworkClock).unwrap_err().code, 1000);
+        assert_eq!(validate_chain(&blockchain, &vec![], &ConsensusRules::default(), &NetworkClock).unwrap_err().code, 1000);
+        assert!(validate_chain(&blockchain, &blockchain, &ConsensusRules::default(), &NetworkClock).is_ok());
+        assert!(!get_is_heavier_chain(&blockchain, &blockchain));
 
         let mut new_blockchain = blockchain.clone();
         new_blockchain.push(Block::generate(&vec![], previous, 1));
-        assert!(get_is_replace_chain(&blockchain, &new_blockchain));
+        assert!(get_is_replace_chain(&blockchain, &new_blockchain, &ConsensusRules::default(), &NetworkClock));
 
         let mut a_blockchain = blockchain.clone();
         a_blockchain.push(Block::generate(&vec![], previous, 1));
         let mut b_blockchain = blockchain.clone();
         b_blockchain.push(Block::generate(&vec![], previous, 0));
-        assert!(!get_is_replace_chain(&a_blockchain, &b_blockchain));
+        assert!(!get_is_replace_chain(&a_blockchain, &b_blockchain, &ConsensusRules::default(), &NetworkClock));
     }
 
     #[test]
@@ -874,6 +1460,7 @@ mod test {
             0,
             0,
         )];
+        let mut chain_index = ChainIndex::new(&blockchain);
         let mut unspent_tx_outs = vec![];
         let mut transaction_pool = vec![];
         let difficulty = get_difficulty(&blockchain);
@@ -892,10 +1479,15 @@ mod test {
             ];
             let transactions = vec![Transaction::generate(&tx_ins, &tx_outs)];
             let block = Block::generate_raw(&blockchain, &transactions);
-            add_block(&mut blockchain, &mut unspent_tx_outs, &mut transaction_pool, &block).expect("error");
+            add_block(&mut blockchain, &mut chain_index, &mut unspent_tx_outs, &mut transaction_pool, &block, &mut ValidationCache::default(), &ConsensusRules::default(), &NetworkClock).expect("error");
         }
         let difficulty = get_difficulty(&blockchain);
         assert_eq!(difficulty, 1);
+
+        let params = ChainParams { block_interval: 0, ..ChainParams::default() };
+        assert_eq!(get_difficulty_with_params(&blockchain, &params), 0);
+        let params = ChainParams { network: Network::Regtest, ..ChainParams::default() };
+        assert_eq!(get_difficulty_with_params(&blockchain, &params), 0);
     }
 
     #[test]
@@ -911,10 +1503,10 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         let genesis_transaction = Transaction::new(
-            "b5516eb9915e9be6868575e87bb450d8285505f004f944bf0d99c6131995bf41".to_string(),
+            "616efe31dd3ab261b1be7ee5872873de00afbcd5f12b94f80989ed5accca66a9".to_string(),
             &vec![TxIn::new("".to_string(), 0, "".to_string())],
             &vec![TxOut::new(
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
@@ -923,7 +1515,7 @@ mod test {
         );
         let genesis_block = Block::new(
             0,
-            "c1fcd470499b2871ed8276cfcd3abbdca6ac1432515f30d59835c9d7e35e2756".to_string(),
+            "92b40e89629439d9173f38eda53cdec04ca5fc13fd21c913800edcfc6f42cfa7".to_string(),
             "".to_string(),
             1655831820,
             vec![genesis_transaction],
@@ -936,5 +1528,13 @@ mod test {
         ];
         let unspent_tx_outs = get_unspent_tx_outs(&blockchain).unwrap();
         assert_eq!(unspent_tx_outs.len(), 2);
+
+        let previous = get_latest_block(&blockchain).clone();
+        let coinbase_tx = get_coinbase_transaction(RECEIVER_ADDRESS, 2, None);
+        let missing_tx_ins = vec![TxIn::new(transactions[0].id.clone(), 1, "".to_string())];
+        let spending_missing = Transaction::generate(&missing_tx_ins, &tx_outs);
+        blockchain.push(Block::generate(&vec![coinbase_tx, spending_missing], &previous, 0));
+        assert!(validate_chain(&vec![genesis_block], &blockchain, &ConsensusRules::default(), &NetworkClock).is_ok());
+        assert_eq!(get_unspent_tx_outs(&blockchain).unwrap_err().code, 2002);
     }
 }
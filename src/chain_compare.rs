@@ -0,0 +1,246 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use serde::Serialize;
+use url::Url;
+
+use crate::Block;
+use crate::block::{get_chain_work, get_work};
+use crate::constants::MAX_HEADERS;
+use crate::errors::AppError;
+use crate::explorer::BlockHeader;
+
+const FETCH_TIMEOUT_SECS: u64 = 10;
+
+/// Pages of headers fetched at most, bounding the headers a peer can make the node hold
+const MAX_HEADER_PAGES: usize = 100;
+
+/// Bytes of a page response read at most, generous for `MAX_HEADERS` headers
+const MAX_PAGE_BYTES: u64 = MAX_HEADERS as u64 * 1024;
+
+/// Chain with more accumulated work.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HeavierChain {
+    Local,
+    Peer,
+    Equal,
+}
+
+/// Where the local chain and the chain of a peer diverge, from headers the peer reports without checking them.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ChainComparison {
+    pub peer: String,
+    pub local_height: usize,
+    pub peer_height: usize,
+
+    /// Height of the last block both chains share, none if their genesis blocks differ
+    pub fork_height: Option<usize>,
+    pub fork_hash: Option<String>,
+
+    /// Count of blocks of each chain after the fork, at heights where the chains differ
+    pub local_blocks_after_fork: usize,
+    pub peer_blocks_after_fork: usize,
+
+    /// Accumulated work of each chain
+    pub local_work: u128,
+    pub peer_work: u128,
+    pub heavier: HeavierChain,
+}
+
+/// Compare blockchain with headers of the chain of peer.
+pub fn compare_chains(peer: &str, blockchain: &[Block], peer_headers: &[BlockHeader]) -> ChainComparison {
+    let shared = blockchain
+        .iter()
+        .zip(peer_headers)
+        .take_while(|(block, header)| block.hash == header.hash)
+        .count();
+    let local_work = get_chain_work(blockchain);
+    let peer_work = peer_headers.iter().fold(0u128, |work, header| work.saturating_add(get_work(header.difficulty)));
+
+    ChainComparison {
+        peer: peer.to_string(),
+        local_height: blockchain.len() - 1,
+        peer_height: peer_headers.len().saturating_sub(1),
+        fork_height: shared.checked_sub(1),
+        fork_hash: shared.checked_sub(1).map(|height| blockchain[height].hash.clone()),
+        local_blocks_after_fork: blockchain.len() - shared,
+        peer_blocks_after_fork: peer_headers.len() - shared,
+        local_work,
+        peer_work,
+        heavier: match local_work.cmp(&peer_work) {
+            std::cmp::Ordering::Greater => HeavierChain::Local,
+            std::cmp::Ordering::Less => HeavierChain::Peer,
+            std::cmp::Ordering::Equal => HeavierChain::Equal,
+        },
+    }
+}
+
+/// Parse url of the http api of a peer.
+///
+/// # Errors
+/// If it is not an http url with a host, it returns error 15000.
+pub fn parse_peer_url(peer: &str) -> Result<Url, AppError> {
+    let url = Url::parse(peer).map_err(|_| AppError::new(15000))?;
+    if url.scheme() != "http" || url.host_str().is_none() {
+        return Err(AppError::new(15000));
+    }
+    Ok(url)
+}
+
+/// Fetch every header of the chain of the peer at url, from its `/api/headers` a page at a time, up to
+/// `MAX_HEADER_PAGES` pages.
+///
+/// # Errors
+/// If a page cannot be fetched or is too large, headers do not link to each other from the genesis block,
+/// or the chain has more pages, it returns error 15001.
+pub fn fetch_headers(url: &Url) -> Result<Vec<BlockHeader>, AppError> {
+    let mut headers: Vec<BlockHeader> = vec![];
+    for _ in 0..MAX_HEADER_PAGES {
+        let page = fetch_page(url, headers.len())?;
+        let is_last = page.len() < MAX_HEADERS;
+        for header in page {
+            if !get_is_following_header(&header, headers.last()) {
+                return Err(AppError::new(15001));
+            }
+            headers.push(header);
+        }
+        if is_last {
+            return match headers.is_empty() {
+                true => Err(AppError::new(15001)),
+                false => Ok(headers),
+            };
+        }
+    }
+    Err(AppError::new(15001))
+}
+
+/// Get flag header follows previous header by index and hash, or is the genesis header without previous header
+fn get_is_following_header(header: &BlockHeader, previous_header: Option<&BlockHeader>) -> bool {
+    match previous_header {
+        Some(previous_header) => header.index == previous_header.index + 1 && header.previous_hash == previous_header.hash,
+        None => header.index == 0,
+    }
+}
+
+fn fetch_page(url: &Url, from: usize) -> Result<Vec<BlockHeader>, AppError> {
+    let host = url.host_str().unwrap();
+    let address = (host, url.port_or_known_default().unwrap_or(80))
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or(AppError::new(15001))?;
+    let timeout = Duration::from_secs(FETCH_TIMEOUT_SECS);
+    let mut stream = TcpStream::connect_timeout(&address, timeout).map_err(|_| AppError::new(15001))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|_| AppError::new(15001))?;
+
+    // HTTP/1.0 keeps the body unchunked and the connection closed after it.
+    let target = format!("{}/api/headers?from={}", url.path().trim_end_matches('/'), from);
+    let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n", target, host);
+    stream.write_all(request.as_bytes()).map_err(|_| AppError::new(15001))?;
+    let mut response = String::new();
+    stream.take(MAX_PAGE_BYTES + 1).read_to_string(&mut response).map_err(|_| AppError::new(15001))?;
+    if response.len() as u64 > MAX_PAGE_BYTES {
+        return Err(AppError::new(15001));
+    }
+
+    let (head, body) = response.split_once("\r\n\r\n").ok_or(AppError::new(15001))?;
+    if head.split_whitespace().nth(1) != Some("200") {
+        return Err(AppError::new(15001));
+    }
+    serde_json::from_str(body).map_err(|_| AppError::new(15001))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::thread;
+    use crate::explorer::get_block_headers;
+    use crate::testkit::{get_genesis_block, ALICE, BOB};
+    use crate::transaction::get_coinbase_transaction;
+    use super::*;
+
+    #[test]
+    fn test_compare_chains() {
+        let genesis_block = get_genesis_block();
+        let a1 = Block::generate(&vec![get_coinbase_transaction(ALICE.address, 1, None)], &genesis_block, 0);
+        let a2 = Block::generate(&vec![get_coinbase_transaction(ALICE.address, 2, None)], &a1, 0);
+        let b1 = Block::generate(&vec![get_coinbase_transaction(BOB.address, 1, None)], &genesis_block, 1);
+        let local = vec![genesis_block.clone(), a1, a2];
+        let peer = vec![genesis_block.clone(), b1];
+
+        let comparison = compare_chains("http://peer", &local, &get_block_headers(&peer, 0, usize::MAX));
+        assert_eq!((comparison.local_height, comparison.peer_height), (2, 1));
+        assert_eq!(comparison.fork_height, Some(0));
+        assert_eq!(comparison.fork_hash, Some(genesis_block.hash.clone()));
+        assert_eq!((comparison.local_blocks_after_fork, comparison.peer_blocks_after_fork), (2, 1));
+        assert_eq!((comparison.local_work, comparison.peer_work), (3, 3));
+        assert_eq!(comparison.heavier, HeavierChain::Equal);
+
+        let comparison = compare_chains("http://peer", &local[..2], &get_block_headers(&local, 0, usize::MAX));
+        assert_eq!(comparison.fork_height, Some(1));
+        assert_eq!((comparison.local_blocks_after_fork, comparison.peer_blocks_after_fork), (0, 1));
+        assert_eq!(comparison.heavier, HeavierChain::Peer);
+
+        let mut headers = get_block_headers(&peer, 0, usize::MAX);
+        headers[0].hash = "other".to_string();
+        assert_eq!(compare_chains("http://peer", &local, &headers).fork_height, None);
+    }
+
+    #[test]
+    fn test_get_is_following_header() {
+        let genesis_block = get_genesis_block();
+        let a1 = Block::generate(&vec![get_coinbase_transaction(ALICE.address, 1, None)], &genesis_block, 0);
+        let headers = get_block_headers(&vec![genesis_block.clone(), a1], 0, usize::MAX);
+        assert!(get_is_following_header(&headers[0], None));
+        assert!(get_is_following_header(&headers[1], Some(&headers[0])));
+        assert!(!get_is_following_header(&headers[1], None));
+        assert!(!get_is_following_header(&headers[0], Some(&headers[0])));
+
+        let mut forged = headers[1].clone();
+        forged.previous_hash = "other".to_string();
+        assert!(!get_is_following_header(&forged, Some(&headers[0])));
+    }
+
+    #[test]
+    fn test_fetch_headers() {
+        let headers = get_block_headers(&vec![get_genesis_block()], 0, 0);
+        let body = serde_json::to_string(&headers).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request = [0u8; 1024];
+                let size = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..size]).to_string();
+                let response = match request.starts_with("GET /node/api/headers?from=0 HTTP/1.0\r\n") {
+                    true => format!("HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{}", body),
+                    false => "HTTP/1.0 404 Not Found\r\n\r\n".to_string(),
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let url = parse_peer_url(&format!("http://127.0.0.1:{}/node/", port)).unwrap();
+        assert_eq!(fetch_headers(&url).unwrap(), headers);
+        let url = parse_peer_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+        assert_eq!(fetch_headers(&url).unwrap_err().code, 15001);
+        server.join().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 1024]);
+            let _ = stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n");
+            let _ = stream.write_all(&vec![b' '; MAX_PAGE_BYTES as usize]);
+        });
+        let url = parse_peer_url(&format!("http://127.0.0.1:{}", port)).unwrap();
+        assert_eq!(fetch_headers(&url).unwrap_err().code, 15001);
+        server.join().unwrap();
+
+        assert_eq!(parse_peer_url("ws://127.0.0.1:6001").unwrap_err().code, 15000);
+        assert_eq!(parse_peer_url("127.0.0.1:3001").unwrap_err().code, 15000);
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(test)]
+use std::cell::Cell;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use crate::constants::DEFAULT_BLOCK_INTERVAL;
+use crate::errors::AppError;
+
+static NETWORK: AtomicU8 = AtomicU8::new(Network::Main as u8);
+static BLOCK_INTERVAL: AtomicUsize = AtomicUsize::new(DEFAULT_BLOCK_INTERVAL);
+
+#[cfg(test)]
+thread_local! {
+    /// Parameters of the network of the running test, so tests of other networks run side by side.
+    static TEST_CHAIN_PARAMS: Cell<Option<ChainParams>> = const { Cell::new(None) };
+}
+
+/// Network a node is part of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Network {
+    /// Network retargeting difficulty to its block interval
+    #[default]
+    Main = 0,
+
+    /// Local network of tests, mining every block at difficulty 0 and generating blocks on request
+    Regtest = 1,
+}
+
+impl Network {
+    fn from_u8(value: u8) -> Network {
+        match value {
+            1 => Network::Regtest,
+            _ => Network::Main,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "main" => Ok(Network::Main),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(AppError::new(1006)),
+        }
+    }
+}
+
+/// Parameters of block generation of a network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainParams {
+    pub network: Network,
+
+    /// Seconds targeted between blocks when difficulty is adjusted
+    pub block_interval: usize,
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        ChainParams { network: Network::default(), block_interval: DEFAULT_BLOCK_INTERVAL }
+    }
+}
+
+impl ChainParams {
+    /// Get flag blocks are mined at difficulty 0 and generated on request
+    pub fn get_is_regtest(&self) -> bool {
+        self.network == Network::Regtest
+    }
+}
+
+/// Get parameters of block generation of this network.
+pub fn get_chain_params() -> ChainParams {
+    #[cfg(test)]
+    if let Some(params) = TEST_CHAIN_PARAMS.with(Cell::get) {
+        return params;
+    }
+
+    ChainParams {
+        network: Network::from_u8(NETWORK.load(Ordering::Relaxed)),
+        block_interval: BLOCK_INTERVAL.load(Ordering::Relaxed),
+    }
+}
+
+/// Set parameters of block generation of this network, once on start.
+#[cfg(feature = "node")]
+pub fn set_chain_params(params: ChainParams) {
+    NETWORK.store(params.network as u8, Ordering::Relaxed);
+    BLOCK_INTERVAL.store(params.block_interval, Ordering::Relaxed);
+}
+
+/// Parameters of regtest, for tests of rules it activates.
+#[cfg(test)]
+pub(crate) const REGTEST: ChainParams = ChainParams { network: Network::Regtest, block_interval: DEFAULT_BLOCK_INTERVAL };
+
+/// Run f with the parameters of this network set to params for the current thread only.
+#[cfg(test)]
+pub(crate) fn with_chain_params<T>(params: ChainParams, f: impl FnOnce() -> T) -> T {
+    let previous = TEST_CHAIN_PARAMS.with(|test_params| test_params.replace(Some(params)));
+    let result = f();
+    TEST_CHAIN_PARAMS.with(|test_params| test_params.set(previous));
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_network() {
+        assert_eq!("regtest".parse::<Network>().unwrap(), Network::Regtest);
+        assert!("testnet".parse::<Network>().is_err());
+        assert_eq!(Network::from_u8(Network::Regtest as u8), Network::Regtest);
+        assert!(!ChainParams::default().get_is_regtest());
+        assert_eq!(get_chain_params(), ChainParams::default());
+    }
+}
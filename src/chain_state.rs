@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use arc_swap::ArcSwap;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::Block;
+use crate::block::ChainIndex;
+use crate::network_time::get_adjusted_timestamp;
+use crate::propagation::{ArrivalLog, BlockArrival};
+use crate::reorg::{get_reorg, Reorg, ReorgHistory};
+
+/// Blockchain published as an immutable snapshot.
+///
+/// Readers load the latest snapshot without locking, writers are serialized and replace it
+/// as a whole, so mining and broadcasting never block readers. Replacements which drop
+/// blocks of the previous snapshot are kept as reorg history, and blocks they add as arrivals.
+///
+/// Blocks an operator invalidated are never part of a chain the node adopts, until reconsidered.
+///
+/// Tx and name indexes of the latest snapshot are kept up to date as it is replaced, so appending
+/// a block checks it without replaying the chain.
+#[derive(Debug)]
+pub struct ChainState {
+    snapshot: ArcSwap<Vec<Block>>,
+    writer: Mutex<()>,
+    reorgs: ReorgHistory,
+    arrivals: ArrivalLog,
+    invalidated: RwLock<HashSet<String>>,
+    chain_index: RwLock<ChainIndex>,
+}
+
+/// Exclusive right to replace the blockchain, released on drop.
+pub struct ChainWriter<'a> {
+    state: &'a ChainState,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl ChainState {
+    /// Returns a chain state publishing the blockchain
+    pub fn new(blockchain: Vec<Block>) -> ChainState {
+        ChainState {
+            chain_index: RwLock::new(ChainIndex::new(&blockchain)),
+            snapshot: ArcSwap::from_pointee(blockchain),
+            writer: Mutex::new(()),
+            reorgs: ReorgHistory::default(),
+            arrivals: ArrivalLog::default(),
+            invalidated: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Get latest snapshot without blocking
+    pub fn load(&self) -> Arc<Vec<Block>> {
+        self.snapshot.load_full()
+    }
+
+    /// Wait for other writers and return the right to replace the blockchain
+    pub async fn write(&self) -> ChainWriter<'_> {
+        ChainWriter {
+            _guard: self.writer.lock().await,
+            state: self,
+        }
+    }
+
+    /// Same as `write`, blocking the current thread outside of async code
+    pub fn blocking_write(&self) -> ChainWriter<'_> {
+        ChainWriter {
+            _guard: self.writer.blocking_lock(),
+            state: self,
+        }
+    }
+
+    /// Get indexes of the latest snapshot. Release it before taking any other lock, the snapshot is not replaced while it is held.
+    pub fn read_chain_index(&self) -> RwLockReadGuard<'_, ChainIndex> {
+        self.chain_index.read().unwrap()
+    }
+
+    /// Get reorgs of the blockchain, latest first
+    pub fn get_reorgs(&self) -> Vec<Reorg> {
+        self.reorgs.get_reorgs()
+    }
+
+    /// Get arrival of block hash
+    pub fn get_arrival(&self, hash: &str) -> Option<BlockArrival> {
+        self.arrivals.get(hash)
+    }
+
+    /// Get up to limit block arrivals, latest first
+    pub fn get_arrivals(&self, limit: usize) -> Vec<BlockArrival> {
+        self.arrivals.get_arrivals(limit)
+    }
+
+    /// Get hashes of invalidated blocks, sorted
+    pub fn get_invalidated(&self) -> Vec<String> {
+        let mut hashes: Vec<String> = self.invalidated.read().unwrap().iter().cloned().collect();
+        hashes.sort();
+        hashes
+    }
+
+    /// Get flag blockchain includes an invalidated block, and so descends from it
+    pub fn get_has_invalidated(&self, blockchain: &[Block]) -> bool {
+        let invalidated = self.invalidated.read().unwrap();
+        !invalidated.is_empty() && blockchain.iter().any(|block| invalidated.contains(&block.hash))
+    }
+}
+
+impl<'a> ChainWriter<'a> {
+    /// Get latest snapshot, unchanged until stored by this writer
+    pub fn load(&self) -> Arc<Vec<Block>> {
+        self.state.load()
+    }
+
+    /// Publish blockchain received from source as the latest snapshot, none if mined locally.
+    ///
+    /// Records a reorg if it drops blocks, and arrival of the blocks it adds.
+    pub fn store(&self, blockchain: Vec<Block>, source: Option<&str>) -> Arc<Vec<Block>> {
+        let previous = self.load();
+        if let Some(reorg) = get_reorg(&previous, &blockchain) {
+            self.state.reorgs.record(reorg);
+        }
+        let shared = previous.iter().zip(&blockchain).take_while(|(old, new)| old.hash == new.hash).count();
+        self.state.arrivals.record(&blockchain[shared..], source, get_adjusted_timestamp());
+        let mut chain_index = self.state.chain_index.write().unwrap();
+        chain_index.update(&blockchain);
+        let blockchain = Arc::new(blockchain);
+        self.state.snapshot.store(Arc::clone(&blockchain));
+        blockchain
+    }
+
+    /// Get indexes of the latest snapshot, to add a block to it before it is stored.
+    /// Release it before storing.
+    pub fn lock_chain_index(&self) -> RwLockWriteGuard<'_, ChainIndex> {
+        self.state.chain_index.write().unwrap()
+    }
+
+    /// Mark block hash invalid, once the latest snapshot no longer includes it
+    pub fn invalidate(&self, hash: &str) {
+        self.state.invalidated.write().unwrap().insert(hash.to_string());
+    }
+
+    /// Unmark block hash invalid, returning whether it was
+    pub fn reconsider(&self, hash: &str) -> bool {
+        self.state.invalidated.write().unwrap().remove(hash)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{get_genesis_block, GENESIS_BLOCK_HASH, RECEIVER_ADDRESS};
+    use crate::transaction::get_coinbase_transaction;
+    use super::*;
+
+    #[test]
+    fn test_chain_state() {
+        let genesis_block = get_genesis_block();
+        let chain_state = ChainState::new(vec![genesis_block.clone()]);
+        let snapshot = chain_state.load();
+
+        let writer = chain_state.blocking_write();
+        let next = Block::generate_raw(&writer.load(), &vec![]);
+        assert_eq!(chain_state.load().len(), 1);
+
+        writer.store(vec![genesis_block, next.clone()], None);
+        assert_eq!(chain_state.load().len(), 2);
+        assert_eq!(chain_state.load().last().unwrap(), &next);
+        assert_eq!(snapshot.len(), 1);
+        assert!(chain_state.get_reorgs().is_empty());
+
+        let coinbase = get_coinbase_transaction(RECEIVER_ADDRESS, 1, None);
+        let mut fork = Block::generate_raw(&snapshot, &vec![coinbase.clone()]);
+        fork.nonce += 1;
+        fork.hash = fork.get_calculated_hash();
+        writer.store(vec![get_genesis_block(), fork.clone()], Some("peer-a"));
+        let reorgs = chain_state.get_reorgs();
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].old_tip, next.hash);
+        assert_eq!(reorgs[0].new_tip, fork.hash);
+        assert!(writer.lock_chain_index().tx_index.contains(&coinbase.id));
+
+        assert!(chain_state.get_arrival(GENESIS_BLOCK_HASH).is_none());
+        assert_eq!(chain_state.get_arrival(&next.hash).unwrap().source, None);
+        assert_eq!(chain_state.get_arrival(&fork.hash).unwrap().source, Some("peer-a".to_string()));
+        assert_eq!(chain_state.get_arrivals(10).len(), 2);
+
+        writer.invalidate(&next.hash);
+        assert_eq!(chain_state.get_invalidated(), vec![next.hash.clone()]);
+        assert!(chain_state.get_has_invalidated(&[get_genesis_block(), next.clone()]));
+        assert!(!chain_state.get_has_invalidated(&[get_genesis_block(), fork]));
+        assert!(writer.reconsider(&next.hash));
+        assert!(!writer.reconsider(&next.hash));
+        assert!(!chain_state.get_has_invalidated(&[get_genesis_block(), next]));
+    }
+}
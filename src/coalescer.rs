@@ -0,0 +1,113 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::{Block, Transaction};
+use crate::events::BroadcastEvents;
+use crate::payload::{Payload, PayloadType};
+
+/// Keeps only the latest chain and pool update within a broadcast window.
+///
+/// Both updates are full snapshots, so a newer one replaces the pending one. The peer it came
+/// from is only skipped if every merged update came from that peer.
+#[derive(Debug, Default)]
+pub struct BroadcastCoalescer {
+    blockchain: Option<(Arc<Vec<Block>>, Option<String>)>,
+    transactions: Option<(Vec<Transaction>, Option<String>)>,
+}
+
+fn merge_except(pending: Option<Option<String>>, except: Option<String>) -> Option<String> {
+    match pending {
+        Some(previous) if previous != except => None,
+        _ => except,
+    }
+}
+
+impl BroadcastCoalescer {
+    /// Get nothing is pending
+    pub fn is_empty(&self) -> bool {
+        self.blockchain.is_none() && self.transactions.is_none()
+    }
+
+    /// Hold a chain or pool update, returning any other event untouched
+    pub fn push(&mut self, event: BroadcastEvents) -> Option<BroadcastEvents> {
+        match event {
+            BroadcastEvents::Blockchain(blockchain, except) => {
+                let except = merge_except(self.blockchain.take().map(|(_, p)| p), except);
+                self.blockchain = Some((blockchain, except));
+                None
+            }
+            BroadcastEvents::Transaction(transactions, except) => {
+                let except = merge_except(self.transactions.take().map(|(_, p)| p), except);
+                self.transactions = Some((transactions, except));
+                None
+            }
+            event => Some(event),
+        }
+    }
+
+    /// Take pending updates, chain first so peers can validate the pool against it
+    pub fn take(&mut self) -> Vec<BroadcastEvents> {
+        let mut events = vec![];
+        if let Some((blockchain, except)) = self.blockchain.take() {
+            events.push(BroadcastEvents::Blockchain(blockchain, except));
+        }
+        if let Some((transactions, except)) = self.transactions.take() {
+            events.push(BroadcastEvents::Transaction(transactions, except));
+        }
+        events
+    }
+}
+
+/// Digest of the last payload of each type sent to a peer.
+#[derive(Debug, Default)]
+pub struct SentDigests {
+    digests: HashMap<PayloadType, u64>,
+}
+
+impl SentDigests {
+    /// Record payload as sent, returning false if it is identical to the last one of its type
+    pub fn insert(&mut self, payload: &Payload) -> bool {
+        let mut hasher = DefaultHasher::new();
+        payload.data.hash(&mut hasher);
+        self.digests.insert(payload.r#type, hasher.finish()) != Some(hasher.finish())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::get_genesis_block;
+    use super::*;
+
+    #[test]
+    fn test_broadcast_coalescer() {
+        let mut coalescer = BroadcastCoalescer::default();
+        let blockchain = Arc::new(vec![get_genesis_block()]);
+        assert!(coalescer.is_empty());
+
+        assert!(coalescer.push(BroadcastEvents::Transaction(vec![], Some("a".to_string()))).is_none());
+        assert!(coalescer.push(BroadcastEvents::Transaction(vec![], Some("a".to_string()))).is_none());
+        assert!(coalescer.push(BroadcastEvents::Blockchain(Arc::clone(&blockchain), Some("a".to_string()))).is_none());
+        assert!(coalescer.push(BroadcastEvents::Blockchain(Arc::clone(&blockchain), None)).is_none());
+        assert!(coalescer.push(BroadcastEvents::QueryLatest).is_some());
+
+        let events = coalescer.take();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], BroadcastEvents::Blockchain(_, None)));
+        assert!(matches!(&events[1], BroadcastEvents::Transaction(_, Some(p)) if p == "a"));
+        assert!(coalescer.is_empty());
+    }
+
+    #[test]
+    fn test_sent_digests() {
+        let mut sent_digests = SentDigests::default();
+        let blockchain = vec![get_genesis_block()];
+        let payload = Payload::new(PayloadType::Blockchain, &blockchain);
+
+        assert!(sent_digests.insert(&payload));
+        assert!(!sent_digests.insert(&payload));
+        assert!(sent_digests.insert(&Payload::new(PayloadType::Transaction, &blockchain)));
+        assert!(sent_digests.insert(&Payload::new(PayloadType::Blockchain, &vec![0])));
+    }
+}
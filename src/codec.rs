@@ -0,0 +1,233 @@
+use crate::pow::PowAlgorithm;
+use crate::script::{Op, Script};
+use crate::transaction::{Transaction, TxIn, TxOut, UnspentTxOut};
+
+/// Canonical binary encoding of consensus data, the content of block hashes and transaction ids.
+///
+/// Fields are written in an explicit order with big-endian fixed-width integers, and strings and
+/// lists are prefixed with their length, so hashes do not depend on serde attributes or serializers.
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+/// Writer of canonical bytes.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    /// Returns an empty encoder
+    pub fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Put usize as 8 bytes, so encoding is the same on every platform
+    pub fn put_usize(&mut self, value: usize) {
+        self.bytes.extend_from_slice(&(value as u64).to_be_bytes());
+    }
+
+    /// Put string prefixed with its length in bytes
+    pub fn put_str(&mut self, value: &str) {
+        self.put_usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    /// Put a flag byte, followed by the value if it exists
+    pub fn put_option<T: Encode>(&mut self, value: &Option<T>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                value.encode(self);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    /// Put values prefixed with their count
+    pub fn put_list<T: Encode>(&mut self, values: &[T]) {
+        self.put_usize(values.len());
+        for value in values {
+            value.encode(self);
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Get canonical bytes of value.
+pub fn encode<T: Encode + ?Sized>(value: &T) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    value.encode(&mut encoder);
+    encoder.into_bytes()
+}
+
+/// Get bytes identifying a transaction, leaving out signatures which sign the id.
+///
+/// Extra data is appended only if it exists, so ids of transactions without it stay the same.
+pub fn encode_transaction_content(version: u32, tx_ins: &[TxIn], tx_outs: &[TxOut], extra_data: Option<&str>) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.put_u32(version);
+    encoder.put_usize(tx_ins.len());
+    for tx_in in tx_ins {
+        encoder.put_str(&tx_in.tx_out_id);
+        encoder.put_usize(tx_in.tx_out_index);
+    }
+    encoder.put_list(tx_outs);
+    if let Some(extra_data) = extra_data {
+        encoder.put_str(extra_data);
+    }
+    encoder.into_bytes()
+}
+
+/// Get bytes of a block hashed by proof of work, ending with the nonce.
+///
+/// Transactions are written in place of their root only if it does not exist, and utxo commitment only if it exists,
+/// so hashes of blocks without them stay the same.
+pub fn encode_block_content(
+    version: u32,
+    pow: PowAlgorithm,
+    index: usize,
+    previous_hash: &str,
+    timestamp: usize,
+    data: &[Transaction],
+    tx_root: Option<&str>,
+    utxo_commitment: Option<&str>,
+    difficulty: usize,
+    nonce: usize,
+) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    encoder.put_u32(version);
+    encoder.put_u8(pow as u8);
+    encoder.put_usize(index);
+    encoder.put_str(previous_hash);
+    encoder.put_usize(timestamp);
+    match tx_root {
+        Some(tx_root) => encoder.put_str(tx_root),
+        None => encoder.put_list(data),
+    }
+    if let Some(utxo_commitment) = utxo_commitment {
+        encoder.put_str(utxo_commitment);
+    }
+    encoder.put_usize(difficulty);
+    encoder.put_usize(nonce);
+    encoder.into_bytes()
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_str(self);
+    }
+}
+
+impl Encode for TxIn {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_str(&self.tx_out_id);
+        encoder.put_usize(self.tx_out_index);
+        encoder.put_str(&self.signature);
+    }
+}
+
+impl Encode for TxOut {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_str(&self.address);
+        encoder.put_usize(self.amount);
+        encoder.put_option(&self.script);
+        encoder.put_option(&self.asset);
+    }
+}
+
+impl Encode for UnspentTxOut {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_str(&self.tx_out_id);
+        encoder.put_usize(self.tx_out_index);
+        encoder.put_str(&self.address);
+        encoder.put_usize(self.amount);
+        encoder.put_option(&self.script);
+        encoder.put_option(&self.asset);
+    }
+}
+
+impl Encode for Transaction {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_u32(self.version);
+        encoder.put_str(&self.id);
+        encoder.put_list(&self.tx_ins);
+        encoder.put_list(&self.tx_outs);
+    }
+}
+
+impl Encode for Script {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put_list(&self.0);
+    }
+}
+
+impl Encode for Op {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Op::Push(data) => {
+                encoder.put_u8(0);
+                encoder.put_str(data);
+            }
+            Op::CheckSig => encoder.put_u8(1),
+            Op::CheckMultisig(required, count) => {
+                encoder.put_u8(2);
+                encoder.put_usize(*required);
+                encoder.put_usize(*count);
+            }
+            Op::CheckLocktime(timestamp) => {
+                encoder.put_u8(3);
+                encoder.put_usize(*timestamp);
+            }
+            Op::Data(data) => {
+                encoder.put_u8(4);
+                encoder.put_str(data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let tx_out = TxOut::new("a".to_string(), 1);
+        assert_eq!(encode(&tx_out), vec![0, 0, 0, 0, 0, 0, 0, 1, b'a', 0, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+
+        let tx_out = TxOut::with_script("a".to_string(), 1, Script::pay_to_public_key("b"));
+        assert_eq!(&encode(&tx_out)[17..], &[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 1, b'b', 1, 0]);
+
+        let tx_in = TxIn::new("a".to_string(), 0, "signature".to_string());
+        let unsigned = TxIn::new("a".to_string(), 0, "".to_string());
+        assert_eq!(
+            encode_transaction_content(1, &vec![tx_in], &vec![], None),
+            encode_transaction_content(1, &vec![unsigned], &vec![], None),
+        );
+        assert_ne!(encode_transaction_content(1, &vec![], &vec![], None), encode_transaction_content(2, &vec![], &vec![], None));
+        assert_ne!(encode_transaction_content(1, &vec![], &vec![], None), encode_transaction_content(1, &vec![], &vec![], Some("")));
+        assert_ne!(
+            encode_block_content(1, PowAlgorithm::Sha256, 1, "a", 0, &vec![], None, None, 0, 0),
+            encode_block_content(1, PowAlgorithm::Sha256, 1, "", 0, &vec![], None, None, 0, 0),
+        );
+        let content = encode_block_content(1, PowAlgorithm::Sha256, 1, "", 0, &vec![], None, Some("a"), 0, 7);
+        assert_ne!(content, encode_block_content(1, PowAlgorithm::Sha256, 1, "", 0, &vec![], None, None, 0, 7));
+        assert_eq!(&content[content.len() - 8..], &7u64.to_be_bytes());
+        assert_ne!(
+            encode_block_content(1, PowAlgorithm::Sha256, 1, "", 0, &vec![], Some("a"), None, 0, 0),
+            encode_block_content(1, PowAlgorithm::Sha256, 1, "", 0, &vec![], None, None, 0, 0),
+        );
+    }
+}
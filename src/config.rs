@@ -1,7 +1,23 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use uuid::Uuid;
 use rustop::opts;
 
-use crate::constants::{DEFAULT_WEBSOCKET_PORT, DEFAULT_HTTP_PORT, PRIVATE_KEY_PATH};
+use crate::block::MAX_MINER_TAG;
+use crate::chain_params::{ChainParams, Network};
+use crate::constants::{DEFAULT_WEBSOCKET_PORT, DEFAULT_HTTP_PORT, LABELS_PATH, PRIVATE_KEY_PATH, WEBHOOKS_PATH};
+use crate::constants::{DEFAULT_DUST_LIMIT, DEFAULT_MAX_INBOUND, DEFAULT_MAX_OUTBOUND, DEFAULT_PRICE_INTERVAL, DEFAULT_SHARE_DIFFICULTY};
+use crate::constants::{DEFAULT_BLOCK_INTERVAL, DEFAULT_DNS_SEED_INTERVAL, DEFAULT_MAX_STREAMS, DEFAULT_STATSD_INTERVAL};
+use crate::dns_seed::DnsSeed;
+use crate::consensus_rules::ConsensusRules;
+use crate::errors::AppError;
+use crate::mempool_policy::MempoolPolicy;
+use crate::miner::MiningPolicy;
+use crate::pow::PowAlgorithm;
+use crate::routes::FieldNaming;
+use crate::signer::{get_verifier, KeyType};
+use crate::spend_policy::SpendPolicy;
+use crate::transport::TransportKind;
+use crate::wire::WireFormat;
 
 /// Current app config for blockchain
 #[derive(Debug)]
@@ -17,6 +33,153 @@ pub struct Config {
 
     /// path of private key
     pub private_key_path: String,
+
+    /// type of key created when private key does not exist
+    pub key_type: KeyType,
+
+    /// start even if private key is readable by other users
+    pub allow_insecure_key: bool,
+
+    /// token for admin endpoints
+    pub admin_token: Option<String>,
+
+    /// allow admin rollback of the chain on main network
+    pub allow_rollback: bool,
+
+    /// compress http responses
+    pub compression: bool,
+
+    /// naming of fields of blocks, transactions and unspent tx outs in http responses
+    pub api_field_naming: FieldNaming,
+
+    /// indent json of blocks, transactions and unspent tx outs in http responses
+    pub api_pretty: bool,
+
+    /// endpoint of external signer used instead of private key
+    pub remote_signer: Option<String>,
+
+    /// rotate private key on start
+    pub rotate_key: bool,
+
+    /// smallest amount of an output accepted by pool and wallet
+    pub dust_limit: usize,
+
+    /// smallest fee of a transaction accepted by pool
+    pub min_fee: usize,
+
+    /// accept pool transactions with nonstandard scripts
+    pub allow_nonstandard: bool,
+
+    /// smallest amount of an output in a block, any when none
+    pub consensus_dust_limit: Option<usize>,
+
+    /// most connections accepted from peers
+    pub max_inbound: usize,
+
+    /// most connections dialed to peers
+    pub max_outbound: usize,
+
+    /// announce and dial peers on the local network
+    pub discovery: bool,
+
+    /// addresses socket listens on, IPv4 or IPv6
+    pub bind_addresses: Vec<IpAddr>,
+
+    /// DNS names listing bootstrap peers
+    pub dns_seeds: Vec<DnsSeed>,
+
+    /// seconds between resolutions of DNS seeds
+    pub dns_seed_interval: u64,
+
+    /// backend connecting to peers
+    pub transport: TransportKind,
+
+    /// encoding of payloads written to peers
+    pub wire_format: WireFormat,
+
+    /// secret shared by nodes of a private network, signing every payload
+    pub network_secret: Option<String>,
+
+    /// hash algorithm blocks of the network are mined with
+    pub pow_algorithm: PowAlgorithm,
+
+    /// network of the node, regtest mining every block at difficulty 0
+    pub network: Network,
+
+    /// seconds targeted between blocks of the network
+    pub block_interval: usize,
+
+    /// sync an empty chain from a utxo snapshot of a peer, replaying only blocks after it
+    pub fast_sync: bool,
+
+    /// path of journal recording received payloads and http mutations
+    pub journal: Option<String>,
+
+    /// path of journal replayed instead of running the node
+    pub replay: Option<String>,
+
+    /// path of local labels of transactions and addresses
+    pub labels_path: String,
+
+    /// most coins the wallet pays others in a transaction
+    pub max_spend: Option<usize>,
+
+    /// most coins the wallet pays others in a day
+    pub daily_spend_limit: Option<usize>,
+
+    /// only addresses the wallet pays, any when empty
+    pub allowed_destinations: Vec<String>,
+
+    /// http url of a JSON document holding the fiat price of a coin
+    pub price_feed_url: Option<String>,
+
+    /// dotted path of the price in the document of price feed
+    pub price_feed_path: String,
+
+    /// fiat currency of price feed
+    pub price_currency: String,
+
+    /// seconds between fetches of price feed
+    pub price_interval: u64,
+
+    /// address paid by coinbase of mined blocks, the wallet when none
+    pub payout_address: Option<String>,
+
+    /// text embedded in coinbase of mined blocks
+    pub miner_tag: Option<String>,
+
+    /// most hashes tried per second by the miner, unlimited when none
+    pub max_hashrate: Option<usize>,
+
+    /// wait for transactions in the pool before mining coinbase jobs
+    pub pause_empty_pool: bool,
+
+    /// seconds since the latest block after which an empty pool is mined anyway
+    pub empty_block_interval: Option<u64>,
+
+    /// port of stratum for external workers, disabled when none
+    pub stratum_port: Option<u16>,
+
+    /// leading zero bits of a share submitted to stratum
+    pub share_difficulty: usize,
+
+    /// http url called back by watches of transactions and addresses, disabled when none
+    pub webhook_url: Option<String>,
+
+    /// path of watches and undelivered callbacks of webhooks
+    pub webhooks_path: String,
+
+    /// address of statsd pushed metrics of the node, disabled when none
+    pub statsd_address: Option<String>,
+
+    /// prefix of names of metrics pushed to statsd
+    pub statsd_prefix: String,
+
+    /// seconds between pushes of metrics to statsd
+    pub statsd_interval: u64,
+
+    /// most clients of the tip stream at once, each holding an http worker
+    pub max_streams: usize,
 }
 
 impl Config {
@@ -29,14 +192,217 @@ impl Config {
     /// let config = Config::new();
     /// ```
     pub fn new() -> Config {
+        Config::parse(None)
+    }
+
+    /// Returns a config with args given instead of those of the command line, such as `["--dust-limit", "5"]`.
+    ///
+    /// # Panics
+    /// If args are invalid, as the command line would exit.
+    pub fn from_args(args: &[&str]) -> Config {
+        Config::parse(Some(args))
+    }
+
+    fn parse(cli_args: Option<&[&str]>) -> Config {
         let uuid = format!("{}", Uuid::new_v4());
-        let (args, _) = opts! {
+        let parser = opts! {
             synopsis "This is a blockchain program."; // short info message for the help page
             opt socket_port:u16 = DEFAULT_WEBSOCKET_PORT, desc:"The port of socket."; // an option -s or --socket-port
             opt http_port:u16 = DEFAULT_HTTP_PORT, desc:"The port of http."; // an option -t or --http-port
             opt private_key_path:String = PRIVATE_KEY_PATH.to_string(), desc:"The path of private key."; // an option -p or --private-key-path
-        }.parse_or_exit();
+            opt key_type:String = "secp256k1".to_string(), desc:"The type of created key, secp256k1 or ed25519."; // an option -k or --key-type
+            opt allow_insecure_key:bool, desc:"Start even if the private key file is readable by other users."; // a flag --allow-insecure-key
+            opt admin_token:Option<String>, desc:"The token for admin endpoints."; // an option -a or --admin-token
+            opt allow_rollback:bool, desc:"Allow admins to roll back the chain or invalidate blocks on main network, always allowed on regtest."; // a flag --allow-rollback
+            opt compression:bool = true, desc:"Disable compression of http responses."; // a flag -c or --no-compression
+            opt api_field_naming:String = "snake".to_string(), desc:"The naming of fields of blocks, transactions and unspent tx outs in http responses, snake or camel like naivecoin."; // an option --api-field-naming
+            opt api_pretty:bool, desc:"Indent json of blocks, transactions and unspent tx outs in http responses, unless asked with ?pretty=false."; // a flag --api-pretty
+            opt remote_signer:Option<String>, desc:"The endpoint of external signer, e.g. 127.0.0.1:7000."; // an option -r or --remote-signer
+            opt rotate_key:bool, desc:"Rotate private key on start, sweeping its balance to the new key."; // a flag -o or --rotate-key
+            opt dust_limit:usize = DEFAULT_DUST_LIMIT, desc:"The smallest amount of an output accepted by pool and wallet."; // an option -d or --dust-limit
+            opt min_fee:usize = 0, desc:"The smallest fee of a transaction accepted by pool."; // an option --min-fee
+            opt allow_nonstandard:bool, desc:"Accept pool transactions with scripts other than pay to public key, multisig, locktime and data."; // a flag --allow-nonstandard
+            opt consensus_dust_limit:Option<usize>, desc:"Reject blocks with outputs below this amount, a consensus rule every node of a network must share."; // an option --consensus-dust-limit
+            opt max_inbound:usize = DEFAULT_MAX_INBOUND, desc:"The most connections accepted from peers."; // an option -m or --max-inbound
+            opt max_outbound:usize = DEFAULT_MAX_OUTBOUND, desc:"The most connections dialed to peers."; // an option -x or --max-outbound
+            opt discovery:bool, desc:"Announce this node and dial peers found on the local network with mDNS."; // a flag -i or --discovery
+            opt bind:Option<String>, desc:"The addresses socket listens on, separated by commas, e.g. 127.0.0.1,::1. Defaults to 127.0.0.1, or 0.0.0.0 with discovery. :: listens on IPv4 too where the system allows dual-stack sockets."; // an option --bind
+            opt dns_seeds:Option<String>, desc:"The DNS names listing bootstrap peers, separated by commas, as name or name:port. A and AAAA records are nodes on the port, socket port by default, TXT records list peer addresses."; // an option --dns-seeds
+            opt dns_seed_interval:u64 = DEFAULT_DNS_SEED_INTERVAL, desc:"The seconds between resolutions of DNS seeds."; // an option --dns-seed-interval
+            opt transport:String = "websocket".to_string(), desc:"The transport to peers, websocket or libp2p. Peers of libp2p are multiaddrs."; // an option --transport
+            opt wire_format:String = "json".to_string(), desc:"The encoding of payloads written to websocket peers, json, protobuf of proto/payload.proto or naivecoin. Peers dialed as naivecoin+ws://host:port always use naivecoin, which only translates messages and needs peers following this node's consensus rules."; // an option --wire-format
+            opt network_secret:Option<String>, desc:"The secret shared by nodes of a private network, payloads of other nodes are rejected."; // an option -e or --network-secret
+            opt pow_algorithm:String = "sha256".to_string(), desc:"The hash algorithm of proof of work, sha256, sha256d, blake3 or argon2. Every node of a network must use the same."; // an option --pow-algorithm
+            opt network:String = "main".to_string(), desc:"The network of the node, main or regtest. Regtest mines every block at difficulty 0 and generates blocks at /api/generate."; // an option --network
+            opt block_interval:usize = DEFAULT_BLOCK_INTERVAL, desc:"The seconds targeted between blocks when difficulty is adjusted. Every node of a network must use the same."; // an option --block-interval
+            opt fast_sync:bool, desc:"Sync an empty chain from headers and a snapshot of unspent tx outs of a peer, checked against the utxo commitment once it is active, replaying only later blocks. Older blocks are kept without data."; // a flag --fast-sync
+            opt journal:Option<String>, desc:"The path of an append-only journal recording every received payload and http mutation."; // an option -j or --journal
+            opt replay:Option<String>, desc:"Replay a journal through the handlers of payloads and http routes, print the chain and exit."; // an option --replay
+            opt labels_path:String = LABELS_PATH.to_string(), desc:"The path of local labels of transactions and addresses, never shared with peers."; // an option -l or --labels-path
+            opt max_spend:Option<usize>, desc:"The most coins the wallet pays other addresses in a transaction."; // an option --max-spend
+            opt daily_spend_limit:Option<usize>, desc:"The most coins the wallet pays other addresses in a UTC day."; // an option --daily-spend-limit
+            opt allowed_destinations:Option<String>, desc:"The only addresses the wallet pays, separated by commas."; // an option --allowed-destinations
+            opt price_feed_url:Option<String>, desc:"The http url of a JSON document with the fiat price of a coin, adding fiat amounts to balances and transactions."; // an option --price-feed-url
+            opt price_feed_path:String = "price".to_string(), desc:"The dotted path of the price in the price feed document, e.g. data.price."; // an option --price-feed-path
+            opt price_currency:String = "USD".to_string(), desc:"The fiat currency of the price feed."; // an option --price-currency
+            opt price_interval:u64 = DEFAULT_PRICE_INTERVAL, desc:"The seconds between fetches of the price feed."; // an option --price-interval
+            opt payout_address:Option<String>, desc:"The address paid by coinbase of mined blocks instead of the wallet, e.g. of a cold wallet."; // an option --payout-address
+            opt miner_tag:Option<String>, desc:"The text embedded in coinbase of mined blocks, at most 48 bytes."; // an option --miner-tag
+            opt max_hashrate:Option<usize>, desc:"The most hashes tried per second by the miner, keeping continuous mining off a full core. Adjustable at /api/miner/throttle."; // an option --max-hashrate
+            opt pause_empty_pool:bool, desc:"Wait for transactions in the pool before mining a block with coinbase transaction."; // a flag --pause-empty-pool
+            opt empty_block_interval:Option<u64>, desc:"The seconds since the latest block after which a block is mined on an empty pool anyway, with --pause-empty-pool."; // an option --empty-block-interval
+            opt stratum_port:Option<u16>, desc:"The port of stratum, a mining protocol of JSON lines over TCP for external workers."; // an option --stratum-port
+            opt share_difficulty:usize = DEFAULT_SHARE_DIFFICULTY, desc:"The leading zero bits of a share submitted to stratum, at most the block difficulty."; // an option --share-difficulty
+            opt webhook_url:Option<String>, desc:"The http url posted a JSON callback when a watched transaction reaches its confirmations or leaves the chain."; // an option --webhook-url
+            opt webhooks_path:String = WEBHOOKS_PATH.to_string(), desc:"The path of watches and undelivered callbacks of webhooks."; // an option --webhooks-path
+            opt statsd_address:Option<String>, desc:"The host:port of a statsd daemon pushed height, peers, pool size and hashrate of the node as gauges."; // an option --statsd-address
+            opt statsd_prefix:String = "blockchain".to_string(), desc:"The prefix of names of metrics pushed to statsd."; // an option --statsd-prefix
+            opt statsd_interval:u64 = DEFAULT_STATSD_INTERVAL, desc:"The seconds between pushes of metrics to statsd."; // an option --statsd-interval
+            opt max_streams:usize = DEFAULT_MAX_STREAMS, desc:"The most clients of /api/stream/tip at once. Each holds an http worker, added on top of the workers serving the api."; // an option --max-streams
+        };
+        let args = match cli_args {
+            Some(cli_args) => parser.parse_args(cli_args.iter().copied()).unwrap_or_else(|e| panic!("{}", e)).0,
+            None => parser.parse_or_exit().0,
+        };
+
+        Config {
+            socket_port: args.socket_port,
+            http_port: args.http_port,
+            private_key_path: args.private_key_path,
+            key_type: args.key_type.parse().unwrap_or_else(|e| panic!("{}", e)),
+            allow_insecure_key: args.allow_insecure_key,
+            admin_token: args.admin_token,
+            allow_rollback: args.allow_rollback,
+            compression: args.compression,
+            api_field_naming: args.api_field_naming.parse().unwrap_or_else(|e| panic!("{}", e)),
+            api_pretty: args.api_pretty,
+            remote_signer: args.remote_signer,
+            rotate_key: args.rotate_key,
+            dust_limit: args.dust_limit,
+            min_fee: args.min_fee,
+            allow_nonstandard: args.allow_nonstandard,
+            consensus_dust_limit: args.consensus_dust_limit,
+            max_inbound: args.max_inbound,
+            max_outbound: args.max_outbound,
+            discovery: args.discovery,
+            bind_addresses: match args.bind {
+                Some(bind) => bind
+                    .split(',')
+                    .map(|address| address.trim().parse().unwrap_or_else(|_| panic!("{}", AppError::new(5006))))
+                    .collect(),
+                None if args.discovery => vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)],
+                None => vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            },
+            dns_seeds: args.dns_seeds
+                .map(|seeds| seeds.split(',').map(|seed| DnsSeed::parse(seed, args.socket_port).unwrap_or_else(|e| panic!("{}", e))).collect())
+                .unwrap_or_default(),
+            dns_seed_interval: args.dns_seed_interval,
+            transport: args.transport.parse().unwrap_or_else(|e| panic!("{}", e)),
+            wire_format: args.wire_format.parse().unwrap_or_else(|e| panic!("{}", e)),
+            network_secret: args.network_secret,
+            pow_algorithm: args.pow_algorithm.parse().unwrap_or_else(|e| panic!("{}", e)),
+            network: args.network.parse().unwrap_or_else(|e| panic!("{}", e)),
+            block_interval: args.block_interval,
+            fast_sync: args.fast_sync,
+            journal: args.journal,
+            replay: args.replay,
+            labels_path: args.labels_path,
+            max_spend: args.max_spend,
+            daily_spend_limit: args.daily_spend_limit,
+            allowed_destinations: args.allowed_destinations
+                .map(|addresses| addresses.split(',').map(|address| address.trim().to_string()).collect())
+                .unwrap_or_default(),
+            price_feed_url: args.price_feed_url,
+            price_feed_path: args.price_feed_path,
+            price_currency: args.price_currency,
+            price_interval: args.price_interval,
+            payout_address: args.payout_address.map(|address| match get_verifier(&address) {
+                Some(_) => address,
+                None => panic!("{}", AppError::new(1004)),
+            }),
+            miner_tag: args.miner_tag.map(|miner_tag| {
+                if miner_tag.len() > MAX_MINER_TAG {
+                    panic!("{}", AppError::new(1005));
+                }
+                miner_tag
+            }),
+            max_hashrate: args.max_hashrate,
+            pause_empty_pool: args.pause_empty_pool,
+            empty_block_interval: args.empty_block_interval,
+            stratum_port: args.stratum_port,
+            share_difficulty: args.share_difficulty,
+            webhook_url: args.webhook_url,
+            webhooks_path: args.webhooks_path,
+            statsd_address: args.statsd_address,
+            statsd_prefix: args.statsd_prefix,
+            statsd_interval: args.statsd_interval,
+            max_streams: args.max_streams,
+            uuid,
+        }
+    }
+
+    /// Get policy of config of transactions entering the pool
+    pub fn get_mempool_policy(&self) -> MempoolPolicy {
+        MempoolPolicy {
+            min_fee: self.min_fee,
+            allow_nonstandard: self.allow_nonstandard,
+            ..MempoolPolicy::new(self.dust_limit)
+        }
+    }
+
+    /// Get consensus rules of config of blocks
+    pub fn get_consensus_rules(&self) -> ConsensusRules {
+        ConsensusRules::new(self.consensus_dust_limit)
+    }
+
+    /// Get parameters of block generation of the network of config
+    pub fn get_chain_params(&self) -> ChainParams {
+        ChainParams { network: self.network, block_interval: self.block_interval }
+    }
+
+    /// Get policy of config of when coinbase jobs are mined
+    pub fn get_mining_policy(&self) -> MiningPolicy {
+        MiningPolicy { pause_empty_pool: self.pause_empty_pool, empty_block_interval: self.empty_block_interval }
+    }
+
+    /// Get socket addresses of config to listen on
+    pub fn get_socket_addrs(&self) -> Vec<SocketAddr> {
+        self.bind_addresses.iter().map(|ip| SocketAddr::new(*ip, self.socket_port)).collect()
+    }
+
+    /// Get spend policy of config, nothing spent yet
+    pub fn get_spend_policy(&self) -> SpendPolicy {
+        SpendPolicy::new(self.max_spend, self.daily_spend_limit, self.allowed_destinations.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_args() {
+        let config = Config::from_args(&[]);
+        assert_eq!(config.socket_port, DEFAULT_WEBSOCKET_PORT);
+        assert_eq!(config.bind_addresses, vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        assert_eq!(config.transport, TransportKind::WebSocket);
+        assert_eq!(config.get_chain_params(), ChainParams::default());
+
+        let config = Config::from_args(&["--socket-port", "7001", "--bind", "127.0.0.1, ::1", "--dust-limit", "5"]);
+        assert_eq!(config.get_socket_addrs(), vec!["127.0.0.1:7001".parse().unwrap(), "[::1]:7001".parse().unwrap()]);
+        assert_eq!(config.get_mempool_policy().dust_limit, 5);
+        assert_eq!(config.get_consensus_rules(), ConsensusRules::default());
+
+        let config = Config::from_args(&["--network", "regtest", "--block-interval", "1"]);
+        assert_eq!(config.get_chain_params(), ChainParams { network: Network::Regtest, block_interval: 1 });
+
+        assert_eq!(Config::from_args(&[]).api_field_naming, FieldNaming::Snake);
+        assert_eq!(Config::from_args(&["--api-field-naming", "camel"]).api_field_naming, FieldNaming::Camel);
+    }
 
-        Config { socket_port: args.socket_port, http_port: args.http_port, private_key_path: args.private_key_path, uuid }
+    #[test]
+    #[should_panic]
+    fn test_from_args_invalid() {
+        Config::from_args(&["--socket-port", "port"]);
     }
 }
@@ -1,22 +1,27 @@
-use tokio::net::TcpStream;
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio::sync::mpsc::UnboundedSender;
 
-use futures_util::stream::SplitSink;
+use crate::coalescer::SentDigests;
+use crate::inventory::KnownTxs;
+use crate::payload::Payload;
+use crate::peers::{Direction, Handshake, PeerClock, PeerStats};
 
 #[derive(Debug)]
 pub struct Connection {
     pub peer: String,
-    pub listener: Option<SplitSink<WebSocketStream<TcpStream>, Message>>,
-    pub connector: Option<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>,
+    pub direction: Direction,
+    /// Payloads received from peer, handled in order by its receiver task
+    pub inbox: UnboundedSender<Payload>,
+    pub sent_digests: SentDigests,
+    pub handshake: Option<Handshake>,
+    pub known_txs: KnownTxs,
+    /// Reason this node gave when closing the connection
+    pub close_reason: Option<String>,
+    pub stats: PeerStats,
+    pub clock: Option<PeerClock>,
 }
 
 impl Connection {
-    pub fn new(
-        peer: String,
-        listener: Option<SplitSink<WebSocketStream<TcpStream>, Message>>,
-        connector: Option<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>
-    ) -> Self {
-        Self { peer, listener, connector }
+    pub fn new(peer: String, direction: Direction, inbox: UnboundedSender<Payload>) -> Self {
+        Self { peer, direction, inbox, sent_digests: SentDigests::default(), handshake: None, known_txs: KnownTxs::default(), close_reason: None, stats: PeerStats::default(), clock: None }
     }
 }
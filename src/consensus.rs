@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use serde::Serialize;
+
+use crate::Block;
+use crate::sync_status::PeerTip;
+
+/// How the latest block a peer announced relates to the local chain.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TipAgreement {
+    /// Peer is at the local tip
+    Agree,
+
+    /// Peer tip is an earlier block of the local chain
+    Behind,
+
+    /// Peer tip is higher than the local tip, which may be on another branch
+    Ahead,
+
+    /// Peer tip is not higher than the local tip and not in the local chain
+    Forked,
+
+    /// Peer did not announce its latest block yet
+    Unknown,
+}
+
+/// Tip of a connected peer against the local chain.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PeerConsensus {
+    pub peer: String,
+    pub agreement: TipAgreement,
+
+    /// Fields below are none until the peer announced its latest block
+    pub hash: Option<String>,
+    pub height: Option<usize>,
+
+    /// Peer minus local height
+    pub distance: Option<i64>,
+}
+
+/// Agreement of connected peers with the local tip.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ConsensusCheck {
+    /// Hash and index of the local latest block
+    pub hash: String,
+    pub height: usize,
+
+    /// Every peer which announced its tip agrees, vacuously true without such peers
+    pub is_consistent: bool,
+
+    pub agree: usize,
+    pub behind: usize,
+    pub ahead: usize,
+    pub forked: usize,
+    pub unknown: usize,
+
+    pub peers: Vec<PeerConsensus>,
+}
+
+/// Get agreement of tip with blockchain
+pub fn get_tip_agreement(blockchain: &[Block], tip: &PeerTip) -> TipAgreement {
+    let height = blockchain.len() - 1;
+    match blockchain.get(tip.height) {
+        Some(block) if block.hash == tip.hash && tip.height == height => TipAgreement::Agree,
+        Some(block) if block.hash == tip.hash => TipAgreement::Behind,
+        Some(_) => TipAgreement::Forked,
+        None => TipAgreement::Ahead,
+    }
+}
+
+/// Check tips announced by connected peers against blockchain, peers without a tip being unknown.
+pub fn check_consensus(blockchain: &[Block], peers: &[String], tips: &HashMap<String, PeerTip>) -> ConsensusCheck {
+    let latest = blockchain.last().expect("blockchain has a genesis block");
+    let peers: Vec<PeerConsensus> = peers
+        .iter()
+        .map(|peer| match tips.get(peer) {
+            Some(tip) => PeerConsensus {
+                peer: peer.clone(),
+                agreement: get_tip_agreement(blockchain, tip),
+                hash: Some(tip.hash.clone()),
+                height: Some(tip.height),
+                distance: Some(tip.height as i64 - latest.index as i64),
+            },
+            None => PeerConsensus { peer: peer.clone(), agreement: TipAgreement::Unknown, hash: None, height: None, distance: None },
+        })
+        .collect();
+    let count = |agreement: TipAgreement| peers.iter().filter(|peer| peer.agreement == agreement).count();
+
+    ConsensusCheck {
+        hash: latest.hash.clone(),
+        height: latest.index,
+        is_consistent: peers.iter().all(|peer| matches!(peer.agreement, TipAgreement::Agree | TipAgreement::Unknown)),
+        agree: count(TipAgreement::Agree),
+        behind: count(TipAgreement::Behind),
+        ahead: count(TipAgreement::Ahead),
+        forked: count(TipAgreement::Forked),
+        unknown: count(TipAgreement::Unknown),
+        peers,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::get_genesis_block;
+    use super::*;
+
+    #[test]
+    fn test_check_consensus() {
+        let genesis_block = get_genesis_block();
+        let a1 = Block::generate(&vec![], &genesis_block, 0);
+        let a2 = Block::generate(&vec![], &a1, 0);
+        let b1 = Block::generate(&vec![], &genesis_block, 1);
+        let blockchain = vec![genesis_block.clone(), a1.clone(), a2.clone()];
+
+        let tip = |block: &Block| PeerTip { hash: block.hash.clone(), height: block.index };
+        let mut tips = HashMap::new();
+        tips.insert("agree".to_string(), tip(&a2));
+        tips.insert("behind".to_string(), tip(&a1));
+        tips.insert("forked".to_string(), tip(&b1));
+        tips.insert("ahead".to_string(), PeerTip { hash: "unknown".to_string(), height: 5 });
+        tips.insert("disconnected".to_string(), tip(&b1));
+
+        let peers: Vec<String> = vec!["agree", "behind", "forked", "ahead", "new"].into_iter().map(str::to_string).collect();
+        let check = check_consensus(&blockchain, &peers, &tips);
+        assert_eq!(check.height, 2);
+        assert_eq!(check.hash, a2.hash);
+        assert!(!check.is_consistent);
+        assert_eq!((check.agree, check.behind, check.ahead, check.forked, check.unknown), (1, 1, 1, 1, 1));
+        assert_eq!(
+            check.peers.iter().map(|peer| peer.agreement).collect::<Vec<_>>(),
+            vec![TipAgreement::Agree, TipAgreement::Behind, TipAgreement::Forked, TipAgreement::Ahead, TipAgreement::Unknown]
+        );
+        assert_eq!(check.peers[1].distance, Some(-1));
+        assert_eq!(check.peers[3].distance, Some(3));
+
+        let check = check_consensus(&blockchain, &peers[..1], &tips);
+        assert!(check.is_consistent);
+    }
+}
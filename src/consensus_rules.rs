@@ -0,0 +1,63 @@
+use crate::Block;
+use crate::transaction::{Transaction, TransactionLimits};
+
+/// Limits every transaction in a block must satisfy.
+pub const CONSENSUS_TRANSACTION_LIMITS: TransactionLimits = TransactionLimits {
+    max_size: 100_000,
+    max_tx_ins: 1_000,
+    max_tx_outs: 1_000,
+};
+
+/// Rules of block validity a network may tighten, kept apart from the policy of the pool so relaxing one never
+/// forks the other.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConsensusRules {
+    /// Smallest amount of a spendable output in a block, any when none
+    pub dust_limit: Option<usize>,
+}
+
+impl ConsensusRules {
+    /// Returns rules rejecting blocks with spendable outputs below dust limit, if any
+    pub fn new(dust_limit: Option<usize>) -> ConsensusRules {
+        ConsensusRules { dust_limit }
+    }
+
+    /// Get blocks are acceptable under the rules
+    pub fn get_is_valid_blocks(&self, blocks: &[Block]) -> bool {
+        blocks.iter().all(|block| self.get_is_valid_transactions(&block.data))
+    }
+
+    /// Get transactions of a block are acceptable under the rules
+    pub fn get_is_valid_transactions(&self, transactions: &[Transaction]) -> bool {
+        let dust_limit = match self.dust_limit {
+            Some(dust_limit) => dust_limit,
+            None => return true,
+        };
+        transactions.iter()
+            .flat_map(|tx| tx.tx_outs.iter())
+            .all(|tx_out| tx_out.amount >= dust_limit || tx_out.get_is_unspendable())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::transaction::{TxIn, TxOut};
+    use super::*;
+
+    #[test]
+    fn test_consensus_rules() {
+        let transaction = Transaction::generate(
+            &vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, "".to_string())],
+            &vec![
+                TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 49),
+                TxOut::new("03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40".to_string(), 1),
+            ],
+        );
+        let block = Block::new(1, "".to_string(), "".to_string(), 0, vec![transaction], 0, 0);
+
+        assert!(ConsensusRules::default().get_is_valid_blocks(&[block.clone()]));
+        assert!(ConsensusRules::new(Some(1)).get_is_valid_blocks(&[block.clone()]));
+        assert!(!ConsensusRules::new(Some(2)).get_is_valid_blocks(&[block.clone()]));
+        assert!(!ConsensusRules::new(Some(2)).get_is_valid_transactions(&block.data));
+    }
+}
@@ -1,5 +1,59 @@
 
+#[cfg(feature = "node")]
 pub const DEFAULT_WEBSOCKET_PORT: u16 = 2794;
+#[cfg(feature = "node")]
 pub const DEFAULT_HTTP_PORT: u16 = 8000;
+#[cfg(feature = "node")]
 pub const PRIVATE_KEY_PATH: &'static str = "wallet/private_key";
+#[cfg(feature = "node")]
+pub const LABELS_PATH: &'static str = "wallet/labels.json";
+#[cfg(feature = "node")]
+pub const WEBHOOKS_PATH: &'static str = "wallet/webhooks.json";
 pub const COINBASE_AMOUNT: usize = 50;
+pub const DEFAULT_BLOCK_INTERVAL: usize = 10;
+pub const MAX_COINBASE_EXTRA_DATA: usize = 64;
+#[cfg(feature = "node")]
+pub const DEFAULT_RICHEST_LIMIT: usize = 10;
+pub const WIF_PREFIX: u8 = 0x80;
+#[cfg(any(test, feature = "node"))]
+pub const DEFAULT_DUST_LIMIT: usize = 1;
+pub const VALIDATION_CACHE_SIZE: usize = 10_000;
+pub const CHAIN_VERDICT_CACHE_SIZE: usize = 64;
+#[cfg(feature = "node")]
+pub const MINER_JOB_HISTORY: usize = 100;
+#[cfg(feature = "node")]
+pub const DEFAULT_MAX_INBOUND: usize = 32;
+#[cfg(feature = "node")]
+pub const DEFAULT_MAX_OUTBOUND: usize = 8;
+#[cfg(feature = "node")]
+pub const PEER_HISTORY: usize = 50;
+#[cfg(feature = "node")]
+pub const REORG_HISTORY: usize = 50;
+#[cfg(feature = "node")]
+pub const ARRIVAL_HISTORY: usize = 1000;
+#[cfg(feature = "node")]
+pub const DEFAULT_ARRIVAL_LIMIT: usize = 20;
+#[cfg(feature = "node")]
+pub const DEFAULT_INVOICE_EXPIRY: usize = 3600;
+#[cfg(feature = "node")]
+pub const DEFAULT_PRICE_INTERVAL: u64 = 60;
+#[cfg(feature = "node")]
+pub const DEFAULT_SHARE_DIFFICULTY: usize = 8;
+#[cfg(feature = "node")]
+pub const DEFAULT_DNS_SEED_INTERVAL: u64 = 600;
+#[cfg(feature = "node")]
+pub const WEBHOOK_OUTBOX: usize = 1000;
+#[cfg(feature = "node")]
+pub const DEFAULT_STATSD_INTERVAL: u64 = 10;
+#[cfg(feature = "node")]
+pub const CONSENSUS_CHECK_WAIT_MILLIS: u64 = 500;
+#[cfg(feature = "node")]
+pub const MAX_GENERATE_BLOCKS: usize = 1000;
+#[cfg(feature = "node")]
+pub const MAX_OUTPOINT_LOOKUP: usize = 1000;
+#[cfg(feature = "node")]
+pub const MAX_HEADERS: usize = 2000;
+#[cfg(feature = "node")]
+pub const UTXO_SNAPSHOT_DEPTH: usize = 10;
+#[cfg(feature = "node")]
+pub const DEFAULT_MAX_STREAMS: usize = 4;
@@ -0,0 +1,82 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::thread;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{BroadcastEvents, Config};
+use crate::errors::AppError;
+use crate::peers::{format_socket_addr, USER_AGENT};
+use crate::transport::TransportKind;
+
+/// mDNS service type announced by nodes.
+pub const SERVICE_TYPE: &'static str = "_blockchain._tcp.local.";
+
+/// Announce socket port on the local network and dial every other node discovered.
+///
+/// # Errors
+/// If mDNS daemon cannot start, it returns error 5000.
+pub fn launch_discovery(config: &Config, broadcast_sender: UnboundedSender<BroadcastEvents>) -> Result<ServiceDaemon, AppError> {
+    let daemon = ServiceDaemon::new().map_err(|_| AppError::new(5000))?;
+    let properties = HashMap::from([("agent".to_string(), USER_AGENT.to_string())]);
+    let service_info = ServiceInfo::new(SERVICE_TYPE, &config.uuid, &format!("{}.local.", config.uuid), (), config.socket_port, properties)
+        .map_err(|_| AppError::new(5000))?
+        .enable_addr_auto();
+    let fullname = service_info.get_fullname().to_string();
+    let transport = config.transport;
+    daemon.register(service_info).map_err(|_| AppError::new(5000))?;
+    let receiver = daemon.browse(SERVICE_TYPE).map_err(|_| AppError::new(5000))?;
+
+    thread::spawn(move || {
+        let mut dialed: HashSet<String> = HashSet::new();
+        while let Ok(event) = receiver.recv() {
+            if let ServiceEvent::ServiceResolved(info) = event {
+                if info.get_fullname() == fullname {
+                    continue;
+                }
+                if let Some(peer) = get_peer_address(&info, transport) {
+                    if dialed.insert(peer.clone()) {
+                        println!("Discovered peer : {}", peer);
+                        let _ = broadcast_sender.send(BroadcastEvents::Peer(peer));
+                    }
+                }
+            }
+        }
+    });
+    Ok(daemon)
+}
+
+/// Get address of a resolved node to dial with transport, preferring IPv4.
+fn get_peer_address(info: &ServiceInfo, transport: TransportKind) -> Option<String> {
+    let mut addresses: Vec<_> = info.get_addresses().iter().collect();
+    addresses.sort_by_key(|address| (!address.is_ipv4(), address.to_string()));
+    addresses.first().map(|address| get_dial_address(SocketAddr::new(**address, info.get_port()), transport))
+}
+
+/// Get address to dial a node listening on socket address with transport.
+pub fn get_dial_address(addr: SocketAddr, transport: TransportKind) -> String {
+    match (transport, addr.ip()) {
+        (TransportKind::WebSocket, _) => format!("ws://{}", format_socket_addr(addr)),
+        (TransportKind::Libp2p, IpAddr::V4(ip)) => format!("/ip4/{}/tcp/{}", ip, addr.port()),
+        (TransportKind::Libp2p, IpAddr::V6(ip)) => format!("/ip6/{}/tcp/{}", ip, addr.port()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_peer_address() {
+        let info = ServiceInfo::new(SERVICE_TYPE, "node", "node.local.", "fe80::1,192.168.0.2", 2794, None).unwrap();
+        assert_eq!(get_peer_address(&info, TransportKind::WebSocket).unwrap(), "ws://192.168.0.2:2794");
+        assert_eq!(get_peer_address(&info, TransportKind::Libp2p).unwrap(), "/ip4/192.168.0.2/tcp/2794");
+
+        let info = ServiceInfo::new(SERVICE_TYPE, "node", "node.local.", "fe80::1", 2794, None).unwrap();
+        assert_eq!(get_peer_address(&info, TransportKind::WebSocket).unwrap(), "ws://[fe80::1]:2794");
+        assert_eq!(get_peer_address(&info, TransportKind::Libp2p).unwrap(), "/ip6/fe80::1/tcp/2794");
+
+        let info = ServiceInfo::new(SERVICE_TYPE, "node", "node.local.", (), 2794, None).unwrap();
+        assert!(get_peer_address(&info, TransportKind::WebSocket).is_none());
+    }
+}
@@ -0,0 +1,275 @@
+//! Bootstrap peers listed by DNS seeds.
+//!
+//! Address records of a seed are nodes listening on the port of the seed. TXT records list peer addresses
+//! separated by whitespace or commas, e.g. `ws://[2001:db8::1]:2794 /ip4/192.0.2.1/tcp/2794`, so nodes on
+//! other ports or transports can be listed too.
+
+use std::collections::HashSet;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use chrono::Utc;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::BroadcastEvents;
+use crate::discovery::get_dial_address;
+use crate::errors::AppError;
+use crate::peers::{normalize_peer_address, PeerRegistry};
+use crate::transport::TransportKind;
+
+const RESOLV_CONF_PATH: &'static str = "/etc/resolv.conf";
+const DNS_PORT: u16 = 53;
+const DNS_TIMEOUT_SECS: u64 = 5;
+const MAX_DNS_MESSAGE: usize = 4096;
+const TYPE_TXT: u16 = 16;
+const CLASS_IN: u16 = 1;
+
+/// DNS name listing bootstrap peers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsSeed {
+    pub name: String,
+
+    /// Port nodes of address records listen on
+    pub port: u16,
+}
+
+impl DnsSeed {
+    /// Returns seed of `name` or `name:port`, nodes of its address records listening on default port without port.
+    ///
+    /// # Errors
+    /// If name is not a DNS name or port is not a number, it returns error 5008.
+    pub fn parse(seed: &str, default_port: u16) -> Result<DnsSeed, AppError> {
+        let seed = seed.trim();
+        let (name, port) = match seed.rsplit_once(':') {
+            Some((name, port)) => (name, port.parse().map_err(|_| AppError::new(5008))?),
+            None => (seed, default_port),
+        };
+        let name = name.trim_end_matches('.').to_lowercase();
+        let is_valid_label = |label: &str| {
+            !label.is_empty() && label.len() <= 63 && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if name.len() > 253 || !name.split('.').all(is_valid_label) {
+            return Err(AppError::new(5008));
+        }
+        Ok(DnsSeed { name, port })
+    }
+}
+
+/// Get peer addresses of seed to dial with transport, from its address and TXT records.
+///
+/// # Errors
+/// If seed has neither address nor TXT records listing a peer, it returns error 5009.
+pub fn resolve_seed(seed: &DnsSeed, transport: TransportKind) -> Result<Vec<String>, AppError> {
+    let mut peers: Vec<String> = match (seed.name.as_str(), seed.port).to_socket_addrs() {
+        Ok(addrs) => addrs.map(|addr| get_dial_address(addr, transport)).collect(),
+        Err(_) => vec![],
+    };
+    match get_nameserver().and_then(|nameserver| query_txt(nameserver, &seed.name)) {
+        Ok(records) => peers.extend(records.iter().flat_map(|record| get_txt_peers(record))),
+        Err(e) => tracing::debug!(seed = %seed.name, error = %e, "dns seed txt query failed"),
+    }
+
+    let mut seen = HashSet::new();
+    peers.retain(|peer| seen.insert(peer.clone()));
+    if peers.is_empty() {
+        return Err(AppError::new(5009));
+    }
+    Ok(peers)
+}
+
+/// Spawn a thread resolving seeds now and every interval, dialing peers they list which are not connected.
+pub fn launch_dns_seeds(
+    seeds: Vec<DnsSeed>,
+    transport: TransportKind,
+    interval: Duration,
+    peer_registry: &Arc<PeerRegistry>,
+    broadcast_sender: UnboundedSender<BroadcastEvents>,
+) {
+    let peer_registry = Arc::clone(peer_registry);
+    thread::spawn(move || loop {
+        let connected: HashSet<String> = peer_registry.get_peers().into_iter().map(|peer| peer.peer).collect();
+        for seed in &seeds {
+            match resolve_seed(seed, transport) {
+                Ok(peers) => {
+                    for peer in peers.into_iter().filter(|peer| !connected.contains(peer)) {
+                        println!("Seed peer : {} from {}", peer, seed.name);
+                        if broadcast_sender.send(BroadcastEvents::Peer(peer)).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!(seed = %seed.name, error = %e, "dns seed resolution failed"),
+            }
+        }
+        thread::sleep(interval);
+    });
+}
+
+/// Get peers listed by a TXT record, skipping entries which are not peer addresses.
+fn get_txt_peers(record: &str) -> Vec<String> {
+    record
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| normalize_peer_address(entry).ok())
+        .collect()
+}
+
+/// Get first nameserver of the system resolver.
+fn get_nameserver() -> Result<SocketAddr, AppError> {
+    let resolv_conf = fs::read_to_string(RESOLV_CONF_PATH).map_err(|_| AppError::new(5009))?;
+    resolv_conf
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|ip| ip.trim().split('%').next()?.parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .next()
+        .ok_or(AppError::new(5009))
+}
+
+/// Query TXT records of name from nameserver over UDP, each record as its strings joined.
+fn query_txt(nameserver: SocketAddr, name: &str) -> Result<Vec<String>, AppError> {
+    let local: IpAddr = if nameserver.is_ipv4() { Ipv4Addr::UNSPECIFIED.into() } else { Ipv6Addr::UNSPECIFIED.into() };
+    let socket = UdpSocket::bind(SocketAddr::new(local, 0)).map_err(|_| AppError::new(5009))?;
+    socket.set_read_timeout(Some(Duration::from_secs(DNS_TIMEOUT_SECS))).map_err(|_| AppError::new(5009))?;
+    socket.connect(nameserver).map_err(|_| AppError::new(5009))?;
+
+    let id = Utc::now().timestamp_subsec_nanos() as u16;
+    socket.send(&encode_txt_query(id, name)?).map_err(|_| AppError::new(5009))?;
+    let mut response = [0u8; MAX_DNS_MESSAGE];
+    let size = socket.recv(&mut response).map_err(|_| AppError::new(5009))?;
+    decode_txt_response(id, &response[..size])
+}
+
+/// Encode query of TXT records of name, asking for recursion.
+fn encode_txt_query(id: u16, name: &str) -> Result<Vec<u8>, AppError> {
+    let mut query = vec![];
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(AppError::new(5008));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_TXT.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Decode TXT records answering query id, ignoring other answers such as CNAME records.
+fn decode_txt_response(id: u16, response: &[u8]) -> Result<Vec<String>, AppError> {
+    let read_u16 = |position: usize| -> Result<u16, AppError> {
+        response.get(position..position + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])).ok_or(AppError::new(5009))
+    };
+    let is_response = response.get(2).map_or(false, |flags| flags & 0x80 != 0);
+    let rcode = response.get(3).map_or(0, |flags| flags & 0x0f);
+    if read_u16(0)? != id || !is_response || rcode != 0 {
+        return Err(AppError::new(5009));
+    }
+
+    let questions = read_u16(4)?;
+    let answers = read_u16(6)?;
+    let mut position = 12;
+    for _ in 0..questions {
+        position = skip_name(response, position)? + 4;
+    }
+
+    let mut records = vec![];
+    for _ in 0..answers {
+        position = skip_name(response, position)?;
+        let r#type = read_u16(position)?;
+        let length = read_u16(position + 8)? as usize;
+        let start = position + 10;
+        let data = response.get(start..start + length).ok_or(AppError::new(5009))?;
+        position = start + length;
+        if r#type != TYPE_TXT {
+            continue;
+        }
+
+        let mut record = String::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let size = data[offset] as usize;
+            let bytes = data.get(offset + 1..offset + 1 + size).ok_or(AppError::new(5009))?;
+            record.push_str(&String::from_utf8_lossy(bytes));
+            offset += 1 + size;
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Get position after a possibly compressed name at position.
+fn skip_name(message: &[u8], mut position: usize) -> Result<usize, AppError> {
+    loop {
+        let length = *message.get(position).ok_or(AppError::new(5009))? as usize;
+        match length {
+            0 => return Ok(position + 1),
+            _ if length & 0xc0 == 0xc0 => return Ok(position + 2),
+            _ => position += 1 + length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Get response to query with a CNAME and a TXT record of two strings, names compressed.
+    fn get_response(query: &[u8]) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] = 0x81;
+        response[3] = 0x80;
+        response[7] = 2;
+        response.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 12]);
+        let strings: &[&[u8]] = &[b"ws://[2001:db8::1]:2794 ", b"192.0.2.1:2794,bogus"];
+        let length: usize = strings.iter().map(|string| string.len() + 1).sum();
+        response.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, length as u8]);
+        for string in strings {
+            response.push(string.len() as u8);
+            response.extend_from_slice(string);
+        }
+        response
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(DnsSeed::parse("Seed.Example.org.", 2794).unwrap(), DnsSeed { name: "seed.example.org".to_string(), port: 2794 });
+        assert_eq!(DnsSeed::parse("seed.example.org:6001", 2794).unwrap().port, 6001);
+        assert_eq!(DnsSeed::parse("seed..org", 2794).unwrap_err().code, 5008);
+        assert_eq!(DnsSeed::parse("seed.example.org:port", 2794).unwrap_err().code, 5008);
+        assert_eq!(DnsSeed::parse("ws://seed.example.org", 2794).unwrap_err().code, 5008);
+    }
+
+    #[test]
+    fn test_decode_txt_response() {
+        let query = encode_txt_query(7, "seed.example.org").unwrap();
+        assert_eq!(&query[12..18], b"\x04seed\x07");
+
+        let records = decode_txt_response(7, &get_response(&query)).unwrap();
+        assert_eq!(records, vec!["ws://[2001:db8::1]:2794 192.0.2.1:2794,bogus".to_string()]);
+        assert_eq!(get_txt_peers(&records[0]), vec!["ws://[2001:db8::1]:2794", "ws://192.0.2.1:2794"]);
+
+        assert_eq!(decode_txt_response(8, &get_response(&query)).unwrap_err().code, 5009);
+        assert_eq!(decode_txt_response(7, &get_response(&query)[..40]).unwrap_err().code, 5009);
+    }
+
+    #[test]
+    fn test_query_txt() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let nameserver = server.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut query = [0u8; MAX_DNS_MESSAGE];
+            let (size, client) = server.recv_from(&mut query).unwrap();
+            server.send_to(&get_response(&query[..size]), client).unwrap();
+        });
+
+        let records = query_txt(nameserver, "seed.example.org").unwrap();
+        assert_eq!(records.len(), 1);
+        handle.join().unwrap();
+    }
+}
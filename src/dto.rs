@@ -0,0 +1,286 @@
+//! Models of blocks and transactions as peers and api clients see them, converted from and to the structs of
+//! consensus so those can change without breaking the wire protocol or the api.
+
+use std::sync::Arc;
+use serde::{Serialize, Serializer, Deserialize};
+use serde::de::DeserializeOwned;
+
+use crate::Block;
+use crate::pow::PowAlgorithm;
+use crate::script::Script;
+use crate::transaction::{get_initial_version, get_is_initial_version, Transaction, TxIn, TxOut, UnspentTxOut};
+#[cfg(any(test, feature = "node"))]
+use crate::utxo_snapshot::UtxoSnapshot;
+
+/// Struct of consensus exchanged through a DTO.
+pub trait Model: Sized {
+    type Dto: Serialize + DeserializeOwned;
+
+    fn to_dto(&self) -> Self::Dto;
+
+    // Only the node decodes models it receives, each impl keeps its half of the round trip.
+    #[cfg_attr(not(feature = "node"), allow(dead_code))]
+    fn from_dto(dto: Self::Dto) -> Self;
+}
+
+/// Serialize a model through its DTO, for a field of a response holding one.
+pub fn serialize_model<T: Model, S: Serializer>(model: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    model.to_dto().serialize(serializer)
+}
+
+impl<T: Model> Model for Vec<T> {
+    type Dto = Vec<T::Dto>;
+
+    fn to_dto(&self) -> Self::Dto {
+        self.iter().map(Model::to_dto).collect()
+    }
+
+    fn from_dto(dto: Self::Dto) -> Self {
+        dto.into_iter().map(T::from_dto).collect()
+    }
+}
+
+impl<T: Model> Model for Option<T> {
+    type Dto = Option<T::Dto>;
+
+    fn to_dto(&self) -> Self::Dto {
+        self.as_ref().map(Model::to_dto)
+    }
+
+    fn from_dto(dto: Self::Dto) -> Self {
+        dto.map(T::from_dto)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockDto {
+    #[serde(default = "get_initial_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub pow: PowAlgorithm,
+    pub index: usize,
+    pub hash: String,
+    pub previous_hash: String,
+    pub timestamp: usize,
+    pub data: Vec<TransactionDto>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_root: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utxo_commitment: Option<String>,
+    pub difficulty: usize,
+    pub nonce: usize,
+}
+
+impl Model for Block {
+    type Dto = BlockDto;
+
+    fn to_dto(&self) -> BlockDto {
+        BlockDto {
+            version: self.version,
+            pow: self.pow,
+            index: self.index,
+            hash: self.hash.clone(),
+            previous_hash: self.previous_hash.clone(),
+            timestamp: self.timestamp,
+            data: self.data.iter().map(Model::to_dto).collect(),
+            tx_root: self.tx_root.clone(),
+            utxo_commitment: self.utxo_commitment.clone(),
+            difficulty: self.difficulty,
+            nonce: self.nonce,
+        }
+    }
+
+    fn from_dto(dto: BlockDto) -> Block {
+        Block {
+            version: dto.version,
+            pow: dto.pow,
+            index: dto.index,
+            hash: dto.hash,
+            previous_hash: dto.previous_hash,
+            timestamp: dto.timestamp,
+            data: Arc::new(Model::from_dto(dto.data)),
+            tx_root: dto.tx_root,
+            utxo_commitment: dto.utxo_commitment,
+            difficulty: dto.difficulty,
+            nonce: dto.nonce,
+            chain_work: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionDto {
+    #[serde(default = "get_initial_version", skip_serializing_if = "get_is_initial_version")]
+    pub version: u32,
+    pub id: String,
+    pub tx_ins: Vec<TxInDto>,
+    pub tx_outs: Vec<TxOutDto>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<String>,
+}
+
+impl Model for Transaction {
+    type Dto = TransactionDto;
+
+    fn to_dto(&self) -> TransactionDto {
+        TransactionDto {
+            version: self.version,
+            id: self.id.clone(),
+            tx_ins: self.tx_ins.to_dto(),
+            tx_outs: self.tx_outs.to_dto(),
+            extra_data: self.extra_data.clone(),
+        }
+    }
+
+    fn from_dto(dto: TransactionDto) -> Transaction {
+        Transaction {
+            version: dto.version,
+            id: dto.id,
+            tx_ins: Model::from_dto(dto.tx_ins),
+            tx_outs: Model::from_dto(dto.tx_outs),
+            extra_data: dto.extra_data,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TxInDto {
+    pub tx_out_id: String,
+    pub tx_out_index: usize,
+    pub signature: String,
+}
+
+impl Model for TxIn {
+    type Dto = TxInDto;
+
+    fn to_dto(&self) -> TxInDto {
+        TxInDto { tx_out_id: self.tx_out_id.clone(), tx_out_index: self.tx_out_index, signature: self.signature.clone() }
+    }
+
+    fn from_dto(dto: TxInDto) -> TxIn {
+        TxIn::new(dto.tx_out_id, dto.tx_out_index, dto.signature)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TxOutDto {
+    pub address: String,
+    pub amount: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+}
+
+impl Model for TxOut {
+    type Dto = TxOutDto;
+
+    fn to_dto(&self) -> TxOutDto {
+        TxOutDto { address: self.address.clone(), amount: self.amount, script: self.script.clone(), asset: self.asset.clone() }
+    }
+
+    fn from_dto(dto: TxOutDto) -> TxOut {
+        TxOut { address: dto.address, amount: dto.amount, script: dto.script, asset: dto.asset }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnspentTxOutDto {
+    pub tx_out_id: String,
+    pub tx_out_index: usize,
+    pub address: String,
+    pub amount: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+}
+
+impl Model for UnspentTxOut {
+    type Dto = UnspentTxOutDto;
+
+    fn to_dto(&self) -> UnspentTxOutDto {
+        UnspentTxOutDto {
+            tx_out_id: self.tx_out_id.clone(),
+            tx_out_index: self.tx_out_index,
+            address: self.address.clone(),
+            amount: self.amount,
+            script: self.script.clone(),
+            asset: self.asset.clone(),
+        }
+    }
+
+    fn from_dto(dto: UnspentTxOutDto) -> UnspentTxOut {
+        UnspentTxOut {
+            tx_out_id: dto.tx_out_id,
+            tx_out_index: dto.tx_out_index,
+            address: dto.address,
+            amount: dto.amount,
+            script: dto.script,
+            asset: dto.asset,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "node"))]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UtxoSnapshotDto {
+    pub headers: Vec<BlockDto>,
+    pub unspent_tx_outs: Vec<UnspentTxOutDto>,
+    pub blocks: Vec<BlockDto>,
+}
+
+#[cfg(any(test, feature = "node"))]
+impl Model for UtxoSnapshot {
+    type Dto = UtxoSnapshotDto;
+
+    fn to_dto(&self) -> UtxoSnapshotDto {
+        UtxoSnapshotDto {
+            headers: self.headers.to_dto(),
+            unspent_tx_outs: self.unspent_tx_outs.to_dto(),
+            blocks: self.blocks.to_dto(),
+        }
+    }
+
+    fn from_dto(dto: UtxoSnapshotDto) -> UtxoSnapshot {
+        UtxoSnapshot {
+            headers: Model::from_dto(dto.headers),
+            unspent_tx_outs: Model::from_dto(dto.unspent_tx_outs),
+            blocks: Model::from_dto(dto.blocks),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::get_genesis_block;
+    use crate::testkit::{get_signed_transaction, ALICE};
+    use super::*;
+
+    #[test]
+    fn test_model() {
+        let mut transaction = get_signed_transaction();
+        transaction.extra_data = Some("00".to_string());
+        transaction.tx_outs.push(TxOut {
+            asset: Some("GOLD".to_string()),
+            ..TxOut::with_script(ALICE.address.to_string(), 1, Script::multisig(1, &vec![ALICE.address.to_string()]))
+        });
+        let block = Block {
+            data: Arc::new(vec![transaction]),
+            tx_root: Some("beef".to_string()),
+            utxo_commitment: Some("c0ffee".to_string()),
+            ..get_genesis_block()
+        };
+        let blockchain = vec![get_genesis_block(), block];
+
+        // The wire protocol and the api are kept as the structs of consensus serialized.
+        assert_eq!(serde_json::to_value(blockchain.to_dto()).unwrap(), serde_json::to_value(&blockchain).unwrap());
+        assert_eq!(Vec::<Block>::from_dto(blockchain.to_dto()), blockchain);
+
+        let unspent_tx_outs = vec![UnspentTxOut { asset: Some("GOLD".to_string()), ..UnspentTxOut::new("a".to_string(), 0, ALICE.address.to_string(), 1) }];
+        assert_eq!(serde_json::to_value(unspent_tx_outs.to_dto()).unwrap(), serde_json::to_value(&unspent_tx_outs).unwrap());
+
+        let dto: BlockDto = serde_json::from_str(r#"{"index":0,"hash":"a","previous_hash":"","timestamp":0,"data":[],"difficulty":0,"nonce":0}"#).unwrap();
+        assert_eq!(Block::from_dto(dto).version, get_initial_version());
+    }
+}
@@ -1,7 +1,22 @@
 use std::fmt;
+#[cfg(feature = "node")]
+use rocket::http::Status;
+#[cfg(feature = "node")]
+use rocket::request::Request;
+#[cfg(feature = "node")]
+use rocket::response::{self, Responder, Response};
+#[cfg(feature = "node")]
 use rocket_contrib::json::Json;
-use serde::{Serialize};
+#[cfg(feature = "node")]
+use serde::{forward_to_deserialize_any, Serialize};
+#[cfg(feature = "node")]
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+#[cfg(feature = "node")]
+use serde_json::{json, Value};
+#[cfg(feature = "node")]
 use validator::{Validate, ValidationError, ValidationErrors};
+#[cfg(feature = "node")]
+use crate::spend_policy::PolicyViolation;
 
 /// Error for app
 #[derive(Debug)]
@@ -22,43 +37,236 @@ impl AppError {
     pub fn new(code: usize) -> Self {
         Self { code }
     }
-}
 
-impl fmt::Display for AppError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let message = match self.code {
+    /// Get http status answering a request failing with the error
+    pub fn get_status(&self) -> u16 {
+        self.get_mapping().0
+    }
+
+    /// Get machine-readable kind of the error.
+    ///
+    /// Kinds are stable: a kind is never renamed nor reused for another failure, so clients can match on it,
+    /// while statuses and hints may be refined. Codes of the same failure share a kind, e.g. dust outputs of a
+    /// block and of a pool transaction.
+    pub fn get_kind(&self) -> &'static str {
+        self.get_mapping().1
+    }
+
+    /// Get hint on how to fix the request failing with the error
+    pub fn get_hint(&self) -> &'static str {
+        self.get_mapping().2
+    }
+
+    /// Get message of the error
+    pub fn get_message(&self) -> &'static str {
+        match self.code {
             1000 => "Fail to add block with invalid block",
+            1001 => "Fail to add block with dust outputs",
+            1002 => "Fail to add block registering an invalid or taken name",
+            1003 => "Fail to parse pow algorithm",
+            1004 => "Fail to mine to invalid payout address",
+            1005 => "Fail to mine with too long miner tag",
+            1006 => "Fail to parse network",
+            1007 => "Fail to add block with invalid utxo commitment",
+            1008 => "Fail to find utxo snapshot",
+            1009 => "Fail to add block replaying a transaction",
+            1010 => "Fail to roll back to height above the tip",
+            1011 => "Fail to roll back into headers of utxo snapshot",
+            1012 => "Fail to roll back on main network",
+            1013 => "Fail to invalidate the genesis block",
+            1014 => "Fail to send chain pruned to a utxo snapshot",
             2000 => "Fail to sign in",
             2001 => "Fail to process transactions with invalid transactions structure",
             2002 => "Fail to process transactions block transactions",
             2003 => "Fail to send transactions",
+            2004 => "Fail to send amount below dust limit",
+            2005 => "Fail to issue asset with invalid symbol",
+            2006 => "Fail to register invalid name",
+            2007 => "Fail to parse payment uri",
+            2008 => "Fail to pay uri without amount",
+            2009 => "Fail to parse address",
             3000 => "Fail to read private key",
             3001 => "Fail to create private key",
             3002 => "Fail to write private key",
+            3003 => "Fail to parse key type",
+            3004 => "Fail to connect remote signer",
+            3005 => "Fail to sign with remote signer",
+            3006 => "Fail to rotate key of remote signer",
+            3007 => "Fail to archive key file",
+            3008 => "Fail to encode wif private key",
+            3009 => "Fail to decode wif private key",
+            3010 => "Fail to export key of remote signer",
+            3011 => "Fail to load private key readable by other users",
+            3012 => "Fail to read private key from file without key",
+            3013 => "Fail to read private key from file with several keys",
+            3014 => "Fail to read private key with invalid hex",
+            3015 => "Fail to read private key with invalid length",
+            3016 => "Fail to read private key out of range",
+            3017 => "Fail to derive key of remote signer",
             4000 => "Fail to add transaction pool with invalid unspent tx outs",
             4001 => "Fail to add transaction pool with invalid transaction pool",
+            4002 => "Fail to add transaction pool with dust outputs",
+            4003 => "Fail to add transaction pool with transaction over size limits",
+            4004 => "Fail to add transaction pool with fee below min fee",
+            4005 => "Fail to add transaction pool with nonstandard script",
+            5000 => "Fail to start peer discovery",
+            5001 => "Fail to parse transport",
+            5002 => "Fail to start transport",
+            5003 => "Fail to parse wire format",
+            5004 => "Fail to encode payload",
+            5005 => "Fail to decode payload",
+            5006 => "Fail to parse bind address",
+            5007 => "Fail to parse peer address",
+            5008 => "Fail to parse dns seed",
+            5009 => "Fail to resolve dns seed",
+            6000 => "Fail to read ffi argument",
+            6001 => "Fail to complete ffi call which panicked",
+            7000 => "Fail to open journal",
+            7001 => "Fail to read journal",
+            8000 => "Fail to create invoice with invalid memo",
+            9000 => "Fail to read labels",
+            9001 => "Fail to write labels",
+            9002 => "Fail to label unknown target",
+            10000 => "Fail to read price feed url",
+            10001 => "Fail to fetch price",
+            11000 => "Fail to start stratum",
+            11001 => "Fail to parse stratum request",
+            11002 => "Fail to submit share of stale job",
+            11003 => "Fail to submit duplicate share",
+            11004 => "Fail to submit share above target",
+            11005 => "Fail to submit share before subscribe",
+            12000 => "Fail to read webhooks",
+            12001 => "Fail to write webhooks",
+            12002 => "Fail to read webhook url",
+            12003 => "Fail to deliver webhook",
+            12004 => "Fail to watch with invalid target",
+            13000 => "Fail to read statsd address",
+            13001 => "Fail to push metrics",
+            14000 => "Fail to parse api field naming",
+            15000 => "Fail to read peer url",
+            15001 => "Fail to fetch headers of peer",
             _ => "Unknown",
-        };
+        }
+    }
 
-        write!(f, "[{}]: {}", self.code, message)
+    fn get_mapping(&self) -> (u16, &'static str, &'static str) {
+        match self.code {
+            1000 => (409, "invalid_block", "The block does not extend the latest block, retry once the chain is synced."),
+            1001 => (422, "dust_output", "Raise every output to at least the dust limit."),
+            1002 => (409, "name_unavailable", "Register another name."),
+            1003 => (400, "invalid_pow_algorithm", "Use one of the supported pow algorithms."),
+            1004 => (400, "invalid_payout_address", "Pay out to a public key address."),
+            1005 => (400, "miner_tag_too_long", "Shorten the miner tag."),
+            1006 => (400, "invalid_network", "Use one of the supported networks."),
+            1007 => (409, "utxo_commitment_mismatch", "Commit to the unspent tx outs of the chain once the block is added."),
+            1008 => (404, "utxo_snapshot_unavailable", "Sync from a peer whose chain commits to unspent tx outs deep enough."),
+            1009 => (409, "replayed_transaction", "Leave out transactions already confirmed or spending spent outputs."),
+            1010 => (400, "invalid_rollback_height", "Roll back to a height at most the tip."),
+            1011 => (409, "rollback_into_snapshot", "Roll back to a height after the utxo snapshot the node synced from."),
+            1012 => (403, "rollback_disabled", "Roll back or invalidate blocks on regtest, or start the node with --allow-rollback."),
+            1013 => (400, "genesis_invalidation", "Invalidate a block after the genesis block."),
+            1014 => (409, "chain_pruned", "Sync from a full node, or query a utxo snapshot with --fast-sync."),
+            2000 => (500, "signing_failed", "Check the key of the wallet can sign."),
+            2001 => (400, "invalid_transaction", "Check inputs and outputs of the transaction."),
+            2002 => (422, "invalid_block_transactions", "Check transactions of the block spend unspent outputs once."),
+            2003 => (422, "insufficient_funds", "Lower the amount or wait for incoming coins to be mined."),
+            2004 => (422, "amount_below_dust", "Send at least the dust limit."),
+            2005 => (400, "invalid_asset_symbol", "Use a short symbol of uppercase letters and digits."),
+            2006 => (400, "invalid_name", "Use a short name of lowercase letters, digits and hyphens."),
+            2007 => (400, "invalid_payment_uri", "Use a payment uri of the form scheme:address?amount=."),
+            2008 => (400, "payment_uri_without_amount", "Add an amount to the payment uri."),
+            2009 => (400, "invalid_address", "Use the hex public key of a secp256k1 or ed25519 key."),
+            3000 | 3002 | 3007 => (500, "key_file_unavailable", "Check the key file and its directory are readable and writable."),
+            3001 => (500, "key_generation_failed", "Retry creating the key."),
+            3003 => (400, "invalid_key_type", "Use one of the supported key types."),
+            3004 | 3005 | 3006 | 3010 | 3017 => (502, "remote_signer_unavailable", "Check the remote signer is reachable and holds the key."),
+            3008 | 3009 => (400, "invalid_wif", "Use a wif encoded private key of this network."),
+            3011 => (500, "key_file_exposed", "Restrict permissions of the key file to its owner."),
+            3012 | 3013 => (400, "invalid_key_file", "Keep exactly one private key in the key file."),
+            3014 | 3015 | 3016 => (400, "invalid_private_key", "Use a hex private key of the key type."),
+            4000 => (409, "spent_tx_outs", "Spend outputs which are still unspent."),
+            4001 => (409, "pool_conflict", "Wait for the pooled transaction spending the same outputs to be mined."),
+            4002 => (422, "dust_output", "Raise every output to at least the dust limit."),
+            4003 => (413, "transaction_too_large", "Split the payment into smaller transactions."),
+            4004 => (422, "fee_too_low", "Pay at least the min fee of the pool."),
+            4005 => (422, "nonstandard_script", "Use a pay to public key, multisig, locktime or data script."),
+            5000 | 5002 => (500, "network_unavailable", "Check the listen address is free."),
+            5001 => (400, "invalid_transport", "Use one of the supported transports."),
+            5003 => (400, "invalid_wire_format", "Use one of the supported wire formats."),
+            5004 => (500, "encoding_failed", "Retry the request."),
+            5005 => (400, "decoding_failed", "Check the payload matches the wire format."),
+            5006 => (400, "invalid_bind_address", "Use an address of the form host:port."),
+            5007 => (400, "invalid_peer_address", "Use a peer address such as ws://host:port or a multiaddr."),
+            5008 => (400, "invalid_dns_seed", "Use a dns name, optionally followed by a port."),
+            5009 => (502, "dns_seed_unresolved", "Check the dns seed lists peers."),
+            6000 => (400, "invalid_ffi_argument", "Pass valid utf-8 strings and json."),
+            6001 => (500, "ffi_panic", "Report the arguments of the call."),
+            7000 | 7001 => (500, "journal_unavailable", "Check the journal file is readable and writable."),
+            8000 => (400, "invalid_invoice_memo", "Use a short memo which does not look like a name registration."),
+            9000 | 9001 => (500, "labels_unavailable", "Check the labels file is readable and writable."),
+            9002 => (404, "label_target_not_found", "Label a known address or transaction."),
+            10000 => (400, "invalid_price_feed_url", "Use an http url with a host."),
+            10001 => (502, "price_unavailable", "Retry once the price feed is reachable."),
+            11000 => (500, "stratum_unavailable", "Check the stratum address is free."),
+            11001 => (400, "invalid_stratum_request", "Send a stratum json-rpc request."),
+            11002 => (409, "stale_share", "Mine the latest job."),
+            11003 => (409, "duplicate_share", "Submit every share once."),
+            11004 => (422, "share_above_target", "Submit shares meeting the target of the job."),
+            11005 => (409, "not_subscribed", "Subscribe before submitting shares."),
+            12000 | 12001 => (500, "webhooks_unavailable", "Check the webhooks file is readable and writable."),
+            12002 => (400, "invalid_webhook_url", "Use an http url with a host."),
+            12003 => (502, "webhook_undeliverable", "Check the webhook url answers a 2xx status."),
+            12004 => (400, "invalid_watch", "Watch either a transaction or an address, at one confirmation or more."),
+            13000 => (400, "invalid_statsd_address", "Use a host:port address of a statsd daemon."),
+            13001 => (502, "metrics_unavailable", "Check statsd is reachable."),
+            14000 => (400, "invalid_field_naming", "Use snake or camel."),
+            15000 => (400, "invalid_peer_url", "Use the http url of the api of the peer, e.g. http://127.0.0.1:3001."),
+            15001 => (502, "peer_unavailable", "Check the api of the peer is reachable and serves /api/headers."),
+            _ => (500, "unknown", "Retry the request."),
+        }
     }
 }
 
-/// Error for api
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}]: {}", self.code, self.get_message())
+    }
+}
+
+/// Error for api, answered with its code as http status.
+///
+/// Kind is machine-readable and stable, see `AppError::get_kind`; message and hint are for humans and may change.
+#[cfg(feature = "node")]
 #[derive(Debug, Serialize)]
 pub struct ApiError {
-    /// code of error
+    /// http status of error
     code: usize,
 
+    /// machine-readable kind of error
+    kind: String,
+
     /// message of error
     message: String,
 
+    /// how to fix the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+
+    /// code of app error causing the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<usize>,
+
     /// errors of validation
     errors: Option<ValidationErrors>,
+
+    /// rule of spend policy broken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    violation: Option<PolicyViolation>,
 }
 
+#[cfg(feature = "node")]
 impl ApiError {
-    /// Returns a error with args
+    /// Returns a error with args, its kind named after the status
     ///
     /// # Examples
     ///
@@ -68,15 +276,59 @@ impl ApiError {
     /// let error = ApiError::new(404, "Not found".to_string(), Some(ValidationErrors::new()));
     /// ```
     pub fn new(code: usize, message: String, errors: Option<ValidationErrors>) -> Self {
-        Self { code, message, errors }
+        let kind = match code {
+            400 => "bad_request",
+            403 => "forbidden",
+            404 => "not_found",
+            409 => "conflict",
+            422 => "invalid_fields",
+            _ => "internal",
+        };
+        Self { code, kind: kind.to_string(), message, hint: None, error_code: None, errors, violation: None }
+    }
+
+    /// Returns a error of a request failing with app error, prefixed by message
+    pub fn from_app_error(message: &str, error: &AppError) -> Self {
+        Self {
+            code: error.get_status() as usize,
+            kind: error.get_kind().to_string(),
+            message: format!("{}: {}", message, error.get_message()),
+            hint: Some(error.get_hint().to_string()),
+            error_code: Some(error.code),
+            errors: None,
+            violation: None,
+        }
+    }
+
+    /// Returns a error of a transaction breaking a rule of spend policy
+    pub fn from_violation(violation: PolicyViolation) -> Self {
+        Self {
+            code: 403,
+            kind: "policy_violation".to_string(),
+            message: format!("Spend policy violated: {}", violation),
+            hint: None,
+            error_code: None,
+            errors: None,
+            violation: Some(violation),
+        }
     }
 }
 
+#[cfg(feature = "node")]
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let status = Status::from_code(self.code as u16).unwrap_or(Status::InternalServerError);
+        Response::build_from(Json(self).respond_to(request)?).status(status).ok()
+    }
+}
+
+#[cfg(feature = "node")]
 pub struct FieldValidator {
     errors: ValidationErrors,
 }
 
 
+#[cfg(feature = "node")]
 impl Default for FieldValidator {
     fn default() -> Self {
         Self {
@@ -85,6 +337,7 @@ impl Default for FieldValidator {
     }
 }
 
+#[cfg(feature = "node")]
 impl FieldValidator {
     pub fn validate<T: Validate>(model: &T) -> Self {
         Self {
@@ -92,23 +345,129 @@ impl FieldValidator {
         }
     }
 
+    /// Add error of code to field.
+    pub fn add(&mut self, field_name: &'static str, code: &'static str) {
+        self.errors.add(field_name, ValidationError::new(code));
+    }
+
     /// Convenience method to trigger early returns with ? operator.
-    pub fn check(self) -> Result<(), Json<ApiError>> {
+    pub fn check(self) -> Result<(), ApiError> {
         if self.errors.is_empty() {
             Ok(())
         } else {
-            Err(Json(ApiError::new(500,"Invalid fields".to_string(), Some(self.errors))))
+            self.fail()
         }
     }
 
-    pub fn extract<T>(&mut self, field_name: &'static str, field: Option<T>) -> T
-        where
-            T: Default,
-    {
-        field.unwrap_or_else(|| {
-            self.errors
-                .add(field_name, ValidationError::new("INVALID_FIELD"));
-            T::default()
-        })
+    fn fail<T>(self) -> Result<T, ApiError> {
+        Err(ApiError::new(422, "Invalid fields".to_string(), Some(self.errors)))
+    }
+
+    /// Parse JSON body into a request with mandatory fields, none of them defaulted.
+    ///
+    /// # Errors
+    /// If body is not JSON, it returns error 400.
+    /// If fields are missing, of a wrong type or fail validation, it returns error 422 with all of them.
+    pub fn parse<T: DeserializeOwned + Validate>(body: &str) -> Result<T, ApiError> {
+        let mut value = serde_json::from_str::<Value>(body).map_err(|e| ApiError::new(400, e.to_string(), None))?;
+        let mut validator = FieldValidator::default();
+        if !value.is_object() {
+            validator.errors.add("body", ValidationError::new("object"));
+            return validator.fail();
+        }
+
+        // A missing field is filled with a placeholder of its type, so the next missing one is reported too.
+        for field in get_field_names::<T>() {
+            match serde_json::from_value::<T>(value.clone()) {
+                Err(e) if e.to_string() == format!("missing field `{}`", field) => {
+                    validator.errors.add(field, ValidationError::new("required"));
+                    let is_filled = get_placeholders().into_iter().any(|placeholder| {
+                        value[*field] = placeholder;
+                        serde_json::from_value::<T>(value.clone()).map_or_else(|e| e.to_string().starts_with("missing field"), |_| true)
+                    });
+                    if !is_filled {
+                        return validator.fail();
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        match serde_json::from_value::<T>(value) {
+            Ok(request) => {
+                let missing: Vec<&str> = validator.errors.field_errors().keys().copied().collect();
+                for (field, errors) in request.validate().err().unwrap_or_else(ValidationErrors::new).field_errors() {
+                    if !missing.contains(&field) {
+                        errors.iter().for_each(|error| validator.errors.add(field, error.clone()));
+                    }
+                }
+                validator.check().map(|_| request)
+            }
+            Err(e) => {
+                validator.errors.add("body", ValidationError { message: Some(e.to_string().into()), ..ValidationError::new("type") });
+                validator.fail()
+            }
+        }
+    }
+}
+
+/// Values a missing field is filled with, one of each JSON type.
+#[cfg(feature = "node")]
+fn get_placeholders() -> Vec<Value> {
+    vec![json!(0), json!(""), json!(false), json!([]), json!({})]
+}
+
+/// Get names of fields of a struct, as its `Deserialize` asks for them.
+#[cfg(feature = "node")]
+fn get_field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+#[cfg(feature = "node")]
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+#[cfg(feature = "node")]
+impl<'de, 'a> Deserializer<'de> for FieldNames<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("fields read"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_kind() {
+        let error = AppError::new(2003);
+        assert_eq!((error.get_status(), error.get_kind()), (422, "insufficient_funds"));
+        assert_eq!(AppError::new(1000).get_kind(), "invalid_block");
+        assert_eq!(AppError::new(1001).get_kind(), AppError::new(4002).get_kind());
+        assert_eq!((AppError::new(42).get_status(), AppError::new(42).get_kind()), (500, "unknown"));
+
+        for code in (1000..15000).filter(|code| AppError::new(*code).get_message() != "Unknown") {
+            let error = AppError::new(code);
+            assert_ne!(error.get_kind(), "unknown", "{} has no kind", error);
+            assert!((400..600).contains(&error.get_status()));
+        }
     }
 }
@@ -1,11 +1,23 @@
+use std::sync::Arc;
+
+use tokio::sync::oneshot;
+
 use crate::{Block, Transaction};
-use crate::connection::Connection;
+use crate::payload::Payload;
+use crate::peers::{Handshake, PeerClock, PeerStats, Reject};
 
 #[derive(Debug)]
 pub enum BroadcastEvents {
-    Join(Connection),
-    Quit(String),
     Peer(String),
-    Blockchain(Vec<Block>, Option<String>),
+    Disconnect(String, String),
+    Invalid(String),
+    Rejected(String, Reject),
+    Stats(String, oneshot::Sender<Option<PeerStats>>),
+    Handshake(String, Handshake),
+    Clock(String, PeerClock),
+    QueryLatest,
+    Known(String, Vec<String>),
+    Reply(String, Payload),
+    Blockchain(Arc<Vec<Block>>, Option<String>),
     Transaction(Vec<Transaction>, Option<String>),
 }
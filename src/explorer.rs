@@ -0,0 +1,314 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{Block, Transaction};
+use crate::block::get_miner_tag;
+use crate::dto::serialize_model;
+use crate::address_index::AddressIndex;
+use crate::price_feed::FiatAmount;
+use crate::propagation::BlockArrival;
+
+/// Block with data derived from the rest of the chain.
+#[derive(Debug, Serialize)]
+pub struct BlockSummary {
+    /// Block itself
+    #[serde(serialize_with = "serialize_model")]
+    pub block: Block,
+
+    /// Count of blocks on top including itself
+    pub confirmations: usize,
+
+    /// Sum of outputs excluding coinbase
+    pub total_value: usize,
+
+    /// Sum of inputs minus outputs excluding coinbase
+    pub total_fees: usize,
+
+    /// Address paid by coinbase transaction
+    pub coinbase_recipient: Option<String>,
+
+    /// Text the miner embedded in coinbase transaction
+    pub miner_tag: Option<String>,
+
+    /// Serialized size in bytes
+    pub size: usize,
+
+    /// Previous block hash
+    pub previous_hash: Option<String>,
+
+    /// Next block hash
+    pub next_hash: Option<String>,
+
+    /// When and from which peer this node first received the block, none if it was never recorded
+    pub arrival: Option<BlockArrival>,
+}
+
+/// Block without its data, for clients following the chain.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlockHeader {
+    pub index: usize,
+    pub hash: String,
+    pub previous_hash: String,
+    pub timestamp: usize,
+    pub difficulty: usize,
+    pub nonce: usize,
+
+    /// Count of transactions in data including coinbase
+    pub tx_count: usize,
+}
+
+/// Transaction with the block containing it.
+#[derive(Debug, Serialize)]
+pub struct TransactionSummary {
+    /// Transaction itself
+    #[serde(serialize_with = "serialize_model")]
+    pub transaction: Transaction,
+
+    /// Hash of containing block, none while in pool
+    pub block_hash: Option<String>,
+
+    /// Count of blocks on top including containing block
+    pub confirmations: usize,
+
+    /// Local label of the transaction, never on chain
+    pub label: Option<String>,
+
+    /// Fiat equivalent of coins paid by outputs, when a price feed is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatAmount>,
+}
+
+/// Type of entity found by search.
+#[derive(Debug, Serialize, PartialEq)]
+pub enum SearchType {
+    Block,
+    Transaction,
+    Address,
+}
+
+/// Entity found by search.
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    /// Type of entity
+    pub r#type: SearchType,
+
+    /// Hash, transaction id, or address
+    pub id: String,
+
+    /// Canonical url of entity
+    pub url: String,
+}
+
+fn find_tx_out_amount(blockchain: &Vec<Block>, tx_out_id: &str, tx_out_index: usize) -> usize {
+    blockchain
+        .iter()
+        .flat_map(|block| block.data.iter())
+        .find(|tx| tx.id.eq(tx_out_id))
+        .and_then(|tx| tx.tx_outs.get(tx_out_index))
+        .map_or(0, |tx_out| tx_out.amount)
+}
+
+fn get_tx_out_total(transaction: &Transaction) -> usize {
+    transaction.tx_outs.iter().map(|tx_out| tx_out.amount).sum()
+}
+
+fn get_tx_in_total(blockchain: &Vec<Block>, transaction: &Transaction) -> usize {
+    transaction.tx_ins
+        .iter()
+        .map(|tx_in| find_tx_out_amount(blockchain, &tx_in.tx_out_id, tx_in.tx_out_index))
+        .sum()
+}
+
+/// Get summary of block by hash.
+pub fn get_block_summary(blockchain: &Vec<Block>, hash: &str) -> Option<BlockSummary> {
+    let position = blockchain.iter().position(|block| block.hash.eq(hash))?;
+    let block = blockchain.get(position).unwrap();
+    let transactions = block.data.iter().skip(1);
+
+    let total_value = transactions.clone().map(get_tx_out_total).sum();
+    let total_fees = transactions
+        .map(|tx| get_tx_in_total(blockchain, tx).saturating_sub(get_tx_out_total(tx)))
+        .sum();
+
+    Some(BlockSummary {
+        block: block.clone(),
+        confirmations: blockchain.len() - position,
+        total_value,
+        total_fees,
+        coinbase_recipient: block.data.get(0).and_then(|tx| tx.tx_outs.get(0)).map(|tx_out| tx_out.address.clone()),
+        miner_tag: block.data.get(0).and_then(get_miner_tag),
+        size: serde_json::to_string(block).unwrap().len(),
+        previous_hash: if position == 0 { None } else { Some(block.previous_hash.clone()) },
+        next_hash: blockchain.get(position + 1).map(|next| next.hash.clone()),
+        arrival: None,
+    })
+}
+
+/// Get headers of blocks from height to height inclusive, as far as the chain goes.
+pub fn get_block_headers(blockchain: &[Block], from: usize, to: usize) -> Vec<BlockHeader> {
+    blockchain
+        .iter()
+        .skip(from)
+        .take(to.saturating_sub(from).saturating_add(1))
+        .map(|block| BlockHeader {
+            index: block.index,
+            hash: block.hash.clone(),
+            previous_hash: block.previous_hash.clone(),
+            timestamp: block.timestamp,
+            difficulty: block.difficulty,
+            nonce: block.nonce,
+            tx_count: block.data.len(),
+        })
+        .collect()
+}
+
+/// Get transaction by id from blockchain or transaction pool.
+pub fn get_transaction_summary(blockchain: &Vec<Block>, transaction_pool: &Vec<Transaction>, id: &str) -> Option<TransactionSummary> {
+    for (position, block) in blockchain.iter().enumerate() {
+        if let Some(tx) = block.data.iter().find(|tx| tx.id.eq(id)) {
+            return Some(TransactionSummary {
+                transaction: tx.clone(),
+                block_hash: Some(block.hash.clone()),
+                confirmations: blockchain.len() - position,
+                label: None,
+                fiat: None,
+            });
+        }
+    }
+
+    transaction_pool
+        .iter()
+        .find(|tx| tx.id.eq(id))
+        .map(|tx| TransactionSummary { transaction: tx.clone(), block_hash: None, confirmations: 0, label: None, fiat: None })
+}
+
+/// Search block hash, transaction id, or address.
+pub fn search(
+    blockchain: &Vec<Block>,
+    transaction_pool: &Vec<Transaction>,
+    address_index: &AddressIndex,
+    query: &str,
+) -> Option<SearchResult> {
+    let query = query.trim().to_lowercase();
+    if !query.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    if query.len() == 64 {
+        if blockchain.iter().any(|block| block.hash.eq(&query)) {
+            return Some(SearchResult { r#type: SearchType::Block, url: format!("/api/explorer/block/{}", query), id: query });
+        }
+        if get_transaction_summary(blockchain, transaction_pool, &query).is_some() {
+            return Some(SearchResult { r#type: SearchType::Transaction, url: format!("/api/explorer/transaction/{}", query), id: query });
+        }
+    } else if query.len() == 66 && address_index.get_balance(&query) > 0 {
+        return Some(SearchResult { r#type: SearchType::Address, url: format!("/api/explorer/address/{}", query), id: query });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::transaction::{TxIn, TxOut};
+    use super::*;
+
+    #[test]
+    fn test_get_block_summary() {
+        let coinbase = Transaction::generate(
+            &vec![TxIn::new("".to_string(), 0, "".to_string())],
+            &vec![TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)],
+        );
+        let genesis = Block::new(
+            0,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
+            "".to_string(),
+            1465154705,
+            vec![coinbase.clone()],
+            0,
+            0,
+        );
+        let spend = Transaction::generate(
+            &vec![TxIn::new(coinbase.id.clone(), 0, "".to_string())],
+            &vec![TxOut::new("03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40".to_string(), 40)],
+        );
+        let next_coinbase = Transaction::generate(
+            &vec![TxIn::new("".to_string(), 1, "".to_string())],
+            &vec![TxOut::new("03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40".to_string(), 50)],
+        );
+        let next = Block::new(
+            1,
+            "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3".to_string(),
+            genesis.hash.clone(),
+            1465154706,
+            vec![next_coinbase, spend],
+            0,
+            0,
+        );
+        let blockchain = vec![genesis.clone(), next.clone()];
+
+        let summary = get_block_summary(&blockchain, &genesis.hash).unwrap();
+        assert_eq!(summary.confirmations, 2);
+        assert_eq!(summary.total_value, 0);
+        assert_eq!(summary.previous_hash, None);
+        assert_eq!(summary.next_hash, Some(next.hash.clone()));
+        assert_eq!(summary.coinbase_recipient, Some("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string()));
+        assert_eq!(summary.miner_tag, None);
+
+        let summary = get_block_summary(&blockchain, &next.hash).unwrap();
+        assert_eq!(summary.confirmations, 1);
+        assert_eq!(summary.total_value, 40);
+        assert_eq!(summary.total_fees, 10);
+        assert_eq!(summary.previous_hash, Some(genesis.hash.clone()));
+        assert_eq!(summary.next_hash, None);
+        assert!(summary.size > 0);
+
+        assert!(get_block_summary(&blockchain, "unknown").is_none());
+
+        let headers = get_block_headers(&blockchain, 0, 5);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[1], BlockHeader {
+            index: 1,
+            hash: next.hash.clone(),
+            previous_hash: genesis.hash.clone(),
+            timestamp: 1465154706,
+            difficulty: 0,
+            nonce: 0,
+            tx_count: 2,
+        });
+        assert_eq!(get_block_headers(&blockchain, 1, 1).len(), 1);
+        assert!(get_block_headers(&blockchain, 2, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn test_search() {
+        let coinbase = Transaction::generate(
+            &vec![TxIn::new("".to_string(), 0, "".to_string())],
+            &vec![TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)],
+        );
+        let genesis = Block::new(
+            0,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
+            "".to_string(),
+            1465154705,
+            vec![coinbase.clone()],
+            0,
+            0,
+        );
+        let blockchain = vec![genesis.clone()];
+        let address_index = AddressIndex::new(&blockchain);
+
+        let result = search(&blockchain, &vec![], &address_index, &genesis.hash).unwrap();
+        assert_eq!(result.r#type, SearchType::Block);
+        assert_eq!(result.url, format!("/api/explorer/block/{}", genesis.hash));
+
+        let result = search(&blockchain, &vec![], &address_index, &coinbase.id.to_uppercase()).unwrap();
+        assert_eq!(result.r#type, SearchType::Transaction);
+        assert_eq!(result.id, coinbase.id);
+
+        let result = search(&blockchain, &vec![], &address_index, "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b").unwrap();
+        assert_eq!(result.r#type, SearchType::Address);
+
+        assert!(search(&blockchain, &vec![], &address_index, "unknown").is_none());
+        assert!(search(&blockchain, &vec![], &address_index, "10be4410b3b58e0ea796826f6190236bcb2f6f0117f0187cf366ca91ea7965a3").is_none());
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::{Cursor, Write};
+use std::time::Instant;
+use flate2::Compression as Level;
+use flate2::write::{DeflateEncoder, GzEncoder};
+use rocket::{Data, Request, Response};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::ContentType;
+use uuid::Uuid;
+
+const REQUEST_ID_HEADER: &'static str = "X-Request-Id";
+const MIN_COMPRESSION_SIZE: usize = 1024;
+
+struct RequestTrace {
+    id: String,
+    started: Instant,
+}
+
+impl RequestTrace {
+    fn new() -> Self {
+        Self { id: format!("{}", Uuid::new_v4()), started: Instant::now() }
+    }
+}
+
+/// Fairing which assigns an id to each request and logs its latency.
+pub struct RequestTracer;
+
+impl Fairing for RequestTracer {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracer",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        request.local_cache(RequestTrace::new);
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let trace = request.local_cache(RequestTrace::new);
+        tracing::info!(
+            request_id = %trace.id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            status = response.status().code,
+            duration_ms = trace.started.elapsed().as_millis() as u64,
+            "http request"
+        );
+        response.set_raw_header(REQUEST_ID_HEADER, trace.id.clone());
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    fn encode(&self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Level::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = DeflateEncoder::new(vec![], Level::default());
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Get encoding accepted by client, preferring gzip.
+fn get_accepted_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let accepted: Vec<&str> = accept_encoding
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(|part| part.trim());
+            let name = parts.next()?;
+            let rejected = parts.any(|param| param.replace(' ', "").eq("q=0"));
+            if rejected { None } else { Some(name) }
+        })
+        .collect();
+
+    if accepted.iter().any(|name| name.eq_ignore_ascii_case("gzip")) {
+        Some(Encoding::Gzip)
+    } else if accepted.iter().any(|name| name.eq_ignore_ascii_case("deflate")) {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+/// Fairing which compresses large JSON responses with gzip or deflate.
+pub struct Compression;
+
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        if response.content_type() != Some(ContentType::JSON) || response.headers().contains("Content-Encoding") {
+            return;
+        }
+
+        let encoding = match request.headers().get("Accept-Encoding").find_map(get_accepted_encoding) {
+            Some(encoding) => encoding,
+            None => return,
+        };
+
+        let body = match response.body_bytes() {
+            Some(body) => body,
+            None => return,
+        };
+
+        if body.len() < MIN_COMPRESSION_SIZE {
+            response.set_sized_body(Cursor::new(body));
+            return;
+        }
+
+        match encoding.encode(&body) {
+            Ok(encoded) => {
+                response.set_raw_header("Content-Encoding", encoding.name());
+                response.set_raw_header("Vary", "Accept-Encoding");
+                response.set_sized_body(Cursor::new(encoded));
+            }
+            Err(_) => response.set_sized_body(Cursor::new(body)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_accepted_encoding() {
+        assert_eq!(get_accepted_encoding("gzip, deflate, br"), Some(Encoding::Gzip));
+        assert_eq!(get_accepted_encoding("deflate;q=0.5, gzip;q=0"), Some(Encoding::Deflate));
+        assert_eq!(get_accepted_encoding("br"), None);
+        assert_eq!(get_accepted_encoding(""), None);
+    }
+
+    #[test]
+    fn test_encoding_encode() {
+        let body = "{}".repeat(MIN_COMPRESSION_SIZE);
+        assert!(Encoding::Gzip.encode(body.as_bytes()).unwrap().len() < body.len());
+        assert!(Encoding::Deflate.encode(body.as_bytes()).unwrap().len() < body.len());
+    }
+}
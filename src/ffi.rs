@@ -0,0 +1,275 @@
+//! C bindings of wallets, transaction building and block verification, enabled with the `ffi` feature.
+//!
+//! Strings are NUL terminated UTF-8 and structured values are JSON, as served by the http api.
+//! Returned strings belong to the caller and are released with `blockchain_string_free`.
+//! A failing function returns null or -1 and records the error code, read with
+//! `blockchain_last_error_code`. Panics never unwind into the caller, they fail with error 6001.
+//!
+//! The header `include/blockchain.h` is generated with
+//! `cbindgen --config cbindgen.toml --crate blockchain --output include/blockchain.h`
+//! and a shared library is built with
+//! `cargo rustc --release --lib --crate-type cdylib --no-default-features --features ffi`.
+
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::block::{get_is_valid_new_block, Block};
+use crate::errors::AppError;
+use crate::network_time::NetworkClock;
+use crate::signer::{generate_signer, get_signer};
+use crate::transaction::{process_transactions, BlockContext, UnspentTxOut};
+use crate::validation_cache::ValidationCache;
+use crate::wallet::{create_transaction, Wallet};
+
+thread_local! {
+    static LAST_ERROR_CODE: Cell<usize> = Cell::new(0);
+}
+
+/// Get code of the last error on this thread, 0 if the last call succeeded.
+#[no_mangle]
+pub extern "C" fn blockchain_last_error_code() -> usize {
+    guard(6001, || LAST_ERROR_CODE.with(|code| code.get()))
+}
+
+/// Release a string returned by this library.
+///
+/// # Safety
+/// `s` must be null or returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_string_free(s: *mut c_char) {
+    guard((), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+/// Create a wallet key of `key_type`, `secp256k1` or `ed25519`, and return its hex private key.
+///
+/// # Safety
+/// `key_type` must be null or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_wallet_create(key_type: *const c_char) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        into_c_string(read_str(key_type).and_then(|key_type| {
+            let signer = generate_signer(key_type.parse()?)?;
+            Ok(signer.get_private_key())
+        }))
+    })
+}
+
+/// Derive the address of a hex or WIF private key.
+///
+/// # Safety
+/// `private_key` must be null or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_address_derive(private_key: *const c_char) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        into_c_string(read_str(private_key).and_then(|private_key| Ok(get_signer(private_key)?.get_address())))
+    })
+}
+
+/// Build a transaction sending `amount` to `receiver_address` from `unspent_tx_outs_json`,
+/// a JSON array of unspent tx outs, sign it with `private_key` and return it as JSON.
+///
+/// # Safety
+/// Every string must be null or NUL terminated.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_transaction_create(
+    receiver_address: *const c_char,
+    amount: usize,
+    private_key: *const c_char,
+    unspent_tx_outs_json: *const c_char,
+    dust_limit: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        into_c_string((|| {
+            let receiver_address = read_str(receiver_address)?;
+            let private_key = read_str(private_key)?;
+            let unspent_tx_outs: Vec<UnspentTxOut> = read_json(unspent_tx_outs_json)?;
+            let wallet = Wallet {
+                private_key: private_key.to_string(),
+                public_key: get_signer(private_key)?.get_address(),
+                remote_signer: None,
+                imported_keys: vec![],
+            };
+            let transaction = create_transaction(receiver_address, amount, &wallet, &unspent_tx_outs, dust_limit)?;
+            Ok(serde_json::to_string(&transaction).unwrap())
+        })())
+    })
+}
+
+/// Verify JSON `block_json` follows `previous_block_json` and its transactions spend `unspent_tx_outs_json`,
+/// a JSON array of the unspent tx outs after the previous block, returning 1 if valid, 0 if not and -1 on error.
+///
+/// Only the previous block is known, so transactions or names repeating ones earlier in the chain are not checked.
+///
+/// # Safety
+/// Every string must be null or NUL terminated.
+#[no_mangle]
+pub unsafe extern "C" fn blockchain_block_verify(
+    block_json: *const c_char,
+    previous_block_json: *const c_char,
+    unspent_tx_outs_json: *const c_char,
+) -> c_int {
+    guard(-1, || {
+        let result: Result<_, AppError> = (|| {
+            let block: Block = read_json(block_json)?;
+            let previous_block: Block = read_json(previous_block_json)?;
+            let unspent_tx_outs: Vec<UnspentTxOut> = read_json(unspent_tx_outs_json)?;
+            Ok((block, previous_block, unspent_tx_outs))
+        })();
+        match result {
+            Ok((block, previous_block, unspent_tx_outs)) => {
+                set_last_error_code(0);
+                let is_valid = get_is_valid_new_block(&block, &previous_block, &NetworkClock)
+                    && process_transactions(&block.data, &unspent_tx_outs, BlockContext::of(&block), &mut ValidationCache::default()).is_ok();
+                is_valid as c_int
+            }
+            Err(e) => {
+                set_last_error_code(e.code);
+                -1
+            }
+        }
+    })
+}
+
+/// Run body of an exported function, returning failed and recording error 6001 if it panics.
+fn guard<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_| {
+        set_last_error_code(6001);
+        failed
+    })
+}
+
+fn set_last_error_code(code: usize) {
+    LAST_ERROR_CODE.with(|last| last.set(code));
+}
+
+unsafe fn read_str<'a>(s: *const c_char) -> Result<&'a str, AppError> {
+    if s.is_null() {
+        return Err(AppError::new(6000));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| AppError::new(6000))
+}
+
+unsafe fn read_json<T: serde::de::DeserializeOwned>(s: *const c_char) -> Result<T, AppError> {
+    serde_json::from_str(read_str(s)?).map_err(|_| AppError::new(6000))
+}
+
+fn into_c_string(result: Result<String, AppError>) -> *mut c_char {
+    match result {
+        Ok(s) => {
+            set_last_error_code(0);
+            CString::new(s).unwrap().into_raw()
+        }
+        Err(e) => {
+            set_last_error_code(e.code);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{get_funding_unspent_tx_outs, ALICE, RECEIVER_ADDRESS};
+    use crate::transaction::{get_coinbase_transaction, Transaction};
+    use super::*;
+
+    unsafe fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let value = CStr::from_ptr(s).to_str().unwrap().to_string();
+        blockchain_string_free(s);
+        value
+    }
+
+    #[test]
+    fn test_wallet_create() {
+        unsafe {
+            let key_type = CString::new("ed25519").unwrap();
+            let private_key = take_string(blockchain_wallet_create(key_type.as_ptr()));
+            assert_eq!(blockchain_last_error_code(), 0);
+            assert!(get_signer(&private_key).is_ok());
+
+            let key_type = CString::new("rsa").unwrap();
+            assert!(blockchain_wallet_create(key_type.as_ptr()).is_null());
+            assert_eq!(blockchain_last_error_code(), 3003);
+
+            assert!(blockchain_wallet_create(ptr::null()).is_null());
+            assert_eq!(blockchain_last_error_code(), 6000);
+        }
+    }
+
+    #[test]
+    fn test_address_derive() {
+        unsafe {
+            let private_key = CString::new(ALICE.private_key).unwrap();
+            assert_eq!(take_string(blockchain_address_derive(private_key.as_ptr())), ALICE.address);
+
+            let private_key = CString::new("invalid").unwrap();
+            assert!(blockchain_address_derive(private_key.as_ptr()).is_null());
+            assert_ne!(blockchain_last_error_code(), 0);
+        }
+    }
+
+    #[test]
+    fn test_transaction_create() {
+        unsafe {
+            let receiver_address = CString::new(RECEIVER_ADDRESS).unwrap();
+            let private_key = CString::new(ALICE.private_key).unwrap();
+            let unspent_tx_outs = CString::new(serde_json::to_string(&get_funding_unspent_tx_outs()).unwrap()).unwrap();
+            let transaction = take_string(blockchain_transaction_create(
+                receiver_address.as_ptr(),
+                10,
+                private_key.as_ptr(),
+                unspent_tx_outs.as_ptr(),
+                0,
+            ));
+            let transaction: Transaction = serde_json::from_str(&transaction).unwrap();
+            assert_eq!(transaction.tx_outs[0].address, RECEIVER_ADDRESS);
+            assert_eq!(transaction.tx_outs[0].amount, 10);
+            assert_eq!(transaction.tx_outs[1].amount, 40);
+            assert!(!transaction.tx_ins[0].signature.is_empty());
+
+            let invalid = CString::new("[").unwrap();
+            assert!(blockchain_transaction_create(receiver_address.as_ptr(), 10, private_key.as_ptr(), invalid.as_ptr(), 0).is_null());
+            assert_eq!(blockchain_last_error_code(), 6000);
+        }
+    }
+
+    #[test]
+    fn test_block_verify() {
+        let previous = Block::new(
+            0,
+            "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
+            "".to_string(),
+            1465154705,
+            vec![],
+            0,
+            0,
+        );
+        let next = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 1, None)], &previous, 0);
+        let wrong_coinbase = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 2, None)], &previous, 0);
+        let previous_json = CString::new(serde_json::to_string(&previous).unwrap()).unwrap();
+        let next_json = CString::new(serde_json::to_string(&next).unwrap()).unwrap();
+        let wrong_coinbase_json = CString::new(serde_json::to_string(&wrong_coinbase).unwrap()).unwrap();
+        let unspent_tx_outs = CString::new("[]").unwrap();
+        unsafe {
+            assert_eq!(blockchain_block_verify(next_json.as_ptr(), previous_json.as_ptr(), unspent_tx_outs.as_ptr()), 1);
+            assert_eq!(blockchain_block_verify(previous_json.as_ptr(), next_json.as_ptr(), unspent_tx_outs.as_ptr()), 0);
+            assert_eq!(blockchain_block_verify(wrong_coinbase_json.as_ptr(), previous_json.as_ptr(), unspent_tx_outs.as_ptr()), 0);
+            assert_eq!(blockchain_block_verify(next_json.as_ptr(), ptr::null(), unspent_tx_outs.as_ptr()), -1);
+            assert_eq!(blockchain_last_error_code(), 6000);
+        }
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(guard(-1, || panic!("ffi")), -1);
+        assert_eq!(blockchain_last_error_code(), 6001);
+        assert_eq!(guard(-1, || 1), 1);
+    }
+}
@@ -1,16 +1,34 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 use std::thread;
-use rocket_contrib::json::Json;
+use rocket::Rocket;
 use rocket_cors::{Cors, CorsOptions};
+use tokio::sync::RwLock;
 use tokio::sync::mpsc::UnboundedSender;
 
-use crate::{Block, BroadcastEvents, Config, routes, Transaction, UnspentTxOut, Wallet};
+use crate::{BroadcastEvents, Config, routes, Transaction, UnspentTxOut, Wallet};
+use crate::address_index::AddressIndex;
+use crate::auth::AdminToken;
+use crate::chain_state::ChainState;
 use crate::errors::ApiError;
+use crate::fairings::{Compression, RequestTracer};
+use crate::invoice::InvoiceBook;
+use crate::labels::LabelStore;
+use crate::journal::Journal;
+use crate::miner::Miner;
+use crate::peers::PeerRegistry;
+use crate::price_feed::PriceFeed;
+use crate::spend_policy::SpendPolicy;
+use crate::stratum::Stratum;
+use crate::sync_status::SyncTracker;
+use crate::tip_stream::TipNotifier;
+use crate::validation_cache::ValidationCache;
+use crate::wallet::KeySettings;
+use crate::webhooks::WebhookBook;
 
 #[catch(404)]
 #[allow(dead_code)]
-fn not_found() -> Json<ApiError> {
-    Json(ApiError::new(404, "Resource was not found.".to_string(), None))
+fn not_found() -> ApiError {
+    ApiError::new(404, "Resource was not found.".to_string(), None)
 }
 
 fn cors_fairing() -> Cors {
@@ -21,40 +39,555 @@ fn cors_fairing() -> Cors {
 
 pub fn launch_http(
     config: &Config,
-    blockchain: &Arc<RwLock<Vec<Block>>>,
+    blockchain: &Arc<ChainState>,
     unspent_tx_outs: &Arc<RwLock<Vec<UnspentTxOut>>>,
     transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
     wallet: &Arc<RwLock<Wallet>>,
+    address_index: &Arc<RwLock<AddressIndex>>,
+    tip_notifier: &Arc<TipNotifier>,
+    invoices: &Arc<InvoiceBook>,
+    label_store: &Arc<LabelStore>,
+    spend_policy: &Arc<SpendPolicy>,
+    validation_cache: &Arc<RwLock<ValidationCache>>,
+    miner: &Arc<Miner>,
+    sync_tracker: &Arc<SyncTracker>,
+    peer_registry: &Arc<PeerRegistry>,
+    price_feed: &Option<Arc<PriceFeed>>,
+    stratum: &Option<Arc<Stratum>>,
+    webhooks: &Option<Arc<WebhookBook>>,
+    journal: &Option<Arc<Journal>>,
     broadcast_sender: UnboundedSender<BroadcastEvents>,
 ) {
+    let server = build_http(
+        config, blockchain, unspent_tx_outs, transaction_pool, wallet, address_index, tip_notifier, invoices, label_store,
+        spend_policy, validation_cache, miner, sync_tracker, peer_registry, price_feed, stratum, webhooks, journal,
+        broadcast_sender,
+    );
+    thread::spawn(move || {
+        server.launch();
+    });
+}
+
+/// Build the http server on state of the node, without launching it.
+pub fn build_http(
+    config: &Config,
+    blockchain: &Arc<ChainState>,
+    unspent_tx_outs: &Arc<RwLock<Vec<UnspentTxOut>>>,
+    transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
+    wallet: &Arc<RwLock<Wallet>>,
+    address_index: &Arc<RwLock<AddressIndex>>,
+    tip_notifier: &Arc<TipNotifier>,
+    invoices: &Arc<InvoiceBook>,
+    label_store: &Arc<LabelStore>,
+    spend_policy: &Arc<SpendPolicy>,
+    validation_cache: &Arc<RwLock<ValidationCache>>,
+    miner: &Arc<Miner>,
+    sync_tracker: &Arc<SyncTracker>,
+    peer_registry: &Arc<PeerRegistry>,
+    price_feed: &Option<Arc<PriceFeed>>,
+    stratum: &Option<Arc<Stratum>>,
+    webhooks: &Option<Arc<WebhookBook>>,
+    journal: &Option<Arc<Journal>>,
+    broadcast_sender: UnboundedSender<BroadcastEvents>,
+) -> Rocket {
     let b = Arc::clone(blockchain);
     let u = Arc::clone(unspent_tx_outs);
     let t = Arc::clone(transaction_pool);
     let w = Arc::clone(wallet);
-    let config = rocket::config::Config::build(rocket::config::Environment::Development).port(config.http_port).finalize().unwrap();
+    let a = Arc::clone(address_index);
+    let n = Arc::clone(tip_notifier);
+    let i = Arc::clone(invoices);
+    let l = Arc::clone(label_store);
+    let p = Arc::clone(spend_policy);
+    let v = Arc::clone(validation_cache);
+    let m = Arc::clone(miner);
+    let s = Arc::clone(sync_tracker);
+    let r = Arc::clone(peer_registry);
+    let f = price_feed.clone();
+    let o = stratum.clone();
+    let h = webhooks.clone();
+    let j = journal.clone();
+    let admin_token = AdminToken(config.admin_token.clone());
+    let key_settings = KeySettings {
+        private_key_path: config.private_key_path.clone(),
+        key_type: config.key_type,
+    };
+    let compression = config.compression;
+    let field_naming = config.api_field_naming;
+    let json_layout = routes::JsonLayout { pretty: config.api_pretty };
+    let rollback_settings = routes::RollbackSettings { allow_main: config.allow_rollback };
+    let mempool_policy = config.get_mempool_policy();
+    let transport = config.transport;
+    // Streams hold a worker each, so they get workers of their own besides the default two per core.
+    let workers = thread::available_parallelism().map_or(1, |cores| cores.get()) * 2 + config.max_streams;
+    let config = rocket::config::Config::build(rocket::config::Environment::Development)
+        .port(config.http_port)
+        .workers(workers.min(u16::MAX as usize) as u16)
+        .finalize()
+        .unwrap();
 
-    thread::spawn(move || {
-        rocket::custom(config)
-            .mount("/api", routes![
-                routes::ping,
-                routes::blocks,
-                routes::mine_raw_block,
-                routes::mine_block,
-                routes::address,
-                routes::balance,
-                routes::unspent_transaction_outputs,
-                routes::my_unspent_transaction_outputs,
-                routes::mine_transaction,
-                routes::send_transaction,
-                routes::transaction_pool,
-                routes::add_peer
-            ])
-            .attach(cors_fairing())
-            .manage(b)
-            .manage(u)
-            .manage(t)
-            .manage(w)
-            .manage(broadcast_sender)
-            .launch();
-    });
+    let mut server = rocket::custom(config)
+        .mount("/api", routes![
+            routes::ping,
+            routes::blocks,
+            routes::headers,
+            routes::mine_raw_block,
+            routes::mine_block,
+            routes::address,
+            routes::balance,
+            routes::unspent_transaction_outputs,
+            routes::my_unspent_transaction_outputs,
+            routes::lookup_utxos,
+            routes::mine_transaction,
+            routes::send_transaction,
+            routes::can_send,
+            routes::test_transaction,
+            routes::assets,
+            routes::send_asset,
+            routes::issue_asset,
+            routes::name,
+            routes::register_name,
+            routes::pay_uri,
+            routes::create_invoice,
+            routes::invoice,
+            routes::transaction_pool,
+            routes::add_peer,
+            routes::address_stats,
+            routes::reorgs,
+            routes::debug_compare,
+            routes::audit_supply,
+            routes::explorer_block,
+            routes::explorer_propagation,
+            routes::explorer_transaction,
+            routes::explorer_address,
+            routes::labels,
+            routes::set_label,
+            routes::delete_label,
+            routes::search,
+            routes::stream_tip,
+            routes::clear_pool,
+            routes::rollback,
+            routes::invalidated_blocks,
+            routes::invalidate_block,
+            routes::reconsider_block,
+            routes::rotate_key,
+            routes::import_key,
+            routes::export_key,
+            routes::create_miner_job,
+            routes::generate,
+            routes::miner_job,
+            routes::cancel_miner_job,
+            routes::miner_throttle,
+            routes::set_miner_throttle,
+            routes::stratum_workers,
+            routes::sync_status,
+            routes::consensus_check,
+            routes::peers,
+            routes::peer_history,
+            routes::peer_stats,
+            routes::disconnect_peer,
+            routes::webhooks,
+            routes::create_webhook,
+            routes::delete_webhook
+        ])
+        .attach(cors_fairing())
+        .attach(RequestTracer)
+        .manage(b)
+        .manage(u)
+        .manage(t)
+        .manage(w)
+        .manage(a)
+        .manage(n)
+        .manage(i)
+        .manage(l)
+        .manage(p)
+        .manage(v)
+        .manage(m)
+        .manage(s)
+        .manage(r)
+        .manage(f)
+        .manage(o)
+        .manage(h)
+        .manage(j)
+        .manage(admin_token)
+        .manage(key_settings)
+        .manage(mempool_policy)
+        .manage(field_naming)
+        .manage(json_layout)
+        .manage(rollback_settings)
+        .manage(transport)
+        .manage(broadcast_sender);
+
+    if compression {
+        server = server.attach(Compression);
+    }
+    server
+}
+
+#[cfg(test)]
+mod test {
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::{Client, LocalResponse};
+    use serde_json::{json, Value};
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+    use crate::auth::ADMIN_TOKEN_HEADER;
+    use crate::block::{get_genesis_block, get_unspent_tx_outs};
+    use crate::constants::{COINBASE_AMOUNT, MAX_HEADERS};
+    use crate::errors::AppError;
+    use crate::testkit::{get_wallet, ALICE, BOB};
+    use super::*;
+
+    /// Http server of a node on the genesis block with the wallet of `ALICE`, with its miner and broadcasts.
+    struct Node {
+        client: Client,
+        miner: Arc<Miner>,
+        broadcast_receiver: UnboundedReceiver<BroadcastEvents>,
+    }
+
+    fn get_node() -> Node {
+        get_node_with(&[])
+    }
+
+    fn get_node_with(args: &[&str]) -> Node {
+        let config = Config::from_args(args);
+        let blockchain = Arc::new(ChainState::new(vec![get_genesis_block()]));
+        let unspent_tx_outs = Arc::new(RwLock::new(get_unspent_tx_outs(&blockchain.load()).unwrap()));
+        let transaction_pool = Arc::new(RwLock::new(vec![]));
+        let wallet = Arc::new(RwLock::new(get_wallet(ALICE)));
+        let address_index = Arc::new(RwLock::new(AddressIndex::new(&blockchain.load())));
+        let validation_cache = Arc::new(RwLock::new(ValidationCache::default()));
+        let (broadcast_sender, broadcast_receiver) = unbounded_channel();
+        let miner = Arc::new(Miner::launch(
+            &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &validation_cache,
+            None, None, config.get_mempool_policy(), config.get_consensus_rules(), broadcast_sender.clone(),
+        ));
+        let server = build_http(
+            &config, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &Arc::new(TipNotifier::default()),
+            &Arc::new(InvoiceBook::default()), &Arc::new(LabelStore::default()), &Arc::new(config.get_spend_policy()),
+            &validation_cache, &miner, &Arc::new(SyncTracker::default()), &Arc::new(PeerRegistry::default()), &None, &None, &None, &None,
+            broadcast_sender,
+        );
+        Node { client: Client::new(server).expect("Http server cannot be built"), miner, broadcast_receiver }
+    }
+
+    fn get_body(response: &mut LocalResponse) -> Value {
+        serde_json::from_str(&response.body_string().unwrap()).unwrap()
+    }
+
+    fn post(client: &Client, uri: &str, body: Value) -> Value {
+        let mut response = client.post(uri).header(ContentType::JSON).body(body.to_string()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        get_body(&mut response)
+    }
+
+    fn get(client: &Client, uri: &str) -> Value {
+        let mut response = client.get(uri).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        get_body(&mut response)
+    }
+
+    /// Get error of response, answered with its code as status.
+    fn get_error(mut response: LocalResponse, status: Status) -> Value {
+        assert_eq!(response.status(), status);
+        let error = get_body(&mut response);
+        assert_eq!(error["code"], status.code);
+        error
+    }
+
+    fn post_error(client: &Client, uri: &str, body: Value, status: Status) -> Value {
+        get_error(client.post(uri).header(ContentType::JSON).body(body.to_string()).dispatch(), status)
+    }
+
+    #[test]
+    fn test_mine_block() {
+        let node = get_node();
+        assert_eq!(get(&node.client, "/api/balance")["balance"], COINBASE_AMOUNT);
+
+        let mut response = node.client.post("/api/mine-block").dispatch();
+        assert_eq!(response.status(), Status::Accepted);
+        let job = get_body(&mut response);
+        assert_eq!(job["status"], "queued");
+        node.miner.wait_job(job["id"].as_str().unwrap()).unwrap();
+
+        let job = get(&node.client, &format!("/api/miner/jobs/{}", job["id"].as_str().unwrap()));
+        assert_eq!(job["status"], "mined");
+        assert_eq!(job["block"]["index"], 1);
+        assert_eq!(get(&node.client, "/api/blocks").as_array().unwrap().len(), 2);
+        assert_eq!(get(&node.client, "/api/balance")["balance"], 2 * COINBASE_AMOUNT);
+
+        let error = get_error(node.client.post("/api/mine-block?address=bogus").dispatch(), Status::BadRequest);
+        assert_eq!(error["kind"], "bad_request");
+        assert_eq!(error["message"], "Payout address is invalid.");
+    }
+
+    #[test]
+    fn test_send_transaction() {
+        let mut node = get_node();
+        let transaction = post(&node.client, "/api/send-transaction", json!({ "address": BOB.address, "amount": 10 }));
+        assert_eq!(transaction["tx_outs"][0]["address"], BOB.address);
+        assert_eq!(transaction["tx_outs"][0]["amount"], 10);
+        assert!(matches!(node.broadcast_receiver.try_recv(), Ok(BroadcastEvents::Transaction(transactions, None)) if transactions.len() == 1));
+
+        let transaction_pool = get(&node.client, "/api/transaction-pool");
+        assert_eq!(transaction_pool[0]["id"], transaction["id"]);
+
+        let error = post_error(&node.client, "/api/send-transaction", json!({ "address": BOB.address }), Status::UnprocessableEntity);
+        assert_eq!(error["kind"], "invalid_fields");
+        assert_eq!(error["message"], "Invalid fields");
+        assert_eq!(error["errors"]["amount"][0]["code"], "required");
+
+        let error = post_error(&node.client, "/api/send-transaction", json!({ "amount": "10" }), Status::UnprocessableEntity);
+        assert!(error["errors"]["body"].is_array());
+        let error = post_error(&node.client, "/api/send-transaction", json!({}), Status::UnprocessableEntity);
+        assert_eq!(error["errors"]["address"][0]["code"], "required");
+        assert_eq!(error["errors"]["amount"][0]["code"], "required");
+
+        let error = post_error(&node.client, "/api/send-transaction", json!({ "address": "bogus", "amount": 10 }), Status::BadRequest);
+        assert_eq!(error["kind"], "invalid_address");
+        assert_eq!(error["message"], "Address is invalid: Fail to parse address");
+
+        let body = json!({ "address": BOB.address, "amount": 10 * COINBASE_AMOUNT });
+        let error = post_error(&node.client, "/api/send-transaction", body, Status::UnprocessableEntity);
+        assert_eq!(error["kind"], "insufficient_funds");
+        assert_eq!(error["error_code"], 2003);
+        assert_eq!(error["message"], "Add transaction pool fail: Fail to send transactions");
+        assert_eq!(error["hint"], AppError::new(2003).get_hint());
+        assert_eq!(get(&node.client, "/api/transaction-pool").as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_can_send() {
+        let mut node = get_node();
+        let preflight = post(&node.client, "/api/can-send", json!({ "address": BOB.address, "amount": 10 }));
+        assert_eq!(preflight["is_possible"], true);
+        assert_eq!(preflight["tx_outs"].as_array().unwrap().len(), 1);
+        assert_eq!(preflight["change"], COINBASE_AMOUNT - 10);
+        assert_eq!(preflight["fee"], 0);
+        assert!(preflight.get("error").is_none());
+        assert_eq!(get(&node.client, "/api/transaction-pool"), json!([]));
+        assert!(node.broadcast_receiver.try_recv().is_err());
+
+        let preflight = post(&node.client, "/api/can-send", json!({ "address": BOB.address, "amount": 10 * COINBASE_AMOUNT }));
+        assert_eq!(preflight["is_possible"], false);
+        assert_eq!(preflight["tx_outs"], json!([]));
+        assert_eq!(preflight["error"]["kind"], "insufficient_funds");
+
+        post(&node.client, "/api/send-transaction", json!({ "address": BOB.address, "amount": 10 }));
+        let preflight = post(&node.client, "/api/can-send", json!({ "address": BOB.address, "amount": 10 }));
+        assert_eq!(preflight["is_possible"], false);
+        assert_eq!(preflight["error"]["kind"], "pool_conflict");
+
+        post_error(&node.client, "/api/can-send", json!({ "address": BOB.address }), Status::UnprocessableEntity);
+        let error = post_error(&node.client, "/api/can-send", json!({ "address": BOB.address, "amount": 0 }), Status::UnprocessableEntity);
+        assert_eq!(error["errors"]["amount"][0]["code"], "range");
+        let error = post_error(&node.client, "/api/can-send", json!({ "address": BOB.address, "amount": usize::MAX }), Status::UnprocessableEntity);
+        assert_eq!(error["errors"]["amount"][0]["code"], "max_supply");
+        let error = post_error(&node.client, "/api/can-send", json!({ "address": ALICE.address, "amount": 10 }), Status::UnprocessableEntity);
+        assert_eq!(error["errors"]["address"][0]["code"], "self_send");
+        let preflight = post(&node.client, "/api/can-send", json!({ "address": ALICE.address, "amount": 10, "allow_self_send": true }));
+        assert_eq!(preflight["amount"], 10);
+    }
+
+    #[test]
+    fn test_test_transaction() {
+        let mut node = get_node();
+        let transaction = post(&node.client, "/api/send-transaction", json!({ "address": BOB.address, "amount": 10 }));
+        assert!(node.broadcast_receiver.try_recv().is_ok());
+
+        let verdict = post(&node.client, "/api/test-transaction", json!({ "transaction": transaction }));
+        assert_eq!(verdict["tx_id"], transaction["id"]);
+        assert_eq!(verdict["is_allowed"], false);
+        assert_eq!(verdict["fee"], 0);
+        assert!(verdict.get("invalidity").is_none());
+        assert_eq!(verdict["reasons"][0]["kind"], "pool_conflict");
+
+        let mut tampered = transaction.clone();
+        tampered["tx_outs"][0]["amount"] = json!(20);
+        let verdict = post(&node.client, "/api/test-transaction", json!({ "transaction": tampered }));
+        assert_eq!(verdict["invalidity"], "id");
+        assert_eq!(verdict["reasons"][0]["kind"], "spent_tx_outs");
+        assert_eq!(get(&node.client, "/api/transaction-pool").as_array().unwrap().len(), 1);
+        assert!(node.broadcast_receiver.try_recv().is_err());
+
+        let error = post_error(&node.client, "/api/test-transaction", json!({}), Status::UnprocessableEntity);
+        assert_eq!(error["errors"]["transaction"][0]["code"], "required");
+    }
+
+    #[test]
+    fn test_rollback() {
+        let node = get_node_with(&["--admin-token", "secret", "--allow-rollback"]);
+        let rollback = |uri: &str| node.client.post(uri.to_string()).header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        let transaction = post(&node.client, "/api/send-transaction", json!({ "address": BOB.address, "amount": 10 }));
+        let job = get_body(&mut node.client.post("/api/mine-block").dispatch());
+        node.miner.wait_job(job["id"].as_str().unwrap()).unwrap();
+        assert_eq!(get(&node.client, "/api/transaction-pool"), json!([]));
+
+        let rolled_back = get_body(&mut rollback("/api/admin/rollback?to_height=0"));
+        assert_eq!(rolled_back["old_height"], 1);
+        assert_eq!(rolled_back["height"], 0);
+        assert_eq!(rolled_back["returned_transactions"], json!([transaction.clone()]));
+        assert_eq!(rolled_back["dropped_transactions"], json!([]));
+        assert_eq!(get(&node.client, "/api/blocks").as_array().unwrap().len(), 1);
+        assert_eq!(get(&node.client, "/api/transaction-pool"), json!([transaction]));
+        assert_eq!(get(&node.client, "/api/reorgs")[0]["depth"], 1);
+
+        let error = get_error(rollback("/api/admin/rollback?to_height=1"), Status::BadRequest);
+        assert_eq!(error["kind"], "invalid_rollback_height");
+        assert_eq!(node.client.post("/api/admin/rollback?to_height=0").dispatch().status(), Status::Unauthorized);
+
+        let node = get_node_with(&["--admin-token", "secret"]);
+        let response = node.client.post("/api/admin/rollback?to_height=0").header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        assert_eq!(get_error(response, Status::Forbidden)["kind"], "rollback_disabled");
+    }
+
+    #[test]
+    fn test_invalidate_block() {
+        let mut node = get_node_with(&["--admin-token", "secret", "--allow-rollback"]);
+        let admin_post = |uri: String| node.client.post(uri).header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        let job = get_body(&mut node.client.post("/api/mine-block").dispatch());
+        node.miner.wait_job(job["id"].as_str().unwrap()).unwrap();
+        let blocks = get(&node.client, "/api/blocks");
+        let (genesis_hash, hash) = (blocks[0]["hash"].as_str().unwrap(), blocks[1]["hash"].as_str().unwrap());
+        while node.broadcast_receiver.try_recv().is_ok() {}
+
+        let invalidated = get_body(&mut admin_post(format!("/api/admin/invalidate-block/{}", hash)));
+        assert_eq!(invalidated["rollback"]["height"], 0);
+        assert_eq!(invalidated["invalidated"], json!([hash]));
+        assert_eq!(get(&node.client, "/api/blocks").as_array().unwrap().len(), 1);
+        assert!(matches!(node.broadcast_receiver.try_recv(), Ok(BroadcastEvents::Blockchain(blockchain, None)) if blockchain.len() == 1));
+        let invalidated = get_body(&mut admin_post("/api/admin/invalidate-block/unknown".to_string()));
+        assert_eq!(invalidated["rollback"], Value::Null);
+        let error = get_error(admin_post(format!("/api/admin/invalidate-block/{}", genesis_hash)), Status::BadRequest);
+        assert_eq!(error["kind"], "genesis_invalidation");
+
+        let mut response = node.client.get("/api/admin/invalidated-blocks").header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        assert_eq!(get_body(&mut response).as_array().unwrap().len(), 2);
+        assert_eq!(get_body(&mut admin_post(format!("/api/admin/reconsider-block/{}", hash))), json!(["unknown"]));
+        get_error(admin_post(format!("/api/admin/reconsider-block/{}", hash)), Status::NotFound);
+
+        let node = get_node_with(&["--admin-token", "secret"]);
+        let response = node.client.post(format!("/api/admin/invalidate-block/{}", hash)).header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        assert_eq!(get_error(response, Status::Forbidden)["kind"], "rollback_disabled");
+        let mut response = node.client.get("/api/admin/invalidated-blocks").header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        assert_eq!(get_body(&mut response), json!([]));
+    }
+
+    #[test]
+    fn test_debug_compare() {
+        let node = get_node_with(&["--admin-token", "secret"]);
+        let compare = |peer: &str| node.client.get(format!("/api/debug/compare?peer={}", peer)).header(Header::new(ADMIN_TOKEN_HEADER, "secret")).dispatch();
+        assert_eq!(get_error(compare("ws://127.0.0.1:6001"), Status::BadRequest)["kind"], "invalid_peer_url");
+        assert_eq!(get_error(compare("http://127.0.0.1:1"), Status::BadGateway)["kind"], "peer_unavailable");
+        assert_eq!(node.client.get("/api/debug/compare?peer=http://127.0.0.1:1").dispatch().status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_add_peer() {
+        let mut node = get_node();
+        let peer = post(&node.client, "/api/add-peer", json!({ "peer": "127.0.0.1:6001" }));
+        assert_eq!(peer, json!({ "peer": "ws://127.0.0.1:6001", "status": "dialing" }));
+        assert!(matches!(node.broadcast_receiver.try_recv(), Ok(BroadcastEvents::Peer(peer)) if peer == "ws://127.0.0.1:6001"));
+
+        let error = post_error(&node.client, "/api/add-peer", json!({ "peer": "http://127.0.0.1:6001" }), Status::BadRequest);
+        assert_eq!(error["kind"], "invalid_peer_address");
+        assert_eq!(error["message"], "Peer address is invalid: Fail to parse peer address");
+        post_error(&node.client, "/api/add-peer", json!({ "peer": "/ip4/127.0.0.1/tcp/6001" }), Status::BadRequest);
+        post_error(&node.client, "/api/add-peer", json!({}), Status::UnprocessableEntity);
+        assert!(node.broadcast_receiver.try_recv().is_err());
+
+        assert_eq!(get(&node.client, "/api/peers"), json!([]));
+        assert_eq!(get(&node.client, "/api/peers?include=pending"), json!({ "peers": [], "pending": [] }));
+        get_error(node.client.get("/api/peers?include=all").dispatch(), Status::BadRequest);
+
+        let check = get(&node.client, "/api/consensus-check");
+        assert_eq!(check["height"], 0);
+        assert_eq!(check["is_consistent"], true);
+        assert_eq!(check["peers"], json!([]));
+        assert!(node.broadcast_receiver.try_recv().is_err());
+        assert_eq!(node.client.get("/api/unknown").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_field_naming() {
+        let node = get_node();
+        let blocks = get(&node.client, "/api/blocks");
+        assert_eq!(blocks[0]["previous_hash"], "");
+        assert!(blocks[0]["data"][0]["tx_ins"].is_array());
+
+        let node = get_node_with(&["--api-field-naming", "camel"]);
+        let blocks = get(&node.client, "/api/blocks");
+        assert_eq!(blocks[0]["previousHash"], "");
+        assert!(blocks[0].get("previous_hash").is_none());
+        assert_eq!(blocks[0]["data"][0]["txIns"][0]["txOutIndex"], 0);
+        let unspent_tx_outs = get(&node.client, "/api/unspent-transaction-outputs");
+        assert_eq!(unspent_tx_outs[0]["txOutId"], blocks[0]["data"][0]["id"]);
+    }
+
+    #[test]
+    fn test_lookup_utxos() {
+        let node = get_node();
+        let unspent_tx_out = get(&node.client, "/api/unspent-transaction-outputs")[0].clone();
+        post(&node.client, "/api/send-transaction", json!({ "address": BOB.address, "amount": 10 }));
+        let job = get_body(&mut node.client.post("/api/mine-block").dispatch());
+        node.miner.wait_job(job["id"].as_str().unwrap()).unwrap();
+        let outpoints = json!({ "outpoints": [
+            { "tx_out_id": unspent_tx_out["tx_out_id"], "tx_out_index": 0 },
+            { "tx_out_id": "unknown", "tx_out_index": 0 },
+        ] });
+        let lookups = post(&node.client, "/api/utxos/lookup", outpoints);
+        assert_eq!(lookups[0]["status"], "spent");
+        assert_eq!(lookups[0]["spend"]["block_index"], 1);
+        assert!(lookups[0].get("unspent_tx_out").is_none());
+        assert_eq!(lookups[1], json!({ "tx_out_id": "unknown", "tx_out_index": 0, "status": "unknown" }));
+
+        let unspent_tx_out = get(&node.client, "/api/unspent-transaction-outputs")[0].clone();
+        let outpoints = json!({ "outpoints": [{ "tx_out_id": unspent_tx_out["tx_out_id"], "tx_out_index": unspent_tx_out["tx_out_index"] }] });
+        let lookups = post(&node.client, "/api/utxos/lookup", outpoints);
+        assert_eq!(lookups[0]["status"], "unspent");
+        assert_eq!(lookups[0]["unspent_tx_out"], unspent_tx_out);
+
+        let error = post_error(&node.client, "/api/utxos/lookup", json!({ "outpoints": [] }), Status::UnprocessableEntity);
+        assert_eq!(error["errors"]["outpoints"][0]["code"], "length");
+    }
+
+    #[test]
+    fn test_headers() {
+        let node = get_node();
+        let headers = get(&node.client, "/api/headers");
+        assert_eq!(headers.as_array().unwrap().len(), 1);
+        assert_eq!(headers[0]["index"], 0);
+        assert_eq!(headers[0]["tx_count"], 1);
+        assert!(headers[0].get("data").is_none());
+        assert_eq!(get(&node.client, "/api/headers?from=1"), json!([]));
+
+        let error = get_error(node.client.get("/api/headers?from=2&to=1").dispatch(), Status::BadRequest);
+        assert_eq!(error["message"], "Range is invalid, from must not be above to.");
+        get_error(node.client.get(format!("/api/headers?to={}", MAX_HEADERS)).dispatch(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_json_layout() {
+        let node = get_node();
+        let compact = node.client.get("/api/blocks").dispatch().body_string().unwrap();
+        assert!(!compact.contains('\n'));
+        let pretty = node.client.get("/api/blocks?pretty=true").dispatch().body_string().unwrap();
+        assert!(pretty.contains("\n  {"));
+        assert_eq!(serde_json::from_str::<Value>(&pretty).unwrap(), serde_json::from_str::<Value>(&compact).unwrap());
+        get_error(node.client.get("/api/blocks?pretty=maybe").dispatch(), Status::BadRequest);
+
+        let hash = get(&node.client, "/api/blocks")[0]["hash"].clone();
+        let blocks = get(&node.client, "/api/blocks?fields=index,hash,unknown");
+        assert_eq!(blocks[0], json!({ "index": 0, "hash": hash }));
+        let unspent_tx_outs = get(&node.client, "/api/unspent-transaction-outputs?fields=amount");
+        assert_eq!(unspent_tx_outs[0], json!({ "amount": COINBASE_AMOUNT }));
+        let pretty = node.client.get("/api/unspent-transaction-outputs?pretty=true").dispatch().body_string().unwrap();
+        assert_eq!(pretty, serde_json::to_string_pretty(&get(&node.client, "/api/unspent-transaction-outputs")).unwrap());
+        assert_eq!(node.client.get("/api/transaction-pool?pretty=true").dispatch().body_string().unwrap(), "[]");
+
+        let node = get_node_with(&["--api-pretty", "--api-field-naming", "camel"]);
+        assert!(node.client.get("/api/blocks").dispatch().body_string().unwrap().contains('\n'));
+        assert!(!node.client.get("/api/blocks?pretty=false").dispatch().body_string().unwrap().contains('\n'));
+        let blocks = get(&node.client, "/api/blocks?fields=previousHash");
+        assert_eq!(blocks[0], json!({ "previousHash": "" }));
+    }
 }
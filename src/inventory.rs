@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+
+use crate::Transaction;
+
+/// Ids of transactions a peer is known to have, to announce only the rest.
+#[derive(Debug, Default)]
+pub struct KnownTxs {
+    ids: HashSet<String>,
+}
+
+impl KnownTxs {
+    /// Record ids the peer sent or announced
+    pub fn insert_all(&mut self, ids: &[String]) {
+        self.ids.extend(ids.iter().cloned());
+    }
+
+    /// Get ids of pool the peer does not know yet, recording them as known.
+    ///
+    /// Ids no longer in pool are dropped first, which keeps the set as small as the pool.
+    pub fn announce(&mut self, transaction_pool: &[Transaction]) -> Vec<String> {
+        let pool_ids: HashSet<&String> = transaction_pool.iter().map(|tx| &tx.id).collect();
+        self.ids.retain(|id| pool_ids.contains(id));
+
+        transaction_pool
+            .iter()
+            .filter(|tx| self.ids.insert(tx.id.clone()))
+            .map(|tx| tx.id.clone())
+            .collect()
+    }
+}
+
+/// Get ids which are not in pool.
+pub fn get_missing_ids(ids: &[String], transaction_pool: &[Transaction]) -> Vec<String> {
+    ids.iter().filter(|id| !transaction_pool.iter().any(|tx| tx.id.eq(*id))).cloned().collect()
+}
+
+/// Get transactions of pool with ids.
+pub fn get_transactions(ids: &[String], transaction_pool: &[Transaction]) -> Vec<Transaction> {
+    transaction_pool.iter().filter(|tx| ids.contains(&tx.id)).cloned().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_pool(ids: &[&str]) -> Vec<Transaction> {
+        ids.iter().map(|id| Transaction::new(id.to_string(), &vec![], &vec![])).collect()
+    }
+
+    #[test]
+    fn test_known_txs() {
+        let mut known_txs = KnownTxs::default();
+        known_txs.insert_all(&["a".to_string()]);
+
+        assert_eq!(known_txs.announce(&get_pool(&["a", "b"])), vec!["b"]);
+        assert!(known_txs.announce(&get_pool(&["a", "b"])).is_empty());
+        assert_eq!(known_txs.announce(&get_pool(&["b", "c"])), vec!["c"]);
+        assert_eq!(known_txs.announce(&get_pool(&["a"])), vec!["a"]);
+    }
+
+    #[test]
+    fn test_get_missing_ids() {
+        let pool = get_pool(&["a", "b"]);
+        let ids = vec!["b".to_string(), "c".to_string()];
+
+        assert_eq!(get_missing_ids(&ids, &pool), vec!["c"]);
+        assert_eq!(get_transactions(&ids, &pool).len(), 1);
+    }
+}
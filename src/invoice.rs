@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{Block, Transaction, Wallet};
+use crate::errors::AppError;
+use crate::wallet::{derive_wallet_key, get_is_valid_memo, PaymentUri};
+
+/// Status of an invoice, `paid` and `expired` are final.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    /// Waiting for payment
+    Open,
+
+    /// Paid including transactions of the pool, not all of them in blocks yet
+    Pending,
+
+    /// Paid by transactions in blocks
+    Paid,
+
+    /// Expired before it was paid
+    Expired,
+}
+
+/// Request of payment to an address derived for the invoice alone.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Invoice {
+    pub id: String,
+
+    /// Address derived from the wallet key, only paid by this invoice
+    pub address: String,
+    pub amount: usize,
+    pub memo: Option<String>,
+
+    /// Payment uri to share with the payer
+    pub uri: String,
+
+    /// Adjusted time the invoice was created
+    pub created_at: usize,
+
+    /// Adjusted time after which the invoice expires unless paid
+    pub expires_at: usize,
+    pub status: InvoiceStatus,
+
+    /// Coins received by address, in blocks and the pool
+    pub received: usize,
+
+    /// Ids of transactions paying address
+    pub transactions: Vec<String>,
+}
+
+impl Invoice {
+    fn get_is_final(&self) -> bool {
+        self.status == InvoiceStatus::Paid || self.status == InvoiceStatus::Expired
+    }
+}
+
+/// Invoices of the node, kept in memory and updated as blocks and pool transactions arrive.
+#[derive(Debug, Default)]
+pub struct InvoiceBook {
+    invoices: RwLock<BTreeMap<String, Invoice>>,
+}
+
+impl InvoiceBook {
+    /// Create an invoice of amount at timestamp, expiring after expires_in seconds, with a key derived from wallet.
+    ///
+    /// # Errors
+    /// If amount is below dust limit, it returns error 2004, if memo is invalid error 8000,
+    /// and if the key cannot be derived or written one of the errors of `derive_wallet_key`.
+    pub fn create(
+        &self,
+        wallet: &mut Wallet,
+        private_key_path: &str,
+        amount: usize,
+        memo: Option<&str>,
+        timestamp: usize,
+        expires_in: usize,
+        dust_limit: usize,
+    ) -> Result<Invoice, AppError> {
+        if amount < dust_limit {
+            return Err(AppError::new(2004));
+        }
+        if memo.map_or(false, |memo| !get_is_valid_memo(memo)) {
+            return Err(AppError::new(8000));
+        }
+
+        let mut invoices = self.invoices.write().unwrap();
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}:{}:{}", wallet.public_key, timestamp, invoices.len()).as_bytes());
+        let id = hex::encode(&hasher.finalize()[..8]);
+        let address = derive_wallet_key(wallet, &format!("invoice:{}", id), private_key_path)?;
+
+        let mut payment_uri = PaymentUri::new(&address).amount(amount);
+        if let Some(memo) = memo {
+            payment_uri = payment_uri.memo(memo);
+        }
+        let invoice = Invoice {
+            id: id.clone(),
+            address,
+            amount,
+            memo: memo.map(|memo| memo.to_string()),
+            uri: payment_uri.to_string(),
+            created_at: timestamp,
+            expires_at: timestamp + expires_in,
+            status: InvoiceStatus::Open,
+            received: 0,
+            transactions: vec![],
+        };
+        invoices.insert(id, invoice.clone());
+        Ok(invoice)
+    }
+
+    /// Get invoice by id as of timestamp, reported expired once past its expiry even before the next update.
+    pub fn get(&self, id: &str, timestamp: usize) -> Option<Invoice> {
+        let mut invoice = self.invoices.read().unwrap().get(id)?.clone();
+        if !invoice.get_is_final() && timestamp > invoice.expires_at {
+            invoice.status = InvoiceStatus::Expired;
+        }
+        Some(invoice)
+    }
+
+    /// Update invoices which are not final with payments in blockchain and transaction pool at timestamp,
+    /// returning those whose status changed.
+    pub fn update(&self, blockchain: &[Block], transaction_pool: &[Transaction], timestamp: usize) -> Vec<Invoice> {
+        let mut invoices = self.invoices.write().unwrap();
+        let addresses: Vec<String> = invoices.values()
+            .filter(|invoice| !invoice.get_is_final())
+            .map(|invoice| invoice.address.clone())
+            .collect();
+        if addresses.is_empty() {
+            return vec![];
+        }
+
+        let confirmed = get_payments(&addresses, blockchain.iter().flat_map(|block| block.data.iter()));
+        let pending = get_payments(&addresses, transaction_pool.iter());
+
+        let mut changed = vec![];
+        for invoice in invoices.values_mut().filter(|invoice| !invoice.get_is_final()) {
+            let (confirmed_amount, confirmed_ids) = confirmed.get(&invoice.address).cloned().unwrap_or_default();
+            let (pending_amount, pending_ids) = pending.get(&invoice.address).cloned().unwrap_or_default();
+            let status = if confirmed_amount >= invoice.amount {
+                InvoiceStatus::Paid
+            } else if timestamp > invoice.expires_at {
+                InvoiceStatus::Expired
+            } else if confirmed_amount + pending_amount >= invoice.amount {
+                InvoiceStatus::Pending
+            } else {
+                InvoiceStatus::Open
+            };
+
+            invoice.received = confirmed_amount + pending_amount;
+            invoice.transactions = confirmed_ids.into_iter().chain(pending_ids).collect();
+            if invoice.status != status {
+                invoice.status = status;
+                changed.push(invoice.clone());
+            }
+        }
+        changed
+    }
+}
+
+/// Sum coins paid to each of addresses by transactions, with the ids of the paying transactions.
+fn get_payments<'a>(
+    addresses: &[String],
+    transactions: impl Iterator<Item = &'a Transaction>,
+) -> HashMap<String, (usize, Vec<String>)> {
+    let mut payments: HashMap<String, (usize, Vec<String>)> = HashMap::new();
+    for transaction in transactions {
+        for tx_out in &transaction.tx_outs {
+            if tx_out.asset.is_some() || tx_out.script.is_some() || !addresses.contains(&tx_out.address) {
+                continue;
+            }
+            let (amount, ids) = payments.entry(tx_out.address.clone()).or_default();
+            *amount += tx_out.amount;
+            if !ids.contains(&transaction.id) {
+                ids.push(transaction.id.clone());
+            }
+        }
+    }
+    payments
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::remove_file;
+    use crate::testkit::{get_genesis_block, get_wallet, ALICE};
+    use crate::transaction::{TransactionSigner, TxOut};
+    use crate::wallet::read_imported_keys;
+    use super::*;
+
+    fn get_payment(address: &str, amount: usize) -> Transaction {
+        Transaction::generate(&vec![], &vec![TxOut::new(address.to_string(), amount)])
+    }
+
+    #[test]
+    fn test_create() {
+        let path = "sample/invoice_private_key";
+        let invoice_book = InvoiceBook::default();
+        let mut wallet = get_wallet(ALICE);
+        assert_eq!(invoice_book.create(&mut wallet, path, 0, None, 100, 60, 1).unwrap_err().code, 2004);
+        assert_eq!(invoice_book.create(&mut wallet, path, 10, Some("name:bob"), 100, 60, 1).unwrap_err().code, 8000);
+
+        let invoice = invoice_book.create(&mut wallet, path, 10, Some("coffee"), 100, 60, 1).unwrap();
+        let other = invoice_book.create(&mut wallet, path, 10, None, 100, 60, 1).unwrap();
+        assert_ne!(invoice.id, other.id);
+        assert_ne!(invoice.address, other.address);
+        assert!(wallet.get_addresses().contains(&invoice.address));
+        assert_eq!(invoice.uri.parse::<PaymentUri>().unwrap(), PaymentUri::new(&invoice.address).amount(10).memo("coffee"));
+        assert_eq!(invoice.expires_at, 160);
+
+        assert_eq!(invoice_book.get(&invoice.id, 160).unwrap().status, InvoiceStatus::Open);
+        assert_eq!(invoice_book.get(&invoice.id, 161).unwrap().status, InvoiceStatus::Expired);
+        assert!(invoice_book.get("missing", 100).is_none());
+        assert_eq!(read_imported_keys(path).unwrap(), wallet.imported_keys);
+        remove_file(format!("{}.imported", path)).unwrap();
+    }
+
+    #[test]
+    fn test_update() {
+        let path = "sample/updated_invoice_private_key";
+        let invoice_book = InvoiceBook::default();
+        let mut wallet = get_wallet(ALICE);
+        let invoice = invoice_book.create(&mut wallet, path, 10, None, 100, 60, 1).unwrap();
+        let unpaid = invoice_book.create(&mut wallet, path, 10, None, 100, 60, 1).unwrap();
+        let genesis_block = get_genesis_block();
+        assert!(invoice_book.update(&vec![genesis_block.clone()], &vec![], 110).is_empty());
+
+        let first = get_payment(&invoice.address, 4);
+        let second = get_payment(&invoice.address, 6);
+        let changed = invoice_book.update(&vec![genesis_block.clone()], &vec![first.clone(), second.clone()], 120);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].status, InvoiceStatus::Pending);
+        assert_eq!(changed[0].received, 10);
+
+        let block = Block::generate(&vec![first.clone(), second.clone()], &genesis_block, 0);
+        let changed = invoice_book.update(&vec![genesis_block.clone(), block.clone()], &vec![], 130);
+        assert_eq!(changed[0].status, InvoiceStatus::Paid);
+        assert_eq!(changed[0].transactions, vec![first.id.clone(), second.id.clone()]);
+
+        let changed = invoice_book.update(&vec![genesis_block.clone(), block.clone()], &vec![], 200);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].id, unpaid.id);
+        assert_eq!(changed[0].status, InvoiceStatus::Expired);
+
+        let late = get_payment(&unpaid.address, 10);
+        assert!(invoice_book.update(&vec![genesis_block, block], &vec![late], 210).is_empty());
+        assert_eq!(invoice_book.get(&invoice.id, 210).unwrap().status, InvoiceStatus::Paid);
+        assert_eq!(invoice_book.get(&unpaid.id, 210).unwrap().status, InvoiceStatus::Expired);
+        remove_file(format!("{}.imported", path)).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+
+use crate::errors::AppError;
+use crate::payload::Payload;
+
+/// Input of the node recorded in the journal.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Input {
+    /// Payload received from a peer, after its mac was verified
+    Payload { peer: String, payload: Payload },
+
+    /// Mutating http request, with the uri it was mounted at
+    Http { method: String, uri: String, body: String },
+}
+
+/// Line of the journal.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Entry {
+    /// Unadjusted clock when the input arrived
+    pub timestamp: usize,
+
+    #[serde(flatten)]
+    pub input: Input,
+}
+
+/// Append-only journal of inputs, one JSON entry per line, replayed to reproduce a run.
+#[derive(Debug)]
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Open journal at path, appending to entries already in it.
+    /// On Unix the file is made readable only by its owner, as requests may hold addresses and amounts of the wallet.
+    ///
+    /// # Errors
+    /// If file cannot be opened, it returns error 7000.
+    pub fn open(path: &str) -> Result<Journal, AppError> {
+        if let Some(prefix) = Path::new(path).parent() {
+            std::fs::create_dir_all(prefix).map_err(|_| AppError::new(7000))?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let file = options.open(path).map_err(|_| AppError::new(7000))?;
+        #[cfg(unix)]
+        file.set_permissions(std::fs::Permissions::from_mode(0o600)).map_err(|_| AppError::new(7000))?;
+        Ok(Journal { file: Mutex::new(file) })
+    }
+
+    /// Append input, a failed write is logged and does not stop the node.
+    pub fn record(&self, input: Input) {
+        let entry = Entry { timestamp: Utc::now().timestamp() as usize, input };
+        let mut line = serde_json::to_string(&entry).unwrap();
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            tracing::warn!(error = %e, "journal write failed");
+        }
+    }
+}
+
+/// Read entries of journal at path, in the order they were recorded.
+///
+/// # Errors
+/// If file cannot be read or a line is not an entry, it returns error 7001.
+pub fn read_journal(path: &str) -> Result<Vec<Entry>, AppError> {
+    let file = File::open(path).map_err(|_| AppError::new(7001))?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|line| {
+            let line = line.map_err(|_| AppError::new(7001))?;
+            serde_json::from_str(&line).map_err(|_| AppError::new(7001))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, remove_file, write};
+    use crate::payload::PayloadType;
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let path = "sample/journal";
+        let _ = remove_file(path);
+
+        let journal = Journal::open(path).unwrap();
+        let payload = Input::Payload { peer: "127.0.0.1:2794".to_string(), payload: Payload::new(PayloadType::QueryAll, &()) };
+        let request = Input::Http { method: "POST".to_string(), uri: "/api/mine-block".to_string(), body: "".to_string() };
+        journal.record(payload.clone());
+        journal.record(request.clone());
+        drop(journal);
+
+        let journal = Journal::open(path).unwrap();
+        journal.record(payload.clone());
+
+        let inputs: Vec<Input> = read_journal(path).unwrap().into_iter().map(|entry| entry.input).collect();
+        assert_eq!(inputs, vec![payload.clone(), request, payload]);
+        #[cfg(unix)]
+        assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_journal() {
+        assert_eq!(read_journal("sample/missing_journal").unwrap_err().code, 7001);
+
+        let path = "sample/invalid_journal";
+        create_dir_all("sample").unwrap();
+        write(path, "{\"timestamp\":0,\"kind\":\"http\",\"method\":\"POST\",\"uri\":\"/api/mine-block\",\"body\":\"\"}\n\nnot json\n").unwrap();
+        assert_eq!(read_journal(path).unwrap_err().code, 7001);
+
+        write(path, "{\"timestamp\":0,\"kind\":\"http\",\"method\":\"POST\",\"uri\":\"/api/mine-block\",\"body\":\"\"}\n\n").unwrap();
+        assert_eq!(read_journal(path).unwrap().len(), 1);
+
+        remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::fs::{rename, File};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::RwLock;
+use serde::{Serialize, Deserialize};
+
+use crate::errors::AppError;
+
+/// Entity a label is attached to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelTarget {
+    Transaction,
+    Address,
+}
+
+impl FromStr for LabelTarget {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transaction" => Ok(LabelTarget::Transaction),
+            "address" => Ok(LabelTarget::Address),
+            _ => Err(AppError::new(9002)),
+        }
+    }
+}
+
+/// Labels of transactions and addresses, notes of the node operator which never leave the node.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Labels {
+    /// Labels by transaction id
+    #[serde(default)]
+    pub transactions: BTreeMap<String, String>,
+
+    /// Labels by address
+    #[serde(default)]
+    pub addresses: BTreeMap<String, String>,
+}
+
+impl Labels {
+    fn get_map(&self, target: LabelTarget) -> &BTreeMap<String, String> {
+        match target {
+            LabelTarget::Transaction => &self.transactions,
+            LabelTarget::Address => &self.addresses,
+        }
+    }
+
+    fn get_map_mut(&mut self, target: LabelTarget) -> &mut BTreeMap<String, String> {
+        match target {
+            LabelTarget::Transaction => &mut self.transactions,
+            LabelTarget::Address => &mut self.addresses,
+        }
+    }
+}
+
+/// Labels saved as a JSON file, or kept in memory only when there is no path.
+#[derive(Debug, Default)]
+pub struct LabelStore {
+    path: Option<String>,
+    labels: RwLock<Labels>,
+}
+
+impl LabelStore {
+    /// Open labels saved at path, none yet if the file does not exist.
+    ///
+    /// # Errors
+    /// If file cannot be read or does not hold labels, it returns error 9000.
+    pub fn open(path: &str) -> Result<LabelStore, AppError> {
+        let labels = if Path::new(path).exists() {
+            let file = File::open(path).map_err(|_| AppError::new(9000))?;
+            serde_json::from_reader(file).map_err(|_| AppError::new(9000))?
+        } else {
+            Labels::default()
+        };
+        Ok(LabelStore { path: Some(path.to_string()), labels: RwLock::new(labels) })
+    }
+
+    /// Get label of transaction or address id
+    pub fn get(&self, target: LabelTarget, id: &str) -> Option<String> {
+        self.labels.read().unwrap().get_map(target).get(id).cloned()
+    }
+
+    /// Get every label
+    pub fn get_labels(&self) -> Labels {
+        self.labels.read().unwrap().clone()
+    }
+
+    /// Set label of transaction or address id, removing it when none, and save labels.
+    ///
+    /// # Errors
+    /// If labels cannot be saved, it returns error 9001 and keeps the labels before the change.
+    pub fn set(&self, target: LabelTarget, id: &str, label: Option<&str>) -> Result<(), AppError> {
+        let mut labels = self.labels.write().unwrap();
+        let mut changed = labels.clone();
+        match label {
+            Some(label) => changed.get_map_mut(target).insert(id.to_string(), label.to_string()),
+            None => changed.get_map_mut(target).remove(id),
+        };
+
+        if let Some(path) = &self.path {
+            save_labels(path, &changed)?;
+        }
+        *labels = changed;
+        Ok(())
+    }
+}
+
+/// Write labels to a temporary file renamed over path, so a crash never leaves them half written.
+fn save_labels(path: &str, labels: &Labels) -> Result<(), AppError> {
+    if let Some(prefix) = Path::new(path).parent() {
+        std::fs::create_dir_all(prefix).map_err(|_| AppError::new(9001))?;
+    }
+    let temp_path = format!("{}.tmp", path);
+    let mut file = File::create(&temp_path).map_err(|_| AppError::new(9001))?;
+    file.write_all(serde_json::to_string_pretty(labels).unwrap().as_bytes()).map_err(|_| AppError::new(9001))?;
+    file.sync_all().map_err(|_| AppError::new(9001))?;
+    rename(&temp_path, path).map_err(|_| AppError::new(9001))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::{create_dir_all, remove_file, write};
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let path = "sample/labels.json";
+        let _ = remove_file(path);
+
+        let label_store = LabelStore::open(path).unwrap();
+        label_store.set(LabelTarget::Transaction, "b6c6a943", Some("rent")).unwrap();
+        label_store.set(LabelTarget::Address, "03b37587", Some("landlord")).unwrap();
+        label_store.set(LabelTarget::Address, "03196c14", Some("savings")).unwrap();
+        label_store.set(LabelTarget::Address, "03196c14", None).unwrap();
+        assert_eq!(label_store.get(LabelTarget::Transaction, "b6c6a943"), Some("rent".to_string()));
+        assert_eq!(label_store.get(LabelTarget::Address, "b6c6a943"), None);
+
+        let label_store = LabelStore::open(path).unwrap();
+        let labels = label_store.get_labels();
+        assert_eq!(labels.transactions.len(), 1);
+        assert_eq!(labels.addresses.into_iter().collect::<Vec<_>>(), vec![("03b37587".to_string(), "landlord".to_string())]);
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_open() {
+        let path = "sample/invalid_labels.json";
+        create_dir_all("sample").unwrap();
+        write(path, "not json").unwrap();
+        assert_eq!(LabelStore::open(path).unwrap_err().code, 9000);
+        remove_file(path).unwrap();
+
+        let label_store = LabelStore::default();
+        label_store.set(LabelTarget::Address, "03b37587", Some("landlord")).unwrap();
+        assert_eq!(label_store.get(LabelTarget::Address, "03b37587"), Some("landlord".to_string()));
+        assert_eq!("block".parse::<LabelTarget>().unwrap_err().code, 9002);
+    }
+}
@@ -1,72 +1,289 @@
-#![feature(proc_macro_hygiene, decl_macro)]
+#![cfg_attr(feature = "node", feature(proc_macro_hygiene, decl_macro))]
 
+#[cfg(feature = "node")]
 #[macro_use]
 extern crate rocket;
+#[cfg(feature = "node")]
 extern crate rocket_cors;
 
+#[cfg(feature = "node")]
 #[macro_use]
 extern crate validator_derive;
 
-use std::sync::{Arc, RwLock};
-use tokio::sync::mpsc;
+#[cfg(feature = "node")]
+use std::sync::Arc;
+#[cfg(feature = "node")]
+use std::time::Duration;
+#[cfg(feature = "node")]
+use tokio::sync::{mpsc, RwLock};
 
 pub mod block;
 pub mod errors;
+mod utils;
+pub mod transaction;
+mod codec;
+mod asset;
+mod names;
+mod tx_index;
+mod script;
+mod activation;
+mod pow;
+mod chain_params;
+mod secp256k1;
+pub mod wallet;
+mod constants;
+mod transaction_pool;
+mod consensus_rules;
+#[cfg(any(test, feature = "node"))]
+mod mempool_policy;
+pub mod network_time;
+pub mod signer;
+mod remote_signer;
+mod wif;
+mod validation_cache;
+mod utxo_commitment;
+mod tx_root;
+#[cfg(any(test, feature = "node"))]
+mod utxo_snapshot;
+#[cfg(any(test, feature = "node"))]
+mod rollback;
+mod dto;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+
+#[cfg(feature = "node")]
 pub mod config;
+#[cfg(feature = "node")]
 mod socket;
+#[cfg(feature = "node")]
 mod events;
+#[cfg(feature = "node")]
 mod connection;
+#[cfg(feature = "node")]
+pub mod transport;
+#[cfg(feature = "node")]
+mod websocket_transport;
+#[cfg(feature = "node")]
+mod libp2p_transport;
+#[cfg(feature = "node")]
+mod peers;
+#[cfg(feature = "node")]
+mod peer_auth;
+#[cfg(feature = "node")]
+mod discovery;
+#[cfg(feature = "node")]
+mod dns_seed;
+#[cfg(feature = "node")]
+mod coalescer;
+#[cfg(feature = "node")]
+mod inventory;
+#[cfg(feature = "node")]
 mod http;
+#[cfg(feature = "node")]
 mod routes;
+#[cfg(feature = "node")]
 mod payload;
-mod utils;
-mod transaction;
-mod secp256k1;
-mod wallet;
-mod constants;
-mod transaction_pool;
+#[cfg(feature = "node")]
+mod wire;
+#[cfg(feature = "node")]
+mod naivecoin;
+#[cfg(feature = "node")]
+mod address_index;
+#[cfg(feature = "node")]
+mod explorer;
+#[cfg(feature = "node")]
+mod chain_compare;
+#[cfg(feature = "node")]
+mod tip_stream;
+#[cfg(feature = "node")]
+mod sync_status;
+#[cfg(feature = "node")]
+mod consensus;
+#[cfg(feature = "node")]
+mod auth;
+#[cfg(feature = "node")]
+mod fairings;
+#[cfg(feature = "node")]
+mod chain_state;
+#[cfg(feature = "node")]
+mod reorg;
+#[cfg(feature = "node")]
+mod propagation;
+#[cfg(feature = "node")]
+mod invoice;
+#[cfg(feature = "node")]
+mod labels;
+#[cfg(feature = "node")]
+mod spend_policy;
+#[cfg(feature = "node")]
+mod price_feed;
+#[cfg(feature = "node")]
+mod stratum;
+#[cfg(feature = "node")]
+mod miner;
+#[cfg(feature = "node")]
+mod journal;
+#[cfg(feature = "node")]
+mod recorder;
+#[cfg(feature = "node")]
+mod replay;
+#[cfg(feature = "node")]
+mod audit;
+#[cfg(feature = "node")]
+mod webhooks;
+#[cfg(feature = "node")]
+mod metrics;
 
-use crate::block::{Block, get_unspent_tx_outs};
-use crate::config::Config;
-use crate::events::BroadcastEvents;
-use crate::socket::launch_socket;
-use crate::http::launch_http;
-use crate::transaction::{Transaction, TxIn, TxOut, UnspentTxOut};
-use crate::wallet::Wallet;
+use crate::block::Block;
+use crate::transaction::UnspentTxOut;
+#[cfg(feature = "node")]
+use crate::transaction::{BlockContext, Transaction};
+
+#[cfg(feature = "node")]
+use crate::{
+    address_index::AddressIndex,
+    block::{get_genesis_block, get_latest_block, get_unspent_tx_outs},
+    chain_params::set_chain_params,
+    chain_state::ChainState,
+    config::Config,
+    discovery::launch_discovery,
+    errors::AppError,
+    dns_seed::launch_dns_seeds,
+    events::BroadcastEvents,
+    socket::launch_socket,
+    http::{build_http, launch_http},
+    invoice::InvoiceBook,
+    journal::Journal,
+    labels::LabelStore,
+    metrics::StatsdClient,
+    miner::Miner,
+    network_time::NetworkClock,
+    peers::PeerRegistry,
+    pow::set_pow_algorithm,
+    price_feed::{HttpPriceSource, PriceFeed},
+    replay::replay,
+    spend_policy::SpendPolicy,
+    stratum::Stratum,
+    sync_status::SyncTracker,
+    tip_stream::TipNotifier,
+    transaction_pool::add_to_transaction_pool,
+    validation_cache::ValidationCache,
+    wallet::{check_key_file, prepare_rotation, Wallet},
+    webhooks::WebhookBook,
+};
 
 /// # Rust Blockchain
 ///
 /// A library for studying rust and blockchain.
 
-pub fn run(config: Config) {
-    let genesis_transaction = Transaction::new(
-        "b5516eb9915e9be6868575e87bb450d8285505f004f944bf0d99c6131995bf41".to_string(),
-        &vec![TxIn::new("".to_string(), 0, "".to_string())],
-        &vec![TxOut::new(
-            "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
-            50,
-        )]
-    );
-    let genesis_block = Block::new(
-        0,
-        "c1fcd470499b2871ed8276cfcd3abbdca6ac1432515f30d59835c9d7e35e2756".to_string(),
-        "".to_string(),
-        1655831820,
-        vec![genesis_transaction],
-        0,
-        0,
-    );
-    let blockchain: Arc<RwLock<Vec<Block>>> = Arc::new(RwLock::new(vec![genesis_block]));
+/// Run the node of config until its servers stop.
+///
+/// # Errors
+/// If the wallet, a store or a service of config cannot be opened at startup, it returns the error it failed with.
+/// If the wallet key cannot be rotated at startup, it returns the error of the rotation or of its sweep transaction,
+/// leaving the key file as it is.
+#[cfg(feature = "node")]
+pub fn run(config: Config) -> Result<(), AppError> {
+    tracing_subscriber::fmt::init();
+    set_pow_algorithm(config.pow_algorithm);
+    set_chain_params(config.get_chain_params());
+
+    let blockchain: Arc<ChainState> = Arc::new(ChainState::new(vec![get_genesis_block()]));
     let transaction_pool: Arc<RwLock<Vec<Transaction>>> = Arc::new(RwLock::new(vec![]));
-    let wallet: Arc<RwLock<Wallet>> = Arc::new(RwLock::new(Wallet::new(config.private_key_path.to_string())));
+    let wallet = match &config.remote_signer {
+        Some(endpoint) => Wallet::remote(endpoint.to_string())?,
+        None => {
+            if !config.allow_insecure_key {
+                check_key_file(&config.private_key_path)?;
+            }
+            Wallet::new(config.private_key_path.to_string(), config.key_type)
+        }
+    };
+    let wallet: Arc<RwLock<Wallet>> = Arc::new(RwLock::new(wallet));
+    let tip_notifier: Arc<TipNotifier> = Arc::new(TipNotifier::new(config.max_streams));
+    let invoices: Arc<InvoiceBook> = Arc::new(InvoiceBook::default());
+    let spend_policy: Arc<SpendPolicy> = Arc::new(config.get_spend_policy());
+    let peer_registry: Arc<PeerRegistry> = Arc::new(PeerRegistry::default());
+    let sync_tracker: Arc<SyncTracker> = Arc::new(SyncTracker::new(config.fast_sync));
+    let validation_cache: Arc<RwLock<ValidationCache>> = Arc::new(RwLock::new(ValidationCache::default()));
     let broadcast_channel = mpsc::unbounded_channel::<BroadcastEvents>();
 
-    let b = blockchain.read().unwrap();
-    let unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>> = Arc::new(RwLock::new(get_unspent_tx_outs(&b).unwrap()));
-    drop(b);
+    let unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>> = Arc::new(RwLock::new(get_unspent_tx_outs(&blockchain.load()).unwrap()));
+    let address_index: Arc<RwLock<AddressIndex>> = Arc::new(RwLock::new(AddressIndex::new(&blockchain.load())));
+
+    if config.rotate_key {
+        let mut t_guard = transaction_pool.blocking_write();
+        let u_guard = unspent_tx_outs.blocking_read();
+        let mut w_guard = wallet.blocking_write();
+        let pending = prepare_rotation(&w_guard, config.key_type, &u_guard, &t_guard)?;
+        if let Some(tx) = &pending.sweep_transaction {
+            let context = BlockContext::next(get_latest_block(&blockchain.load()), &NetworkClock);
+            add_to_transaction_pool(tx, &mut t_guard, &u_guard, context, &config.get_mempool_policy(), &mut validation_cache.blocking_write())?;
+        }
+        let rotation = pending.commit(&mut w_guard, &config.private_key_path, &mut t_guard)?;
+        println!("{:?}", rotation);
+    }
+
+    println!("{:?}{:?}", blockchain.load(), config);
+
+    let miner: Arc<Miner> = Arc::new(Miner::launch(
+        &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &validation_cache,
+        config.payout_address.clone(), config.miner_tag.clone(), config.get_mempool_policy(), config.get_consensus_rules(),
+        broadcast_channel.0.clone(),
+    ));
+    miner.set_max_hashrate(config.max_hashrate);
+    miner.set_policy(config.get_mining_policy());
+
+    if let Some(path) = &config.replay {
+        // Labels set by replayed requests are kept in memory, leaving the labels file as it is.
+        let label_store: Arc<LabelStore> = Arc::new(LabelStore::default());
+        let server = build_http(&config, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &tip_notifier, &invoices, &label_store, &spend_policy, &validation_cache, &miner, &sync_tracker, &peer_registry, &None, &None, &None, &None, broadcast_channel.0.clone());
+        let count = replay(
+            path, server, config.admin_token.clone(), &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index,
+            &validation_cache, &sync_tracker, &miner, config.get_mempool_policy(), config.get_consensus_rules(), broadcast_channel.0.clone(),
+        )?;
+        println!("Replayed {} inputs : \n{:#?}", count, blockchain.load());
+        return Ok(());
+    }
+
+    let journal: Option<Arc<Journal>> = config.journal.as_deref().map(|path| Journal::open(path).map(Arc::new)).transpose()?;
+    let label_store: Arc<LabelStore> = Arc::new(LabelStore::open(&config.labels_path)?);
+    let price_feed: Option<Arc<PriceFeed>> = config.price_feed_url.as_deref().map(|url| {
+        let source = HttpPriceSource::new(url, &config.price_feed_path)?;
+        let price_feed = Arc::new(PriceFeed::new(&config.price_currency, Duration::from_secs(config.price_interval)));
+        price_feed.launch(Box::new(source));
+        Ok::<_, AppError>(price_feed)
+    }).transpose()?;
+    let stratum: Option<Arc<Stratum>> = config.stratum_port.map(|port| {
+        let stratum = Arc::new(Stratum::new(config.share_difficulty));
+        stratum.launch(port, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, config.payout_address.clone(), config.miner_tag.clone(), config.get_consensus_rules(), &miner)?;
+        Ok::<_, AppError>(stratum)
+    }).transpose()?;
+    let webhooks: Option<Arc<WebhookBook>> = config.webhook_url.as_deref().map(|url| {
+        let webhooks = Arc::new(WebhookBook::open(url, Some(&config.webhooks_path))?);
+        webhooks.launch(&blockchain);
+        Ok::<_, AppError>(webhooks)
+    }).transpose()?;
+
+    if let Some(address) = &config.statsd_address {
+        let statsd = StatsdClient::connect(address, &config.statsd_prefix)?;
+        statsd.launch(Duration::from_secs(config.statsd_interval), &blockchain, &transaction_pool, &peer_registry);
+    }
 
-    println!("{:?}{:?}", blockchain, config);
+    let _discovery = if config.discovery {
+        Some(launch_discovery(&config, broadcast_channel.0.clone())?)
+    } else {
+        None
+    };
+    if !config.dns_seeds.is_empty() {
+        launch_dns_seeds(
+            config.dns_seeds.clone(), config.transport, Duration::from_secs(config.dns_seed_interval), &peer_registry, broadcast_channel.0.clone(),
+        );
+    }
 
-    launch_http(&config, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, broadcast_channel.0.clone());
-    launch_socket(&config, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, broadcast_channel);
+    launch_http(&config, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &tip_notifier, &invoices, &label_store, &spend_policy, &validation_cache, &miner, &sync_tracker, &peer_registry, &price_feed, &stratum, &webhooks, &journal, broadcast_channel.0.clone());
+    launch_socket(&config, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &tip_notifier, &invoices, &validation_cache, &sync_tracker, &peer_registry, &journal, broadcast_channel);
+    Ok(())
 }
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use futures_util::StreamExt;
+use libp2p::{gossipsub, noise, tcp, yamux, Multiaddr, PeerId, StreamProtocol, Swarm};
+use libp2p::request_response::{self, ProtocolSupport};
+use libp2p::swarm::{ConnectionId, NetworkBehaviour, SwarmEvent};
+use libp2p::swarm::dial_opts::DialOpts;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::errors::AppError;
+use crate::payload::Payload;
+use crate::peers::Direction;
+use crate::transport::{PeerTransport, TransportEvent};
+
+/// Gossipsub topic of announced payloads.
+pub const TOPIC: &'static str = "blockchain";
+
+/// Protocol of payloads sent to a single peer.
+pub const PROTOCOL: &'static str = "/blockchain/payload/1";
+
+/// Bytes of an announced payload at most, the most the json codec of request response reads of a request, so a
+/// chain announced with gossipsub is as large as one sent to a single peer.
+pub const MAX_TRANSMIT_SIZE: usize = 1024 * 1024;
+
+#[derive(NetworkBehaviour)]
+struct Behaviour {
+    gossipsub: gossipsub::Behaviour,
+    request_response: request_response::json::Behaviour<Payload, ()>,
+}
+
+#[derive(Debug)]
+enum Command {
+    Dial(String),
+    Send(String, Payload),
+    Announce(Payload),
+    Close(String),
+}
+
+/// Transport over libp2p, naming peers by peer id and dialing multiaddrs.
+///
+/// Announcements are flooded with gossipsub, other payloads are requests answered with an empty response.
+pub struct Libp2pTransport {
+    commands: UnboundedSender<Command>,
+}
+
+impl Libp2pTransport {
+    /// Listen on tcp addresses with a new identity, driving the swarm in background
+    ///
+    /// # Errors
+    /// If swarm cannot be built or listen on an address, it returns error 5002.
+    pub fn listen(addrs: &[SocketAddr], events: UnboundedSender<TransportEvent>) -> Result<Libp2pTransport, AppError> {
+        let mut swarm = build_swarm().map_err(|_| AppError::new(5002))?;
+        let topic = gossipsub::IdentTopic::new(TOPIC);
+        swarm.behaviour_mut().gossipsub.subscribe(&topic).map_err(|_| AppError::new(5002))?;
+        for addr in addrs {
+            swarm.listen_on(to_multiaddr(addr)).map_err(|_| AppError::new(5002))?;
+        }
+
+        let (commands, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(swarm, topic, receiver, events));
+        Ok(Libp2pTransport { commands })
+    }
+}
+
+impl PeerTransport for Libp2pTransport {
+    fn dial(&mut self, address: &str) {
+        let _ = self.commands.send(Command::Dial(address.to_string()));
+    }
+
+    fn send(&mut self, peer: &str, payload: &Payload) {
+        let _ = self.commands.send(Command::Send(peer.to_string(), payload.clone()));
+    }
+
+    fn announce(&mut self, _peers: &[String], payload: &Payload) {
+        let _ = self.commands.send(Command::Announce(payload.clone()));
+    }
+
+    fn close(&mut self, peer: &str, reason: &str) {
+        println!("Close Connection : {}, {}", peer, reason);
+        let _ = self.commands.send(Command::Close(peer.to_string()));
+    }
+}
+
+/// Get multiaddr of tcp address
+fn to_multiaddr(addr: &SocketAddr) -> Multiaddr {
+    let protocol = if addr.is_ipv4() { "ip4" } else { "ip6" };
+    format!("/{}/{}/tcp/{}", protocol, addr.ip(), addr.port()).parse().unwrap()
+}
+
+fn build_swarm() -> Result<Swarm<Behaviour>, Box<dyn std::error::Error>> {
+    Ok(libp2p::SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_tcp(tcp::Config::default(), noise::Config::new, yamux::Config::default)?
+        .with_behaviour(|key| {
+            let config = gossipsub::ConfigBuilder::default().max_transmit_size(MAX_TRANSMIT_SIZE).build()?;
+            let gossipsub = gossipsub::Behaviour::new(gossipsub::MessageAuthenticity::Signed(key.clone()), config)?;
+            let request_response = request_response::json::Behaviour::new(
+                [(StreamProtocol::new(PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default(),
+            );
+            Ok(Behaviour { gossipsub, request_response })
+        })?
+        .with_swarm_config(|config| config.with_idle_connection_timeout(Duration::from_secs(u64::MAX)))
+        .build())
+}
+
+/// Run commands against the swarm and forward its events until the transport is dropped.
+async fn run(
+    mut swarm: Swarm<Behaviour>,
+    topic: gossipsub::IdentTopic,
+    mut commands: UnboundedReceiver<Command>,
+    events: UnboundedSender<TransportEvent>,
+) {
+    // Addresses of dials in flight, so their outcome is raised against the address dialed.
+    let mut dials: HashMap<ConnectionId, String> = HashMap::new();
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Dial(address)) => match address.parse::<Multiaddr>() {
+                    Ok(multiaddr) => {
+                        let opts = DialOpts::unknown_peer_id().address(multiaddr).build();
+                        let connection_id = opts.connection_id();
+                        match swarm.dial(opts) {
+                            Ok(()) => {
+                                dials.insert(connection_id, address);
+                            }
+                            Err(e) => {
+                                println!("Libp2p dial error : {:?}", e);
+                                let _ = events.send(TransportEvent::DialFailed(address, e.to_string()));
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        println!("Invalid peer multiaddr : {:?}", e);
+                        let _ = events.send(TransportEvent::DialFailed(address, e.to_string()));
+                    }
+                },
+                Some(Command::Send(peer, payload)) => {
+                    if let Ok(peer_id) = peer.parse::<PeerId>() {
+                        swarm.behaviour_mut().request_response.send_request(&peer_id, payload);
+                    }
+                }
+                Some(Command::Announce(payload)) => {
+                    let data = serde_json::to_vec(&payload).unwrap();
+                    if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic.clone(), data) {
+                        println!("Libp2p publish error : {:?}", e);
+                    }
+                }
+                Some(Command::Close(peer)) => {
+                    if let Ok(peer_id) = peer.parse::<PeerId>() {
+                        let _ = swarm.disconnect_peer_id(peer_id);
+                    }
+                }
+                None => break,
+            },
+            event = swarm.select_next_some() => match event {
+                SwarmEvent::NewListenAddr { address, .. } => {
+                    println!("Listening on: {}/p2p/{}", address, swarm.local_peer_id());
+                }
+                SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, num_established, .. } => {
+                    if let Some(address) = dials.remove(&connection_id) {
+                        let _ = events.send(TransportEvent::Dialed(address, peer_id.to_string()));
+                    }
+                    if num_established.get() == 1 {
+                        let direction = if endpoint.is_dialer() { Direction::Outbound } else { Direction::Inbound };
+                        let _ = events.send(TransportEvent::Connected(peer_id.to_string(), direction));
+                    }
+                }
+                SwarmEvent::OutgoingConnectionError { connection_id, error, .. } => {
+                    if let Some(address) = dials.remove(&connection_id) {
+                        let _ = events.send(TransportEvent::DialFailed(address, error.to_string()));
+                    }
+                }
+                SwarmEvent::ConnectionClosed { peer_id, num_established: 0, cause, .. } => {
+                    let _ = events.send(TransportEvent::Disconnected(peer_id.to_string(), cause.map(|cause| cause.to_string())));
+                }
+                // Payloads are raised against the peer which published them, not the one relaying them, as the
+                // reply of a peer goes to the author of the announcement.
+                SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
+                    let source = match message.source {
+                        Some(source) => source,
+                        None => continue,
+                    };
+                    match serde_json::from_slice::<Payload>(&message.data) {
+                        Ok(payload) => {
+                            let _ = events.send(TransportEvent::Received(source.to_string(), payload));
+                        }
+                        Err(e) => println!("Invalid payload from {} : {:?}", source, e),
+                    }
+                }
+                SwarmEvent::Behaviour(BehaviourEvent::RequestResponse(request_response::Event::Message {
+                    peer,
+                    message: request_response::Message::Request { request, channel, .. },
+                    ..
+                })) => {
+                    let _ = swarm.behaviour_mut().request_response.send_response(channel, ());
+                    let _ = events.send(TransportEvent::Received(peer.to_string(), request));
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use tokio::time::{sleep, timeout};
+    use crate::payload::PayloadType;
+    use super::*;
+
+    /// Get a tcp address free to listen on
+    fn get_free_addr() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_libp2p_transport() {
+        let (a_events, mut a_receiver) = mpsc::unbounded_channel();
+        let (b_events, mut b_receiver) = mpsc::unbounded_channel();
+        let a_addr = get_free_addr();
+        let mut a = Libp2pTransport::listen(&[a_addr], a_events).unwrap();
+        let mut b = Libp2pTransport::listen(&[get_free_addr()], b_events).unwrap();
+
+        let address = to_multiaddr(&a_addr).to_string();
+        b.dial(&address);
+        let a_peer = match b_receiver.recv().await {
+            Some(TransportEvent::Dialed(dialed, peer)) if dialed == address => peer,
+            event => panic!("{:?}", event),
+        };
+        assert!(matches!(b_receiver.recv().await, Some(TransportEvent::Connected(peer, Direction::Outbound)) if peer == a_peer));
+        let b_peer = match a_receiver.recv().await {
+            Some(TransportEvent::Connected(peer, Direction::Inbound)) => peer,
+            event => panic!("{:?}", event),
+        };
+
+        b.send(&a_peer, &Payload::new(PayloadType::QueryLatest, &()));
+        assert!(matches!(
+            a_receiver.recv().await,
+            Some(TransportEvent::Received(peer, payload)) if peer == b_peer && payload.r#type == PayloadType::QueryLatest
+        ));
+
+        // Announcements are dropped until the peers learned they share the topic, so they are repeated.
+        let chain = "x".repeat(100_000);
+        let announced = timeout(Duration::from_secs(30), async {
+            loop {
+                a.announce(&[], &Payload::new(PayloadType::Blockchain, &chain));
+                tokio::select! {
+                    event = b_receiver.recv() => return event,
+                    _ = sleep(Duration::from_millis(500)) => {}
+                }
+            }
+        }).await.unwrap();
+        assert!(matches!(
+            announced,
+            Some(TransportEvent::Received(peer, payload)) if peer == a_peer && payload.r#type == PayloadType::Blockchain
+        ));
+
+        a.close(&b_peer, "Bye");
+        assert!(matches!(a_receiver.recv().await, Some(TransportEvent::Disconnected(peer, _)) if peer == b_peer));
+    }
+}
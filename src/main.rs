@@ -5,5 +5,8 @@ use blockchain::run;
 
 fn main() {
     let config = Config::new();
-    run(config);
+    if let Err(e) = run(config) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
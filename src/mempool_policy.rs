@@ -0,0 +1,147 @@
+use crate::UnspentTxOut;
+use crate::errors::AppError;
+use crate::script::{Op, Script};
+use crate::transaction::{Transaction, TransactionLimits};
+use crate::transaction_pool::get_transaction_fee;
+
+/// Stricter limits for relaying and pool admission.
+pub const STANDARD_TRANSACTION_LIMITS: TransactionLimits = TransactionLimits {
+    max_size: 20_000,
+    max_tx_ins: 200,
+    max_tx_outs: 200,
+};
+
+/// Rules a valid transaction must also follow to enter the pool and be relayed, configured apart from consensus
+/// so a node may relax them without rejecting blocks of other nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MempoolPolicy {
+    /// Smallest amount of a spendable output, also the smallest amount the wallet pays
+    pub dust_limit: usize,
+
+    /// Limits on size and input/output count
+    pub limits: TransactionLimits,
+
+    /// Smallest fee paid
+    pub min_fee: usize,
+
+    /// Whether outputs with scripts other than the templates of the wallet are accepted
+    pub allow_nonstandard: bool,
+}
+
+impl MempoolPolicy {
+    /// Returns a policy with dust limit, standard limits, no fee floor and standard scripts only
+    pub fn new(dust_limit: usize) -> MempoolPolicy {
+        MempoolPolicy {
+            dust_limit,
+            limits: STANDARD_TRANSACTION_LIMITS,
+            min_fee: 0,
+            allow_nonstandard: false,
+        }
+    }
+
+    /// Get transaction has a spendable output below the dust limit
+    pub fn get_has_dust(&self, transaction: &Transaction) -> bool {
+        transaction.tx_outs.iter().any(|tx_out| tx_out.amount < self.dust_limit && !tx_out.get_is_unspendable())
+    }
+
+    /// Check a transaction valid under consensus follows the policy, spending unspent tx outs.
+    ///
+    /// # Errors
+    /// If it has dust outputs it returns error 4002, if it is over limits error 4003, if it pays less than the min
+    /// fee error 4004 and if it has a nonstandard script error 4005.
+    pub fn check(&self, transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> Result<(), AppError> {
+        match self.get_violations(transaction, unspent_tx_outs).into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Get every error of `check` the transaction fails, in the same order.
+    pub fn get_violations(&self, transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> Vec<AppError> {
+        let mut violations = vec![];
+        if self.get_has_dust(transaction) {
+            violations.push(AppError::new(4002));
+        }
+
+        if !self.limits.get_is_within(transaction) {
+            violations.push(AppError::new(4003));
+        }
+
+        if get_transaction_fee(transaction, unspent_tx_outs) < self.min_fee {
+            violations.push(AppError::new(4004));
+        }
+
+        let is_standard = transaction.tx_outs.iter()
+            .filter_map(|tx_out| tx_out.script.as_ref())
+            .all(get_is_standard_script);
+        if !self.allow_nonstandard && !is_standard {
+            violations.push(AppError::new(4005));
+        }
+
+        violations
+    }
+}
+
+/// Get flag script is one of the templates of `Script`, optionally time locked.
+pub fn get_is_standard_script(script: &Script) -> bool {
+    let ops = match script.0.first() {
+        Some(Op::CheckLocktime(_)) => &script.0[1..],
+        _ => &script.0[..],
+    };
+    match ops {
+        [Op::Push(_), Op::CheckSig] => true,
+        [Op::Data(_)] => true,
+        [keys @ .., Op::CheckMultisig(_, count)] => keys.len() == *count && keys.iter().all(|op| matches!(op, Op::Push(_))),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{ALICE, BOB, FUNDING_TX_OUT_ID};
+    use crate::transaction::{TxIn, TxOut};
+    use super::*;
+
+    #[test]
+    fn test_mempool_policy() {
+        let unspent_tx_outs = vec![UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, ALICE.address.to_string(), 50)];
+        let tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, "".to_string())];
+        let transaction = Transaction::generate(
+            &tx_ins,
+            &vec![TxOut::new(ALICE.address.to_string(), 47), TxOut::new(BOB.address.to_string(), 1)],
+        );
+
+        assert!(MempoolPolicy::new(1).check(&transaction, &unspent_tx_outs).is_ok());
+        assert!(MempoolPolicy::new(2).get_has_dust(&transaction));
+        assert_eq!(MempoolPolicy::new(2).check(&transaction, &unspent_tx_outs).unwrap_err().code, 4002);
+
+        let policy = MempoolPolicy { min_fee: 3, ..MempoolPolicy::new(1) };
+        assert_eq!(policy.check(&transaction, &unspent_tx_outs).unwrap_err().code, 4004);
+        let policy = MempoolPolicy { min_fee: 2, ..MempoolPolicy::new(1) };
+        assert!(policy.check(&transaction, &unspent_tx_outs).is_ok());
+
+        let policy = MempoolPolicy { limits: TransactionLimits { max_tx_outs: 1, ..STANDARD_TRANSACTION_LIMITS }, ..MempoolPolicy::new(1) };
+        assert_eq!(policy.check(&transaction, &unspent_tx_outs).unwrap_err().code, 4003);
+        let policy = MempoolPolicy { min_fee: 3, ..policy };
+        let codes: Vec<usize> = policy.get_violations(&transaction, &unspent_tx_outs).iter().map(|e| e.code).collect();
+        assert_eq!(codes, vec![4003, 4004]);
+
+        let script = Script(vec![Op::Push(BOB.address.to_string()), Op::Push(BOB.address.to_string())]);
+        let transaction = Transaction::generate(&tx_ins, &vec![TxOut::with_script(BOB.address.to_string(), 48, script)]);
+        assert_eq!(MempoolPolicy::new(1).check(&transaction, &unspent_tx_outs).unwrap_err().code, 4005);
+        let policy = MempoolPolicy { allow_nonstandard: true, ..MempoolPolicy::new(1) };
+        assert!(policy.check(&transaction, &unspent_tx_outs).is_ok());
+    }
+
+    #[test]
+    fn test_get_is_standard_script() {
+        let public_keys = vec![ALICE.address.to_string(), BOB.address.to_string()];
+        assert!(get_is_standard_script(&Script::pay_to_public_key(ALICE.address)));
+        assert!(get_is_standard_script(&Script::multisig(1, &public_keys)));
+        assert!(get_is_standard_script(&Script::locktime(10, Script::multisig(2, &public_keys))));
+        assert!(get_is_standard_script(&Script::data("memo")));
+        assert!(!get_is_standard_script(&Script(vec![Op::CheckSig])));
+        assert!(!get_is_standard_script(&Script(vec![Op::Push(ALICE.address.to_string()), Op::CheckMultisig(1, 2)])));
+        assert!(!get_is_standard_script(&Script::locktime(10, Script::locktime(10, Script::pay_to_public_key(ALICE.address)))));
+    }
+}
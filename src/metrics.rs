@@ -0,0 +1,162 @@
+//! Push of node metrics to statsd, for environments without a scraper.
+//!
+//! Metrics are gauges sent in one datagram per interval, e.g. `blockchain.height:42|g`, so graphite can store them
+//! through the graphite backend of statsd.
+
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::{Block, Transaction};
+use crate::block::get_chain_work;
+use crate::chain_state::ChainState;
+use crate::errors::AppError;
+use crate::peers::PeerRegistry;
+
+/// Latest blocks the hashrate is estimated over.
+const HASHRATE_WINDOW: usize = 10;
+
+/// Gauges of the node pushed every interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeMetrics {
+    /// Index of the latest block
+    pub height: usize,
+
+    /// Connected peers
+    pub peers: usize,
+
+    /// Transactions of the pool
+    pub pool_size: usize,
+
+    /// Hashes per second the network spent on the latest blocks
+    pub hashrate: f64,
+
+    /// Hashes the chain took on average
+    pub chain_work: u128,
+}
+
+impl NodeMetrics {
+    /// Encode metrics as statsd gauges named under prefix, one per line
+    pub fn encode(&self, prefix: &str) -> String {
+        let gauges = [
+            ("height", self.height as f64),
+            ("peers", self.peers as f64),
+            ("pool_size", self.pool_size as f64),
+            ("hashrate", self.hashrate),
+            ("chain_work", self.chain_work as f64),
+        ];
+        gauges.iter().map(|(name, value)| format!("{}.{}:{}|g", prefix, name, value)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// Estimate hashes per second spent on the latest blocks of blockchain, from their difficulty and timestamps.
+///
+/// A block of difficulty d takes 2^d hashes on average. Zero if the blocks span no time.
+pub fn get_network_hashrate(blockchain: &[Block]) -> f64 {
+    let blocks = &blockchain[blockchain.len().saturating_sub(HASHRATE_WINDOW + 1)..];
+    let (first, latest) = match (blocks.first(), blocks.last()) {
+        (Some(first), Some(latest)) if latest.timestamp > first.timestamp => (first, latest),
+        _ => return 0.0,
+    };
+    let hashes: f64 = blocks.iter().skip(1).map(|block| 2f64.powi(block.difficulty as i32)).sum();
+    hashes / (latest.timestamp - first.timestamp) as f64
+}
+
+/// Client sending gauges to a statsd daemon over UDP.
+#[derive(Debug)]
+pub struct StatsdClient {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdClient {
+    /// Returns a client of statsd at address, as host:port, naming gauges under prefix.
+    ///
+    /// # Errors
+    /// If address does not resolve or no socket can be bound to reach it, it returns error 13000.
+    pub fn connect(address: &str, prefix: &str) -> Result<StatsdClient, AppError> {
+        let address: SocketAddr = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or(AppError::new(13000))?;
+        let local = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local).map_err(|_| AppError::new(13000))?;
+        socket.connect(address).map_err(|_| AppError::new(13000))?;
+        Ok(StatsdClient { socket, prefix: prefix.to_string() })
+    }
+
+    /// Send metrics as one datagram.
+    ///
+    /// # Errors
+    /// If the datagram cannot be sent, it returns error 13001.
+    pub fn push(&self, metrics: &NodeMetrics) -> Result<(), AppError> {
+        self.socket.send(metrics.encode(&self.prefix).as_bytes()).map_err(|_| AppError::new(13001))?;
+        Ok(())
+    }
+
+    /// Spawn a thread pushing metrics of the node every interval.
+    pub fn launch(
+        self,
+        interval: Duration,
+        blockchain: &Arc<ChainState>,
+        transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
+        peer_registry: &Arc<PeerRegistry>,
+    ) {
+        let blockchain = Arc::clone(blockchain);
+        let transaction_pool = Arc::clone(transaction_pool);
+        let peer_registry = Arc::clone(peer_registry);
+        thread::spawn(move || loop {
+            let chain = blockchain.load();
+            let metrics = NodeMetrics {
+                height: chain.len() - 1,
+                peers: peer_registry.get_peers().len(),
+                pool_size: transaction_pool.blocking_read().len(),
+                hashrate: get_network_hashrate(&chain),
+                chain_work: get_chain_work(&chain),
+            };
+            if let Err(e) = self.push(&metrics) {
+                tracing::warn!(error = %e, "metrics push failed");
+            }
+            thread::sleep(interval);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::get_genesis_block;
+    use super::*;
+
+    #[test]
+    fn test_get_network_hashrate() {
+        let genesis_block = get_genesis_block();
+        assert_eq!(get_network_hashrate(&vec![genesis_block.clone()]), 0.0);
+
+        let mut blockchain = vec![genesis_block];
+        for difficulty in [2, 3] {
+            let mut block = Block::generate(&vec![], blockchain.last().unwrap(), difficulty);
+            block.timestamp = blockchain.last().unwrap().timestamp + 4;
+            blockchain.push(block);
+        }
+        assert_eq!(get_network_hashrate(&blockchain), 1.5);
+    }
+
+    #[test]
+    fn test_push() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = StatsdClient::connect(&server.local_addr().unwrap().to_string(), "node").unwrap();
+        let metrics = NodeMetrics { height: 42, peers: 3, pool_size: 0, hashrate: 1.5, chain_work: 1024 };
+        client.push(&metrics).unwrap();
+
+        let mut datagram = [0u8; 512];
+        let size = server.recv(&mut datagram).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&datagram[..size]),
+            "node.height:42|g\nnode.peers:3|g\nnode.pool_size:0|g\nnode.hashrate:1.5|g\nnode.chain_work:1024|g"
+        );
+        assert_eq!(StatsdClient::connect("statsd", "node").unwrap_err().code, 13000);
+    }
+}
@@ -0,0 +1,481 @@
+use std::collections::{HashMap, VecDeque};
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+use crate::{Block, BroadcastEvents, Transaction, UnspentTxOut, Wallet};
+use crate::address_index::AddressIndex;
+use crate::block::{add_block, get_coinbase_template, get_difficulty, get_latest_block, get_transaction_template, MiningProgress, Throttle};
+use crate::chain_state::ChainState;
+use crate::constants::MINER_JOB_HISTORY;
+use crate::dto::serialize_model;
+use crate::errors::AppError;
+use crate::network_time::{Clock, NetworkClock};
+use crate::utxo_commitment::get_next_utxo_commitment;
+use crate::consensus_rules::ConsensusRules;
+use crate::mempool_policy::MempoolPolicy;
+use crate::validation_cache::ValidationCache;
+
+const JOB_POLL_MILLIS: u64 = 10;
+const POOL_POLL_MILLIS: u64 = 100;
+
+/// Block to mine.
+#[derive(Debug, Clone)]
+pub enum MineRequest {
+    /// Block with the given data
+    Raw(Vec<Transaction>),
+
+    /// Block with coinbase transaction and transaction pool, paying payout address over the one of the miner
+    Coinbase { payout_address: Option<String> },
+
+    /// Block with coinbase transaction and a transaction paying address
+    Transaction { address: String, amount: usize },
+
+    /// Block already mined by an external worker, appended as it is
+    Solved(Block),
+}
+
+/// When coinbase jobs are mined, sparing small networks a churn of empty blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MiningPolicy {
+    /// Wait for transactions in the pool before mining a coinbase job
+    pub pause_empty_pool: bool,
+
+    /// Seconds since the latest block after which a paused coinbase job is mined on an empty pool anyway
+    pub empty_block_interval: Option<u64>,
+}
+
+impl MiningPolicy {
+    /// Get if a coinbase job is mined on a pool of pool size, the latest block being timestamped latest timestamp
+    pub fn get_is_mining(&self, pool_size: usize, latest_timestamp: usize, now: usize) -> bool {
+        !self.pause_empty_pool
+            || pool_size > 0
+            || self.empty_block_interval.map_or(false, |interval| now.saturating_sub(latest_timestamp) as u64 >= interval)
+    }
+}
+
+/// State of a mining job.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Mining,
+    Mined {
+        #[serde(serialize_with = "serialize_model")]
+        block: Block,
+    },
+    Failed { code: usize },
+    Cancelled,
+}
+
+/// Mining job queried by id.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct MinerJob {
+    pub id: String,
+
+    #[serde(flatten)]
+    pub status: JobStatus,
+
+    /// Count of hashes tried
+    pub hashes: usize,
+
+    /// Milliseconds spent mining, still counting while mining
+    pub elapsed_millis: u64,
+}
+
+#[derive(Debug)]
+struct Job {
+    status: JobStatus,
+    progress: Arc<MiningProgress>,
+    started_at: Option<Instant>,
+    elapsed: Duration,
+}
+
+impl Job {
+    fn get_miner_job(&self, id: &str) -> MinerJob {
+        let elapsed = match (&self.status, self.started_at) {
+            (JobStatus::Mining, Some(started_at)) => started_at.elapsed(),
+            _ => self.elapsed,
+        };
+        MinerJob {
+            id: id.to_string(),
+            status: self.status.clone(),
+            hashes: self.progress.hashes.load(Ordering::Relaxed),
+            elapsed_millis: elapsed.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Jobs {
+    jobs: HashMap<String, Job>,
+    order: VecDeque<String>,
+}
+
+/// Mines queued jobs one at a time on a dedicated thread, off the HTTP handlers.
+#[derive(Debug)]
+pub struct Miner {
+    sender: Mutex<Sender<(String, MineRequest)>>,
+    jobs: Arc<Mutex<Jobs>>,
+    throttle: Arc<Throttle>,
+    policy: Arc<Mutex<MiningPolicy>>,
+}
+
+impl Miner {
+    /// Spawn the miner thread and return a handle to queue jobs, coinbase paying payout address or the wallet when none
+    /// and tagged with miner tag
+    pub fn launch(
+        blockchain: &Arc<ChainState>,
+        unspent_tx_outs: &Arc<RwLock<Vec<UnspentTxOut>>>,
+        transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
+        wallet: &Arc<RwLock<Wallet>>,
+        address_index: &Arc<RwLock<AddressIndex>>,
+        validation_cache: &Arc<RwLock<ValidationCache>>,
+        payout_address: Option<String>,
+        miner_tag: Option<String>,
+        mempool_policy: MempoolPolicy,
+        consensus_rules: ConsensusRules,
+        broadcast_sender: UnboundedSender<BroadcastEvents>,
+    ) -> Miner {
+        let (sender, receiver) = channel();
+        let jobs: Arc<Mutex<Jobs>> = Arc::new(Mutex::new(Jobs::default()));
+        let policy: Arc<Mutex<MiningPolicy>> = Arc::new(Mutex::new(MiningPolicy::default()));
+
+        let b = Arc::clone(blockchain);
+        let u = Arc::clone(unspent_tx_outs);
+        let t = Arc::clone(transaction_pool);
+        let w = Arc::clone(wallet);
+        let a = Arc::clone(address_index);
+        let v = Arc::clone(validation_cache);
+        let j = Arc::clone(&jobs);
+        let p = Arc::clone(&policy);
+        thread::spawn(move || run(b, u, t, w, a, v, payout_address, miner_tag, mempool_policy, consensus_rules, broadcast_sender, j, p, receiver));
+
+        Miner { sender: Mutex::new(sender), jobs, throttle: Arc::new(Throttle::default()), policy }
+    }
+
+    /// Queue a mining request and return its job
+    pub fn submit(&self, request: MineRequest) -> MinerJob {
+        let id = format!("{}", Uuid::new_v4());
+        let job = Job {
+            status: JobStatus::Queued,
+            progress: Arc::new(MiningProgress { throttle: Arc::clone(&self.throttle), ..MiningProgress::default() }),
+            started_at: None,
+            elapsed: Duration::ZERO,
+        };
+        let miner_job = job.get_miner_job(&id);
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.jobs.insert(id.clone(), job);
+        jobs.order.push_back(id.clone());
+        while jobs.order.len() > MINER_JOB_HISTORY {
+            let oldest = jobs.order.pop_front().unwrap();
+            jobs.jobs.remove(&oldest);
+        }
+        drop(jobs);
+
+        let _ = self.sender.lock().unwrap().send((id, request));
+        miner_job
+    }
+
+    /// Get job by id, none once it is dropped from history
+    pub fn get_job(&self, id: &str) -> Option<MinerJob> {
+        self.jobs.lock().unwrap().jobs.get(id).map(|job| job.get_miner_job(id))
+    }
+
+    /// Cancel job by id if it is queued or mining, returning it as it is after, none once it is dropped from history.
+    ///
+    /// A block found before the miner thread stops is still appended, and the job reported mined.
+    pub fn cancel(&self, id: &str) -> Option<MinerJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.jobs.get_mut(id)?;
+        if job.status == JobStatus::Queued || job.status == JobStatus::Mining {
+            job.progress.cancelled.store(true, Ordering::Relaxed);
+            job.elapsed = job.started_at.map_or(Duration::ZERO, |started_at| started_at.elapsed());
+            job.status = JobStatus::Cancelled;
+        }
+        Some(job.get_miner_job(id))
+    }
+
+    /// Get most hashes tried per second, none when unlimited
+    pub fn get_max_hashrate(&self) -> Option<usize> {
+        self.throttle.get_max_hashrate()
+    }
+
+    /// Set most hashes tried per second, applied to the job being mined too, none lifting the cap
+    pub fn set_max_hashrate(&self, max_hashrate: Option<usize>) {
+        self.throttle.set_max_hashrate(max_hashrate);
+    }
+
+    /// Get when coinbase jobs are mined
+    pub fn get_policy(&self) -> MiningPolicy {
+        *self.policy.lock().unwrap()
+    }
+
+    /// Set when coinbase jobs are mined, applied to a job paused on an empty pool too
+    pub fn set_policy(&self, policy: MiningPolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Block until job is mined or failed, none once it is dropped from history
+    pub fn wait_job(&self, id: &str) -> Option<MinerJob> {
+        loop {
+            let job = self.get_job(id)?;
+            match job.status {
+                JobStatus::Queued | JobStatus::Mining => thread::sleep(Duration::from_millis(JOB_POLL_MILLIS)),
+                _ => return Some(job),
+            }
+        }
+    }
+}
+
+/// Start mining job, returning its progress, none if it was cancelled or dropped from history while queued
+fn start_job(jobs: &Mutex<Jobs>, id: &str) -> Option<Arc<MiningProgress>> {
+    let mut jobs = jobs.lock().unwrap();
+    let job = jobs.jobs.get_mut(id).filter(|job| job.status == JobStatus::Queued)?;
+    job.status = JobStatus::Mining;
+    job.started_at = Some(Instant::now());
+    Some(Arc::clone(&job.progress))
+}
+
+fn finish_job(jobs: &Mutex<Jobs>, id: &str, status: JobStatus) {
+    if let Some(job) = jobs.lock().unwrap().jobs.get_mut(id) {
+        job.elapsed = job.started_at.map_or(Duration::ZERO, |started_at| started_at.elapsed());
+        job.status = status;
+    }
+}
+
+fn run(
+    blockchain: Arc<ChainState>,
+    unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>>,
+    transaction_pool: Arc<RwLock<Vec<Transaction>>>,
+    wallet: Arc<RwLock<Wallet>>,
+    address_index: Arc<RwLock<AddressIndex>>,
+    validation_cache: Arc<RwLock<ValidationCache>>,
+    payout_address: Option<String>,
+    miner_tag: Option<String>,
+    mempool_policy: MempoolPolicy,
+    consensus_rules: ConsensusRules,
+    broadcast_sender: UnboundedSender<BroadcastEvents>,
+    jobs: Arc<Mutex<Jobs>>,
+    policy: Arc<Mutex<MiningPolicy>>,
+    receiver: Receiver<(String, MineRequest)>,
+) {
+    for (id, request) in receiver {
+        let progress = match start_job(&jobs, &id) {
+            Some(progress) => progress,
+            None => continue,
+        };
+        let status = match mine(&request, &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &payout_address, &miner_tag, &mempool_policy, &consensus_rules, &policy, &progress) {
+            Ok(Some(new_block)) => match append_block(new_block, &blockchain, &unspent_tx_outs, &transaction_pool, &address_index, &validation_cache, &consensus_rules, &broadcast_sender) {
+                Ok(block) => JobStatus::Mined { block },
+                Err(e) => JobStatus::Failed { code: e.code },
+            },
+            Ok(None) => JobStatus::Cancelled,
+            Err(e) => JobStatus::Failed { code: e.code },
+        };
+        finish_job(&jobs, &id, status);
+    }
+}
+
+/// Wait until policy lets a coinbase job be mined on the pool, false if progress is cancelled first.
+fn wait_for_pool(
+    blockchain: &ChainState,
+    transaction_pool: &RwLock<Vec<Transaction>>,
+    policy: &Mutex<MiningPolicy>,
+    progress: &MiningProgress,
+) -> bool {
+    loop {
+        if progress.cancelled.load(Ordering::Relaxed) {
+            return false;
+        }
+        let latest_timestamp = get_latest_block(&blockchain.load()).timestamp;
+        let pool_size = transaction_pool.blocking_read().len();
+        if policy.lock().unwrap().get_is_mining(pool_size, latest_timestamp, NetworkClock.now()) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(POOL_POLL_MILLIS));
+    }
+}
+
+/// Mine a block on snapshots, without holding any write lock, none if it is cancelled.
+///
+/// A coinbase job is paused while policy keeps it off an empty pool, still reported mining, and leaves out pool
+/// transactions the consensus rules reject.
+///
+/// # Errors
+/// If the data of the block has a spendable output below the dust limit of consensus rules, it returns error 1001
+/// before any proof of work is done.
+fn mine(
+    request: &MineRequest,
+    blockchain: &ChainState,
+    unspent_tx_outs: &RwLock<Vec<UnspentTxOut>>,
+    transaction_pool: &RwLock<Vec<Transaction>>,
+    wallet: &RwLock<Wallet>,
+    payout_address: &Option<String>,
+    miner_tag: &Option<String>,
+    mempool_policy: &MempoolPolicy,
+    consensus_rules: &ConsensusRules,
+    policy: &Mutex<MiningPolicy>,
+    progress: &MiningProgress,
+) -> Result<Option<Block>, AppError> {
+    if matches!(request, MineRequest::Coinbase { .. }) && !wait_for_pool(blockchain, transaction_pool, policy, progress) {
+        return Ok(None);
+    }
+
+    let miner_tag = miner_tag.as_deref().unwrap_or_default();
+    let chain_snapshot = blockchain.load();
+    let data = match request {
+        MineRequest::Solved(block) => return Ok(Some(block.clone())),
+        MineRequest::Raw(data) => data.clone(),
+        MineRequest::Coinbase { payout_address: request_payout_address } => {
+            let pool_snapshot = get_consensus_pool(&transaction_pool.blocking_read(), consensus_rules);
+            let payout_address = request_payout_address
+                .clone()
+                .or_else(|| payout_address.clone())
+                .unwrap_or_else(|| wallet.blocking_read().public_key.clone());
+            get_coinbase_template(&chain_snapshot, &blockchain.read_chain_index().name_registry, &pool_snapshot, &payout_address, miner_tag)
+        }
+        MineRequest::Transaction { address, amount } => {
+            let utxo_snapshot = unspent_tx_outs.blocking_read().to_vec();
+            get_transaction_template(&chain_snapshot, &wallet.blocking_read(), &utxo_snapshot, address, *amount, mempool_policy.dust_limit, miner_tag)?
+        }
+    };
+
+    if !consensus_rules.get_is_valid_transactions(&data) {
+        return Err(AppError::new(1001));
+    }
+
+    let latest = get_latest_block(&chain_snapshot);
+    let utxo_commitment = get_next_utxo_commitment(&data, &unspent_tx_outs.blocking_read(), latest.index + 1);
+    let new_block = match Block::generate_with_progress(&data, latest, get_difficulty(&chain_snapshot), utxo_commitment, progress, &NetworkClock) {
+        Some(new_block) => new_block,
+        None => return Ok(None),
+    };
+    Ok(Some(new_block))
+}
+
+/// Get transactions of transaction pool the consensus rules accept in a block.
+pub fn get_consensus_pool(transaction_pool: &[Transaction], consensus_rules: &ConsensusRules) -> Vec<Transaction> {
+    transaction_pool.iter()
+        .filter(|tx| consensus_rules.get_is_valid_transactions(slice::from_ref(tx)))
+        .cloned()
+        .collect()
+}
+
+/// Append a block mined on a snapshot, holding write locks only while it is checked against the latest tip.
+fn append_block(
+    new_block: Block,
+    blockchain: &ChainState,
+    unspent_tx_outs: &RwLock<Vec<UnspentTxOut>>,
+    transaction_pool: &RwLock<Vec<Transaction>>,
+    address_index: &RwLock<AddressIndex>,
+    validation_cache: &RwLock<ValidationCache>,
+    consensus_rules: &ConsensusRules,
+    broadcast_sender: &UnboundedSender<BroadcastEvents>,
+) -> Result<Block, AppError> {
+    let b_writer = blockchain.blocking_write();
+    let mut u_guard = unspent_tx_outs.blocking_write();
+    let mut t_guard = transaction_pool.blocking_write();
+    let mut new_blockchain = b_writer.load().to_vec();
+    let mut chain_index = b_writer.lock_chain_index();
+    add_block(&mut new_blockchain, &mut chain_index, &mut u_guard, &mut t_guard, &new_block, &mut validation_cache.blocking_write(), consensus_rules, &NetworkClock)?;
+    drop(chain_index);
+    address_index.blocking_write().apply_block(&new_block);
+
+    let _ = broadcast_sender.send(BroadcastEvents::Blockchain(b_writer.store(new_blockchain, None), None));
+    Ok(new_block)
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc::unbounded_channel;
+    use crate::block::{get_miner_tag, get_unspent_tx_outs};
+    use crate::testkit::{get_genesis_block, get_wallet, ALICE, BOB};
+    use super::*;
+
+    fn wait(miner: &Miner, id: &str) -> JobStatus {
+        miner.wait_job(id).unwrap().status
+    }
+
+    #[test]
+    fn test_miner() {
+        let blockchain = Arc::new(ChainState::new(vec![get_genesis_block()]));
+        let unspent_tx_outs = Arc::new(RwLock::new(get_unspent_tx_outs(&blockchain.load()).unwrap()));
+        let transaction_pool = Arc::new(RwLock::new(vec![]));
+        let wallet = Arc::new(RwLock::new(get_wallet(ALICE)));
+        let address_index = Arc::new(RwLock::new(AddressIndex::new(&blockchain.load())));
+        let validation_cache = Arc::new(RwLock::new(ValidationCache::default()));
+        let (broadcast_sender, mut broadcast_receiver) = unbounded_channel();
+        let miner = Miner::launch(
+            &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &validation_cache,
+            None, Some("pool".to_string()), MempoolPolicy::new(1), ConsensusRules::default(), broadcast_sender,
+        );
+
+        let job = miner.submit(MineRequest::Coinbase { payout_address: None });
+        assert_eq!(job.status, JobStatus::Queued);
+        match wait(&miner, &job.id) {
+            JobStatus::Mined { block } => {
+                assert_eq!(block.index, 1);
+                assert_eq!(block.data[0].tx_outs[0].address, ALICE.address);
+                assert_eq!(get_miner_tag(&block.data[0]), Some("pool".to_string()));
+            }
+            status => panic!("unexpected status {:?}", status),
+        }
+        assert_eq!(blockchain.load().len(), 2);
+        assert!(matches!(broadcast_receiver.try_recv(), Ok(BroadcastEvents::Blockchain(_, None))));
+
+        let job = miner.submit(MineRequest::Coinbase { payout_address: Some(BOB.address.to_string()) });
+        match wait(&miner, &job.id) {
+            JobStatus::Mined { block } => assert_eq!(block.data[0].tx_outs[0].address, BOB.address),
+            status => panic!("unexpected status {:?}", status),
+        }
+        let job = miner.get_job(&job.id).unwrap();
+        assert!(job.hashes >= 1);
+        assert_eq!(miner.cancel(&job.id), Some(job));
+        assert!(miner.cancel("unknown").is_none());
+
+        let job = miner.submit(MineRequest::Transaction { address: ALICE.address.to_string(), amount: 1_000 });
+        assert_eq!(wait(&miner, &job.id), JobStatus::Failed { code: 2003 });
+        assert!(miner.get_job("unknown").is_none());
+
+        assert_eq!(miner.get_max_hashrate(), None);
+        miner.set_max_hashrate(Some(1_000));
+        assert_eq!(miner.get_max_hashrate(), Some(1_000));
+        let job = miner.submit(MineRequest::Coinbase { payout_address: None });
+        assert!(matches!(wait(&miner, &job.id), JobStatus::Mined { .. }));
+        miner.set_max_hashrate(None);
+        assert_eq!(miner.get_max_hashrate(), None);
+
+        miner.set_policy(MiningPolicy { pause_empty_pool: true, empty_block_interval: None });
+        let job = miner.submit(MineRequest::Coinbase { payout_address: None });
+        thread::sleep(Duration::from_millis(2 * POOL_POLL_MILLIS));
+        assert_eq!(miner.get_job(&job.id).unwrap().status, JobStatus::Mining);
+        assert_eq!(miner.get_job(&job.id).unwrap().hashes, 0);
+        assert_eq!(miner.cancel(&job.id).unwrap().status, JobStatus::Cancelled);
+
+        miner.set_policy(MiningPolicy { pause_empty_pool: true, empty_block_interval: Some(0) });
+        let job = miner.submit(MineRequest::Coinbase { payout_address: None });
+        assert!(matches!(wait(&miner, &job.id), JobStatus::Mined { .. }));
+    }
+
+    #[test]
+    fn test_mining_policy() {
+        assert!(MiningPolicy::default().get_is_mining(0, 100, 100));
+
+        let policy = MiningPolicy { pause_empty_pool: true, empty_block_interval: None };
+        assert!(!policy.get_is_mining(0, 100, 1_000));
+        assert!(policy.get_is_mining(1, 100, 100));
+
+        let policy = MiningPolicy { pause_empty_pool: true, empty_block_interval: Some(60) };
+        assert!(!policy.get_is_mining(0, 100, 159));
+        assert!(policy.get_is_mining(0, 100, 160));
+        assert!(!policy.get_is_mining(0, 200, 100));
+    }
+}
@@ -0,0 +1,346 @@
+//! Translator between payloads and messages of naivecoin, the TypeScript node this one follows.
+//!
+//! Naivecoin numbers message types, carries data as a JSON string with camelCase fields and
+//! knows neither handshakes nor inventories, so only blocks and transactions are translated.
+//!
+//! Fields naivecoin does not know, such as versions, roots, extra data and scripts, are carried in an
+//! `extension` object next to the naivecoin fields, which naivecoin ignores, so blocks and transactions
+//! of this node round-trip without loss.
+//!
+//! Only the shape of messages is translated, not consensus. Naivecoin hashes blocks and transactions
+//! and picks its genesis block differently, so blocks and transactions of a naivecoin network fail
+//! validation here and the reverse.
+
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use crate::Block;
+use crate::dto::Model;
+use crate::errors::AppError;
+use crate::activation::INITIAL_VERSION;
+use crate::payload::{Payload, PayloadType};
+use crate::pow::PowAlgorithm;
+use crate::script::Script;
+use crate::transaction::{Transaction, TxIn, TxOut};
+
+/// Prefix of a peer url dialed in the naivecoin format, e.g. `naivecoin+ws://127.0.0.1:6001`.
+pub const NAIVECOIN_SCHEME_PREFIX: &'static str = "naivecoin+";
+
+const QUERY_LATEST: u8 = 0;
+const QUERY_ALL: u8 = 1;
+const RESPONSE_BLOCKCHAIN: u8 = 2;
+const QUERY_TRANSACTION_POOL: u8 = 3;
+const RESPONSE_TRANSACTION_POOL: u8 = 4;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Message {
+    r#type: u8,
+    data: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NaivecoinBlock {
+    index: usize,
+    hash: String,
+    previous_hash: String,
+    timestamp: usize,
+    data: Vec<NaivecoinTransaction>,
+    difficulty: usize,
+    nonce: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extension: Option<BlockExtension>,
+}
+
+/// Fields of a block naivecoin does not know, the initial version and default pow when missing.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockExtension {
+    version: u32,
+    pow: PowAlgorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx_root: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    utxo_commitment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NaivecoinTransaction {
+    id: String,
+    tx_ins: Vec<NaivecoinTxIn>,
+    tx_outs: Vec<NaivecoinTxOut>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extension: Option<TransactionExtension>,
+}
+
+/// Fields of a transaction naivecoin does not know, left out for an initial version transaction without extra data.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionExtension {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extra_data: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NaivecoinTxIn {
+    tx_out_id: String,
+    tx_out_index: usize,
+    signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NaivecoinTxOut {
+    address: String,
+    amount: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extension: Option<TxOutExtension>,
+}
+
+/// Fields of a tx out naivecoin does not know, left out for coins paid to the public key of the address.
+#[derive(Debug, Serialize, Deserialize)]
+struct TxOutExtension {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    script: Option<Script>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asset: Option<String>,
+}
+
+/// Get naivecoin message of payload, none for types naivecoin does not know.
+///
+/// # Errors
+/// If data does not match its type, it returns error 5004.
+pub fn to_naivecoin(payload: &Payload) -> Result<Option<String>, AppError> {
+    let (r#type, data) = match payload.r#type {
+        PayloadType::QueryLatest => (QUERY_LATEST, None),
+        PayloadType::QueryAll => (QUERY_ALL, None),
+        PayloadType::Blockchain => (RESPONSE_BLOCKCHAIN, Some(to_naivecoin_blocks(&parse_model::<Vec<Block>>(payload)?))),
+        PayloadType::Latest => {
+            let blocks: Vec<Block> = parse_model::<Option<Block>>(payload)?.into_iter().collect();
+            (RESPONSE_BLOCKCHAIN, Some(to_naivecoin_blocks(&blocks)))
+        }
+        PayloadType::Transaction => {
+            let transactions = parse_model::<Vec<Transaction>>(payload)?
+                .iter()
+                .map(to_naivecoin_transaction)
+                .collect::<Vec<NaivecoinTransaction>>();
+            (RESPONSE_TRANSACTION_POOL, Some(serde_json::to_string(&transactions).unwrap()))
+        }
+        PayloadType::Handshake | PayloadType::Inventory | PayloadType::GetData | PayloadType::Ping | PayloadType::Pong | PayloadType::Reject
+        | PayloadType::QuerySnapshot | PayloadType::Snapshot => return Ok(None),
+    };
+    Ok(Some(serde_json::to_string(&Message { r#type, data }).unwrap()))
+}
+
+/// Get payload of naivecoin message, none for a query of transaction pool which has no payload.
+///
+/// A response with a single block is the latest block of the peer, as naivecoin answers
+/// a query of latest, so it is read as `Latest` and the whole chain is queried if it is ahead.
+///
+/// # Errors
+/// If text is not a naivecoin message, it returns error 5005.
+pub fn from_naivecoin(text: &str) -> Result<Option<Payload>, AppError> {
+    let message = serde_json::from_str::<Message>(text).map_err(|_| AppError::new(5005))?;
+    let payload = match message.r#type {
+        QUERY_LATEST => Payload::new(PayloadType::QueryLatest, &()),
+        QUERY_ALL => Payload::new(PayloadType::QueryAll, &()),
+        RESPONSE_BLOCKCHAIN => {
+            let mut blocks: Vec<Block> = parse_message_data::<Vec<NaivecoinBlock>>(&message)?
+                .into_iter()
+                .map(from_naivecoin_block)
+                .collect();
+            if blocks.len() == 1 {
+                Payload::with_model(PayloadType::Latest, &blocks.pop())
+            } else {
+                Payload::with_model(PayloadType::Blockchain, &blocks)
+            }
+        }
+        QUERY_TRANSACTION_POOL => return Ok(None),
+        RESPONSE_TRANSACTION_POOL => {
+            let transactions: Vec<Transaction> = parse_message_data::<Vec<NaivecoinTransaction>>(&message)?
+                .into_iter()
+                .map(from_naivecoin_transaction)
+                .collect();
+            Payload::with_model(PayloadType::Transaction, &transactions)
+        }
+        _ => return Err(AppError::new(5005)),
+    };
+    Ok(Some(payload))
+}
+
+fn parse_data<T: DeserializeOwned>(payload: &Payload) -> Result<T, AppError> {
+    serde_json::from_str(&payload.data).map_err(|_| AppError::new(5004))
+}
+
+fn parse_model<T: Model>(payload: &Payload) -> Result<T, AppError> {
+    parse_data::<T::Dto>(payload).map(T::from_dto)
+}
+
+fn parse_message_data<T: DeserializeOwned>(message: &Message) -> Result<T, AppError> {
+    serde_json::from_str(message.data.as_deref().unwrap_or_default()).map_err(|_| AppError::new(5005))
+}
+
+fn to_naivecoin_blocks(blocks: &[Block]) -> String {
+    let blocks: Vec<NaivecoinBlock> = blocks
+        .iter()
+        .map(|block| NaivecoinBlock {
+            index: block.index,
+            hash: block.hash.clone(),
+            previous_hash: block.previous_hash.clone(),
+            timestamp: block.timestamp,
+            data: block.data.iter().map(to_naivecoin_transaction).collect(),
+            difficulty: block.difficulty,
+            nonce: block.nonce,
+            extension: Some(BlockExtension {
+                version: block.version,
+                pow: block.pow,
+                tx_root: block.tx_root.clone(),
+                utxo_commitment: block.utxo_commitment.clone(),
+            }),
+        })
+        .collect();
+    serde_json::to_string(&blocks).unwrap()
+}
+
+fn from_naivecoin_block(block: NaivecoinBlock) -> Block {
+    let mut new_block = Block::new(
+        block.index,
+        block.hash,
+        block.previous_hash,
+        block.timestamp,
+        block.data.into_iter().map(from_naivecoin_transaction).collect(),
+        block.difficulty,
+        block.nonce,
+    );
+    if let Some(extension) = block.extension {
+        new_block.version = extension.version;
+        new_block.pow = extension.pow;
+        new_block.tx_root = extension.tx_root;
+        new_block.utxo_commitment = extension.utxo_commitment;
+    }
+    new_block
+}
+
+fn to_naivecoin_transaction(transaction: &Transaction) -> NaivecoinTransaction {
+    let has_extension = transaction.version != INITIAL_VERSION || transaction.extra_data.is_some();
+    NaivecoinTransaction {
+        id: transaction.id.clone(),
+        tx_ins: transaction.tx_ins
+            .iter()
+            .map(|tx_in| NaivecoinTxIn {
+                tx_out_id: tx_in.tx_out_id.clone(),
+                tx_out_index: tx_in.tx_out_index,
+                signature: tx_in.signature.clone(),
+            })
+            .collect(),
+        tx_outs: transaction.tx_outs
+            .iter()
+            .map(|tx_out| NaivecoinTxOut {
+                address: tx_out.address.clone(),
+                amount: tx_out.amount,
+                extension: (tx_out.script.is_some() || tx_out.asset.is_some())
+                    .then(|| TxOutExtension { script: tx_out.script.clone(), asset: tx_out.asset.clone() }),
+            })
+            .collect(),
+        extension: has_extension.then(|| TransactionExtension { version: transaction.version, extra_data: transaction.extra_data.clone() }),
+    }
+}
+
+fn from_naivecoin_transaction(transaction: NaivecoinTransaction) -> Transaction {
+    let mut new_transaction = Transaction::new(
+        transaction.id,
+        &transaction.tx_ins
+            .into_iter()
+            .map(|tx_in| TxIn::new(tx_in.tx_out_id, tx_in.tx_out_index, tx_in.signature))
+            .collect(),
+        &transaction.tx_outs
+            .into_iter()
+            .map(|tx_out| {
+                let extension = tx_out.extension.unwrap_or(TxOutExtension { script: None, asset: None });
+                TxOut { address: tx_out.address, amount: tx_out.amount, script: extension.script, asset: extension.asset }
+            })
+            .collect(),
+    );
+    if let Some(extension) = transaction.extension {
+        new_transaction.version = extension.version;
+        new_transaction.extra_data = extension.extra_data;
+    }
+    new_transaction
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::{get_coinbase_template, get_genesis_block};
+    use crate::chain_params::{with_chain_params, REGTEST};
+    use crate::names::NameRegistry;
+    use crate::peers::Handshake;
+    use crate::script::Script;
+    use crate::testkit::{get_signed_transaction, ALICE};
+    use super::*;
+
+    #[test]
+    fn test_to_naivecoin() {
+        let message = to_naivecoin(&Payload::new(PayloadType::QueryLatest, &())).unwrap().unwrap();
+        assert_eq!(message, r#"{"type":0,"data":null}"#);
+
+        let message = to_naivecoin(&Payload::new(PayloadType::Latest, &Some(get_genesis_block()))).unwrap().unwrap();
+        let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(message["type"], RESPONSE_BLOCKCHAIN);
+        let blocks: serde_json::Value = serde_json::from_str(message["data"].as_str().unwrap()).unwrap();
+        assert_eq!(blocks[0]["previousHash"], "");
+        assert_eq!(blocks[0]["data"][0]["txOuts"][0]["address"], ALICE.address);
+        assert_eq!(blocks[0]["data"][0]["txIns"][0]["txOutIndex"], 0);
+
+        assert!(to_naivecoin(&Payload::new(PayloadType::Handshake, &Handshake::local(0, false))).unwrap().is_none());
+
+        let mut transaction = get_signed_transaction();
+        transaction.tx_outs.push(TxOut::with_script(ALICE.address.to_string(), 0, Script::data("hello")));
+        let message = to_naivecoin(&Payload::new(PayloadType::Transaction, &vec![transaction])).unwrap().unwrap();
+        let message: serde_json::Value = serde_json::from_str(&message).unwrap();
+        let transactions: serde_json::Value = serde_json::from_str(message["data"].as_str().unwrap()).unwrap();
+        assert!(transactions[0]["extension"].is_null());
+        assert!(transactions[0]["txOuts"][0]["extension"].is_null());
+        assert!(!transactions[0]["txOuts"][1]["extension"]["script"].is_null());
+    }
+
+    #[test]
+    fn test_naivecoin_mined_block() {
+        with_chain_params(REGTEST, || {
+            let genesis_block = get_genesis_block();
+            let blockchain = vec![genesis_block.clone()];
+            let data = get_coinbase_template(&blockchain, &NameRegistry::new(&blockchain), &vec![], ALICE.address, "tag");
+            let mut block = Block::generate_raw(&blockchain, &data);
+            block.utxo_commitment = Some("commitment".to_string());
+            assert!(block.data[0].extra_data.is_some());
+            assert!(block.tx_root.is_some());
+
+            let blockchain = vec![genesis_block, block];
+            let payload = Payload::new(PayloadType::Blockchain, &blockchain);
+            let message = to_naivecoin(&payload).unwrap().unwrap();
+            assert_eq!(from_naivecoin(&message).unwrap().unwrap(), payload);
+        });
+    }
+
+    #[test]
+    fn test_from_naivecoin() {
+        let payload = from_naivecoin(r#"{"type":1,"data":null}"#).unwrap().unwrap();
+        assert_eq!(payload.r#type, PayloadType::QueryAll);
+        assert!(from_naivecoin(r#"{"type":3,"data":null}"#).unwrap().is_none());
+        assert_eq!(from_naivecoin(r#"{"type":9,"data":null}"#).unwrap_err().code, 5005);
+        assert_eq!(from_naivecoin(r#"{"type":"QueryAll","data":"null"}"#).unwrap_err().code, 5005);
+
+        let genesis_block = get_genesis_block();
+        let latest = to_naivecoin(&Payload::new(PayloadType::Latest, &Some(genesis_block.clone()))).unwrap().unwrap();
+        assert_eq!(from_naivecoin(&latest).unwrap().unwrap(), Payload::new(PayloadType::Latest, &Some(genesis_block.clone())));
+
+        let blockchain = vec![genesis_block.clone(), genesis_block];
+        let message = to_naivecoin(&Payload::new(PayloadType::Blockchain, &blockchain)).unwrap().unwrap();
+        assert_eq!(from_naivecoin(&message).unwrap().unwrap(), Payload::new(PayloadType::Blockchain, &blockchain));
+
+        let transactions = vec![get_signed_transaction()];
+        let message = to_naivecoin(&Payload::new(PayloadType::Transaction, &transactions)).unwrap().unwrap();
+        assert_eq!(from_naivecoin(&message).unwrap().unwrap(), Payload::new(PayloadType::Transaction, &transactions));
+    }
+}
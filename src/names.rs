@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+
+use crate::Block;
+use crate::transaction::{Transaction, TxOut};
+
+/// Prefix of output data registering a name.
+pub const NAME_PREFIX: &'static str = "name:";
+
+/// Longest registrable name.
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// Name mapped to an address by the first confirmed output registering it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Registration {
+    pub name: String,
+
+    /// Address the name points to
+    pub address: String,
+
+    /// Id of the registering transaction
+    pub tx_id: String,
+
+    /// Index of the block confirming the registration
+    pub block_index: usize,
+}
+
+/// Get name is lowercase alphanumeric with dashes.
+pub fn get_is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LENGTH).contains(&name.len()) &&
+        name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Get name registered by an output, its address is the one the name points to.
+pub fn get_registered_name(tx_out: &TxOut) -> Option<&str> {
+    tx_out.script.as_ref()?.get_data()?.strip_prefix(NAME_PREFIX)
+}
+
+fn get_registered_names(transaction: &Transaction) -> impl Iterator<Item = &str> {
+    transaction.tx_outs.iter().filter_map(get_registered_name)
+}
+
+/// Names registered on the chain, first come first served.
+#[derive(Debug, Default)]
+pub struct NameRegistry {
+    names: HashMap<String, Registration>,
+}
+
+impl NameRegistry {
+    /// Returns a registry replaying blockchain
+    pub fn new(blockchain: &[Block]) -> NameRegistry {
+        let mut registry = NameRegistry::default();
+        for block in blockchain {
+            registry.apply_block(block);
+        }
+        registry
+    }
+
+    /// Get registration of name
+    pub fn get(&self, name: &str) -> Option<&Registration> {
+        self.names.get(name)
+    }
+
+    /// Record names registered by a block, keeping earlier registrations
+    pub fn apply_block(&mut self, block: &Block) {
+        for transaction in block.data.iter() {
+            for tx_out in &transaction.tx_outs {
+                if let Some(name) = get_registered_name(tx_out) {
+                    self.names.entry(name.to_string()).or_insert_with(|| Registration {
+                        name: name.to_string(),
+                        address: tx_out.address.clone(),
+                        tx_id: transaction.id.clone(),
+                        block_index: block.index,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Get transactions register only valid names, none taken or registered twice
+    pub fn get_is_valid_transactions(&self, transactions: &[Transaction]) -> bool {
+        let mut names = HashSet::new();
+        transactions
+            .iter()
+            .flat_map(get_registered_names)
+            .all(|name| get_is_valid_name(name) && !self.names.contains_key(name) && names.insert(name))
+    }
+
+    /// Get transactions that can be confirmed together, dropping ones registering a taken name
+    pub fn filter_transactions(&self, transactions: &[Transaction]) -> Vec<Transaction> {
+        let mut names = HashSet::new();
+        transactions
+            .iter()
+            .filter(|transaction| {
+                let mut transaction_names = HashSet::new();
+                let is_valid = get_registered_names(transaction).all(|name| {
+                    get_is_valid_name(name) && !self.names.contains_key(name) && !names.contains(name) && transaction_names.insert(name)
+                });
+                if is_valid {
+                    names.extend(transaction_names);
+                }
+                is_valid
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Get every block of blockchain registers only valid names not taken before it.
+pub fn get_is_valid_names(blockchain: &[Block]) -> bool {
+    let mut registry = NameRegistry::default();
+    blockchain.iter().all(|block| {
+        let is_valid = registry.get_is_valid_transactions(&block.data);
+        registry.apply_block(block);
+        is_valid
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::script::Script;
+    use crate::testkit::{get_block, ALICE, BOB};
+    use crate::transaction::TxIn;
+    use super::*;
+
+    fn get_registration(name: &str, address: &str, tx_out_index: usize) -> Transaction {
+        Transaction::generate(
+            &vec![TxIn::new("".to_string(), tx_out_index, "".to_string())],
+            &vec![TxOut::with_script(address.to_string(), 0, Script::data(&format!("{}{}", NAME_PREFIX, name)))],
+        )
+    }
+
+    #[test]
+    fn test_get_is_valid_name() {
+        assert!(get_is_valid_name("alice-01"));
+        assert!(!get_is_valid_name(""));
+        assert!(!get_is_valid_name("Alice"));
+        assert!(!get_is_valid_name(&"a".repeat(MAX_NAME_LENGTH + 1)));
+    }
+
+    #[test]
+    fn test_name_registry() {
+        let alice = get_registration("alice", ALICE.address, 0);
+        let blockchain = vec![get_block(0, vec![]), get_block(1, vec![alice.clone()])];
+        let registry = NameRegistry::new(&blockchain);
+        let registration = registry.get("alice").unwrap();
+        assert_eq!(registration.address, ALICE.address);
+        assert_eq!(registration.tx_id, alice.id);
+        assert_eq!(registration.block_index, 1);
+        assert!(registry.get("bob").is_none());
+
+        let taken = get_registration("alice", BOB.address, 1);
+        let bob = get_registration("bob", BOB.address, 2);
+        let invalid = get_registration("Bob", BOB.address, 3);
+        assert!(!registry.get_is_valid_transactions(&vec![taken.clone()]));
+        assert!(!registry.get_is_valid_transactions(&vec![bob.clone(), bob.clone()]));
+        assert!(!registry.get_is_valid_transactions(&vec![invalid.clone()]));
+        assert!(registry.get_is_valid_transactions(&vec![bob.clone()]));
+        assert_eq!(registry.filter_transactions(&vec![taken, bob.clone(), invalid, bob.clone()]), vec![bob.clone()]);
+
+        assert!(get_is_valid_names(&blockchain));
+        let mut blockchain = blockchain;
+        blockchain.push(get_block(2, vec![get_registration("alice", BOB.address, 4)]));
+        assert!(!get_is_valid_names(&blockchain));
+    }
+}
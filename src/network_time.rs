@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use chrono::Utc;
+
+/// Peers needed before the local clock is adjusted.
+const MIN_TIME_SAMPLES: usize = 3;
+
+/// Largest adjustment in seconds, a clock further off has to be fixed by the operator.
+const MAX_TIME_OFFSET: i64 = 70 * 60;
+
+static TIME_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Get current timestamp adjusted by the offset of peer clocks.
+pub fn get_adjusted_timestamp() -> usize {
+    (Utc::now().timestamp() + TIME_OFFSET.load(Ordering::Relaxed)) as usize
+}
+
+/// Get offset applied to the local clock.
+pub fn get_time_offset() -> i64 {
+    TIME_OFFSET.load(Ordering::Relaxed)
+}
+
+/// Set offset applied to the local clock.
+pub fn set_time_offset(offset: i64) {
+    TIME_OFFSET.store(offset, Ordering::Relaxed);
+}
+
+/// Source of the current timestamp, given to block generation and validation.
+pub trait Clock: Send + Sync {
+    /// Get current timestamp in seconds
+    fn now(&self) -> usize;
+}
+
+/// Local clock adjusted by the offset of peer clocks, the clock of a running node.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NetworkClock;
+
+impl Clock for NetworkClock {
+    fn now(&self) -> usize {
+        get_adjusted_timestamp()
+    }
+}
+
+/// Clock which only moves when told to, for tests and simulations.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicUsize,
+}
+
+impl MockClock {
+    /// Returns a clock stopped at timestamp
+    pub fn new(timestamp: usize) -> MockClock {
+        MockClock { now: AtomicUsize::new(timestamp) }
+    }
+
+    /// Move clock to timestamp, back in time too
+    pub fn set(&self, timestamp: usize) {
+        self.now.store(timestamp, Ordering::Relaxed);
+    }
+
+    /// Move clock forward by seconds
+    pub fn advance(&self, seconds: usize) {
+        self.now.fetch_add(seconds, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> usize {
+        self.now.load(Ordering::Relaxed)
+    }
+}
+
+/// Clock offsets of connected peers, sampled from their handshakes and refined by their pongs.
+#[derive(Debug, Default)]
+pub struct TimeOffsets {
+    offsets: HashMap<String, i64>,
+}
+
+impl TimeOffsets {
+    /// Record timestamp a peer sent, against the unadjusted local clock, saturating for timestamps out of range
+    pub fn insert(&mut self, peer: &str, timestamp: usize) {
+        let timestamp = i64::try_from(timestamp).unwrap_or(i64::MAX);
+        self.offsets.insert(peer.to_string(), timestamp.saturating_sub(Utc::now().timestamp()));
+    }
+
+    /// Record offset in seconds of the clock of a peer, measured against the unadjusted local clock
+    pub fn insert_offset(&mut self, peer: &str, offset: i64) {
+        self.offsets.insert(peer.to_string(), offset);
+    }
+
+    /// Forget a disconnected peer
+    pub fn remove(&mut self, peer: &str) {
+        self.offsets.remove(peer);
+    }
+
+    /// Get median offset of peers, zero until enough peers are sampled
+    pub fn get_median(&self) -> i64 {
+        if self.offsets.len() < MIN_TIME_SAMPLES {
+            return 0;
+        }
+        self.get_skew().unwrap_or(0).clamp(-MAX_TIME_OFFSET, MAX_TIME_OFFSET)
+    }
+
+    /// Get median offset of peers against the unadjusted local clock, none until a peer is sampled
+    pub fn get_skew(&self) -> Option<i64> {
+        let mut offsets: Vec<i64> = self.offsets.values().copied().collect();
+        offsets.sort();
+        offsets.get(offsets.len() / 2).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_time_offsets() {
+        let now = Utc::now().timestamp() as usize;
+        let mut offsets = TimeOffsets::default();
+        offsets.insert("a", now + 100);
+        offsets.insert("b", now + 100);
+        assert_eq!(offsets.get_median(), 0);
+
+        offsets.insert("c", now - 100);
+        assert!((99..=100).contains(&offsets.get_median()));
+
+        offsets.insert("a", now + 10 * MAX_TIME_OFFSET as usize);
+        offsets.insert("c", now + 10 * MAX_TIME_OFFSET as usize);
+        assert_eq!(offsets.get_median(), MAX_TIME_OFFSET);
+
+        offsets.insert("c", usize::MAX);
+        assert_eq!(offsets.get_median(), MAX_TIME_OFFSET);
+
+        offsets.remove("c");
+        assert_eq!(offsets.get_median(), 0);
+        assert!(offsets.get_skew().unwrap() > MAX_TIME_OFFSET);
+
+        offsets.insert_offset("a", -5);
+        offsets.insert_offset("b", -3);
+        assert_eq!(offsets.get_skew(), Some(-3));
+        assert_eq!(TimeOffsets::default().get_skew(), None);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1000);
+        assert_eq!(clock.now(), 1000);
+        clock.advance(60);
+        assert_eq!(clock.now(), 1060);
+        clock.set(900);
+        assert_eq!(clock.now(), 900);
+    }
+}
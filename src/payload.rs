@@ -1,35 +1,51 @@
 use serde::{Serialize, Deserialize};
-use tokio_tungstenite::tungstenite::Message;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::dto::Model;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum PayloadType {
     Blockchain,
     Transaction,
+    Handshake,
+    QueryLatest,
+    Latest,
+    QueryAll,
+    Inventory,
+    GetData,
+    Ping,
+    Pong,
+    Reject,
+    QuerySnapshot,
+    Snapshot,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-/// Payload for socket.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+/// Payload exchanged with peers, independent of transport.
 pub struct Payload {
     /// Type for payload.
     pub r#type: PayloadType,
 
     /// Data for payload.
     pub data: String,
+
+    /// Hex encoded mac of type and data, when the network has a secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
 }
 
 impl Payload {
-    /// Returns message to send
-    pub fn serialize<T: Serialize>(r#type: PayloadType, data: &T) -> Message {
-        let payload = Payload {
+    /// Returns payload with serialized data
+    pub fn new<T: Serialize>(r#type: PayloadType, data: &T) -> Payload {
+        Payload {
             r#type,
-            data: serde_json::to_string(&data).unwrap()
-        };
-        Message::Text(serde_json::to_string(&payload).unwrap())
+            data: serde_json::to_string(&data).unwrap(),
+            mac: None,
+        }
     }
 
-    /// Returns deserialized payload from message
-    pub fn deserialize(message: Message) -> Payload {
-        serde_json::from_str::<Payload>(message.into_text().unwrap().as_str()).unwrap()
+    /// Returns payload with data serialized through its DTO
+    pub fn with_model<T: Model>(r#type: PayloadType, data: &T) -> Payload {
+        Payload::new(r#type, &data.to_dto())
     }
 }
 
@@ -39,7 +55,7 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_serialize() {
+    fn test_new() {
         let blockchain = vec![Block::new(
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
@@ -49,12 +65,12 @@ mod test {
             0,
             0,
         )];
-        let message = Payload::serialize(PayloadType::Blockchain, &blockchain);
-        assert!(message.is_text());
+        let payload = Payload::new(PayloadType::Blockchain, &blockchain);
+        assert_eq!(payload.r#type, PayloadType::Blockchain);
     }
 
     #[test]
-    fn test_deserialize() {
+    fn test_data() {
         let blockchain = vec![Block::new(
             0,
             "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
@@ -64,7 +80,7 @@ mod test {
             0,
             0,
         )];
-        let message = Payload::serialize(PayloadType::Blockchain, &blockchain);
-        assert_eq!(Payload::deserialize(message).data, serde_json::to_string(&blockchain).unwrap());
+        let payload = Payload::new(PayloadType::Blockchain, &blockchain);
+        assert_eq!(payload.data, serde_json::to_string(&blockchain).unwrap());
     }
 }
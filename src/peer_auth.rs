@@ -0,0 +1,90 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::payload::Payload;
+use crate::transport::PeerTransport;
+
+/// Signs and verifies payloads with a secret shared by the nodes of a private network.
+#[derive(Clone)]
+pub struct PeerAuthenticator {
+    secret: Vec<u8>,
+}
+
+impl PeerAuthenticator {
+    /// Returns authenticator of secret
+    pub fn new(secret: &str) -> PeerAuthenticator {
+        PeerAuthenticator { secret: secret.as_bytes().to_vec() }
+    }
+
+    fn get_mac(&self, payload: &Payload) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).unwrap();
+        mac.update(serde_json::to_string(&payload.r#type).unwrap().as_bytes());
+        mac.update(payload.data.as_bytes());
+        mac
+    }
+
+    /// Returns payload with mac of its type and data
+    pub fn sign(&self, payload: &Payload) -> Payload {
+        Payload {
+            mac: Some(hex::encode(self.get_mac(payload).finalize().into_bytes())),
+            ..payload.clone()
+        }
+    }
+
+    /// Get payload was signed with the same secret
+    pub fn verify(&self, payload: &Payload) -> bool {
+        match payload.mac.as_ref().and_then(|mac| hex::decode(mac).ok()) {
+            Some(mac) => self.get_mac(payload).verify_slice(&mac).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// Transport signing every payload it sends.
+pub struct SignedTransport {
+    transport: Box<dyn PeerTransport>,
+    authenticator: PeerAuthenticator,
+}
+
+impl SignedTransport {
+    pub fn new(transport: Box<dyn PeerTransport>, authenticator: PeerAuthenticator) -> Self {
+        Self { transport, authenticator }
+    }
+}
+
+impl PeerTransport for SignedTransport {
+    fn dial(&mut self, address: &str) {
+        self.transport.dial(address);
+    }
+
+    fn send(&mut self, peer: &str, payload: &Payload) {
+        self.transport.send(peer, &self.authenticator.sign(payload));
+    }
+
+    fn announce(&mut self, peers: &[String], payload: &Payload) {
+        self.transport.announce(peers, &self.authenticator.sign(payload));
+    }
+
+    fn close(&mut self, peer: &str, reason: &str) {
+        self.transport.close(peer, reason);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::payload::PayloadType;
+    use super::*;
+
+    #[test]
+    fn test_peer_authenticator() {
+        let authenticator = PeerAuthenticator::new("secret");
+        let payload = Payload::new(PayloadType::QueryAll, &());
+        assert!(!authenticator.verify(&payload));
+
+        let signed = authenticator.sign(&payload);
+        assert!(authenticator.verify(&signed));
+        assert!(!PeerAuthenticator::new("other").verify(&signed));
+        assert!(!authenticator.verify(&Payload { data: "1".to_string(), ..signed.clone() }));
+        assert!(!authenticator.verify(&Payload { r#type: PayloadType::QueryLatest, ..signed }));
+    }
+}
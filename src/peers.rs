@@ -0,0 +1,539 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use chrono::Utc;
+use libp2p::Multiaddr;
+use serde::{Serialize, Deserialize};
+use url::{Host, Url};
+
+use crate::connection::Connection;
+use crate::constants::PEER_HISTORY;
+use crate::errors::AppError;
+use crate::naivecoin::NAIVECOIN_SCHEME_PREFIX;
+use crate::payload::{Payload, PayloadType};
+
+/// Node keeps the whole chain.
+pub const SERVICE_FULL: u64 = 1 << 0;
+
+/// Node keeps only recent blocks.
+pub const SERVICE_PRUNED: u64 = 1 << 1;
+
+/// Node mines blocks.
+pub const SERVICE_MINING: u64 = 1 << 2;
+
+const SERVICE_NAMES: [(u64, &'static str); 3] = [(SERVICE_FULL, "full"), (SERVICE_PRUNED, "pruned"), (SERVICE_MINING, "mining")];
+
+/// Node software name and version sent to peers.
+pub const USER_AGENT: &'static str = concat!("/blockchain:", env!("CARGO_PKG_VERSION"), "/");
+
+/// Metadata exchanged when a connection is opened.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Handshake {
+    /// Node software name and version
+    pub user_agent: String,
+
+    /// Index of latest block
+    pub height: usize,
+
+    /// Service flags
+    pub services: u64,
+
+    /// Unadjusted clock of the node, zero if an older node did not send it
+    #[serde(default)]
+    pub timestamp: usize,
+}
+
+impl Handshake {
+    /// Returns handshake of this node at height, keeping only blocks after a utxo snapshot if pruned
+    pub fn local(height: usize, is_pruned: bool) -> Handshake {
+        Handshake {
+            user_agent: USER_AGENT.to_string(),
+            height,
+            services: (if is_pruned { SERVICE_PRUNED } else { SERVICE_FULL }) | SERVICE_MINING,
+            timestamp: Utc::now().timestamp() as usize,
+        }
+    }
+
+    /// Get names of set service flags
+    pub fn get_service_names(&self) -> Vec<&'static str> {
+        SERVICE_NAMES.iter().filter(|(flag, _)| self.services & flag != 0).map(|(_, name)| *name).collect()
+    }
+}
+
+/// Get socket address as peers are named, IPv6 bracketed and IPv4 mapped by a dual-stack listener unmapped.
+pub fn format_socket_addr(addr: SocketAddr) -> String {
+    match addr {
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(IpAddr::V4(ip), v6.port()).to_string(),
+            None => addr.to_string(),
+        },
+        SocketAddr::V4(_) => addr.to_string(),
+    }
+}
+
+/// Get address to dial normalized as peers are named, so the same peer is not known by two addresses.
+///
+/// A socket address is dialed as a websocket url, a websocket url loses its trailing slash and a multiaddr is kept.
+///
+/// # Errors
+/// If address is none of them, it returns error 5007.
+pub fn normalize_peer_address(address: &str) -> Result<String, AppError> {
+    let address = address.trim();
+    if address.starts_with('/') {
+        return address.parse::<Multiaddr>().map(|addr| addr.to_string()).map_err(|_| AppError::new(5007));
+    }
+    if let Ok(addr) = address.parse::<SocketAddr>() {
+        return Ok(format!("ws://{}", format_socket_addr(addr)));
+    }
+
+    let (prefix, url) = match address.strip_prefix(NAIVECOIN_SCHEME_PREFIX) {
+        Some(url) => (NAIVECOIN_SCHEME_PREFIX, url),
+        None => ("", address),
+    };
+    let mut url = Url::parse(url).map_err(|_| AppError::new(5007))?;
+    if !matches!(url.scheme(), "ws" | "wss") {
+        return Err(AppError::new(5007));
+    }
+    match url.host() {
+        Some(Host::Ipv6(ip)) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                url.set_ip_host(IpAddr::V4(ip)).map_err(|_| AppError::new(5007))?;
+            }
+        }
+        Some(_) => {}
+        None => return Err(AppError::new(5007)),
+    }
+
+    let mut peer = url.to_string();
+    if url.path() == "/" && url.query().is_none() && url.fragment().is_none() {
+        peer.pop();
+    }
+    Ok(format!("{}{}", prefix, peer))
+}
+
+/// Get peer id given by an operator as peers are named, ids which are not socket addresses kept.
+pub fn normalize_peer_id(id: String) -> String {
+    id.parse::<SocketAddr>().map(format_socket_addr).unwrap_or(id)
+}
+
+/// Probe of the clock of a peer, sent as ping and echoed as pong with the clock of the peer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// Unadjusted clock in milliseconds of the node which sent the ping
+    pub sent_at: usize,
+
+    /// Unadjusted clock in milliseconds of the node which sent this heartbeat
+    pub timestamp: usize,
+}
+
+impl Heartbeat {
+    /// Returns ping of this node
+    pub fn ping() -> Heartbeat {
+        let now = Utc::now().timestamp_millis() as usize;
+        Heartbeat { sent_at: now, timestamp: now }
+    }
+
+    /// Returns pong of this node answering ping
+    pub fn pong(&self) -> Heartbeat {
+        Heartbeat { sent_at: self.sent_at, timestamp: Utc::now().timestamp_millis() as usize }
+    }
+
+    /// Get clock of the peer which sent this pong, received at local clock in milliseconds.
+    ///
+    /// The peer is assumed to have answered halfway through the round trip.
+    pub fn get_clock(&self, received_at: usize) -> PeerClock {
+        let rtt_millis = received_at.saturating_sub(self.sent_at);
+        PeerClock {
+            rtt_millis,
+            offset_millis: self.timestamp as i64 - (self.sent_at + rtt_millis / 2) as i64,
+        }
+    }
+}
+
+/// Rejection of a block or transaction a peer sent, sent back so the peer learns why.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Reject {
+    /// Type of the rejected payload
+    pub r#type: PayloadType,
+
+    /// Code of the error the payload failed validation with
+    pub code: usize,
+    pub reason: String,
+
+    /// Hash of the rejected block, or id of the rejected transaction
+    pub hash: String,
+}
+
+impl Reject {
+    /// Returns rejection of payload of type holding hash, failed with error
+    pub fn new(r#type: PayloadType, hash: &str, error: &AppError) -> Reject {
+        Reject { r#type, code: error.code, reason: error.to_string(), hash: hash.to_string() }
+    }
+}
+
+/// Round trip time and clock offset of a peer, measured by its latest pong.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct PeerClock {
+    pub rtt_millis: usize,
+
+    /// Clock of the peer minus the unadjusted local clock
+    pub offset_millis: i64,
+}
+
+/// Side which opened the connection.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// Connected peer as shown to operators.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PeerInfo {
+    pub peer: String,
+    pub direction: Direction,
+
+    /// Fields below are none until the peer sent its handshake
+    pub user_agent: Option<String>,
+    pub height: Option<usize>,
+    pub services: Vec<&'static str>,
+
+    /// None until the peer answered a ping
+    pub clock: Option<PeerClock>,
+}
+
+impl PeerInfo {
+    /// Returns peer info of connection
+    pub fn from_connection(conn: &Connection) -> PeerInfo {
+        PeerInfo {
+            peer: conn.peer.clone(),
+            direction: conn.direction,
+            user_agent: conn.handshake.as_ref().map(|handshake| handshake.user_agent.clone()),
+            height: conn.handshake.as_ref().map(|handshake| handshake.height),
+            services: conn.handshake.as_ref().map(|handshake| handshake.get_service_names()).unwrap_or_default(),
+            clock: conn.clock,
+        }
+    }
+}
+
+/// Count of payloads of one type and bytes of their data.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct TrafficCounter {
+    pub messages: usize,
+    pub bytes: usize,
+}
+
+impl TrafficCounter {
+    fn record(&mut self, payload: &Payload) {
+        self.messages += 1;
+        self.bytes += payload.data.len();
+    }
+}
+
+/// Traffic with a peer since it connected, kept by the socket broadcaster.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct PeerStats {
+    pub sent: BTreeMap<PayloadType, TrafficCounter>,
+    pub received: BTreeMap<PayloadType, TrafficCounter>,
+
+    /// Payloads rejected as malformed or unauthenticated
+    pub invalid: usize,
+
+    /// Blocks and transactions of this node the peer rejected, with the latest rejection
+    pub rejected: usize,
+    pub last_reject: Option<Reject>,
+
+    /// Timestamp of the latest payload received
+    pub last_activity: Option<usize>,
+}
+
+impl PeerStats {
+    /// Count payload sent to peer
+    pub fn record_sent(&mut self, payload: &Payload) {
+        self.sent.entry(payload.r#type).or_default().record(payload);
+    }
+
+    /// Count payload received from peer
+    pub fn record_received(&mut self, payload: &Payload) {
+        self.received.entry(payload.r#type).or_default().record(payload);
+        self.last_activity = Some(Utc::now().timestamp() as usize);
+    }
+
+    /// Count payload of peer which was rejected
+    pub fn record_invalid(&mut self) {
+        self.invalid += 1;
+    }
+
+    /// Count rejection of a payload of this node sent by peer
+    pub fn record_reject(&mut self, reject: Reject) {
+        self.rejected += 1;
+        self.last_reject = Some(reject);
+    }
+}
+
+/// Peer whose connection was closed, as shown to operators.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct DisconnectedPeer {
+    #[serde(flatten)]
+    pub info: PeerInfo,
+
+    /// Reason given by whichever side closed the connection
+    pub reason: Option<String>,
+    pub timestamp: usize,
+}
+
+impl DisconnectedPeer {
+    /// Returns disconnected peer of connection, closed now
+    pub fn from_connection(conn: &Connection, reason: Option<String>) -> DisconnectedPeer {
+        DisconnectedPeer {
+            info: PeerInfo::from_connection(conn),
+            reason,
+            timestamp: Utc::now().timestamp() as usize,
+        }
+    }
+}
+
+/// Address dialed which is not connected yet, as shown to operators.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PendingDial {
+    pub peer: String,
+    pub attempts: usize,
+
+    /// Error of the latest failed attempt, none while the first attempt is in flight
+    pub last_error: Option<String>,
+    pub last_attempt: usize,
+}
+
+/// Snapshot of connected peers, published by the socket broadcaster, with connection slots, pending dials and recent disconnects.
+#[derive(Debug, Default)]
+pub struct PeerRegistry {
+    peers: RwLock<Vec<PeerInfo>>,
+    dials: RwLock<Vec<PendingDial>>,
+    history: RwLock<VecDeque<DisconnectedPeer>>,
+    inbound: AtomicUsize,
+    outbound: AtomicUsize,
+}
+
+impl PeerRegistry {
+    fn get_slots(&self, direction: Direction) -> &AtomicUsize {
+        match direction {
+            Direction::Inbound => &self.inbound,
+            Direction::Outbound => &self.outbound,
+        }
+    }
+
+    /// Take a connection slot, returning false if max connections are open
+    pub fn try_reserve(&self, direction: Direction, max: usize) -> bool {
+        self.get_slots(direction)
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| if count < max { Some(count + 1) } else { None })
+            .is_ok()
+    }
+
+    /// Release a slot taken by a closed connection
+    pub fn release(&self, direction: Direction) {
+        let _ = self.get_slots(direction).fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| count.checked_sub(1));
+    }
+
+    /// Get count of open connections
+    pub fn get_count(&self, direction: Direction) -> usize {
+        self.get_slots(direction).load(Ordering::SeqCst)
+    }
+
+    /// Replace peers, sorted by address
+    pub fn publish(&self, mut peers: Vec<PeerInfo>) {
+        peers.sort_by(|a, b| a.peer.cmp(&b.peer));
+        *self.peers.write().unwrap() = peers;
+    }
+
+    /// Get connected peers
+    pub fn get_peers(&self) -> Vec<PeerInfo> {
+        self.peers.read().unwrap().to_vec()
+    }
+
+    /// Get connected peer of id, none if it is not connected
+    pub fn get_peer(&self, peer: &str) -> Option<PeerInfo> {
+        self.peers.read().unwrap().iter().find(|info| info.peer == peer).cloned()
+    }
+
+    /// Record a closed connection, forgetting the oldest past history size
+    pub fn record(&self, peer: DisconnectedPeer) {
+        let mut history = self.history.write().unwrap();
+        history.push_front(peer);
+        history.truncate(PEER_HISTORY);
+    }
+
+    /// Get closed connections, latest first
+    pub fn get_history(&self) -> Vec<DisconnectedPeer> {
+        self.history.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Record an attempt to dial address, forgetting the dial attempted longest ago past history size
+    pub fn record_dial(&self, address: &str) {
+        let mut dials = self.dials.write().unwrap();
+        let timestamp = Utc::now().timestamp() as usize;
+        match dials.iter_mut().find(|dial| dial.peer == address) {
+            Some(dial) => {
+                dial.attempts += 1;
+                dial.last_attempt = timestamp;
+            }
+            None => dials.push(PendingDial { peer: address.to_string(), attempts: 1, last_error: None, last_attempt: timestamp }),
+        }
+        if dials.len() > PEER_HISTORY {
+            dials.sort_by(|a, b| b.last_attempt.cmp(&a.last_attempt));
+            dials.truncate(PEER_HISTORY);
+        }
+        dials.sort_by(|a, b| a.peer.cmp(&b.peer));
+    }
+
+    /// Record error of the latest attempt to dial address
+    pub fn record_dial_error(&self, address: &str, error: String) {
+        if let Some(dial) = self.dials.write().unwrap().iter_mut().find(|dial| dial.peer == address) {
+            dial.last_error = Some(error);
+        }
+    }
+
+    /// Forget dial of address once it is up
+    pub fn remove_dial(&self, address: &str) {
+        self.dials.write().unwrap().retain(|dial| dial.peer != address);
+    }
+
+    /// Get dials which are not up, sorted by address
+    pub fn get_dials(&self) -> Vec<PendingDial> {
+        self.dials.read().unwrap().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_handshake() {
+        let handshake = Handshake::local(3, false);
+        assert!(handshake.user_agent.starts_with("/blockchain:"));
+        assert_eq!(handshake.get_service_names(), vec!["full", "mining"]);
+        assert_eq!(Handshake::local(3, true).get_service_names(), vec!["pruned", "mining"]);
+
+        let handshake = Handshake { services: SERVICE_PRUNED, ..handshake };
+        assert_eq!(handshake.get_service_names(), vec!["pruned"]);
+    }
+
+    #[test]
+    fn test_normalize_peer_address() {
+        assert_eq!(format_socket_addr("[::ffff:10.0.0.1]:2794".parse().unwrap()), "10.0.0.1:2794");
+        assert_eq!(format_socket_addr("[::1]:2794".parse().unwrap()), "[::1]:2794");
+        assert_eq!(normalize_peer_address("[0:0::1]:2794").unwrap(), "ws://[::1]:2794");
+        assert_eq!(normalize_peer_address("127.0.0.1:2794").unwrap(), "ws://127.0.0.1:2794");
+        assert_eq!(normalize_peer_address("ws://[::FFFF:10.0.0.1]:2794/").unwrap(), "ws://10.0.0.1:2794");
+        assert_eq!(normalize_peer_address("naivecoin+ws://[fe80::1]:6001").unwrap(), "naivecoin+ws://[fe80::1]:6001");
+        assert_eq!(normalize_peer_address("ws://Node.Local:2794/path").unwrap(), "ws://node.local:2794/path");
+        assert_eq!(normalize_peer_address("/ip6/::1/tcp/2794").unwrap(), "/ip6/::1/tcp/2794");
+        assert_eq!(normalize_peer_address("ws://::1:2794").unwrap_err().code, 5007);
+        assert_eq!(normalize_peer_address("http://127.0.0.1:2794").unwrap_err().code, 5007);
+        assert_eq!(normalize_peer_address("/ip6/nonsense").unwrap_err().code, 5007);
+
+        assert_eq!(normalize_peer_id("[::ffff:10.0.0.1]:2794".to_string()), "10.0.0.1:2794");
+        assert_eq!(normalize_peer_id("12D3KooW".to_string()), "12D3KooW");
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let ping = Heartbeat { sent_at: 1_000, timestamp: 1_000 };
+        let pong = Heartbeat { timestamp: 3_020, ..ping.pong() };
+        assert_eq!(pong.sent_at, 1_000);
+        assert_eq!(pong.get_clock(1_040), PeerClock { rtt_millis: 40, offset_millis: 2_000 });
+        assert_eq!(pong.get_clock(900).rtt_millis, 0);
+    }
+
+    #[test]
+    fn test_peer_registry() {
+        let peer_registry = PeerRegistry::default();
+        let (inbox, _) = tokio::sync::mpsc::unbounded_channel();
+        let conn = Connection::new("b".to_string(), Direction::Outbound, inbox.clone());
+        let mut handshake_conn = Connection::new("a".to_string(), Direction::Inbound, inbox);
+        handshake_conn.handshake = Some(Handshake::local(3, false));
+        handshake_conn.clock = Some(PeerClock { rtt_millis: 40, offset_millis: -5 });
+
+        peer_registry.publish(vec![PeerInfo::from_connection(&conn), PeerInfo::from_connection(&handshake_conn)]);
+        let peers = peer_registry.get_peers();
+        assert_eq!(peers.len(), 2);
+        assert_eq!(peers[0].peer, "a");
+        assert_eq!(peers[0].height, Some(3));
+        assert_eq!(peers[0].clock, Some(PeerClock { rtt_millis: 40, offset_millis: -5 }));
+        assert_eq!(peers[1].user_agent, None);
+        assert_eq!(peers[1].clock, None);
+        assert_eq!(peers[1].direction, Direction::Outbound);
+        assert_eq!(peer_registry.get_peer("b").unwrap().direction, Direction::Outbound);
+        assert!(peer_registry.get_peer("c").is_none());
+    }
+
+    #[test]
+    fn test_peer_stats() {
+        let mut stats = PeerStats::default();
+        stats.record_sent(&Payload::new(PayloadType::Inventory, &vec!["a"]));
+        stats.record_sent(&Payload::new(PayloadType::Inventory, &vec!["b"]));
+        stats.record_received(&Payload::new(PayloadType::QueryLatest, &()));
+        stats.record_invalid();
+        stats.record_reject(Reject::new(PayloadType::Transaction, "b6c6a943", &AppError::new(4001)));
+
+        assert_eq!(stats.sent[&PayloadType::Inventory], TrafficCounter { messages: 2, bytes: 10 });
+        assert_eq!(stats.received[&PayloadType::QueryLatest], TrafficCounter { messages: 1, bytes: 4 });
+        assert_eq!(stats.invalid, 1);
+        assert_eq!(stats.rejected, 1);
+        assert_eq!(stats.last_reject.as_ref().unwrap().reason, "[4001]: Fail to add transaction pool with invalid transaction pool");
+        assert!(stats.last_activity.is_some());
+        assert_eq!(serde_json::to_value(&stats).unwrap()["sent"]["Inventory"]["messages"], 2);
+    }
+
+    #[test]
+    fn test_peer_history() {
+        let peer_registry = PeerRegistry::default();
+        let (inbox, _) = tokio::sync::mpsc::unbounded_channel();
+        for i in 0..PEER_HISTORY + 1 {
+            let conn = Connection::new(i.to_string(), Direction::Inbound, inbox.clone());
+            peer_registry.record(DisconnectedPeer::from_connection(&conn, Some("Bye".to_string())));
+        }
+
+        let history = peer_registry.get_history();
+        assert_eq!(history.len(), PEER_HISTORY);
+        assert_eq!(history[0].info.peer, PEER_HISTORY.to_string());
+        assert_eq!(history[0].reason, Some("Bye".to_string()));
+        assert_eq!(serde_json::to_value(&history[0]).unwrap()["peer"], PEER_HISTORY.to_string());
+    }
+
+    #[test]
+    fn test_pending_dials() {
+        let peer_registry = PeerRegistry::default();
+        peer_registry.record_dial("ws://b:6001");
+        peer_registry.record_dial("ws://a:6001");
+        peer_registry.record_dial_error("ws://a:6001", "Connection refused".to_string());
+        peer_registry.record_dial("ws://a:6001");
+        peer_registry.record_dial_error("ws://unknown:6001", "Connection refused".to_string());
+
+        let dials = peer_registry.get_dials();
+        assert_eq!(dials.len(), 2);
+        assert_eq!(dials[0].peer, "ws://a:6001");
+        assert_eq!(dials[0].attempts, 2);
+        assert_eq!(dials[0].last_error, Some("Connection refused".to_string()));
+        assert_eq!(dials[1].last_error, None);
+
+        peer_registry.remove_dial("ws://a:6001");
+        assert_eq!(peer_registry.get_dials().len(), 1);
+    }
+
+    #[test]
+    fn test_connection_slots() {
+        let peer_registry = PeerRegistry::default();
+        assert!(peer_registry.try_reserve(Direction::Inbound, 1));
+        assert!(!peer_registry.try_reserve(Direction::Inbound, 1));
+        assert!(peer_registry.try_reserve(Direction::Outbound, 1));
+        assert!(!peer_registry.try_reserve(Direction::Outbound, 0));
+
+        peer_registry.release(Direction::Inbound);
+        peer_registry.release(Direction::Inbound);
+        assert_eq!(peer_registry.get_count(Direction::Inbound), 0);
+        assert_eq!(peer_registry.get_count(Direction::Outbound), 1);
+    }
+}
@@ -0,0 +1,143 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::errors::AppError;
+use crate::utils::get_is_hash_matches_difficulty;
+
+/// Salt of memory-hard hashes, fixed so every node computes the same hash.
+const ARGON2_SALT: &'static [u8] = b"blockchain-study";
+
+/// Memory of a memory-hard hash in KiB.
+const ARGON2_MEMORY: u32 = 4 * 1024;
+
+static POW_ALGORITHM: AtomicU8 = AtomicU8::new(PowAlgorithm::Sha256 as u8);
+
+/// Hash algorithm of proof of work, committed in block headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowAlgorithm {
+    /// Single SHA-256, the algorithm of existing chains
+    #[default]
+    Sha256 = 0,
+    Sha256d = 1,
+    Blake3 = 2,
+
+    /// Argon2id, memory-hard against specialized miners
+    Argon2 = 3,
+}
+
+impl PowAlgorithm {
+    /// Get hasher of the algorithm
+    pub fn get_hasher(&self) -> &'static dyn PowHasher {
+        match self {
+            PowAlgorithm::Sha256 => &Sha256Hasher,
+            PowAlgorithm::Sha256d => &Sha256dHasher,
+            PowAlgorithm::Blake3 => &Blake3Hasher,
+            PowAlgorithm::Argon2 => &Argon2Hasher,
+        }
+    }
+
+    fn from_u8(value: u8) -> PowAlgorithm {
+        match value {
+            1 => PowAlgorithm::Sha256d,
+            2 => PowAlgorithm::Blake3,
+            3 => PowAlgorithm::Argon2,
+            _ => PowAlgorithm::Sha256,
+        }
+    }
+}
+
+impl FromStr for PowAlgorithm {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(PowAlgorithm::Sha256),
+            "sha256d" => Ok(PowAlgorithm::Sha256d),
+            "blake3" => Ok(PowAlgorithm::Blake3),
+            "argon2" => Ok(PowAlgorithm::Argon2),
+            _ => Err(AppError::new(1003)),
+        }
+    }
+}
+
+/// Get algorithm blocks of this network are mined with.
+pub fn get_pow_algorithm() -> PowAlgorithm {
+    PowAlgorithm::from_u8(POW_ALGORITHM.load(Ordering::Relaxed))
+}
+
+/// Set algorithm blocks of this network are mined with, once on start.
+#[cfg(feature = "node")]
+pub fn set_pow_algorithm(algorithm: PowAlgorithm) {
+    POW_ALGORITHM.store(algorithm as u8, Ordering::Relaxed);
+}
+
+/// Hash function of proof of work.
+pub trait PowHasher: Sync {
+    /// Get hex hash of content
+    fn hash(&self, content: &[u8]) -> String;
+
+    /// Get hash has enough leading zero bits for difficulty
+    fn get_is_hash_matches_difficulty(&self, hash: &str, difficulty: usize) -> bool {
+        get_is_hash_matches_difficulty(hash, difficulty)
+    }
+}
+
+pub struct Sha256Hasher;
+
+impl PowHasher for Sha256Hasher {
+    fn hash(&self, content: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(content))
+    }
+}
+
+pub struct Sha256dHasher;
+
+impl PowHasher for Sha256dHasher {
+    fn hash(&self, content: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(Sha256::digest(content)))
+    }
+}
+
+pub struct Blake3Hasher;
+
+impl PowHasher for Blake3Hasher {
+    fn hash(&self, content: &[u8]) -> String {
+        blake3::hash(content).to_hex().to_string()
+    }
+}
+
+pub struct Argon2Hasher;
+
+impl PowHasher for Argon2Hasher {
+    fn hash(&self, content: &[u8]) -> String {
+        let params = Params::new(ARGON2_MEMORY, 1, 1, Some(32)).unwrap();
+        let mut output = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(content, ARGON2_SALT, &mut output)
+            .unwrap();
+        hex::encode(output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pow_hasher() {
+        let algorithms = [PowAlgorithm::Sha256, PowAlgorithm::Sha256d, PowAlgorithm::Blake3, PowAlgorithm::Argon2];
+        let hashes: Vec<String> = algorithms.iter().map(|algorithm| algorithm.get_hasher().hash(b"block")).collect();
+        assert_eq!(hashes[0], "496aca80e4d8f29fb8e8cd816c3afb48d3f103970b3a2ee1600c08ca67326dee");
+        assert!(hashes.iter().all(|hash| hash.len() == 64));
+        assert_eq!(hashes.iter().collect::<std::collections::HashSet<_>>().len(), algorithms.len());
+        assert_eq!(hashes[3], PowAlgorithm::Argon2.get_hasher().hash(b"block"));
+
+        assert_eq!("blake3".parse::<PowAlgorithm>().unwrap(), PowAlgorithm::Blake3);
+        assert!("md5".parse::<PowAlgorithm>().is_err());
+        assert_eq!(PowAlgorithm::from_u8(PowAlgorithm::Argon2 as u8), PowAlgorithm::Argon2);
+    }
+}
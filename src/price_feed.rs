@@ -0,0 +1,218 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::errors::AppError;
+
+const FETCH_TIMEOUT_SECS: u64 = 10;
+
+/// Intervals a price is used for after its last update, before it is considered stale.
+const STALE_INTERVALS: usize = 5;
+
+/// Source of the fiat price of a coin.
+pub trait PriceSource: Send + Sync {
+    /// Fetch the current price
+    fn fetch(&self) -> Result<f64, AppError>;
+}
+
+/// Price read from a JSON document served over plain http, at a dotted path such as `data.price`.
+///
+/// Feeds served over https are fetched through a local proxy.
+#[derive(Debug, Clone)]
+pub struct HttpPriceSource {
+    url: Url,
+    path: String,
+}
+
+impl HttpPriceSource {
+    /// Returns a source of the price at path of the document at url.
+    ///
+    /// # Errors
+    /// If url is not an http url with a host, it returns error 10000.
+    pub fn new(url: &str, path: &str) -> Result<HttpPriceSource, AppError> {
+        let url = Url::parse(url).map_err(|_| AppError::new(10000))?;
+        if url.scheme() != "http" || url.host_str().is_none() {
+            return Err(AppError::new(10000));
+        }
+        Ok(HttpPriceSource { url, path: path.to_string() })
+    }
+}
+
+impl PriceSource for HttpPriceSource {
+    /// # Errors
+    /// If the document cannot be fetched or holds no price at path, it returns error 10001.
+    fn fetch(&self) -> Result<f64, AppError> {
+        let host = self.url.host_str().unwrap();
+        let address = (host, self.url.port_or_known_default().unwrap_or(80))
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or(AppError::new(10001))?;
+        let timeout = Duration::from_secs(FETCH_TIMEOUT_SECS);
+        let mut stream = TcpStream::connect_timeout(&address, timeout).map_err(|_| AppError::new(10001))?;
+        stream.set_read_timeout(Some(timeout)).map_err(|_| AppError::new(10001))?;
+
+        // HTTP/1.0 keeps the body unchunked and the connection closed after it.
+        let target = &self.url[url::Position::BeforePath..url::Position::AfterQuery];
+        let request = format!("GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n", target, host);
+        stream.write_all(request.as_bytes()).map_err(|_| AppError::new(10001))?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).map_err(|_| AppError::new(10001))?;
+
+        let (head, body) = response.split_once("\r\n\r\n").ok_or(AppError::new(10001))?;
+        if head.split_whitespace().nth(1) != Some("200") {
+            return Err(AppError::new(10001));
+        }
+        let document: Value = serde_json::from_str(body).map_err(|_| AppError::new(10001))?;
+        get_price_at(&document, &self.path).ok_or(AppError::new(10001))
+    }
+}
+
+/// Get positive price at dotted path of document, as a number or a numeric string.
+fn get_price_at(document: &Value, path: &str) -> Option<f64> {
+    let value = path
+        .split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(document, |value, key| match value {
+            Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+            _ => value.get(key),
+        })?;
+    let price = match value {
+        Value::Number(number) => number.as_f64()?,
+        Value::String(s) => s.parse().ok()?,
+        _ => return None,
+    };
+    Some(price).filter(|price| price.is_finite() && *price > 0.0)
+}
+
+/// Fiat price of a coin when it was fetched.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Price {
+    pub currency: String,
+    pub rate: f64,
+
+    /// Time the price was fetched
+    pub updated_at: usize,
+}
+
+/// Fiat equivalent of an amount of coins, only for display.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FiatAmount {
+    pub currency: String,
+    pub amount: f64,
+    pub rate: f64,
+
+    /// Time the rate was fetched
+    pub updated_at: usize,
+}
+
+/// Latest price of a source, refreshed by a background thread.
+#[derive(Debug)]
+pub struct PriceFeed {
+    currency: String,
+    interval: Duration,
+    price: RwLock<Option<Price>>,
+}
+
+impl PriceFeed {
+    /// Returns a feed of prices in currency refreshed every interval, none fetched yet
+    pub fn new(currency: &str, interval: Duration) -> PriceFeed {
+        PriceFeed { currency: currency.to_string(), interval, price: RwLock::new(None) }
+    }
+
+    /// Spawn a thread fetching the price of source every interval, a failed fetch keeps the last price
+    pub fn launch(self: &Arc<Self>, source: Box<dyn PriceSource>) {
+        let feed = Arc::clone(self);
+        thread::spawn(move || loop {
+            match source.fetch() {
+                Ok(rate) => feed.update(rate, Utc::now().timestamp() as usize),
+                Err(e) => tracing::warn!(error = %e, "price fetch failed"),
+            }
+            thread::sleep(feed.interval);
+        });
+    }
+
+    /// Update price to rate fetched at timestamp
+    pub fn update(&self, rate: f64, timestamp: usize) {
+        *self.price.write().unwrap() = Some(Price { currency: self.currency.clone(), rate, updated_at: timestamp });
+    }
+
+    /// Get price at timestamp, none if it was never fetched or is stale
+    pub fn get_price(&self, timestamp: usize) -> Option<Price> {
+        let max_age = STALE_INTERVALS * self.interval.as_secs() as usize;
+        self.price.read().unwrap().clone().filter(|price| timestamp <= price.updated_at + max_age)
+    }
+
+    /// Get fiat equivalent of amount at timestamp, none without a price
+    pub fn get_fiat_amount(&self, amount: usize, timestamp: usize) -> Option<FiatAmount> {
+        self.get_price(timestamp).map(|price| FiatAmount {
+            currency: price.currency,
+            amount: amount as f64 * price.rate,
+            rate: price.rate,
+            updated_at: price.updated_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use super::*;
+
+    #[test]
+    fn test_get_price_at() {
+        let document: Value = serde_json::from_str(r#"{"data":{"price":"12.5","quotes":[{"usd":3}]},"price":-1}"#).unwrap();
+        assert_eq!(get_price_at(&document, "data.price"), Some(12.5));
+        assert_eq!(get_price_at(&document, "data.quotes.0.usd"), Some(3.0));
+        assert_eq!(get_price_at(&document, "price"), None);
+        assert_eq!(get_price_at(&document, "data.missing"), None);
+        assert_eq!(get_price_at(&document, "data"), None);
+    }
+
+    #[test]
+    fn test_fetch() {
+        assert_eq!(HttpPriceSource::new("https://example.com/price", "price").unwrap_err().code, 10000);
+        assert_eq!(HttpPriceSource::new("not a url", "price").unwrap_err().code, 10000);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            for (index, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request = [0u8; 1024];
+                let size = stream.read(&mut request).unwrap();
+                assert!(String::from_utf8_lossy(&request[..size]).starts_with("GET /ticker?pair=coin HTTP/1.0\r\n"));
+                let response = match index {
+                    0 => "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"last\":42.5}",
+                    _ => "HTTP/1.0 503 Service Unavailable\r\n\r\n",
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let source = HttpPriceSource::new(&format!("http://127.0.0.1:{}/ticker?pair=coin", port), "last").unwrap();
+        assert_eq!(source.fetch().unwrap(), 42.5);
+        assert_eq!(source.fetch().unwrap_err().code, 10001);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_get_fiat_amount() {
+        let price_feed = PriceFeed::new("USD", Duration::from_secs(60));
+        assert_eq!(price_feed.get_fiat_amount(10, 1000), None);
+
+        price_feed.update(2.5, 1000);
+        assert_eq!(
+            price_feed.get_fiat_amount(10, 1000),
+            Some(FiatAmount { currency: "USD".to_string(), amount: 25.0, rate: 2.5, updated_at: 1000 }),
+        );
+        assert!(price_feed.get_fiat_amount(10, 1300).is_some());
+        assert_eq!(price_feed.get_fiat_amount(10, 1301), None);
+    }
+}
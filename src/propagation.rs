@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use serde::Serialize;
+
+use crate::Block;
+use crate::constants::ARRIVAL_HISTORY;
+
+/// When and from where a block was first seen by this node.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct BlockArrival {
+    pub hash: String,
+    pub index: usize,
+
+    /// Timestamp the block claims
+    pub timestamp: usize,
+
+    /// Adjusted time the block was first stored
+    pub received_at: usize,
+
+    /// Received minus claimed time, negative for blocks claiming a time ahead of this node
+    pub delay: i64,
+
+    /// Peer the block was received from, none if it was mined by this node
+    pub source: Option<String>,
+}
+
+/// Bounded history of block arrivals, latest first.
+///
+/// Blocks dropped by a reorg keep their arrival, so competing branches can be compared.
+#[derive(Debug, Default)]
+pub struct ArrivalLog {
+    arrivals: RwLock<VecDeque<BlockArrival>>,
+}
+
+impl ArrivalLog {
+    /// Record arrival of blocks not seen before from source at received at, forgetting the oldest past history size
+    pub fn record(&self, blocks: &[Block], source: Option<&str>, received_at: usize) {
+        let mut arrivals = self.arrivals.write().unwrap();
+        for block in blocks {
+            if arrivals.iter().any(|arrival| arrival.hash == block.hash) {
+                continue;
+            }
+            arrivals.push_front(BlockArrival {
+                hash: block.hash.clone(),
+                index: block.index,
+                timestamp: block.timestamp,
+                received_at,
+                delay: received_at as i64 - block.timestamp as i64,
+                source: source.map(str::to_string),
+            });
+        }
+        arrivals.truncate(ARRIVAL_HISTORY);
+    }
+
+    /// Get arrival of block hash, none if it was never stored or is forgotten
+    pub fn get(&self, hash: &str) -> Option<BlockArrival> {
+        self.arrivals.read().unwrap().iter().find(|arrival| arrival.hash == hash).cloned()
+    }
+
+    /// Get up to limit arrivals, latest first
+    pub fn get_arrivals(&self, limit: usize) -> Vec<BlockArrival> {
+        self.arrivals.read().unwrap().iter().take(limit).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::get_genesis_block;
+    use super::*;
+
+    #[test]
+    fn test_arrival_log() {
+        let genesis_block = get_genesis_block();
+        let a1 = Block::generate(&vec![], &genesis_block, 0);
+        let a2 = Block::generate(&vec![], &a1, 0);
+
+        let arrival_log = ArrivalLog::default();
+        arrival_log.record(&vec![genesis_block.clone(), a1.clone()], Some("peer-a"), a1.timestamp + 3);
+        arrival_log.record(&vec![genesis_block.clone(), a1.clone(), a2.clone()], None, a2.timestamp + 1);
+
+        let arrival = arrival_log.get(&a1.hash).unwrap();
+        assert_eq!(arrival.source, Some("peer-a".to_string()));
+        assert_eq!(arrival.delay, 3);
+        assert_eq!(arrival_log.get(&a2.hash).unwrap().source, None);
+        assert!(arrival_log.get("unknown").is_none());
+
+        let arrivals = arrival_log.get_arrivals(2);
+        assert_eq!(arrivals.iter().map(|arrival| arrival.index).collect::<Vec<_>>(), vec![2, 1]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Read;
+use std::sync::Arc;
+use rocket::{Data, Outcome, State};
+use rocket::data::{self, FromDataSimple};
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::errors::{ApiError, FieldValidator};
+use crate::journal::{Input, Journal};
+
+/// Default size limit of JSON bodies, the same as `Json`.
+const JSON_LIMIT: u64 = 1 << 20;
+
+/// JSON body of a mutating request, recorded to the journal before it is parsed.
+pub struct Journaled<T>(pub T);
+
+impl<T: DeserializeOwned> FromDataSimple for Journaled<T> {
+    type Error = String;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, String> {
+        let body = match read_body(request, data) {
+            Ok(body) => body,
+            Err(e) => return Outcome::Failure((Status::BadRequest, e)),
+        };
+
+        record(request, body.clone());
+        match serde_json::from_str(&body) {
+            Ok(value) => Outcome::Success(Journaled(value)),
+            Err(e) if e.is_data() => Outcome::Failure((Status::UnprocessableEntity, e.to_string())),
+            Err(e) => Outcome::Failure((Status::BadRequest, e.to_string())),
+        }
+    }
+}
+
+/// JSON body of a mutating request with mandatory fields, recorded like `Journaled`.
+///
+/// Its error tells every field missing or invalid, taken as `Result<Validated<T>, ApiError>` to be answered.
+pub struct Validated<T>(pub T);
+
+impl<T: DeserializeOwned + Validate> FromDataSimple for Validated<T> {
+    type Error = ApiError;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, ApiError> {
+        let body = match read_body(request, data) {
+            Ok(body) => body,
+            Err(e) => return Outcome::Failure((Status::BadRequest, ApiError::new(400, e, None))),
+        };
+
+        record(request, body.clone());
+        match FieldValidator::parse(&body) {
+            Ok(value) => Outcome::Success(Validated(value)),
+            Err(e) => Outcome::Failure((Status::UnprocessableEntity, e)),
+        }
+    }
+}
+
+/// JSON body of a read-only request with mandatory fields, parsed like `Validated` without being journaled.
+///
+/// Requests holding secrets such as private keys take it too, so they are never written to the journal.
+pub struct Parsed<T>(pub T);
+
+impl<T: DeserializeOwned + Validate> FromDataSimple for Parsed<T> {
+    type Error = ApiError;
+
+    fn from_data(request: &Request, data: Data) -> data::Outcome<Self, ApiError> {
+        let body = match read_body(request, data) {
+            Ok(body) => body,
+            Err(e) => return Outcome::Failure((Status::BadRequest, ApiError::new(400, e, None))),
+        };
+
+        match FieldValidator::parse(&body) {
+            Ok(value) => Outcome::Success(Parsed(value)),
+            Err(e) => Outcome::Failure((Status::UnprocessableEntity, e)),
+        }
+    }
+}
+
+/// Guard for mutating requests without body, which records them to the journal.
+pub struct Recorded;
+
+impl<'a, 'r> FromRequest<'a, 'r> for Recorded {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Recorded, ()> {
+        record(request, "".to_string());
+        Outcome::Success(Recorded)
+    }
+}
+
+fn read_body(request: &Request, data: Data) -> Result<String, String> {
+    let size_limit = request.limits().get("json").unwrap_or(JSON_LIMIT);
+    let mut body = String::with_capacity(512);
+    data.open().take(size_limit).read_to_string(&mut body).map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+fn record(request: &Request, body: String) {
+    if let Some(journal) = request.guard::<State<Option<Arc<Journal>>>>().succeeded() {
+        if let Some(journal) = journal.inner() {
+            journal.record(Input::Http {
+                method: request.method().as_str().to_string(),
+                uri: request.uri().to_string(),
+                body,
+            });
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+
+use crate::errors::AppError;
+use crate::transaction::{find_unspent_tx_out, get_sighash, SIGHASH_ALL, TransactionSigner, TxIn};
+use crate::UnspentTxOut;
+
+/// Seconds to wait for the signer to connect, read or answer, long enough for an HSM asking confirmation.
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Bytes of a response line read at most
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+
+/// Request to an external signer, sent as a line of JSON.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SignerRequest {
+    /// Ask address of the held key
+    Address,
+
+    /// Ask signature of a sighash spending an output
+    Sign {
+        transaction_id: String,
+        tx_out_id: String,
+        tx_out_index: usize,
+        address: String,
+        amount: usize,
+        sighash: String,
+    },
+}
+
+/// Response of an external signer, received as a line of JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignerResponse {
+    pub address: Option<String>,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Signer delegating to an external process over TCP, e.g. an HSM bridge.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    pub endpoint: String,
+    pub address: String,
+}
+
+impl RemoteSigner {
+    /// Returns a remote signer after asking the address of its key
+    ///
+    /// # Errors
+    /// If the signer cannot be reached in time or does not answer an address, it returns error 3004.
+    pub fn connect(endpoint: String) -> Result<RemoteSigner, AppError> {
+        let response = request(&endpoint, &SignerRequest::Address, 3004)?;
+        match response.address {
+            Some(address) => Ok(RemoteSigner { endpoint, address }),
+            None => Err(AppError::new(3004)),
+        }
+    }
+}
+
+impl TransactionSigner for RemoteSigner {
+    fn get_address(&self) -> String {
+        self.address.clone()
+    }
+
+    fn sign_tx_in(&self, transaction_id: &str, tx_in: &TxIn, unspent_tx_outs: &Vec<UnspentTxOut>) -> Result<String, AppError> {
+        let referenced_unspent_tx_out = find_unspent_tx_out(&tx_in.tx_out_id, tx_in.tx_out_index, unspent_tx_outs)
+            .filter(|u_tx_o| u_tx_o.address.eq(&self.address))
+            .ok_or(AppError::new(2000))?;
+
+        let sign_request = SignerRequest::Sign {
+            transaction_id: transaction_id.to_string(),
+            tx_out_id: tx_in.tx_out_id.clone(),
+            tx_out_index: tx_in.tx_out_index,
+            address: referenced_unspent_tx_out.address.clone(),
+            amount: referenced_unspent_tx_out.amount,
+            sighash: get_sighash(transaction_id, tx_in, referenced_unspent_tx_out, SIGHASH_ALL),
+        };
+        let response = request(&self.endpoint, &sign_request, 3005)?;
+        match response.signature {
+            Some(signature) => Ok(format!("{}{:02x}", signature, SIGHASH_ALL)),
+            None => Err(AppError::new(3005)),
+        }
+    }
+}
+
+/// Send request to the signer at endpoint and read its response, failing with error code if it is unreachable,
+/// slower than the timeout or answers an invalid line.
+fn request(endpoint: &str, signer_request: &SignerRequest, error_code: usize) -> Result<SignerResponse, AppError> {
+    let address = endpoint
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or(AppError::new(error_code))?;
+    let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+    let mut stream = TcpStream::connect_timeout(&address, timeout).map_err(|_| AppError::new(error_code))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|_| AppError::new(error_code))?;
+    stream.set_write_timeout(Some(timeout)).map_err(|_| AppError::new(error_code))?;
+    let request = format!("{}\n", serde_json::to_string(signer_request).unwrap());
+    stream.write_all(request.as_bytes()).map_err(|_| AppError::new(error_code))?;
+
+    let mut line = String::new();
+    BufReader::new(stream.take(MAX_RESPONSE_BYTES)).read_line(&mut line).map_err(|_| AppError::new(error_code))?;
+    serde_json::from_str(&line).map_err(|_| AppError::new(error_code))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::thread;
+    use crate::signer::get_signer;
+    use crate::transaction::{get_is_valid_transaction, BlockContext, Transaction, TxOut};
+    use super::*;
+
+    fn serve(listener: TcpListener, private_key: &'static str, count: usize) {
+        thread::spawn(move || {
+            let signer = get_signer(private_key).unwrap();
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                let response = match serde_json::from_str::<SignerRequest>(&line).unwrap() {
+                    SignerRequest::Address => SignerResponse { address: Some(signer.get_address()), signature: None, error: None },
+                    SignerRequest::Sign { sighash, .. } => SignerResponse { address: None, signature: Some(signer.sign(&sighash).unwrap()), error: None },
+                };
+                stream.write_all(format!("{}\n", serde_json::to_string(&response).unwrap()).as_bytes()).unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        serve(listener, "27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b", 2);
+
+        let remote_signer = RemoteSigner::connect(endpoint).unwrap();
+        assert_eq!(remote_signer.get_address(), "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b");
+
+        let tx_ins = vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, "".to_string())];
+        let tx_outs = vec![
+            TxOut::new("03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40".to_string(), 50)
+        ];
+        let unspent_tx_outs = vec![
+            UnspentTxOut::new(
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
+                0,
+                "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
+                50,
+            )
+        ];
+        let mut transaction = Transaction::generate(&tx_ins, &tx_outs);
+        let signature = remote_signer.sign_tx_in(&transaction.id, tx_ins.get(0).unwrap(), &unspent_tx_outs).unwrap();
+        transaction.tx_ins = vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, signature)];
+        assert!(get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
+
+        assert!(remote_signer.sign_tx_in(&transaction.id, tx_ins.get(0).unwrap(), &vec![]).is_err());
+    }
+
+    #[test]
+    fn test_remote_signer_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        assert_eq!(RemoteSigner::connect(endpoint).unwrap_err().code, 3004);
+        assert_eq!(RemoteSigner::connect("invalid".to_string()).unwrap_err().code, 3004);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"not json\n").unwrap();
+        });
+        assert_eq!(RemoteSigner::connect(endpoint).unwrap_err().code, 3004);
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::VecDeque;
+use std::sync::RwLock;
+use serde::Serialize;
+
+use crate::Block;
+use crate::constants::REORG_HISTORY;
+use crate::network_time::get_adjusted_timestamp;
+
+/// Replacement of the tip by a competing branch, which dropped blocks of the old chain.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Reorg {
+    /// Hash of latest block before the reorg
+    pub old_tip: String,
+
+    /// Index of latest block before the reorg
+    pub old_height: usize,
+
+    /// Hash of latest block after the reorg
+    pub new_tip: String,
+
+    /// Index of latest block after the reorg
+    pub new_height: usize,
+
+    /// Index of the last block both branches share
+    pub fork_height: usize,
+
+    /// Count of blocks dropped from the old chain
+    pub depth: usize,
+
+    /// Ids of transactions in dropped blocks which the new chain does not include
+    pub reverted_transactions: Vec<String>,
+
+    /// Adjusted time of the reorg
+    pub timestamp: usize,
+}
+
+/// Get reorg replacing old chain by new chain, none if new chain extends the old tip.
+pub fn get_reorg(old_blockchain: &[Block], new_blockchain: &[Block]) -> Option<Reorg> {
+    let old_tip = old_blockchain.last()?;
+    let new_tip = new_blockchain.last()?;
+    if new_blockchain.get(old_tip.index).map_or(false, |block| block.hash == old_tip.hash) {
+        return None;
+    }
+
+    let shared = old_blockchain
+        .iter()
+        .zip(new_blockchain)
+        .take_while(|(old, new)| old.hash == new.hash)
+        .count();
+    let dropped = &old_blockchain[shared..];
+    let reverted_transactions = dropped
+        .iter()
+        .flat_map(|block| block.data.iter())
+        .filter(|transaction| {
+            !new_blockchain[shared..]
+                .iter()
+                .any(|block| block.data.iter().any(|included| included.id == transaction.id))
+        })
+        .map(|transaction| transaction.id.clone())
+        .collect();
+
+    Some(Reorg {
+        old_tip: old_tip.hash.clone(),
+        old_height: old_tip.index,
+        new_tip: new_tip.hash.clone(),
+        new_height: new_tip.index,
+        fork_height: shared.saturating_sub(1),
+        depth: dropped.len(),
+        reverted_transactions,
+        timestamp: get_adjusted_timestamp(),
+    })
+}
+
+/// Bounded history of reorgs, latest first.
+#[derive(Debug, Default)]
+pub struct ReorgHistory {
+    reorgs: RwLock<VecDeque<Reorg>>,
+}
+
+impl ReorgHistory {
+    /// Record a reorg, forgetting the oldest past history size
+    pub fn record(&self, reorg: Reorg) {
+        tracing::info!(old_tip = %reorg.old_tip, new_tip = %reorg.new_tip, depth = reorg.depth, "chain reorganized");
+        let mut reorgs = self.reorgs.write().unwrap();
+        reorgs.push_front(reorg);
+        reorgs.truncate(REORG_HISTORY);
+    }
+
+    /// Get recorded reorgs, latest first
+    pub fn get_reorgs(&self) -> Vec<Reorg> {
+        self.reorgs.read().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{get_genesis_block, get_signed_transaction};
+    use super::*;
+
+    #[test]
+    fn test_get_reorg() {
+        let genesis_block = get_genesis_block();
+        let a1 = Block::generate(&vec![], &genesis_block, 0);
+        let a2 = Block::generate(&vec![get_signed_transaction()], &a1, 0);
+        let mut b1 = Block::generate(&vec![], &genesis_block, 0);
+        b1.nonce += 1;
+        b1.hash = b1.get_calculated_hash();
+        let b2 = Block::generate(&vec![], &b1, 0);
+        let b3 = Block::generate(&vec![], &b2, 0);
+
+        let old = vec![genesis_block.clone(), a1.clone(), a2.clone()];
+        assert_eq!(get_reorg(&old, &vec![genesis_block.clone(), a1.clone(), a2.clone(), b3.clone()]), None);
+
+        let reorg = get_reorg(&old, &vec![genesis_block.clone(), b1.clone(), b2, b3.clone()]).unwrap();
+        assert_eq!(reorg.old_tip, a2.hash);
+        assert_eq!(reorg.old_height, 2);
+        assert_eq!(reorg.new_tip, b3.hash);
+        assert_eq!(reorg.new_height, 3);
+        assert_eq!(reorg.fork_height, 0);
+        assert_eq!(reorg.depth, 2);
+        assert_eq!(reorg.reverted_transactions, vec![get_signed_transaction().id]);
+
+        let b2 = Block::generate(&vec![get_signed_transaction()], &b1, 0);
+        let reorg = get_reorg(&old, &vec![genesis_block, b1, b2]).unwrap();
+        assert!(reorg.reverted_transactions.is_empty());
+    }
+
+    #[test]
+    fn test_reorg_history() {
+        let reorg_history = ReorgHistory::default();
+        for depth in 0..REORG_HISTORY + 1 {
+            reorg_history.record(Reorg {
+                old_tip: "".to_string(),
+                old_height: 0,
+                new_tip: "".to_string(),
+                new_height: 0,
+                fork_height: 0,
+                depth,
+                reverted_transactions: vec![],
+                timestamp: 0,
+            });
+        }
+
+        let reorgs = reorg_history.get_reorgs();
+        assert_eq!(reorgs.len(), REORG_HISTORY);
+        assert_eq!(reorgs[0].depth, REORG_HISTORY);
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use rocket::Rocket;
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::local::Client;
+use tokio::sync::RwLock;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{BroadcastEvents, Transaction, UnspentTxOut, Wallet};
+use crate::address_index::AddressIndex;
+use crate::auth::ADMIN_TOKEN_HEADER;
+use crate::chain_state::ChainState;
+use crate::consensus_rules::ConsensusRules;
+use crate::errors::AppError;
+use crate::journal::{read_journal, Input};
+use crate::mempool_policy::MempoolPolicy;
+use crate::miner::Miner;
+use crate::socket::receive;
+use crate::sync_status::SyncTracker;
+use crate::validation_cache::ValidationCache;
+
+/// Feed inputs of journal at path back through `receive` and the http routes, one at a time in recorded order,
+/// and return how many were replayed.
+///
+/// A mining job queued by a request is waited for before the next input, so the chain only depends on the
+/// journal and the wallet. Blocks mined again get the timestamp of the replay.
+///
+/// # Errors
+/// If journal cannot be read, it returns error 7001.
+pub fn replay(
+    path: &str,
+    server: Rocket,
+    admin_token: Option<String>,
+    blockchain: &Arc<ChainState>,
+    unspent_tx_outs: &Arc<RwLock<Vec<UnspentTxOut>>>,
+    transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
+    wallet: &Arc<RwLock<Wallet>>,
+    address_index: &Arc<RwLock<AddressIndex>>,
+    validation_cache: &Arc<RwLock<ValidationCache>>,
+    sync_tracker: &Arc<SyncTracker>,
+    miner: &Arc<Miner>,
+    mempool_policy: MempoolPolicy,
+    consensus_rules: ConsensusRules,
+    broadcast_sender: UnboundedSender<BroadcastEvents>,
+) -> Result<usize, AppError> {
+    let entries = read_journal(path)?;
+    let client = Client::untracked(server).expect("Http server cannot be built");
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+
+    for entry in &entries {
+        match &entry.input {
+            Input::Payload { peer, payload } => {
+                println!("Replay message : {} {:?}", peer, payload.r#type);
+                let b = Arc::clone(blockchain);
+                let u = Arc::clone(unspent_tx_outs);
+                let t = Arc::clone(transaction_pool);
+                let w = Arc::clone(wallet);
+                let a = Arc::clone(address_index);
+                let v = Arc::clone(validation_cache);
+                let s = Arc::clone(sync_tracker);
+                runtime.block_on(receive(b, u, t, w, a, v, s, &mempool_policy, &consensus_rules, &broadcast_sender, peer.clone(), payload.clone()));
+            }
+            Input::Http { method, uri, body } => {
+                let method: Method = method.parse().map_err(|_| AppError::new(7001))?;
+                let mut request = client.req(method, uri.clone());
+                if !body.is_empty() {
+                    request = request.header(ContentType::JSON).body(body);
+                }
+                if let Some(admin_token) = &admin_token {
+                    request = request.header(Header::new(ADMIN_TOKEN_HEADER, admin_token.clone()));
+                }
+
+                let mut response = request.dispatch();
+                println!("Replay request : {} {} {}", method, uri, response.status());
+                if response.status() == Status::Accepted {
+                    let job = response.body_string()
+                        .and_then(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+                        .and_then(|job| job["id"].as_str().map(|id| id.to_string()));
+                    if let Some(id) = job {
+                        println!("Replay mining : {:?}", miner.wait_job(&id));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(entries.len())
+}
@@ -0,0 +1,71 @@
+use crate::Block;
+use crate::block::get_unspent_tx_outs;
+use crate::errors::AppError;
+use crate::transaction::{Transaction, UnspentTxOut};
+use crate::utxo_snapshot::get_is_pruned;
+
+/// Blockchain truncated to a height, with its unspent tx outs and the transactions of the blocks it drops.
+#[derive(Debug)]
+pub struct Rollback {
+    pub blockchain: Vec<Block>,
+    pub unspent_tx_outs: Vec<UnspentTxOut>,
+
+    /// Transactions of dropped blocks but coinbase transactions, in chain order, to return to the pool
+    pub evicted_transactions: Vec<Transaction>,
+}
+
+impl Rollback {
+    /// Returns rollback of blockchain to the block at height, replaying unspent tx outs up to it.
+    ///
+    /// # Errors
+    /// If height is above the tip, it returns error 1010.
+    /// If blocks up to height are headers of a utxo snapshot without data, it returns error 1011.
+    pub fn new(blockchain: &[Block], height: usize) -> Result<Rollback, AppError> {
+        if height >= blockchain.len() {
+            return Err(AppError::new(1010));
+        }
+        if get_is_pruned(&blockchain[..=height]) {
+            return Err(AppError::new(1011));
+        }
+
+        let new_blockchain = blockchain[..=height].to_vec();
+        let unspent_tx_outs = get_unspent_tx_outs(&new_blockchain)?;
+        let evicted_transactions = blockchain[height + 1..]
+            .iter()
+            .flat_map(|block| block.data.iter().skip(1).cloned())
+            .collect();
+
+        Ok(Rollback { blockchain: new_blockchain, unspent_tx_outs, evicted_transactions })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use crate::utxo_snapshot::get_header;
+    use crate::testkit::{get_genesis_block, get_signed_transaction, ALICE};
+    use crate::transaction::get_coinbase_transaction;
+    use super::*;
+
+    #[test]
+    fn test_rollback() {
+        let genesis_block = get_genesis_block();
+        let next = Block::generate(&vec![get_coinbase_transaction(ALICE.address, 1, None)], &genesis_block, 0);
+        let last = Block::generate(&vec![get_coinbase_transaction(ALICE.address, 2, None), get_signed_transaction()], &next, 0);
+        let blockchain = vec![genesis_block.clone(), next.clone(), last];
+
+        let rollback = Rollback::new(&blockchain, 0).unwrap();
+        assert_eq!(rollback.blockchain, vec![genesis_block.clone()]);
+        assert_eq!(rollback.unspent_tx_outs.len(), 1);
+        assert_eq!(rollback.evicted_transactions, vec![get_signed_transaction()]);
+
+        let rollback = Rollback::new(&blockchain[..2], 1).unwrap();
+        assert_eq!(rollback.blockchain, vec![genesis_block.clone(), next]);
+        assert_eq!(rollback.unspent_tx_outs.len(), 2);
+        assert!(rollback.evicted_transactions.is_empty());
+
+        assert_eq!(Rollback::new(&blockchain, 3).unwrap_err().code, 1010);
+        let headers = vec![get_header(&genesis_block), Block { data: Arc::new(vec![]), ..blockchain[1].clone() }];
+        assert_eq!(Rollback::new(&headers, 1).unwrap_err().code, 1011);
+    }
+}
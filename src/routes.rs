@@ -1,16 +1,353 @@
-use std::sync::{Arc, RwLock};
-use rocket::State;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::mem;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use chrono::Utc;
+use rocket::{Request, State};
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Content, Responder, Stream};
+use rocket::response::status::Accepted;
 use rocket_contrib::json::Json;
 
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use tokio::sync::RwLock;
 use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
 
 use crate::{Block, BroadcastEvents, UnspentTxOut, Wallet};
-use crate::block::{add_block};
-use crate::errors::{ApiError, FieldValidator};
-use crate::transaction::Transaction;
-use crate::transaction_pool::add_to_transaction_pool;
-use crate::wallet::{create_transaction, find_unspent_tx_outs, get_balance};
+use crate::address_index::{AddressBalance, AddressIndex, AddressSummary, DistributionBucket, OutpointLookup};
+use crate::audit::{audit_supply as get_supply_audit, SupplyAudit};
+use crate::auth::Admin;
+use crate::block::get_latest_block;
+use crate::chain_state::{ChainState, ChainWriter};
+use crate::chain_compare::{compare_chains, fetch_headers, parse_peer_url, ChainComparison};
+use crate::chain_params::get_chain_params;
+use crate::consensus::{check_consensus, ConsensusCheck};
+use crate::dto::{serialize_model, Model, TransactionDto, TxOutDto};
+use crate::constants::{
+    CONSENSUS_CHECK_WAIT_MILLIS, DEFAULT_ARRIVAL_LIMIT, DEFAULT_INVOICE_EXPIRY, DEFAULT_RICHEST_LIMIT, MAX_GENERATE_BLOCKS, MAX_HEADERS,
+    MAX_OUTPOINT_LOOKUP,
+};
+use crate::errors::{ApiError, AppError, FieldValidator};
+use crate::explorer::{BlockHeader, BlockSummary, get_block_headers, get_block_summary, get_transaction_summary, search as search_entity, SearchResult, TransactionSummary};
+use crate::invoice::{Invoice, InvoiceBook};
+use crate::labels::{LabelStore, LabelTarget, Labels};
+use crate::mempool_policy::MempoolPolicy;
+use crate::miner::{JobStatus, MineRequest, Miner, MinerJob};
+use crate::names::Registration;
+use crate::network_time::{get_adjusted_timestamp, NetworkClock};
+use crate::peers::{normalize_peer_address, normalize_peer_id, DisconnectedPeer, PeerInfo, PeerRegistry, PeerStats, PendingDial};
+use crate::pow::PowAlgorithm;
+use crate::price_feed::{FiatAmount, PriceFeed};
+use crate::propagation::BlockArrival;
+use crate::recorder::{Journaled, Parsed, Recorded, Validated};
+use crate::reorg::Reorg;
+use crate::rollback::Rollback;
+use crate::script::Script;
+use crate::signer::{get_verifier, Address as PublicKeyAddress};
+use crate::spend_policy::SpendPolicy;
+use crate::stratum::{Stratum, WorkerStats};
+use crate::sync_status::{SyncStatus, SyncTracker};
+use crate::tip_stream::{StreamFilter, TipNotifier, TipStream};
+use crate::transaction::{get_supply, BlockContext, Invalidity, Transaction, TransactionSigner};
+use crate::transaction_pool::{add_to_transaction_pool, check_admission, get_transaction_fee, trim_transaction_pool};
+use crate::transport::TransportKind;
+use crate::validation_cache::ValidationCache;
+use crate::wallet::{create_asset_transaction, create_issuance_transaction, create_payment_transaction, create_registration_transaction, create_transaction, filter_tx_pool_txs, import_wallet_key, KeySettings, PaymentUri, prepare_rotation, Rotation, select_tx_outs};
+use crate::webhooks::{Watch, WatchTarget, WebhookBook};
+
+/// Naming of fields of blocks, transactions and unspent tx outs in responses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldNaming {
+    /// snake_case of the structs hashed by consensus
+    Snake,
+
+    /// camelCase of typical blockchain apis and naivecoin
+    Camel,
+}
+
+impl FromStr for FieldNaming {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "snake" => Ok(FieldNaming::Snake),
+            "camel" => Ok(FieldNaming::Camel),
+            _ => Err(AppError::new(14000)),
+        }
+    }
+}
+
+/// Model served through a camelCase DTO when the api is configured so, and through its DTO otherwise.
+pub trait ApiModel: Model {
+    type Camel: Serialize;
+
+    fn to_camel(&self) -> Self::Camel;
+}
+
+impl<T: ApiModel> ApiModel for Vec<T> {
+    type Camel = Vec<T::Camel>;
+
+    fn to_camel(&self) -> Self::Camel {
+        self.iter().map(ApiModel::to_camel).collect()
+    }
+}
+
+/// Whether admins may roll back or invalidate blocks of the chain on main network, which they always may on regtest.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RollbackSettings {
+    pub allow_main: bool,
+}
+
+/// Layout of json of models in responses.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct JsonLayout {
+    /// Indent json unless asked otherwise with `?pretty=false`
+    pub pretty: bool,
+}
+
+/// Json of a model in the field naming the api is configured with.
+///
+/// `?fields=a,b` keeps only those fields of the model, or of every model of a list, and `?pretty=true` indents it.
+pub struct NamedJson<T>(pub T);
+
+impl<'r, T: ApiModel> Responder<'r> for NamedJson<T> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let options = match JsonOptions::from_request(request) {
+            Ok(options) => options,
+            Err(e) => return e.respond_to(request),
+        };
+        let body = options.to_string(&self.0).map_err(|_| Status::InternalServerError)?;
+        Content(ContentType::JSON, body).respond_to(request)
+    }
+}
+
+/// Json array of a snapshot of models, in the same naming and layout as `NamedJson`.
+///
+/// The body is streamed a model at a time, so a slow client holds the snapshot but no lock of the state.
+pub struct JsonSnapshot<T>(pub Arc<Vec<T>>);
+
+impl<'r, T: ApiModel + 'r> Responder<'r> for JsonSnapshot<T> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let options = match JsonOptions::from_request(request) {
+            Ok(options) => options,
+            Err(e) => return e.respond_to(request),
+        };
+        let reader = JsonArrayReader { models: self.0, options, next: 0, chunk: vec![], offset: 0, is_closed: false };
+        Content(ContentType::JSON, Stream::from(reader)).respond_to(request)
+    }
+}
+
+/// Naming, fields and layout of json a request asks for.
+struct JsonOptions {
+    field_naming: FieldNaming,
+    fields: Option<String>,
+    pretty: bool,
+}
+
+impl JsonOptions {
+    fn from_request(request: &Request) -> Result<JsonOptions, ApiError> {
+        let fields = match request.get_query_value::<String>("fields") {
+            Some(Ok(fields)) => Some(fields),
+            Some(Err(_)) => return Err(ApiError::new(400, "Fields are invalid, list them separated by commas.".to_string(), None)),
+            None => None,
+        };
+        let pretty = match request.get_query_value::<bool>("pretty") {
+            Some(Ok(pretty)) => pretty,
+            Some(Err(_)) => return Err(ApiError::new(400, "Pretty is invalid, use true or false.".to_string(), None)),
+            None => request.guard::<State<JsonLayout>>().succeeded().map_or(false, |layout| layout.pretty),
+        };
+        Ok(JsonOptions {
+            field_naming: request.guard::<State<FieldNaming>>().succeeded().map_or(FieldNaming::Snake, |field_naming| *field_naming),
+            fields,
+            pretty,
+        })
+    }
+
+    fn to_string<T: ApiModel>(&self, model: &T) -> serde_json::Result<String> {
+        let mut value = match self.field_naming {
+            FieldNaming::Camel => serde_json::to_value(model.to_camel())?,
+            FieldNaming::Snake => serde_json::to_value(model.to_dto())?,
+        };
+        if let Some(fields) = &self.fields {
+            select_fields(&mut value, &fields.split(',').collect::<Vec<&str>>());
+        }
+        match self.pretty {
+            true => serde_json::to_string_pretty(&value),
+            false => serde_json::to_string(&value),
+        }
+    }
+}
+
+/// Reader of a json array serializing its next model only once the previous one was read.
+struct JsonArrayReader<T> {
+    models: Arc<Vec<T>>,
+    options: JsonOptions,
+    next: usize,
+    chunk: Vec<u8>,
+    offset: usize,
+    is_closed: bool,
+}
+
+impl<T: ApiModel> JsonArrayReader<T> {
+    fn get_next_chunk(&mut self) -> io::Result<String> {
+        let model = match self.models.get(self.next) {
+            Some(model) => model,
+            None => {
+                self.is_closed = true;
+                let end = match (self.next, self.options.pretty) {
+                    (0, _) => "[]",
+                    (_, true) => "\n]",
+                    (_, false) => "]",
+                };
+                return Ok(end.to_string());
+            }
+        };
+        let json = self.options.to_string(model)?;
+        let separator = if self.next == 0 { "[" } else { "," };
+        self.next += 1;
+
+        // Models are indented one level deeper as items of the array, as `to_string_pretty` of the whole would.
+        match self.options.pretty {
+            true => Ok(format!("{}\n  {}", separator, json.replace('\n', "\n  "))),
+            false => Ok(format!("{}{}", separator, json)),
+        }
+    }
+}
+
+impl<T: ApiModel> Read for JsonArrayReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            if self.is_closed {
+                return Ok(0);
+            }
+            self.chunk = self.get_next_chunk()?.into_bytes();
+            self.offset = 0;
+        }
+        let size = buf.len().min(self.chunk.len() - self.offset);
+        buf[..size].copy_from_slice(&self.chunk[self.offset..self.offset + size]);
+        self.offset += size;
+        Ok(size)
+    }
+}
+
+/// Keep only fields of an object, or of every object of an array.
+fn select_fields(value: &mut Value, fields: &[&str]) {
+    match value {
+        Value::Array(values) => values.iter_mut().for_each(|value| select_fields(value, fields)),
+        Value::Object(object) => object.retain(|key, _| fields.contains(&key.as_str())),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CamelBlock {
+    pub version: u32,
+    pub pow: PowAlgorithm,
+    pub index: usize,
+    pub hash: String,
+    pub previous_hash: String,
+    pub timestamp: usize,
+    pub data: Vec<CamelTransaction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_root: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utxo_commitment: Option<String>,
+    pub difficulty: usize,
+    pub nonce: usize,
+}
+
+impl ApiModel for Block {
+    type Camel = CamelBlock;
+
+    fn to_camel(&self) -> CamelBlock {
+        CamelBlock {
+            version: self.version,
+            pow: self.pow,
+            index: self.index,
+            hash: self.hash.clone(),
+            previous_hash: self.previous_hash.clone(),
+            timestamp: self.timestamp,
+            data: self.data.iter().map(ApiModel::to_camel).collect(),
+            tx_root: self.tx_root.clone(),
+            utxo_commitment: self.utxo_commitment.clone(),
+            difficulty: self.difficulty,
+            nonce: self.nonce,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CamelTransaction {
+    pub version: u32,
+    pub id: String,
+    pub tx_ins: Vec<CamelTxIn>,
+
+    /// Outputs have no field to rename
+    pub tx_outs: Vec<TxOutDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CamelTxIn {
+    pub tx_out_id: String,
+    pub tx_out_index: usize,
+    pub signature: String,
+}
+
+impl ApiModel for Transaction {
+    type Camel = CamelTransaction;
+
+    fn to_camel(&self) -> CamelTransaction {
+        CamelTransaction {
+            version: self.version,
+            id: self.id.clone(),
+            tx_ins: self.tx_ins.iter().map(|tx_in| CamelTxIn {
+                tx_out_id: tx_in.tx_out_id.clone(),
+                tx_out_index: tx_in.tx_out_index,
+                signature: tx_in.signature.clone(),
+            }).collect(),
+            tx_outs: self.tx_outs.to_dto(),
+            extra_data: self.extra_data.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CamelUnspentTxOut {
+    pub tx_out_id: String,
+    pub tx_out_index: usize,
+    pub address: String,
+    pub amount: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+}
+
+impl ApiModel for UnspentTxOut {
+    type Camel = CamelUnspentTxOut;
+
+    fn to_camel(&self) -> CamelUnspentTxOut {
+        CamelUnspentTxOut {
+            tx_out_id: self.tx_out_id.clone(),
+            tx_out_index: self.tx_out_index,
+            address: self.address.clone(),
+            amount: self.amount,
+            script: self.script.clone(),
+            asset: self.asset.clone(),
+        }
+    }
+}
 
 #[get("/ping")]
 pub fn ping() -> &'static str {
@@ -19,60 +356,164 @@ pub fn ping() -> &'static str {
 
 #[get("/blocks")]
 pub fn blocks(
-    blockchain: State<Arc<RwLock<Vec<Block>>>>
-) -> Json<Vec<Block>> {
-    Json(blockchain.read().unwrap().to_vec())
+    blockchain: State<Arc<ChainState>>
+) -> JsonSnapshot<Block> {
+    JsonSnapshot(blockchain.load())
+}
+
+/// Get headers of blocks from height to height inclusive, up to `MAX_HEADERS` of them, to the tip by default.
+#[get("/headers?<from>&<to>")]
+pub fn headers(
+    from: Option<usize>,
+    to: Option<usize>,
+    blockchain: State<Arc<ChainState>>,
+) -> Result<Json<Vec<BlockHeader>>, ApiError> {
+    let b_snapshot = blockchain.load();
+    let from = from.unwrap_or(0);
+    let to = to.unwrap_or_else(|| from.saturating_add(MAX_HEADERS - 1).min(b_snapshot.len() - 1).max(from));
+    if from > to {
+        return Err(ApiError::new(400, "Range is invalid, from must not be above to.".to_string(), None));
+    }
+    if to - from >= MAX_HEADERS {
+        return Err(ApiError::new(400, format!("Range is too long, ask at most {} headers.", MAX_HEADERS), None));
+    }
+    Ok(Json(get_block_headers(&b_snapshot, from, to)))
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct NewBlock {
-    pub data: Option<Vec<Transaction>>,
+    pub data: Vec<TransactionDto>,
 }
 
 #[post("/mine-raw-block", format = "json", data = "<new_block>")]
 pub fn mine_raw_block(
-    new_block: Json<NewBlock>,
-    blockchain: State<Arc<RwLock<Vec<Block>>>>,
-    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
-    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
-    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
-) -> Result<Json<Block>, Json<ApiError>> {
-    let new_block = new_block.0;
-    let mut extractor = FieldValidator::validate(&new_block);
-    let data = extractor.extract("data", new_block.data);
-    extractor.check()?;
+    new_block: Result<Validated<NewBlock>, ApiError>,
+    miner: State<Arc<Miner>>,
+) -> Result<Accepted<Json<MinerJob>>, ApiError> {
+    let Validated(NewBlock { data }) = new_block?;
+    let data = Model::from_dto(data);
+
+    Ok(Accepted(Some(Json(miner.submit(MineRequest::Raw(data))))))
+}
+
+#[post("/mine-block?<address>")]
+pub fn mine_block(
+    address: Option<String>,
+    _recorded: Recorded,
+    miner: State<Arc<Miner>>,
+) -> Result<Accepted<Json<MinerJob>>, ApiError> {
+    submit_coinbase(address, &miner)
+}
+
+#[post("/miner/jobs?<address>")]
+pub fn create_miner_job(
+    address: Option<String>,
+    _recorded: Recorded,
+    miner: State<Arc<Miner>>,
+) -> Result<Accepted<Json<MinerJob>>, ApiError> {
+    submit_coinbase(address, &miner)
+}
 
-    let mut b_guard = blockchain.write().unwrap();
-    let mut u_guard = unspent_tx_outs.write().unwrap();
-    let mut t_guard = transaction_pool.write().unwrap();
-    let new_block = Block::generate_raw(&b_guard, &data);
-    if let Err(e) = add_block(&mut b_guard, &mut u_guard, &mut t_guard, &new_block) {
-        return Err(Json(ApiError::new(500, format!("Add block fail: {}", e.code), None)));
+/// Queue mining of a block with coinbase transaction and transaction pool, paying address if any
+fn submit_coinbase(address: Option<String>, miner: &Miner) -> Result<Accepted<Json<MinerJob>>, ApiError> {
+    if address.as_deref().map_or(false, |address| get_verifier(address).is_none()) {
+        return Err(ApiError::new(400, "Payout address is invalid.".to_string(), None));
     }
 
-    let _ = broadcast_sender.send(BroadcastEvents::Blockchain(b_guard.to_vec(), None));
-    Ok(Json(new_block))
+    Ok(Accepted(Some(Json(miner.submit(MineRequest::Coinbase { payout_address: address })))))
 }
 
-#[post("/mine-block")]
-pub fn mine_block(
-    blockchain: State<Arc<RwLock<Vec<Block>>>>,
-    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
-    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
-    wallet: State<Arc<RwLock<Wallet>>>,
-    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
-) -> Result<Json<Block>, Json<ApiError>> {
-    let mut b_guard = blockchain.write().unwrap();
-    let mut u_guard = unspent_tx_outs.write().unwrap();
-    let mut t_guard = transaction_pool.write().unwrap();
-    let w_guard = wallet.read().unwrap();
-    let new_block = Block::generate_with_coinbase_transaction(&b_guard, &t_guard, &w_guard);
-    if let Err(e) = add_block(&mut b_guard, &mut u_guard, &mut t_guard, &new_block) {
-        return Err(Json(ApiError::new(500, format!("Add block fail: {}", e.code), None)));
+/// Mine count blocks with coinbase transaction and transaction pool at once, paying address if any, on regtest only.
+#[post("/generate?<count>&<address>")]
+pub fn generate(
+    count: Option<usize>,
+    address: Option<String>,
+    _recorded: Recorded,
+    miner: State<Arc<Miner>>,
+) -> Result<NamedJson<Vec<Block>>, ApiError> {
+    if !get_chain_params().get_is_regtest() {
+        return Err(ApiError::new(404, "Generate is only available on regtest.".to_string(), None));
+    }
+    let count = count.unwrap_or(1);
+    if count == 0 || count > MAX_GENERATE_BLOCKS {
+        return Err(ApiError::new(400, format!("Count must be between 1 and {}.", MAX_GENERATE_BLOCKS), None));
     }
 
-    let _ = broadcast_sender.send(BroadcastEvents::Blockchain(b_guard.to_vec(), None));
-    Ok(Json(new_block))
+    let mut blocks = vec![];
+    for _ in 0..count {
+        let job = submit_coinbase(address.clone(), &miner)?.0.unwrap().0;
+        match miner.wait_job(&job.id).map(|job| job.status) {
+            Some(JobStatus::Mined { block }) => blocks.push(block),
+            Some(JobStatus::Failed { code }) => return Err(ApiError::from_app_error("Generate fail", &AppError::new(code))),
+            _ => return Err(ApiError::new(409, "Miner job was cancelled.".to_string(), None)),
+        }
+    }
+    Ok(NamedJson(blocks))
+}
+
+#[get("/miner/jobs/<id>")]
+pub fn miner_job(
+    id: String,
+    miner: State<Arc<Miner>>,
+) -> Result<Json<MinerJob>, ApiError> {
+    match miner.get_job(&id) {
+        Some(job) => Ok(Json(job)),
+        None => Err(ApiError::new(404, "Miner job was not found.".to_string(), None)),
+    }
+}
+
+#[delete("/miner/jobs/<id>")]
+pub fn cancel_miner_job(
+    id: String,
+    _recorded: Recorded,
+    miner: State<Arc<Miner>>,
+) -> Result<Json<MinerJob>, ApiError> {
+    match miner.cancel(&id) {
+        Some(job) => match job.status {
+            JobStatus::Mined { .. } | JobStatus::Failed { .. } => {
+                Err(ApiError::new(409, "Miner job is already finished.".to_string(), None))
+            }
+            _ => Ok(Json(job)),
+        },
+        None => Err(ApiError::new(404, "Miner job was not found.".to_string(), None)),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct MinerThrottle {
+    /// Most hashes tried per second by the miner, unlimited when none
+    #[validate(range(min = 1))]
+    pub max_hashrate: Option<usize>,
+}
+
+#[get("/miner/throttle")]
+pub fn miner_throttle(
+    miner: State<Arc<Miner>>,
+) -> Json<MinerThrottle> {
+    Json(MinerThrottle { max_hashrate: miner.get_max_hashrate() })
+}
+
+/// Cap hashes tried per second by the miner, applied to the job being mined too, lifted by a null cap.
+#[put("/miner/throttle", format = "json", data = "<throttle>")]
+pub fn set_miner_throttle(
+    throttle: Journaled<MinerThrottle>,
+    miner: State<Arc<Miner>>,
+) -> Result<Json<MinerThrottle>, ApiError> {
+    let throttle = throttle.0;
+    FieldValidator::validate(&throttle).check()?;
+
+    miner.set_max_hashrate(throttle.max_hashrate);
+    Ok(Json(MinerThrottle { max_hashrate: miner.get_max_hashrate() }))
+}
+
+#[get("/stratum/workers")]
+pub fn stratum_workers(
+    stratum: State<Option<Arc<Stratum>>>,
+) -> Result<Json<Vec<WorkerStats>>, ApiError> {
+    match stratum.as_ref() {
+        Some(stratum) => Ok(Json(stratum.get_workers())),
+        None => Err(ApiError::new(404, "Stratum is not enabled.".to_string(), None)),
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -82,7 +523,7 @@ pub struct Address {
 
 #[get("/address")]
 pub fn address(wallet: State<Arc<RwLock<Wallet>>>) -> Json<Address> {
-    let w_guard = wallet.read().unwrap();
+    let w_guard = wallet.blocking_read();
     Json(Address {
         public_key: w_guard.public_key.clone(),
     })
@@ -91,139 +532,1184 @@ pub fn address(wallet: State<Arc<RwLock<Wallet>>>) -> Json<Address> {
 #[derive(Debug, Serialize)]
 pub struct Balance {
     pub balance: usize,
+
+    /// Fiat equivalent of balance, when a price feed is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat: Option<FiatAmount>,
 }
 
 #[get("/balance")]
 pub fn balance(
     wallet: State<Arc<RwLock<Wallet>>>,
-    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+    price_feed: State<Option<Arc<PriceFeed>>>,
 ) -> Json<Balance> {
-    let w_guard = wallet.read().unwrap();
-    let u_guard = unspent_tx_outs.read().unwrap();
+    let w_guard = wallet.blocking_read();
+    let a_guard = address_index.blocking_read();
+    let balance = w_guard.get_addresses().iter().map(|address| a_guard.get_balance(address)).sum();
     Json(Balance {
-        balance: get_balance(w_guard.public_key.as_str(), &u_guard),
+        balance,
+        fiat: get_fiat_amount(&price_feed, balance),
     })
 }
 
+/// Get fiat equivalent of coins now, none without a price feed.
+fn get_fiat_amount(price_feed: &Option<Arc<PriceFeed>>, amount: usize) -> Option<FiatAmount> {
+    price_feed.as_ref()?.get_fiat_amount(amount, Utc::now().timestamp() as usize)
+}
+
 #[get("/unspent-transaction-outputs")]
 pub fn unspent_transaction_outputs(
     unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>
-) -> Json<Vec<UnspentTxOut>> {
-    let u_guard = unspent_tx_outs.read().unwrap();
-    Json(u_guard.to_vec())
+) -> JsonSnapshot<UnspentTxOut> {
+    JsonSnapshot(Arc::new(unspent_tx_outs.blocking_read().to_vec()))
 }
 
 #[get("/my-unspent-transaction-outputs")]
 pub fn my_unspent_transaction_outputs(
     wallet: State<Arc<RwLock<Wallet>>>,
-    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
-) -> Json<Vec<UnspentTxOut>> {
-    let w_guard = wallet.read().unwrap();
-    let u_guard = unspent_tx_outs.read().unwrap();
-    Json(find_unspent_tx_outs(w_guard.public_key.as_str(), &u_guard).to_vec())
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+) -> NamedJson<Vec<UnspentTxOut>> {
+    let w_guard = wallet.blocking_read();
+    let a_guard = address_index.blocking_read();
+    NamedJson(w_guard.get_addresses().iter().flat_map(|address| a_guard.get_unspent_tx_outs(address)).collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Outpoint {
+    pub tx_out_id: String,
+    pub tx_out_index: usize,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewOutpointLookup {
+    #[validate(length(min = 1, max = "MAX_OUTPOINT_LOOKUP"))]
+    pub outpoints: Vec<Outpoint>,
+}
+
+/// Get status of outpoints in order, unspent with their output or spent with the block spending them.
+#[post("/utxos/lookup", format = "json", data = "<lookup>")]
+pub fn lookup_utxos(
+    lookup: Result<Parsed<NewOutpointLookup>, ApiError>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+) -> Result<Json<Vec<OutpointLookup>>, ApiError> {
+    let Parsed(NewOutpointLookup { outpoints }) = lookup?;
+    let a_guard = address_index.blocking_read();
+    Ok(Json(outpoints.iter().map(|outpoint| a_guard.lookup(&outpoint.tx_out_id, outpoint.tx_out_index)).collect()))
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct NewTransaction {
     #[validate(length(min = 1))]
-    pub address: Option<String>,
+    pub address: String,
 
-    #[validate(range(min = 0))]
-    pub amount: Option<usize>,
+    #[validate(range(min = 1))]
+    pub amount: usize,
+
+    /// Whether address may be of the wallet itself
+    #[serde(default)]
+    pub allow_self_send: bool,
+}
+
+/// Get address of a payment at least the dust limit and at most the supply issued up to the tip of blockchain, to
+/// another wallet unless allowed.
+///
+/// # Errors
+/// If address is not a public key, it returns error 400, if the payment is invalid error 422.
+fn check_new_transaction(
+    new_transaction: &NewTransaction,
+    wallet: &Wallet,
+    blockchain: &ChainState,
+    dust_limit: usize,
+) -> Result<PublicKeyAddress, ApiError> {
+    let address: PublicKeyAddress = new_transaction.address.parse()
+        .map_err(|e| ApiError::from_app_error("Address is invalid", &e))?;
+
+    let mut validator = FieldValidator::default();
+    if new_transaction.amount < dust_limit {
+        validator.add("amount", "dust");
+    }
+    if new_transaction.amount > get_supply(get_latest_block(&blockchain.load()).index) {
+        validator.add("amount", "max_supply");
+    }
+    let is_self_send = wallet.get_addresses().iter()
+        .any(|own| own.parse::<PublicKeyAddress>().map_or(false, |own| own == address));
+    if !new_transaction.allow_self_send && is_self_send {
+        validator.add("address", "self_send");
+    }
+    validator.check().map(|_| address)
 }
 
 #[post("/mine-transaction", format = "json", data = "<new_transaction>")]
 pub fn mine_transaction(
-    new_transaction: Json<NewTransaction>,
-    blockchain: State<Arc<RwLock<Vec<Block>>>>,
-    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
-    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    new_transaction: Result<Validated<NewTransaction>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
     wallet: State<Arc<RwLock<Wallet>>>,
-    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
-) -> Result<Json<Block>, Json<ApiError>> {
-    let new_transaction = new_transaction.0;
-    let mut extractor = FieldValidator::validate(&new_transaction);
-    let address = extractor.extract("address", new_transaction.address);
-    let amount = extractor.extract("amount", new_transaction.amount);
-    extractor.check()?;
-
-    let mut b_guard = blockchain.write().unwrap();
-    let mut u_guard = unspent_tx_outs.write().unwrap();
-    let mut t_guard = transaction_pool.write().unwrap();
-    let w_guard = wallet.read().unwrap();
-
-    return match Block::generate_with_transaction(&b_guard, &w_guard, &u_guard, &address, amount) {
-        Ok(new_block) => {
-            if let Err(e) = add_block(&mut b_guard, &mut u_guard, &mut t_guard, &new_block) {
-                return Err(Json(ApiError::new(500, format!("Add block fail: {}", e.code), None)));
-            }
-            let _ = broadcast_sender.send(BroadcastEvents::Blockchain(b_guard.to_vec(), None));
-            Ok(Json(new_block))
-        }
-        Err(e) => {
-            Err(Json(ApiError::new(500, format!("Add block fail: {}", e.code), None)))
-        }
-    };
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+    miner: State<Arc<Miner>>,
+) -> Result<Accepted<Json<MinerJob>>, ApiError> {
+    let Validated(new_transaction) = new_transaction?;
+    let address = String::from(check_new_transaction(&new_transaction, &wallet.blocking_read(), &blockchain, mempool_policy.dust_limit)?);
+    let amount = new_transaction.amount;
+
+    // The payment counts toward the daily limit once queued, even if mining it fails.
+    if !wallet.blocking_read().get_addresses().contains(&address) {
+        spend_policy.try_spend_payment(&address, amount, get_adjusted_timestamp())
+            .map_err(ApiError::from_violation)?;
+    }
+
+    Ok(Accepted(Some(Json(miner.submit(MineRequest::Transaction { address, amount })))))
 }
 
 #[post("/send-transaction", format = "json", data = "<new_transaction>")]
 pub fn send_transaction(
-    new_transaction: Json<NewTransaction>,
+    new_transaction: Result<Validated<NewTransaction>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
     transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
     unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
     wallet: State<Arc<RwLock<Wallet>>>,
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
     broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
-) -> Result<Json<Transaction>, Json<ApiError>> {
-    let new_transaction = new_transaction.0;
-    let mut extractor = FieldValidator::validate(&new_transaction);
-    let address = extractor.extract("address", new_transaction.address);
-    let amount = extractor.extract("amount", new_transaction.amount);
-    extractor.check()?;
-
-    let mut t_guard = transaction_pool.write().unwrap();
-    let u_guard = unspent_tx_outs.write().unwrap();
-    let w_guard = wallet.read().unwrap();
-
-    return match create_transaction(&address, amount, &w_guard, &u_guard) {
+) -> Result<NamedJson<Transaction>, ApiError> {
+    let Validated(new_transaction) = new_transaction?;
+
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_write();
+    let w_guard = wallet.blocking_read();
+
+    let address = check_new_transaction(&new_transaction, &w_guard, &blockchain, mempool_policy.dust_limit)?;
+    let tx = create_transaction(address.as_str(), new_transaction.amount, &*w_guard, &u_guard, mempool_policy.dust_limit);
+    submit_transaction(tx, &blockchain, &w_guard, &spend_policy, &mut t_guard, &u_guard, &mempool_policy, &validation_cache, &broadcast_sender)
+}
+
+/// Get context of the block pooled transactions are validated for, the next one on top of blockchain.
+fn get_pool_context(blockchain: &ChainState) -> BlockContext {
+    BlockContext::next(get_latest_block(&blockchain.load()), &NetworkClock)
+}
+
+/// Add a created transaction approved by spend policy to the pool and broadcast the pool.
+fn submit_transaction(
+    tx: Result<Transaction, AppError>,
+    blockchain: &ChainState,
+    wallet: &Wallet,
+    spend_policy: &SpendPolicy,
+    transaction_pool: &mut Vec<Transaction>,
+    unspent_tx_outs: &Vec<UnspentTxOut>,
+    mempool_policy: &MempoolPolicy,
+    validation_cache: &RwLock<ValidationCache>,
+    broadcast_sender: &UnboundedSender<BroadcastEvents>,
+) -> Result<NamedJson<Transaction>, ApiError> {
+    return match tx {
         Ok(tx) => {
-            match add_to_transaction_pool(&tx, &mut t_guard, &u_guard) {
+            let timestamp = get_adjusted_timestamp();
+            let amount = spend_policy.try_spend(&tx, &wallet.get_addresses(), timestamp)
+                .map_err(ApiError::from_violation)?;
+            match add_to_transaction_pool(&tx, transaction_pool, unspent_tx_outs, get_pool_context(blockchain), mempool_policy, &mut validation_cache.blocking_write()) {
                 Ok(_) => {
-                    let _ = broadcast_sender.send(BroadcastEvents::Transaction(t_guard.to_vec(), None));
-                    Ok(Json(tx))
+                    let _ = broadcast_sender.send(BroadcastEvents::Transaction(transaction_pool.to_vec(), None));
+                    Ok(NamedJson(tx))
+                }
+                Err(e) => {
+                    spend_policy.refund(amount, timestamp);
+                    Err(ApiError::from_app_error("Add transaction pool fail", &e))
                 }
-                Err(e) => Err(Json(ApiError::new(500, format!("Add transaction pool fail: {}", e.code), None)))
             }
         }
         Err(e) => {
-            Err(Json(ApiError::new(500, format!("Add transaction pool fail: {}", e.code), None)))
+            Err(ApiError::from_app_error("Add transaction pool fail", &e))
         }
     };
 }
 
+/// Whether the wallet can send an amount now, with the outputs it would spend.
+#[derive(Debug, Serialize)]
+pub struct SpendPreflight {
+    pub is_possible: bool,
+    pub amount: usize,
+
+    /// Outputs the transaction would spend, empty if coins do not cover the amount
+    #[serde(serialize_with = "serialize_model")]
+    pub tx_outs: Vec<UnspentTxOut>,
+
+    /// Coins paid back to the wallet
+    pub change: usize,
+
+    /// Coins left to miners, none as wallet transactions spend inputs in full
+    pub fee: usize,
+
+    /// Why sending would fail
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+/// Check sending amount to address like `send-transaction`, without creating or broadcasting a transaction.
+#[post("/can-send", format = "json", data = "<new_transaction>")]
+pub fn can_send(
+    new_transaction: Result<Validated<NewTransaction>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+) -> Result<Json<SpendPreflight>, ApiError> {
+    let Validated(new_transaction) = new_transaction?;
+
+    let t_guard = transaction_pool.blocking_read();
+    let u_guard = unspent_tx_outs.blocking_read();
+    let w_guard = wallet.blocking_read();
+
+    let address = String::from(check_new_transaction(&new_transaction, &w_guard, &blockchain, mempool_policy.dust_limit)?);
+    let amount = new_transaction.amount;
+
+    // Selection ignores the pool like `send-transaction`, so outputs a pooled transaction spends make the send fail.
+    let selection = select_tx_outs(None, amount, &*w_guard, &u_guard, mempool_policy.dust_limit)
+        .and_then(|selection| match filter_tx_pool_txs(&selection.tx_outs, &t_guard).len() == selection.tx_outs.len() {
+            true => Ok(selection),
+            false => Err(AppError::new(4001)),
+        });
+    let preflight = match selection {
+        Ok(selection) => {
+            let error = match w_guard.get_addresses().contains(&address) {
+                true => None,
+                false => spend_policy.check_payment(&address, amount, get_adjusted_timestamp()).err().map(ApiError::from_violation),
+            };
+            SpendPreflight { is_possible: error.is_none(), amount, tx_outs: selection.tx_outs, change: selection.change, fee: 0, error }
+        }
+        Err(e) => SpendPreflight {
+            is_possible: false,
+            amount,
+            tx_outs: vec![],
+            change: 0,
+            fee: 0,
+            error: Some(ApiError::from_app_error("Add transaction pool fail", &e)),
+        },
+    };
+    Ok(Json(preflight))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewTestTransaction {
+    pub transaction: TransactionDto,
+}
+
+/// Verdict of pool admission on a transaction.
+#[derive(Debug, Serialize)]
+pub struct TransactionTest {
+    pub tx_id: String,
+    pub is_allowed: bool,
+
+    /// Coins left to miners, counting inputs found among unspent tx outs only
+    pub fee: usize,
+
+    /// Consensus rule the transaction breaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalidity: Option<Invalidity>,
+
+    /// Why the pool would reject the transaction, every check it fails in order
+    pub reasons: Vec<ApiError>,
+}
+
+/// Run the checks of pool admission on a signed transaction, without adding it to the pool or broadcasting it.
+#[post("/test-transaction", format = "json", data = "<new_transaction>")]
+pub fn test_transaction(
+    new_transaction: Result<Parsed<NewTestTransaction>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+) -> Result<Json<TransactionTest>, ApiError> {
+    let Parsed(NewTestTransaction { transaction }) = new_transaction?;
+    let tx = Transaction::from_dto(transaction);
+
+    let t_guard = transaction_pool.blocking_read();
+    let u_guard = unspent_tx_outs.blocking_read();
+    let admission = check_admission(&tx, &t_guard, &u_guard, get_pool_context(&blockchain), &mempool_policy, &mut validation_cache.blocking_write());
+    Ok(Json(TransactionTest {
+        is_allowed: admission.errors.is_empty(),
+        fee: get_transaction_fee(&tx, &u_guard),
+        invalidity: admission.invalidity,
+        reasons: admission.errors.iter().map(|e| ApiError::from_app_error("Add transaction pool fail", e)).collect(),
+        tx_id: tx.id,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssetBalances {
+    pub assets: BTreeMap<String, usize>,
+}
+
+#[get("/assets")]
+pub fn assets(
+    wallet: State<Arc<RwLock<Wallet>>>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+) -> Json<AssetBalances> {
+    let w_guard = wallet.blocking_read();
+    let a_guard = address_index.blocking_read();
+    let assets = w_guard.get_addresses()
+        .iter()
+        .flat_map(|address| a_guard.get_asset_balances(address))
+        .fold(BTreeMap::new(), |mut assets, (asset, balance)| {
+            *assets.entry(asset).or_insert(0) += balance;
+            assets
+        });
+    Json(AssetBalances { assets })
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewAssetTransaction {
+    #[validate(length(min = 1))]
+    pub address: String,
+
+    #[validate(length(min = 1))]
+    pub asset: String,
+
+    #[validate(range(min = 0))]
+    pub amount: usize,
+}
+
+#[post("/send-asset", format = "json", data = "<new_transaction>")]
+pub fn send_asset(
+    new_transaction: Result<Validated<NewAssetTransaction>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<NamedJson<Transaction>, ApiError> {
+    let Validated(NewAssetTransaction { address, asset, amount }) = new_transaction?;
+    let address: PublicKeyAddress = address.parse().map_err(|e| ApiError::from_app_error("Address is invalid", &e))?;
+
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_write();
+    let w_guard = wallet.blocking_read();
+
+    let tx = create_asset_transaction(address.as_str(), Some(&asset), amount, &*w_guard, &u_guard, mempool_policy.dust_limit);
+    submit_transaction(tx, &blockchain, &w_guard, &spend_policy, &mut t_guard, &u_guard, &mempool_policy, &validation_cache, &broadcast_sender)
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewIssuance {
+    #[validate(length(min = 1))]
+    pub symbol: String,
+
+    #[validate(range(min = 0))]
+    pub amount: usize,
+}
+
+#[post("/issue-asset", format = "json", data = "<new_issuance>")]
+pub fn issue_asset(
+    new_issuance: Result<Validated<NewIssuance>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<NamedJson<Transaction>, ApiError> {
+    let Validated(NewIssuance { symbol, amount }) = new_issuance?;
+
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_write();
+    let w_guard = wallet.blocking_read();
+
+    let tx = create_issuance_transaction(&symbol, amount, &*w_guard, &u_guard, mempool_policy.dust_limit);
+    submit_transaction(tx, &blockchain, &w_guard, &spend_policy, &mut t_guard, &u_guard, &mempool_policy, &validation_cache, &broadcast_sender)
+}
+
+#[get("/names/<name>")]
+pub fn name(
+    name: String,
+    blockchain: State<Arc<ChainState>>,
+) -> Result<Json<Registration>, ApiError> {
+    match blockchain.read_chain_index().name_registry.get(&name) {
+        Some(registration) => Ok(Json(registration.clone())),
+        None => Err(ApiError::new(404, "Name was not found.".to_string(), None)),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewName {
+    #[validate(length(min = 1))]
+    pub name: String,
+
+    /// Address the name points to, the wallet address when none
+    pub address: Option<String>,
+}
+
+#[post("/names", format = "json", data = "<new_name>")]
+pub fn register_name(
+    new_name: Result<Validated<NewName>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<NamedJson<Transaction>, ApiError> {
+    let Validated(new_name) = new_name?;
+    let name = new_name.name;
+
+    if blockchain.read_chain_index().name_registry.get(&name).is_some() {
+        return Err(ApiError::new(409, "Name is already registered.".to_string(), None));
+    }
+
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_write();
+    let w_guard = wallet.blocking_read();
+    let address = new_name.address.unwrap_or_else(|| w_guard.public_key.clone());
+
+    let tx = create_registration_transaction(&name, &address, &*w_guard, &u_guard);
+    submit_transaction(tx, &blockchain, &w_guard, &spend_policy, &mut t_guard, &u_guard, &mempool_policy, &validation_cache, &broadcast_sender)
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewPaymentUri {
+    /// Payment uri such as `blockchain:<address>?amount=10&memo=coffee`
+    #[validate(length(min = 1))]
+    pub uri: String,
+}
+
+#[post("/pay-uri", format = "json", data = "<new_payment_uri>")]
+pub fn pay_uri(
+    new_payment_uri: Result<Validated<NewPaymentUri>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    spend_policy: State<Arc<SpendPolicy>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<NamedJson<Transaction>, ApiError> {
+    let Validated(NewPaymentUri { uri }) = new_payment_uri?;
+
+    let payment_uri: PaymentUri = uri.parse()
+        .map_err(|e: AppError| ApiError::from_app_error("Payment uri is invalid", &e))?;
+
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_write();
+    let w_guard = wallet.blocking_read();
+
+    let tx = create_payment_transaction(&payment_uri, &*w_guard, &u_guard, mempool_policy.dust_limit);
+    submit_transaction(tx, &blockchain, &w_guard, &spend_policy, &mut t_guard, &u_guard, &mempool_policy, &validation_cache, &broadcast_sender)
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewInvoice {
+    #[validate(range(min = 1))]
+    pub amount: usize,
+
+    pub memo: Option<String>,
+
+    /// Seconds until the invoice expires, an hour when none
+    pub expires_in: Option<usize>,
+}
+
+#[post("/invoices", format = "json", data = "<new_invoice>")]
+pub fn create_invoice(
+    new_invoice: Result<Validated<NewInvoice>, ApiError>,
+    key_settings: State<KeySettings>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    invoices: State<Arc<InvoiceBook>>,
+    mempool_policy: State<MempoolPolicy>,
+) -> Result<Json<Invoice>, ApiError> {
+    let Validated(new_invoice) = new_invoice?;
+    let amount = new_invoice.amount;
+
+    let mut w_guard = wallet.blocking_write();
+    let expires_in = new_invoice.expires_in.unwrap_or(DEFAULT_INVOICE_EXPIRY);
+    match invoices.create(&mut w_guard, &key_settings.private_key_path, amount, new_invoice.memo.as_deref(), get_adjusted_timestamp(), expires_in, mempool_policy.dust_limit) {
+        Ok(invoice) => Ok(Json(invoice)),
+        Err(e) => Err(ApiError::from_app_error("Invoice is invalid", &e)),
+    }
+}
+
+#[get("/invoices/<id>")]
+pub fn invoice(
+    id: String,
+    invoices: State<Arc<InvoiceBook>>,
+) -> Result<Json<Invoice>, ApiError> {
+    match invoices.get(&id, get_adjusted_timestamp()) {
+        Some(invoice) => Ok(Json(invoice)),
+        None => Err(ApiError::new(404, "Invoice was not found.".to_string(), None)),
+    }
+}
+
 #[get("/transaction-pool")]
 pub fn transaction_pool(
     transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
-) -> Json<Vec<Transaction>> {
-    let t_guard = transaction_pool.read().unwrap();
-    Json(t_guard.to_vec())
+) -> JsonSnapshot<Transaction> {
+    JsonSnapshot(Arc::new(transaction_pool.blocking_read().to_vec()))
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct NewPeer {
     #[validate(length(min = 1))]
-    pub peer: Option<String>,
+    pub peer: String,
+}
+
+/// Status of a peer given to add, connected peers are not dialed again.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerStatus {
+    Connected,
+    Dialing,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddedPeer {
+    /// Id the peer is known by, its normalized address
+    pub peer: String,
+    pub status: PeerStatus,
 }
 
 #[post("/add-peer", format = "json", data = "<new_peer>")]
 pub fn add_peer(
-    new_peer: Json<NewPeer>,
+    new_peer: Result<Validated<NewPeer>, ApiError>,
+    transport: State<TransportKind>,
+    peer_registry: State<Arc<PeerRegistry>>,
     broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
-) -> Result<&'static str, Json<ApiError>> {
-    let new_peer = new_peer.0;
-    let mut extractor = FieldValidator::validate(&new_peer);
-    let peer = extractor.extract("peer", new_peer.peer);
-    extractor.check()?;
+) -> Result<Json<AddedPeer>, ApiError> {
+    let Validated(NewPeer { peer }) = new_peer?;
+
+    let peer = normalize_peer_address(&peer)
+        .map_err(|e| ApiError::from_app_error("Peer address is invalid", &e))?;
+    if !transport.get_is_dialable(&peer) {
+        return Err(ApiError::new(400, "Peer address cannot be dialed with the transport.".to_string(), None));
+    }
+    if peer_registry.get_peer(&peer).is_some() {
+        return Ok(Json(AddedPeer { peer, status: PeerStatus::Connected }));
+    }
 
-    let _ = broadcast_sender.send(BroadcastEvents::Peer(peer));
+    let _ = broadcast_sender.send(BroadcastEvents::Peer(peer.clone()));
+    Ok(Json(AddedPeer { peer, status: PeerStatus::Dialing }))
+}
+
+/// Connected peers, with dials which are not up if asked for.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Peers {
+    Connected(Vec<PeerInfo>),
+    WithPending {
+        peers: Vec<PeerInfo>,
+        pending: Vec<PendingDial>,
+    },
+}
+
+/// Get connected peers, with pending dials and their last error given `include=pending`.
+#[get("/peers?<include>")]
+pub fn peers(
+    include: Option<String>,
+    peer_registry: State<Arc<PeerRegistry>>,
+) -> Result<Json<Peers>, ApiError> {
+    match include.as_deref() {
+        None => Ok(Json(Peers::Connected(peer_registry.get_peers()))),
+        Some("pending") => Ok(Json(Peers::WithPending {
+            peers: peer_registry.get_peers(),
+            pending: peer_registry.get_dials(),
+        })),
+        Some(_) => Err(ApiError::new(400, "Include is invalid, only pending is supported.".to_string(), None)),
+    }
+}
+
+#[get("/peers/history")]
+pub fn peer_history(peer_registry: State<Arc<PeerRegistry>>) -> Json<Vec<DisconnectedPeer>> {
+    Json(peer_registry.get_history())
+}
+
+/// Get traffic stats of peer, whose id is percent encoded.
+#[get("/peers/<id>/stats")]
+pub fn peer_stats(
+    id: String,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<Json<PeerStats>, ApiError> {
+    let (sender, receiver) = oneshot::channel();
+    let _ = broadcast_sender.send(BroadcastEvents::Stats(normalize_peer_id(id), sender));
+    match receiver.blocking_recv() {
+        Ok(Some(stats)) => Ok(Json(stats)),
+        _ => Err(ApiError::new(404, "Peer was not found.".to_string(), None)),
+    }
+}
+
+/// Close connection to peer, whose id is percent encoded.
+#[delete("/peers/<id>")]
+pub fn disconnect_peer(
+    _admin: Admin,
+    _recorded: Recorded,
+    id: String,
+    peer_registry: State<Arc<PeerRegistry>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<&'static str, ApiError> {
+    let id = normalize_peer_id(id);
+    if !peer_registry.get_peers().iter().any(|peer| peer.peer.eq(&id)) {
+        return Err(ApiError::new(404, "Peer was not found.".to_string(), None));
+    }
+
+    let _ = broadcast_sender.send(BroadcastEvents::Disconnect(id, "Disconnected by operator".to_string()));
     Ok("ok")
 }
+
+#[derive(Debug, Serialize)]
+pub struct AddressStats {
+    pub address_count: usize,
+    pub total_balance: usize,
+    pub richest: Vec<AddressBalance>,
+    pub distribution: Vec<DistributionBucket>,
+}
+
+#[get("/stats/addresses?<limit>")]
+pub fn address_stats(
+    limit: Option<usize>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+) -> Json<AddressStats> {
+    let a_guard = address_index.blocking_read();
+    Json(AddressStats {
+        address_count: a_guard.get_address_count(),
+        total_balance: a_guard.get_total_balance(),
+        richest: a_guard.get_richest(limit.unwrap_or(DEFAULT_RICHEST_LIMIT)),
+        distribution: a_guard.get_distribution(),
+    })
+}
+
+/// Compare the chain with the chain of a peer, whose headers are fetched from its http api at url, admin only as the
+/// node dials the url.
+#[get("/debug/compare?<peer>")]
+pub fn debug_compare(
+    _admin: Admin,
+    peer: String,
+    blockchain: State<Arc<ChainState>>,
+) -> Result<Json<ChainComparison>, ApiError> {
+    let url = parse_peer_url(&peer).map_err(|e| ApiError::from_app_error("Compare chains fail", &e))?;
+    let peer_headers = fetch_headers(&url).map_err(|e| ApiError::from_app_error("Compare chains fail", &e))?;
+    Ok(Json(compare_chains(&peer, &blockchain.load(), &peer_headers)))
+}
+
+/// Get reorgs of the blockchain, latest first.
+#[get("/reorgs")]
+pub fn reorgs(blockchain: State<Arc<ChainState>>) -> Json<Vec<Reorg>> {
+    Json(blockchain.get_reorgs())
+}
+
+/// Audit coins of the blockchain against the emission schedule.
+#[get("/audit/supply")]
+pub fn audit_supply(blockchain: State<Arc<ChainState>>) -> Json<SupplyAudit> {
+    let b_snapshot = blockchain.load();
+    Json(get_supply_audit(&b_snapshot))
+}
+
+#[get("/explorer/block/<hash>")]
+pub fn explorer_block(
+    hash: String,
+    blockchain: State<Arc<ChainState>>,
+) -> Result<Json<BlockSummary>, ApiError> {
+    let b_snapshot = blockchain.load();
+    match get_block_summary(&b_snapshot, &hash) {
+        Some(summary) => Ok(Json(BlockSummary {
+            arrival: blockchain.get_arrival(&hash),
+            ..summary
+        })),
+        None => Err(ApiError::new(404, "Block was not found.".to_string(), None)),
+    }
+}
+
+/// Get arrivals of blocks at this node, latest first, to study propagation delay.
+#[get("/explorer/propagation?<limit>")]
+pub fn explorer_propagation(
+    limit: Option<usize>,
+    blockchain: State<Arc<ChainState>>,
+) -> Json<Vec<BlockArrival>> {
+    Json(blockchain.get_arrivals(limit.unwrap_or(DEFAULT_ARRIVAL_LIMIT)))
+}
+
+#[get("/explorer/transaction/<id>")]
+pub fn explorer_transaction(
+    id: String,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    label_store: State<Arc<LabelStore>>,
+    price_feed: State<Option<Arc<PriceFeed>>>,
+) -> Result<Json<TransactionSummary>, ApiError> {
+    let b_snapshot = blockchain.load();
+    let t_guard = transaction_pool.blocking_read();
+    match get_transaction_summary(&b_snapshot, &t_guard, &id) {
+        Some(summary) => {
+            let value = summary.transaction.tx_outs
+                .iter()
+                .filter(|tx_out| tx_out.asset.is_none())
+                .map(|tx_out| tx_out.amount)
+                .sum();
+            Ok(Json(TransactionSummary {
+                label: label_store.get(LabelTarget::Transaction, &id),
+                fiat: get_fiat_amount(&price_feed, value),
+                ..summary
+            }))
+        }
+        None => Err(ApiError::new(404, "Transaction was not found.".to_string(), None)),
+    }
+}
+
+#[get("/explorer/address/<address>")]
+pub fn explorer_address(
+    address: String,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+    label_store: State<Arc<LabelStore>>,
+    price_feed: State<Option<Arc<PriceFeed>>>,
+) -> Json<AddressSummary> {
+    let summary = address_index.blocking_read().get_summary(&address);
+    Json(AddressSummary {
+        label: label_store.get(LabelTarget::Address, &address),
+        fiat: get_fiat_amount(&price_feed, summary.balance),
+        ..summary
+    })
+}
+
+/// Get local labels of transactions and addresses.
+#[get("/labels")]
+pub fn labels(label_store: State<Arc<LabelStore>>) -> Json<Labels> {
+    Json(label_store.get_labels())
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewLabel {
+    #[validate(length(min = 1, max = 256))]
+    pub label: String,
+}
+
+/// Label a transaction or an address, `target` being `transaction` or `address`.
+#[put("/labels/<target>/<id>", format = "json", data = "<new_label>")]
+pub fn set_label(
+    target: String,
+    id: String,
+    new_label: Result<Validated<NewLabel>, ApiError>,
+    label_store: State<Arc<LabelStore>>,
+) -> Result<Json<Labels>, ApiError> {
+    let target: LabelTarget = target.parse()
+        .map_err(|_| ApiError::new(404, "Label target was not found.".to_string(), None))?;
+    let Validated(NewLabel { label }) = new_label?;
+
+    label_store.set(target, &id, Some(&label))
+        .map_err(|e| ApiError::from_app_error("Set label fail", &e))?;
+    Ok(Json(label_store.get_labels()))
+}
+
+#[delete("/labels/<target>/<id>")]
+pub fn delete_label(
+    target: String,
+    id: String,
+    _recorded: Recorded,
+    label_store: State<Arc<LabelStore>>,
+) -> Result<Json<Labels>, ApiError> {
+    let target: LabelTarget = target.parse()
+        .map_err(|_| ApiError::new(404, "Label target was not found.".to_string(), None))?;
+    if label_store.get(target, &id).is_none() {
+        return Err(ApiError::new(404, "Label was not found.".to_string(), None));
+    }
+
+    label_store.set(target, &id, None)
+        .map_err(|e| ApiError::from_app_error("Delete label fail", &e))?;
+    Ok(Json(label_store.get_labels()))
+}
+
+#[get("/webhooks")]
+pub fn webhooks(
+    webhooks: State<Option<Arc<WebhookBook>>>,
+) -> Result<Json<Vec<Watch>>, ApiError> {
+    match webhooks.as_ref() {
+        Some(webhooks) => Ok(Json(webhooks.get_watches())),
+        None => Err(ApiError::new(404, "Webhooks are not enabled.".to_string(), None)),
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NewWatch {
+    /// Id of a transaction, instead of address
+    pub transaction: Option<String>,
+
+    /// Address whose transactions are watched, instead of transaction
+    pub address: Option<String>,
+
+    #[validate(range(min = 1))]
+    pub confirmations: usize,
+}
+
+/// Watch a transaction or the transactions of an address, calling back the webhook url at confirmations.
+#[post("/webhooks", format = "json", data = "<new_watch>")]
+pub fn create_webhook(
+    new_watch: Result<Validated<NewWatch>, ApiError>,
+    blockchain: State<Arc<ChainState>>,
+    webhooks: State<Option<Arc<WebhookBook>>>,
+) -> Result<Json<Watch>, ApiError> {
+    let webhooks = webhooks.as_ref()
+        .ok_or_else(|| ApiError::new(404, "Webhooks are not enabled.".to_string(), None))?;
+    let Validated(new_watch) = new_watch?;
+    let confirmations = new_watch.confirmations;
+
+    let target = match (new_watch.transaction, new_watch.address) {
+        (Some(id), None) => WatchTarget::Transaction(id),
+        (None, Some(address)) => WatchTarget::Address(address),
+        _ => return Err(ApiError::from_app_error("Watch is invalid", &AppError::new(12004))),
+    };
+    webhooks.watch(target, confirmations, blockchain.load().len())
+        .map(Json)
+        .map_err(|e| ApiError::from_app_error("Watch fail", &e))
+}
+
+#[delete("/webhooks/<id>")]
+pub fn delete_webhook(
+    id: String,
+    _recorded: Recorded,
+    webhooks: State<Option<Arc<WebhookBook>>>,
+) -> Result<Json<Watch>, ApiError> {
+    let webhooks = webhooks.as_ref()
+        .ok_or_else(|| ApiError::new(404, "Webhooks are not enabled.".to_string(), None))?;
+    match webhooks.unwatch(&id) {
+        Ok(Some(watch)) => Ok(Json(watch)),
+        Ok(None) => Err(ApiError::new(404, "Watch was not found.".to_string(), None)),
+        Err(e) => Err(ApiError::from_app_error("Unwatch fail", &e)),
+    }
+}
+
+#[get("/search?<q>")]
+pub fn search(
+    q: String,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+) -> Result<Json<SearchResult>, ApiError> {
+    let b_snapshot = blockchain.load();
+    let t_guard = transaction_pool.blocking_read();
+    let a_guard = address_index.blocking_read();
+    match search_entity(&b_snapshot, &t_guard, &a_guard, &q) {
+        Some(result) => Ok(Json(result)),
+        None => Err(ApiError::new(404, "Nothing matched the query.".to_string(), None)),
+    }
+}
+
+/// Stream tip changes, with pool changes and confirmations touching comma separated addresses if any.
+///
+/// Every client holds a worker while connected, so clients beyond the configured streams are turned away.
+#[get("/stream/tip?<addresses>")]
+pub fn stream_tip(
+    addresses: Option<String>,
+    tip_notifier: State<Arc<TipNotifier>>,
+) -> Result<Content<Stream<TipStream>>, ApiError> {
+    let filter = match addresses {
+        Some(addresses) => StreamFilter::parse(&addresses)
+            .ok_or_else(|| ApiError::new(400, "Addresses are invalid, at least one is required.".to_string(), None))?,
+        None => StreamFilter::default(),
+    };
+    let tip_stream = tip_notifier.subscribe(filter)
+        .ok_or_else(|| ApiError::new(503, "Too many clients of the stream, retry later.".to_string(), None))?;
+    Ok(Content(ContentType::new("text", "event-stream"), Stream::from(tip_stream)))
+}
+
+#[get("/sync-status")]
+pub fn sync_status(
+    blockchain: State<Arc<ChainState>>,
+    sync_tracker: State<Arc<SyncTracker>>,
+) -> Json<SyncStatus> {
+    Json(sync_tracker.get_status(blockchain.load().len() - 1))
+}
+
+/// Query connected peers for their latest block, then check their tips against the local chain.
+///
+/// Peers which do not answer in time are checked against the tip they announced before, if any.
+#[get("/consensus-check")]
+pub fn consensus_check(
+    blockchain: State<Arc<ChainState>>,
+    sync_tracker: State<Arc<SyncTracker>>,
+    peer_registry: State<Arc<PeerRegistry>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Json<ConsensusCheck> {
+    let peers: Vec<String> = peer_registry.get_peers().into_iter().map(|peer| peer.peer).collect();
+    if !peers.is_empty() && broadcast_sender.send(BroadcastEvents::QueryLatest).is_ok() {
+        thread::sleep(Duration::from_millis(CONSENSUS_CHECK_WAIT_MILLIS));
+    }
+    Json(check_consensus(&blockchain.load(), &peers, &sync_tracker.get_tips()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClearPool {
+    pub min_fee: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClearedPool {
+    pub removed: usize,
+    #[serde(serialize_with = "serialize_model")]
+    pub transaction_pool: Vec<Transaction>,
+}
+
+#[post("/admin/clear-pool", format = "json", data = "<clear_pool>")]
+pub fn clear_pool(
+    _admin: Admin,
+    clear_pool: Journaled<ClearPool>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Json<ClearedPool> {
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_read();
+    let removed = trim_transaction_pool(&mut t_guard, &u_guard, clear_pool.0.min_fee);
+
+    let _ = broadcast_sender.send(BroadcastEvents::Transaction(t_guard.to_vec(), None));
+    Json(ClearedPool {
+        removed,
+        transaction_pool: t_guard.to_vec(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct RolledBack {
+    pub old_height: usize,
+    pub height: usize,
+
+    /// Transactions of dropped blocks returned to the pool
+    #[serde(serialize_with = "serialize_model")]
+    pub returned_transactions: Vec<Transaction>,
+
+    /// Ids of transactions of dropped blocks the pool does not accept anymore
+    pub dropped_transactions: Vec<String>,
+}
+
+/// Truncate the chain to the block at height, rebuilding unspent tx outs and returning transactions of dropped blocks
+/// to the pool, on regtest only unless allowed.
+#[post("/admin/rollback?<to_height>")]
+pub fn rollback(
+    _admin: Admin,
+    _recorded: Recorded,
+    to_height: usize,
+    rollback_settings: State<RollbackSettings>,
+    blockchain: State<Arc<ChainState>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<Json<RolledBack>, ApiError> {
+    if !rollback_settings.allow_main && !get_chain_params().get_is_regtest() {
+        return Err(ApiError::from_app_error("Rollback fail", &AppError::new(1012)));
+    }
+
+    let b_writer = blockchain.blocking_write();
+    let rolled_back = roll_back(
+        &b_writer, to_height, &unspent_tx_outs, &transaction_pool, &address_index, &mempool_policy, &validation_cache,
+        &broadcast_sender,
+    );
+    rolled_back.map(Json).map_err(|e| ApiError::from_app_error("Rollback fail", &e))
+}
+
+/// Truncate the chain of writer to the block at height, updating state derived from it and broadcasting it.
+fn roll_back(
+    b_writer: &ChainWriter,
+    height: usize,
+    unspent_tx_outs: &RwLock<Vec<UnspentTxOut>>,
+    transaction_pool: &RwLock<Vec<Transaction>>,
+    address_index: &RwLock<AddressIndex>,
+    mempool_policy: &MempoolPolicy,
+    validation_cache: &RwLock<ValidationCache>,
+    broadcast_sender: &UnboundedSender<BroadcastEvents>,
+) -> Result<RolledBack, AppError> {
+    let old_height = b_writer.load().len() - 1;
+    let Rollback { blockchain: new_blockchain, unspent_tx_outs: new_unspent_tx_outs, evicted_transactions } = Rollback::new(&b_writer.load(), height)?;
+
+    let mut u_guard = unspent_tx_outs.blocking_write();
+    let mut t_guard = transaction_pool.blocking_write();
+    let new_blockchain = b_writer.store(new_blockchain, None);
+    *u_guard = new_unspent_tx_outs;
+    address_index.blocking_write().rebuild(&new_blockchain);
+    let mut v_guard = validation_cache.blocking_write();
+    v_guard.clear();
+
+    // Dropped transactions were mined before pooled ones, so they enter the pool first.
+    let context = BlockContext::next(get_latest_block(&new_blockchain), &NetworkClock);
+    let pooled_transactions = mem::take(&mut *t_guard);
+    let (returned_transactions, dropped_transactions): (Vec<Transaction>, Vec<Transaction>) = evicted_transactions
+        .into_iter()
+        .partition(|tx| add_to_transaction_pool(tx, &mut t_guard, &u_guard, context, mempool_policy, &mut v_guard).is_ok());
+    for tx in &pooled_transactions {
+        let _ = add_to_transaction_pool(tx, &mut t_guard, &u_guard, context, mempool_policy, &mut v_guard);
+    }
+
+    let _ = broadcast_sender.send(BroadcastEvents::Blockchain(Arc::clone(&new_blockchain), None));
+    let _ = broadcast_sender.send(BroadcastEvents::Transaction(t_guard.to_vec(), None));
+    Ok(RolledBack {
+        old_height,
+        height: new_blockchain.len() - 1,
+        returned_transactions,
+        dropped_transactions: dropped_transactions.into_iter().map(|tx| tx.id).collect(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvalidatedBlock {
+    pub hash: String,
+
+    /// Rollback to the parent of the block, none if the chain did not include it
+    pub rollback: Option<RolledBack>,
+
+    /// Hashes of every invalidated block
+    pub invalidated: Vec<String>,
+}
+
+/// Get hashes of blocks invalidated by an operator.
+#[get("/admin/invalidated-blocks")]
+pub fn invalidated_blocks(
+    _admin: Admin,
+    blockchain: State<Arc<ChainState>>,
+) -> Json<Vec<String>> {
+    Json(blockchain.get_invalidated())
+}
+
+/// Mark a block invalid like `invalidateblock` of bitcoind, rolling back to its parent if the chain includes it, so
+/// the node never adopts a chain including it and follows a competing branch a peer relays. On regtest only unless
+/// rollback is allowed.
+#[post("/admin/invalidate-block/<hash>")]
+pub fn invalidate_block(
+    _admin: Admin,
+    _recorded: Recorded,
+    hash: String,
+    rollback_settings: State<RollbackSettings>,
+    blockchain: State<Arc<ChainState>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<Json<InvalidatedBlock>, ApiError> {
+    if !rollback_settings.allow_main && !get_chain_params().get_is_regtest() {
+        return Err(ApiError::from_app_error("Invalidate block fail", &AppError::new(1012)));
+    }
+
+    let b_writer = blockchain.blocking_write();
+    let rollback = match b_writer.load().iter().position(|block| block.hash.eq(&hash)) {
+        Some(0) => return Err(ApiError::from_app_error("Invalidate block fail", &AppError::new(1013))),
+        Some(height) => Some(roll_back(
+            &b_writer, height - 1, &unspent_tx_outs, &transaction_pool, &address_index, &mempool_policy, &validation_cache,
+            &broadcast_sender,
+        ).map_err(|e| ApiError::from_app_error("Invalidate block fail", &e))?),
+        None => None,
+    };
+    b_writer.invalidate(&hash);
+
+    let _ = broadcast_sender.send(BroadcastEvents::QueryLatest);
+    Ok(Json(InvalidatedBlock { hash, rollback, invalidated: blockchain.get_invalidated() }))
+}
+
+/// Unmark a block invalid like `reconsiderblock` of bitcoind, asking peers for their latest block so a longer
+/// chain including it is synced again.
+#[post("/admin/reconsider-block/<hash>")]
+pub fn reconsider_block(
+    _admin: Admin,
+    _recorded: Recorded,
+    hash: String,
+    blockchain: State<Arc<ChainState>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    if !blockchain.blocking_write().reconsider(&hash) {
+        return Err(ApiError::new(404, "Block was not invalidated.".to_string(), None));
+    }
+
+    let _ = broadcast_sender.send(BroadcastEvents::QueryLatest);
+    Ok(Json(blockchain.get_invalidated()))
+}
+
+#[post("/wallet/rotate")]
+pub fn rotate_key(
+    _admin: Admin,
+    _recorded: Recorded,
+    key_settings: State<KeySettings>,
+    blockchain: State<Arc<ChainState>>,
+    transaction_pool: State<Arc<RwLock<Vec<Transaction>>>>,
+    unspent_tx_outs: State<Arc<RwLock<Vec<UnspentTxOut>>>>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    mempool_policy: State<MempoolPolicy>,
+    validation_cache: State<Arc<RwLock<ValidationCache>>>,
+    broadcast_sender: State<UnboundedSender<BroadcastEvents>>,
+) -> Result<Json<Rotation>, ApiError> {
+    let mut t_guard = transaction_pool.blocking_write();
+    let u_guard = unspent_tx_outs.blocking_read();
+    let mut w_guard = wallet.blocking_write();
+
+    let pending = prepare_rotation(&w_guard, key_settings.key_type, &u_guard, &t_guard)
+        .map_err(|e| ApiError::from_app_error("Rotate key fail", &e))?;
+    if let Some(tx) = &pending.sweep_transaction {
+        add_to_transaction_pool(tx, &mut t_guard, &u_guard, get_pool_context(&blockchain), &mempool_policy, &mut validation_cache.blocking_write())
+            .map_err(|e| ApiError::from_app_error("Add transaction pool fail", &e))?;
+    }
+    let rotation = pending.commit(&mut w_guard, &key_settings.private_key_path, &mut t_guard)
+        .map_err(|e| ApiError::from_app_error("Rotate key fail", &e))?;
+
+    if rotation.sweep_transaction.is_some() {
+        let _ = broadcast_sender.send(BroadcastEvents::Transaction(t_guard.to_vec(), None));
+    }
+    Ok(Json(rotation))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ImportKey {
+    #[validate(length(min = 1))]
+    pub wif: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedKey {
+    pub address: String,
+    pub balance: usize,
+}
+
+/// Import a WIF private key into the wallet. The body holds the key, so the request is not journaled.
+#[post("/wallet/import-key", format = "json", data = "<import_key>")]
+pub fn import_key(
+    _admin: Admin,
+    import_key: Result<Parsed<ImportKey>, ApiError>,
+    key_settings: State<KeySettings>,
+    wallet: State<Arc<RwLock<Wallet>>>,
+    address_index: State<Arc<RwLock<AddressIndex>>>,
+) -> Result<Json<ImportedKey>, ApiError> {
+    let Parsed(ImportKey { wif }) = import_key?;
+
+    let mut w_guard = wallet.blocking_write();
+    let address = import_wallet_key(&mut w_guard, &wif, &key_settings.private_key_path)
+        .map_err(|e| ApiError::from_app_error("Import key fail", &e))?;
+
+    Ok(Json(ImportedKey {
+        balance: address_index.blocking_read().get_balance(&address),
+        address,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedKey {
+    pub address: String,
+    pub wif: String,
+}
+
+#[get("/wallet/export-key")]
+pub fn export_key(
+    _admin: Admin,
+    wallet: State<Arc<RwLock<Wallet>>>,
+) -> Result<Json<ExportedKey>, ApiError> {
+    let w_guard = wallet.blocking_read();
+    let wif = w_guard.export_key()
+        .map_err(|e| ApiError::from_app_error("Export key fail", &e))?;
+
+    Ok(Json(ExportedKey {
+        address: w_guard.public_key.clone(),
+        wif,
+    }))
+}
@@ -0,0 +1,216 @@
+use serde::{Serialize, Deserialize};
+
+use crate::activation::Rule;
+
+/// Stack value of a successful check.
+const TRUE: &'static str = "1";
+
+/// Stack value of a failed check.
+const FALSE: &'static str = "";
+
+/// Largest count of public keys in a multisig condition.
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Longest data carried by an output.
+pub const MAX_DATA_LENGTH: usize = 80;
+
+/// Operation of an output script.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    /// Push data onto the stack
+    Push(String),
+
+    /// Pop a public key and a signature, push whether the signature is valid
+    CheckSig,
+
+    /// Pop `n` public keys and `m` signatures, push whether the signatures match distinct keys in order
+    CheckMultisig(usize, usize),
+
+    /// Fail while the timestamp of the block is before the timestamp
+    CheckLocktime(usize),
+
+    /// Carry data for applications, always failing so the output is never spent
+    Data(String),
+}
+
+/// Conditions to spend an output, run on a stack holding the signatures of the spending tx in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Script(pub Vec<Op>);
+
+impl Script {
+    /// Returns a script spendable by the owner of the address, the rule of outputs without script
+    pub fn pay_to_public_key(address: &str) -> Script {
+        Script(vec![Op::Push(address.to_string()), Op::CheckSig])
+    }
+
+    /// Returns a script spendable with signatures of `required` of the public keys, given in key order
+    pub fn multisig(required: usize, public_keys: &[String]) -> Script {
+        let mut ops: Vec<Op> = public_keys.iter().map(|public_key| Op::Push(public_key.clone())).collect();
+        ops.push(Op::CheckMultisig(required, public_keys.len()));
+        Script(ops)
+    }
+
+    /// Returns a script spendable only from timestamp on
+    pub fn locktime(timestamp: usize, script: Script) -> Script {
+        Script(std::iter::once(Op::CheckLocktime(timestamp)).chain(script.0).collect())
+    }
+
+    /// Returns a script carrying data, for outputs that record instead of pay
+    pub fn data(data: &str) -> Script {
+        Script(vec![Op::Data(data.to_string())])
+    }
+
+    /// Get data carried by the script
+    pub fn get_data(&self) -> Option<&str> {
+        self.0.iter().find_map(|op| match op {
+            Op::Data(data) => Some(data.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Get flag the script can never succeed, so its output is left out of unspent tx outs
+    pub fn get_is_unspendable(&self) -> bool {
+        self.get_data().is_some()
+    }
+
+    /// Get public keys pushed by the script
+    pub fn get_public_keys(&self) -> Vec<String> {
+        self.0.iter()
+            .filter_map(|op| match op {
+                Op::Push(data) => Some(data.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get flag the script is time locked, so its result depends on the clock
+    pub fn get_has_locktime(&self) -> bool {
+        self.0.iter().any(|op| matches!(op, Op::CheckLocktime(_)))
+    }
+
+    /// Get rules enforcing the operations of the script, it is only valid at heights they are active
+    pub fn get_rules(&self) -> Vec<Rule> {
+        self.0.iter()
+            .filter_map(|op| match op {
+                Op::CheckMultisig(_, _) => Some(Rule::Multisig),
+                Op::CheckLocktime(_) => Some(Rule::Locktime),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return structure is valid
+    pub fn get_is_valid_structure(&self) -> bool {
+        !self.0.is_empty() && self.0.iter().all(|op| match op {
+            Op::Push(data) => !data.is_empty(),
+            Op::CheckMultisig(required, count) => 0 < *required && required <= count && *count <= MAX_MULTISIG_KEYS,
+            Op::Data(data) => !data.is_empty() && data.len() <= MAX_DATA_LENGTH,
+            _ => true,
+        })
+    }
+
+    /// Run script after unlocking data, verifying signatures with `verify(public_key, signature)`.
+    ///
+    /// It succeeds when every operation succeeds and only a true value is left on the stack.
+    pub fn evaluate<F>(&self, unlocking_data: &[&str], timestamp: usize, verify: F) -> bool
+    where
+        F: Fn(&str, &str) -> bool,
+    {
+        let mut stack: Vec<String> = unlocking_data.iter().map(|data| data.to_string()).collect();
+
+        for op in &self.0 {
+            match op {
+                Op::Push(data) => stack.push(data.clone()),
+                Op::CheckSig => {
+                    let (public_key, signature) = match (stack.pop(), stack.pop()) {
+                        (Some(public_key), Some(signature)) => (public_key, signature),
+                        _ => return false,
+                    };
+                    stack.push(to_value(verify(&public_key, &signature)));
+                }
+                Op::CheckMultisig(required, count) => {
+                    if stack.len() < required + count {
+                        return false;
+                    }
+                    let public_keys = stack.split_off(stack.len() - count);
+                    let signatures = stack.split_off(stack.len() - required);
+                    let mut public_keys = public_keys.iter();
+                    let is_valid = signatures
+                        .iter()
+                        .all(|signature| public_keys.any(|public_key| verify(public_key, signature)));
+                    stack.push(to_value(is_valid));
+                }
+                Op::CheckLocktime(locktime) => {
+                    if timestamp < *locktime {
+                        return false;
+                    }
+                }
+                Op::Data(_) => return false,
+            }
+        }
+
+        stack.len() == 1 && stack[0] == TRUE
+    }
+}
+
+fn to_value(is_true: bool) -> String {
+    if is_true { TRUE } else { FALSE }.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn verify(public_key: &str, signature: &str) -> bool {
+        signature == format!("sig-{}", public_key)
+    }
+
+    #[test]
+    fn test_pay_to_public_key() {
+        let script = Script::pay_to_public_key("a");
+        assert!(script.evaluate(&["sig-a"], 0, verify));
+        assert!(!script.evaluate(&["sig-b"], 0, verify));
+        assert!(!script.evaluate(&[], 0, verify));
+        assert!(!script.evaluate(&["sig-a", "sig-a"], 0, verify));
+    }
+
+    #[test]
+    fn test_multisig() {
+        let public_keys = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let script = Script::multisig(2, &public_keys);
+        assert!(script.get_is_valid_structure());
+        assert_eq!(script.get_public_keys(), public_keys);
+
+        assert!(script.evaluate(&["sig-a", "sig-c"], 0, verify));
+        assert!(script.evaluate(&["sig-b", "sig-c"], 0, verify));
+        assert!(!script.evaluate(&["sig-c", "sig-a"], 0, verify));
+        assert!(!script.evaluate(&["sig-a", "sig-a"], 0, verify));
+        assert!(!script.evaluate(&["sig-a"], 0, verify));
+
+        assert!(!Script::multisig(0, &public_keys).get_is_valid_structure());
+        assert!(!Script::multisig(4, &public_keys).get_is_valid_structure());
+    }
+
+    #[test]
+    fn test_locktime() {
+        let script = Script::locktime(100, Script::pay_to_public_key("a"));
+        assert!(script.get_has_locktime());
+        assert!(!script.evaluate(&["sig-a"], 99, verify));
+        assert!(script.evaluate(&["sig-a"], 100, verify));
+        assert!(!Script::pay_to_public_key("a").get_has_locktime());
+        assert_eq!(script.get_rules(), vec![Rule::Locktime]);
+        assert!(Script::pay_to_public_key("a").get_rules().is_empty());
+    }
+
+    #[test]
+    fn test_data() {
+        let script = Script::data("hello");
+        assert!(script.get_is_valid_structure());
+        assert!(script.get_is_unspendable());
+        assert_eq!(script.get_data(), Some("hello"));
+        assert!(!script.evaluate(&[], 0, verify));
+        assert!(!Script::data("").get_is_valid_structure());
+        assert!(!Script::pay_to_public_key("a").get_is_unspendable());
+    }
+}
@@ -0,0 +1,393 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+use ed25519_dalek::{Signer as _, Verifier as _};
+use secp256k1::{ecdsa, PublicKey, Secp256k1, SecretKey};
+use secp256k1::rand::RngCore;
+use secp256k1::rand::rngs::OsRng;
+
+use crate::errors::AppError;
+use crate::secp256k1::message_from_str;
+
+/// Version prefix of ed25519 addresses and private keys.
+pub const ED25519_PREFIX: &'static str = "ed";
+
+/// Type of key, encoded in the address version.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyType {
+    Secp256k1,
+    Ed25519,
+}
+
+impl KeyType {
+    /// Returns key type of an address
+    pub fn from_address(address: &str) -> Option<KeyType> {
+        if address.len() != 66 {
+            None
+        } else if address.starts_with("02") || address.starts_with("03") {
+            Some(KeyType::Secp256k1)
+        } else if address.starts_with(ED25519_PREFIX) {
+            Some(KeyType::Ed25519)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "secp256k1" => Ok(KeyType::Secp256k1),
+            "ed25519" => Ok(KeyType::Ed25519),
+            _ => Err(AppError::new(3003)),
+        }
+    }
+}
+
+/// Signs hex encoded message hashes with a private key.
+pub trait Signer {
+    /// Get type of key
+    fn get_key_type(&self) -> KeyType;
+
+    /// Get address of public key
+    fn get_address(&self) -> String;
+
+    /// Get hex encoded private key
+    fn get_private_key(&self) -> String;
+
+    /// Sign hex encoded message hash
+    fn sign(&self, message: &str) -> Result<String, AppError>;
+}
+
+/// Verifies signatures of hex encoded message hashes for an address.
+pub trait Verifier {
+    /// Get type of key
+    fn get_key_type(&self) -> KeyType;
+
+    /// Verify hex encoded signature of hex encoded message hash
+    fn verify(&self, message: &str, signature: &str) -> bool;
+}
+
+pub struct Secp256k1Signer {
+    secret_key: SecretKey,
+}
+
+impl Signer for Secp256k1Signer {
+    fn get_key_type(&self) -> KeyType {
+        KeyType::Secp256k1
+    }
+
+    fn get_address(&self) -> String {
+        PublicKey::from_secret_key(&Secp256k1::new(), &self.secret_key).to_string()
+    }
+
+    fn get_private_key(&self) -> String {
+        hex::encode(self.secret_key.secret_bytes())
+    }
+
+    fn sign(&self, message: &str) -> Result<String, AppError> {
+        let message = message_from_str(message).map_err(|_| AppError::new(2000))?;
+        Ok(Secp256k1::new().sign_ecdsa(&message, &self.secret_key).to_string())
+    }
+}
+
+pub struct Secp256k1Verifier {
+    public_key: PublicKey,
+}
+
+impl Verifier for Secp256k1Verifier {
+    fn get_key_type(&self) -> KeyType {
+        KeyType::Secp256k1
+    }
+
+    fn verify(&self, message: &str, signature: &str) -> bool {
+        match (message_from_str(message), ecdsa::Signature::from_str(signature)) {
+            (Ok(message), Ok(sig)) => Secp256k1::verification_only().verify_ecdsa(&message, &sig, &self.public_key).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+pub struct Ed25519Signer {
+    keypair: ed25519_dalek::Keypair,
+}
+
+impl Signer for Ed25519Signer {
+    fn get_key_type(&self) -> KeyType {
+        KeyType::Ed25519
+    }
+
+    fn get_address(&self) -> String {
+        format!("{}{}", ED25519_PREFIX, hex::encode(self.keypair.public.as_bytes()))
+    }
+
+    fn get_private_key(&self) -> String {
+        format!("{}{}", ED25519_PREFIX, hex::encode(self.keypair.secret.as_bytes()))
+    }
+
+    fn sign(&self, message: &str) -> Result<String, AppError> {
+        let message = hex::decode(message).map_err(|_| AppError::new(2000))?;
+        Ok(hex::encode(self.keypair.sign(&message).to_bytes()))
+    }
+}
+
+pub struct Ed25519Verifier {
+    public_key: ed25519_dalek::PublicKey,
+}
+
+impl Verifier for Ed25519Verifier {
+    fn get_key_type(&self) -> KeyType {
+        KeyType::Ed25519
+    }
+
+    fn verify(&self, message: &str, signature: &str) -> bool {
+        let (message, signature) = match (hex::decode(message), hex::decode(signature)) {
+            (Ok(message), Ok(signature)) => (message, signature),
+            _ => return false,
+        };
+        match ed25519_dalek::Signature::try_from(signature.as_slice()) {
+            Ok(sig) => self.public_key.verify(&message, &sig).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+fn get_ed25519_signer(secret: &[u8]) -> Result<Box<dyn Signer>, AppError> {
+    let secret = ed25519_dalek::SecretKey::from_bytes(secret).map_err(|_| AppError::new(3000))?;
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    Ok(Box::new(Ed25519Signer { keypair: ed25519_dalek::Keypair { secret, public } }))
+}
+
+/// Get signer of a hex encoded private key, ed25519 keys are prefixed with `ed`.
+/// A secp256k1 key starting with `ed` is told apart by its length.
+pub fn get_signer(private_key: &str) -> Result<Box<dyn Signer>, AppError> {
+    match private_key.strip_prefix(ED25519_PREFIX) {
+        Some(secret) if secret.len() == 2 * ed25519_dalek::SECRET_KEY_LENGTH => {
+            let secret = hex::decode(secret).map_err(|_| AppError::new(3000))?;
+            get_ed25519_signer(&secret)
+        }
+        _ => {
+            let secret_key = SecretKey::from_str(private_key).map_err(|_| AppError::new(3000))?;
+            Ok(Box::new(Secp256k1Signer { secret_key }))
+        }
+    }
+}
+
+/// Parse hex encoded private key, trimmed and lower cased, checking its length and range before it is used.
+///
+/// # Errors
+/// If key is not hex, it returns error 3014, if it is not 32 bytes, error 3015, and if it is not a valid
+/// secp256k1 scalar, error 3016.
+pub fn parse_private_key(private_key: &str) -> Result<String, AppError> {
+    let private_key = private_key.trim().to_lowercase();
+    let (prefix, secret) = match private_key.strip_prefix(ED25519_PREFIX) {
+        Some(secret) if secret.len() == 2 * ed25519_dalek::SECRET_KEY_LENGTH => (ED25519_PREFIX, secret),
+        _ => ("", private_key.as_str()),
+    };
+    if !secret.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::new(3014));
+    }
+    if secret.len() != 2 * secp256k1::constants::SECRET_KEY_SIZE {
+        return Err(AppError::new(3015));
+    }
+    if prefix.is_empty() && SecretKey::from_slice(&hex::decode(secret).unwrap()).is_err() {
+        return Err(AppError::new(3016));
+    }
+    Ok(format!("{}{}", prefix, secret))
+}
+
+/// Get verifier dispatching on the key type of address.
+pub fn get_verifier(address: &str) -> Option<Box<dyn Verifier>> {
+    match KeyType::from_address(address)? {
+        KeyType::Secp256k1 => {
+            let public_key = PublicKey::from_str(address).ok()?;
+            Some(Box::new(Secp256k1Verifier { public_key }))
+        }
+        KeyType::Ed25519 => {
+            let bytes = hex::decode(&address[ED25519_PREFIX.len()..]).ok()?;
+            let public_key = ed25519_dalek::PublicKey::from_bytes(&bytes).ok()?;
+            Some(Box::new(Ed25519Verifier { public_key }))
+        }
+    }
+}
+
+/// Address parsed to be a public key of one of the key types, in lowercase hex so equal keys compare equal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address(String);
+
+impl Address {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Address {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match get_verifier(s) {
+            Some(_) => Ok(Address(s.to_ascii_lowercase())),
+            None => Err(AppError::new(2009)),
+        }
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> String {
+        address.0
+    }
+}
+
+/// Source of random bytes new keys are made of.
+pub trait Entropy {
+    /// Fill bytes with random data
+    fn fill_bytes(&mut self, bytes: &mut [u8]);
+}
+
+/// Randomness of the operating system, the entropy of a running node.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OsEntropy;
+
+impl Entropy for OsEntropy {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        OsRng.fill_bytes(bytes);
+    }
+}
+
+/// Bytes stretched from a seed by hashing it with a counter, so tests and simulations get the same keys every run.
+///
+/// Anyone knowing the seed knows the keys, so it must never make keys holding real coins.
+#[derive(Debug, Clone)]
+pub struct SeededEntropy {
+    seed: [u8; 32],
+    counter: u64,
+}
+
+impl SeededEntropy {
+    /// Returns entropy of seed, the first bytes it fills are always the same for the same seed
+    pub fn new(seed: &[u8]) -> SeededEntropy {
+        SeededEntropy { seed: *blake3::hash(seed).as_bytes(), counter: 0 }
+    }
+}
+
+impl Entropy for SeededEntropy {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(blake3::OUT_LEN) {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(&self.seed);
+            hasher.update(&self.counter.to_le_bytes());
+            self.counter += 1;
+            chunk.copy_from_slice(&hasher.finalize().as_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+/// Generate a new signer of key type from the randomness of the operating system.
+pub fn generate_signer(key_type: KeyType) -> Result<Box<dyn Signer>, AppError> {
+    generate_signer_with_entropy(key_type, &mut OsEntropy)
+}
+
+/// Generate a new signer of key type from bytes of entropy.
+pub fn generate_signer_with_entropy(key_type: KeyType, entropy: &mut dyn Entropy) -> Result<Box<dyn Signer>, AppError> {
+    match key_type {
+        KeyType::Secp256k1 => loop {
+            let mut secret = [0u8; secp256k1::constants::SECRET_KEY_SIZE];
+            entropy.fill_bytes(&mut secret);
+            // Bytes of zero or past the curve order are no key, which is all but impossible for random bytes.
+            if let Ok(secret_key) = SecretKey::from_slice(&secret) {
+                return Ok(Box::new(Secp256k1Signer { secret_key }));
+            }
+        },
+        KeyType::Ed25519 => {
+            let mut secret = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+            entropy.fill_bytes(&mut secret);
+            get_ed25519_signer(&secret)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_type_from_address() {
+        assert_eq!(KeyType::from_address("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b"), Some(KeyType::Secp256k1));
+        assert_eq!(KeyType::from_address("ed3b6a27bcceb6a42d62a3a8d02a6f0d73653215771de243a63ac048a18b59da29"), Some(KeyType::Ed25519));
+        assert_eq!(KeyType::from_address("04cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b"), None);
+        assert_eq!(KeyType::from_address("03cbad"), None);
+    }
+
+    #[test]
+    fn test_address_from_str() {
+        let address = "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b";
+        assert_eq!(Address::from_str(address).unwrap().as_str(), address);
+        assert_eq!(Address::from_str(&address.to_uppercase()).unwrap(), Address::from_str(address).unwrap());
+        assert_eq!(Address::from_str("03cbad").unwrap_err().code, 2009);
+        assert_eq!(Address::from_str(&"03".repeat(33)).unwrap_err().code, 2009);
+    }
+
+    #[test]
+    fn test_get_signer() {
+        let signer = get_signer("27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b").unwrap();
+        assert_eq!(signer.get_key_type(), KeyType::Secp256k1);
+        assert_eq!(signer.get_address(), "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b");
+
+        let signer = get_signer("ed9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").unwrap();
+        assert_eq!(signer.get_key_type(), KeyType::Ed25519);
+        assert_eq!(signer.get_address(), "edd75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        assert_eq!(signer.get_private_key(), "ed9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+
+        let private_key = "ed2cb5cf0272094aaec834526655ce243f000a1ce34668862bfe5485e5c73e50";
+        assert_eq!(get_signer(private_key).unwrap().get_key_type(), KeyType::Secp256k1);
+
+        assert!(get_signer("invalid").is_err());
+        assert!(get_signer("edinvalid").is_err());
+    }
+
+    #[test]
+    fn test_parse_private_key() {
+        let private_key = "27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b";
+        assert_eq!(parse_private_key(&format!(" {}\r\n", private_key.to_uppercase())).unwrap(), private_key);
+        let private_key = "ed9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        assert_eq!(parse_private_key(private_key).unwrap(), private_key);
+
+        assert_eq!(parse_private_key("27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5z").unwrap_err().code, 3014);
+        assert_eq!(parse_private_key("27f5005f5f58f8711e99577e8b87e28a b4c2151f9289ac1203ccecdb94602a5b").unwrap_err().code, 3014);
+        assert_eq!(parse_private_key("27f5005f5f58f8711e99577e8b87e28a").unwrap_err().code, 3015);
+        assert_eq!(parse_private_key("ed9d61b1").unwrap_err().code, 3015);
+        assert_eq!(parse_private_key("").unwrap_err().code, 3015);
+        assert_eq!(parse_private_key(&"0".repeat(64)).unwrap_err().code, 3016);
+        assert_eq!(parse_private_key(&"f".repeat(64)).unwrap_err().code, 3016);
+    }
+
+    #[test]
+    fn test_generate_signer_with_entropy() {
+        for key_type in vec![KeyType::Secp256k1, KeyType::Ed25519] {
+            let signer = generate_signer_with_entropy(key_type, &mut SeededEntropy::new(b"alice")).unwrap();
+            let same_signer = generate_signer_with_entropy(key_type, &mut SeededEntropy::new(b"alice")).unwrap();
+            assert_eq!(signer.get_key_type(), key_type);
+            assert_eq!(signer.get_private_key(), same_signer.get_private_key());
+
+            let mut entropy = SeededEntropy::new(b"alice");
+            let _ = generate_signer_with_entropy(key_type, &mut entropy).unwrap();
+            assert_ne!(generate_signer_with_entropy(key_type, &mut entropy).unwrap().get_private_key(), signer.get_private_key());
+            assert_ne!(generate_signer_with_entropy(key_type, &mut SeededEntropy::new(b"bob")).unwrap().get_private_key(), signer.get_private_key());
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let message = "e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea";
+        for key_type in vec![KeyType::Secp256k1, KeyType::Ed25519] {
+            let signer = generate_signer(key_type).unwrap();
+            let signature = signer.sign(message).unwrap();
+            let verifier = get_verifier(&signer.get_address()).unwrap();
+            assert_eq!(verifier.get_key_type(), key_type);
+            assert!(verifier.verify(message, &signature));
+            assert!(!verifier.verify("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645", &signature));
+            assert!(!verifier.verify(message, "invalid"));
+        }
+    }
+}
@@ -1,264 +1,814 @@
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-use std::{thread, time};
+use std::sync::Arc;
+use std::time::Duration;
 use std::mem;
-use tokio_tungstenite::{accept_async, connect_async, MaybeTlsStream, WebSocketStream};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use futures_util::{SinkExt, StreamExt};
-use tokio_tungstenite::tungstenite::Message;
-use url::Url;
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 use crate::{Block, Config, Transaction, UnspentTxOut, Wallet};
-use crate::block::{get_is_replace_chain, get_unspent_tx_outs};
+use crate::address_index::AddressIndex;
+use crate::block::{get_is_heavier_chain, get_is_replace_chain, get_latest_block, get_unspent_tx_outs, validate_chain, TIMESTAMP_INTERVAL};
+use crate::chain_state::ChainState;
+use crate::coalescer::BroadcastCoalescer;
 use crate::connection::Connection;
+use crate::dto::Model;
+use crate::constants::UTXO_SNAPSHOT_DEPTH;
+use crate::errors::AppError;
 use crate::events::BroadcastEvents;
+use crate::inventory::{get_missing_ids, get_transactions};
+use crate::invoice::InvoiceBook;
+use crate::journal::{Input, Journal};
+use crate::network_time::{get_adjusted_timestamp, get_time_offset, set_time_offset, NetworkClock, TimeOffsets};
+use crate::libp2p_transport::Libp2pTransport;
 use crate::payload::{Payload, PayloadType};
+use crate::peer_auth::{PeerAuthenticator, SignedTransport};
+use crate::peers::{Direction, DisconnectedPeer, Handshake, Heartbeat, PeerInfo, PeerRegistry, Reject};
+use crate::sync_status::SyncTracker;
+use crate::tip_stream::TipNotifier;
+use crate::consensus_rules::ConsensusRules;
+use crate::mempool_policy::MempoolPolicy;
+use crate::transaction::BlockContext;
 use crate::transaction_pool::add_to_transaction_pool;
+use crate::transport::{PeerTransport, TransportEvent, TransportKind};
+use crate::utxo_snapshot::{get_is_pruned, UtxoSnapshot};
+use crate::validation_cache::{get_chain_digest, ValidationCache};
+use crate::websocket_transport::WebSocketTransport;
 
-const FIXED_SLEEP: u64 = 60;
+const TIP_QUERY_INTERVAL: u64 = 60;
+const BROADCAST_WINDOW_MILLIS: u64 = 100;
+const SHUTDOWN_GRACE_MILLIS: u64 = 1000;
 
 pub fn launch_socket(
     config: &Config,
-    blockchain: &Arc<RwLock<Vec<Block>>>,
+    blockchain: &Arc<ChainState>,
     unspent_tx_outs: &Arc<RwLock<Vec<UnspentTxOut>>>,
     transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
     wallet: &Arc<RwLock<Wallet>>,
+    address_index: &Arc<RwLock<AddressIndex>>,
+    tip_notifier: &Arc<TipNotifier>,
+    invoices: &Arc<InvoiceBook>,
+    validation_cache: &Arc<RwLock<ValidationCache>>,
+    sync_tracker: &Arc<SyncTracker>,
+    peer_registry: &Arc<PeerRegistry>,
+    journal: &Option<Arc<Journal>>,
     broadcast_channel: (UnboundedSender<BroadcastEvents>, UnboundedReceiver<BroadcastEvents>),
 ) {
-    let runtime = tokio::runtime::Builder::new_multi_thread().enable_io().build().unwrap();
+    let mempool_policy = config.get_mempool_policy();
+    let consensus_rules = config.get_consensus_rules();
+    let max_inbound = config.max_inbound;
+    let max_outbound = config.max_outbound;
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
 
     runtime.block_on(async {
-        let addr = format!("127.0.0.1:{}", config.socket_port);
-        let listener = TcpListener::bind(&addr)
-            .await
-            .expect("Listening to TCP failed.");
+        let addrs = config.get_socket_addrs();
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let mut transport: Box<dyn PeerTransport> = match config.transport {
+            TransportKind::WebSocket => Box::new(
+                WebSocketTransport::listen(&addrs, config.wire_format, event_sender)
+                    .await
+                    .expect("Listening to TCP failed.")
+            ),
+            TransportKind::Libp2p => Box::new(
+                Libp2pTransport::listen(&addrs, event_sender)
+                    .expect("Listening to libp2p failed.")
+            ),
+        };
+        let authenticator = config.network_secret.as_deref().map(PeerAuthenticator::new);
+        if let Some(authenticator) = &authenticator {
+            transport = Box::new(SignedTransport::new(transport, authenticator.clone()));
+        }
 
         let (broadcast_sender, broadcast_receiver) = broadcast_channel;
+        tokio::spawn(run(broadcast_sender.clone()));
 
-        tokio::spawn({
-            let b = Arc::clone(blockchain);
-            let u = Arc::clone(unspent_tx_outs);
-            let t = Arc::clone(transaction_pool);
-            let w = Arc::clone(wallet);
-            broadcast(b, u, t, w, broadcast_sender.clone(), broadcast_receiver)
-        });
-        tokio::spawn({
-            let b = Arc::clone(blockchain);
-            run(b, broadcast_sender.clone())
-        });
-
-        println!("Listening on: {}", addr);
-
-        // A counter to use as client ids.
-
-        // Accept new clients.
-        while let Ok((stream, peer)) = listener.accept().await {
-            match accept_async(stream).await {
-                Err(e) => println!("Websocket connection error : {:?}", e),
-                Ok(ws_stream) => {
-                    println!("New Connection : {:?}", peer);
-                    let b = Arc::clone(blockchain);
-                    let u = Arc::clone(unspent_tx_outs);
-                    let t = Arc::clone(transaction_pool);
-                    let w = Arc::clone(wallet);
-                    tokio::spawn(listen(b, u, t, w, broadcast_sender.clone(), ws_stream, peer.to_string()));
-                }
-            }
-        }
+        let b = Arc::clone(blockchain);
+        let u = Arc::clone(unspent_tx_outs);
+        let t = Arc::clone(transaction_pool);
+        let w = Arc::clone(wallet);
+        let a = Arc::clone(address_index);
+        let n = Arc::clone(tip_notifier);
+        let i = Arc::clone(invoices);
+        let v = Arc::clone(validation_cache);
+        let s = Arc::clone(sync_tracker);
+        let r = Arc::clone(peer_registry);
+        broadcast(
+            transport, authenticator, journal.clone(), b, u, t, w, a, n, i, v, s, r, mempool_policy, consensus_rules, max_inbound, max_outbound,
+            broadcast_sender, broadcast_receiver, event_receiver,
+        ).await;
     });
 }
 
-async fn run(blockchain: Arc<RwLock<Vec<Block>>>, _tx: UnboundedSender<BroadcastEvents>) {
+/// Ask peers for their latest block and ping them periodically, so a node left behind by a partition catches up
+/// and peer clocks are measured again.
+async fn run(tx: UnboundedSender<BroadcastEvents>) {
     loop {
-        thread::sleep(time::Duration::from_secs(FIXED_SLEEP));
-        println!("run {:?}", blockchain);
+        tokio::time::sleep(Duration::from_secs(TIP_QUERY_INTERVAL)).await;
+        if tx.send(BroadcastEvents::QueryLatest).is_err() {
+            break;
+        }
     }
 }
 
 async fn broadcast(
-    blockchain: Arc<RwLock<Vec<Block>>>,
+    mut transport: Box<dyn PeerTransport>,
+    authenticator: Option<PeerAuthenticator>,
+    journal: Option<Arc<Journal>>,
+    blockchain: Arc<ChainState>,
     unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>>,
     transaction_pool: Arc<RwLock<Vec<Transaction>>>,
     wallet: Arc<RwLock<Wallet>>,
+    address_index: Arc<RwLock<AddressIndex>>,
+    tip_notifier: Arc<TipNotifier>,
+    invoices: Arc<InvoiceBook>,
+    validation_cache: Arc<RwLock<ValidationCache>>,
+    sync_tracker: Arc<SyncTracker>,
+    peer_registry: Arc<PeerRegistry>,
+    mempool_policy: MempoolPolicy,
+    consensus_rules: ConsensusRules,
+    max_inbound: usize,
+    max_outbound: usize,
     tx: UnboundedSender<BroadcastEvents>,
     mut rx: UnboundedReceiver<BroadcastEvents>,
+    mut transport_events: UnboundedReceiver<TransportEvent>,
 ) {
     let mut connections: HashMap<String, Connection> = HashMap::new();
+    let mut coalescer = BroadcastCoalescer::default();
+    let mut deadline: Option<Instant> = None;
+    let mut time_offsets = TimeOffsets::default();
+    let mut skewed = false;
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
 
-    while let Some(event) = rx.recv().await {
-        match event {
-            BroadcastEvents::Join(conn) => {
-                println!("Connection join : {:?}", conn);
-                connections.insert(conn.peer.clone(), conn);
-            }
-            BroadcastEvents::Quit(peer) => {
-                println!("Connection quit : {}", peer);
-                connections.remove(peer.as_str());
-            }
-            BroadcastEvents::Peer(peer) => {
-                println!("Connection peer : {:?}", peer);
-                let (ws_stream, _) = connect_async(Url::parse(peer.as_str()).unwrap()).await.expect("Failed to connect");
-                let b = Arc::clone(&blockchain);
-                let u = Arc::clone(&unspent_tx_outs);
-                let t = Arc::clone(&transaction_pool);
-                let w = Arc::clone(&wallet);
-                tokio::spawn(connect(b, u, t, w, tx.clone(), ws_stream, peer));
-            }
-            BroadcastEvents::Blockchain(blockchain, except) => {
-                println!("NotifyBlockchain : \n{:#?}", blockchain);
-                let p = except.unwrap_or_default();
-                for (peer, conn) in connections.iter_mut() {
-                    if peer.eq(&p) {
+    loop {
+        let event = tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) => event,
+                None => break,
+            },
+            Some(event) = transport_events.recv() => {
+                match event {
+                    TransportEvent::Dialed(address, peer) => {
+                        println!("Connection dialed : {} as {}", address, peer);
+                        peer_registry.remove_dial(&address);
                         continue;
                     }
-                    if let Some(listener) = conn.listener.as_mut() {
-                        listener.send(Payload::serialize(PayloadType::Blockchain, &blockchain)).await.expect("ResponseBlockchain: listener send panic");
+                    TransportEvent::DialFailed(address, error) => {
+                        println!("Connection failed : {} {}", address, error);
+                        peer_registry.record_dial_error(&address, error);
+                        continue;
                     }
-                    if let Some(connector) = conn.connector.as_mut() {
-                        connector.send(Payload::serialize(PayloadType::Blockchain, &blockchain)).await.expect("ResponseBlockchain: connector send panic");
+                    TransportEvent::Connected(peer, direction) => {
+                        let max = if direction == Direction::Inbound { max_inbound } else { max_outbound };
+                        if !peer_registry.try_reserve(direction, max) {
+                            println!("Refuse Connection : {}, {} {:?} connections are open", peer, max, direction);
+                            transport.close(&peer, "Too many connections, try again later");
+                            continue;
+                        }
+                        println!("Connection join : {} {:?}", peer, direction);
+                        let (inbox, receiver) = mpsc::unbounded_channel();
+                        let b = Arc::clone(&blockchain);
+                        let u = Arc::clone(&unspent_tx_outs);
+                        let t = Arc::clone(&transaction_pool);
+                        let w = Arc::clone(&wallet);
+                        let a = Arc::clone(&address_index);
+                        let v = Arc::clone(&validation_cache);
+                        let s = Arc::clone(&sync_tracker);
+                        tokio::spawn(receive_all(b, u, t, w, a, v, s, mempool_policy, consensus_rules, tx.clone(), peer.clone(), receiver));
+                        let mut conn = Connection::new(peer.clone(), direction, inbox);
+                        let handshake = Handshake::local(blockchain.load().len() - 1, get_is_pruned(&blockchain.load()));
+                        send_payload(transport.as_mut(), &mut conn, &Payload::new(PayloadType::Handshake, &handshake));
+                        send_payload(transport.as_mut(), &mut conn, &Payload::new(PayloadType::Ping, &Heartbeat::ping()));
+                        connections.insert(peer, conn);
                     }
-                }
-            }
-            BroadcastEvents::Transaction(transactions, except) => {
-                println!("NotifyTransaction : \n{:#?}", transactions);
-                let p = except.unwrap_or_default();
-                for (peer, conn) in connections.iter_mut() {
-                    if peer.eq(&p) {
+                    TransportEvent::Disconnected(peer, reason) => {
+                        println!("Connection quit : {} {:?}", peer, reason);
+                        time_offsets.remove(&peer);
+                        adjust_time(&time_offsets, &mut skewed);
+                        sync_tracker.forget(&peer);
+                        if let Some(conn) = connections.remove(peer.as_str()) {
+                            peer_registry.release(conn.direction);
+                            let reason = conn.close_reason.clone().or(reason);
+                            peer_registry.record(DisconnectedPeer::from_connection(&conn, reason));
+                        }
+                    }
+                    TransportEvent::Received(peer, payload) => {
+                        if authenticator.as_ref().map_or(false, |authenticator| !authenticator.verify(&payload)) {
+                            println!("Reject payload : {} {:?}, mac does not match network secret", peer, payload.r#type);
+                            if let Some(conn) = connections.get_mut(&peer) {
+                                conn.stats.record_invalid();
+                            }
+                            close_peer(transport.as_mut(), &mut connections, &peer, "Unauthenticated payload");
+                            continue;
+                        }
+                        if let Some(conn) = connections.get_mut(&peer) {
+                            if let Some(journal) = &journal {
+                                journal.record(Input::Payload { peer: peer.clone(), payload: payload.clone() });
+                            }
+                            conn.stats.record_received(&payload);
+                            let _ = conn.inbox.send(payload);
+                        }
                         continue;
                     }
-                    if let Some(listener) = conn.listener.as_mut() {
-                        listener.send(Payload::serialize(PayloadType::Transaction, &transactions)).await.expect("ResponseTransaction: listener send panic");
+                }
+                peer_registry.publish(connections.values().map(PeerInfo::from_connection).collect());
+                continue;
+            }
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                deadline = None;
+                for event in coalescer.take() {
+                    send_to_peers(transport.as_mut(), &mut connections, event);
+                }
+                continue;
+            }
+            _ = &mut shutdown => {
+                println!("Shutdown : closing {} connections", connections.len());
+                let peers: Vec<String> = connections.keys().cloned().collect();
+                for peer in peers {
+                    close_peer(transport.as_mut(), &mut connections, &peer, "Node shutting down");
+                }
+                let _ = tokio::time::timeout(Duration::from_millis(SHUTDOWN_GRACE_MILLIS), async {
+                    while !connections.is_empty() {
+                        match transport_events.recv().await {
+                            Some(TransportEvent::Disconnected(peer, _)) => {
+                                connections.remove(&peer);
+                            }
+                            Some(_) => {}
+                            None => break,
+                        }
                     }
-                    if let Some(connector) = conn.connector.as_mut() {
-                        connector.send(Payload::serialize(PayloadType::Transaction, &transactions)).await.expect("ResponseTransaction: connector send panic");
+                }).await;
+                break;
+            }
+        };
+
+        if let BroadcastEvents::Blockchain(blockchain, _) = &event {
+            tip_notifier.notify_blocks(blockchain);
+        }
+        // Tip queries are periodic, so invoices past their expiry are updated without new blocks.
+        if matches!(event, BroadcastEvents::Blockchain(..) | BroadcastEvents::Transaction(..) | BroadcastEvents::QueryLatest) {
+            let t_guard = transaction_pool.read().await;
+            let blockchain = blockchain.load();
+            for invoice in invoices.update(&blockchain, &t_guard, get_adjusted_timestamp()) {
+                tip_notifier.notify_invoice(invoice);
+            }
+            tip_notifier.notify_pool(&t_guard, &blockchain);
+        }
+        let event = match coalescer.push(event) {
+            Some(event) => event,
+            None => {
+                deadline.get_or_insert(Instant::now() + Duration::from_millis(BROADCAST_WINDOW_MILLIS));
+                continue;
+            }
+        };
+
+        match event {
+            BroadcastEvents::Handshake(peer, handshake) => {
+                println!("Connection handshake : {} {:?}", peer, handshake);
+                if let Some(conn) = connections.get_mut(&peer) {
+                    if handshake.timestamp > 0 {
+                        time_offsets.insert(&peer, handshake.timestamp);
+                        adjust_time(&time_offsets, &mut skewed);
                     }
+                    conn.handshake = Some(handshake);
+                }
+                peer_registry.publish(connections.values().map(PeerInfo::from_connection).collect());
+            }
+            BroadcastEvents::Clock(peer, clock) => {
+                if let Some(conn) = connections.get_mut(&peer) {
+                    time_offsets.insert_offset(&peer, clock.offset_millis / 1000);
+                    adjust_time(&time_offsets, &mut skewed);
+                    conn.clock = Some(clock);
+                }
+                peer_registry.publish(connections.values().map(PeerInfo::from_connection).collect());
+            }
+            BroadcastEvents::Disconnect(peer, reason) => {
+                println!("Connection disconnect : {} {}", peer, reason);
+                close_peer(transport.as_mut(), &mut connections, &peer, &reason);
+            }
+            BroadcastEvents::Peer(peer) => {
+                println!("Connection peer : {:?}", peer);
+                if connections.contains_key(&peer) {
+                    println!("Skip Connection : {:?}, already connected", peer);
+                    continue;
+                }
+                peer_registry.record_dial(&peer);
+                if peer_registry.get_count(Direction::Outbound) >= max_outbound {
+                    println!("Skip Connection : {:?}, {} outbound connections are open", peer, max_outbound);
+                    peer_registry.record_dial_error(&peer, format!("{} outbound connections are open", max_outbound));
+                    continue;
                 }
+                transport.dial(&peer);
             }
+            BroadcastEvents::QueryLatest => {
+                println!("QueryLatest : {} peers", connections.len());
+                let payload = Payload::new(PayloadType::QueryLatest, &());
+                let ping = Payload::new(PayloadType::Ping, &Heartbeat::ping());
+                for conn in connections.values_mut() {
+                    send_payload(transport.as_mut(), conn, &payload);
+                    send_payload(transport.as_mut(), conn, &ping);
+                }
+            }
+            BroadcastEvents::Known(peer, ids) => {
+                if let Some(conn) = connections.get_mut(&peer) {
+                    conn.known_txs.insert_all(&ids);
+                }
+            }
+            BroadcastEvents::Reply(peer, payload) => {
+                if let Some(conn) = connections.get_mut(&peer) {
+                    send_payload(transport.as_mut(), conn, &payload);
+                }
+            }
+            BroadcastEvents::Invalid(peer) => {
+                if let Some(conn) = connections.get_mut(&peer) {
+                    conn.stats.record_invalid();
+                }
+            }
+            BroadcastEvents::Rejected(peer, reject) => {
+                if let Some(conn) = connections.get_mut(&peer) {
+                    conn.stats.record_reject(reject);
+                }
+            }
+            BroadcastEvents::Stats(peer, reply) => {
+                let _ = reply.send(connections.get(&peer).map(|conn| conn.stats.clone()));
+            }
+            BroadcastEvents::Blockchain(..) | BroadcastEvents::Transaction(..) => {}
         }
     }
 }
 
-async fn listen(
-    blockchain: Arc<RwLock<Vec<Block>>>,
-    unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>>,
-    transaction_pool: Arc<RwLock<Vec<Transaction>>>,
-    wallet: Arc<RwLock<Wallet>>,
-    tx: UnboundedSender<BroadcastEvents>,
-    ws_stream: WebSocketStream<TcpStream>,
-    peer: String,
-) {
-    let (sender, mut receiver) = ws_stream.split();
-    let conn = Connection::new(peer.clone(), Some(sender), None);
-    let _ = tx.send(BroadcastEvents::Join(conn));
-
-    while let Some(msg) = receiver.next().await {
-        println!("Receive listen message");
-        if let Ok(msg) = msg {
-            println!("Receive listen message : {:#?}", msg);
-            if msg.is_text() {
-                let b = Arc::clone(&blockchain);
-                let u = Arc::clone(&unspent_tx_outs);
-                let t = Arc::clone(&transaction_pool);
-                let w = Arc::clone(&wallet);
-                receive(b, u, t, w, &tx, peer.clone(), msg);
-            } else if msg.is_close() {
-                break; // When we break, we disconnect.
-            }
-        } else {
-            break; // When we break, we disconnect.
+/// Adjust the local clock to the median offset of peers, warning once when the local clock deviates from it
+/// beyond the tolerance of block timestamps.
+fn adjust_time(time_offsets: &TimeOffsets, skewed: &mut bool) {
+    let offset = time_offsets.get_median();
+    if offset != get_time_offset() {
+        tracing::info!(offset, "network time adjusted");
+        set_time_offset(offset);
+    }
+
+    let skew = time_offsets.get_skew().unwrap_or(0);
+    let is_skewed = skew.abs() > TIMESTAMP_INTERVAL as i64;
+    if is_skewed && !*skewed {
+        tracing::warn!(skew, tolerance = TIMESTAMP_INTERVAL, "local clock deviates from the peer median beyond block timestamp tolerance");
+    }
+    *skewed = is_skewed;
+}
+
+/// Send payload to peer, counting it in the peer stats.
+fn send_payload(transport: &mut dyn PeerTransport, conn: &mut Connection, payload: &Payload) {
+    conn.stats.record_sent(payload);
+    transport.send(&conn.peer, payload);
+}
+
+/// Close connection to peer, remembering the reason for its history.
+fn close_peer(transport: &mut dyn PeerTransport, connections: &mut HashMap<String, Connection>, peer: &str, reason: &str) {
+    if let Some(conn) = connections.get_mut(peer) {
+        conn.close_reason = Some(reason.to_string());
+    }
+    transport.close(peer, reason);
+}
+
+/// Announce a chain update to every peer except its origin, skipping peers that already got the same chain,
+/// or announce ids of pooled transactions each peer does not know yet.
+fn send_to_peers(transport: &mut dyn PeerTransport, connections: &mut HashMap<String, Connection>, event: BroadcastEvents) {
+    match event {
+        BroadcastEvents::Blockchain(blockchain, except) => {
+            println!("NotifyBlockchain : \n{:#?}", blockchain);
+            let payload = Payload::with_model(PayloadType::Blockchain, &*blockchain);
+            let p = except.unwrap_or_default();
+            let mut peers = vec![];
+            for (peer, conn) in connections.iter_mut() {
+                if !peer.eq(&p) && conn.sent_digests.insert(&payload) {
+                    conn.stats.record_sent(&payload);
+                    peers.push(peer.clone());
+                }
+            }
+            if !peers.is_empty() {
+                transport.announce(&peers, &payload);
+            }
         }
+        BroadcastEvents::Transaction(transactions, _) => {
+            println!("NotifyTransaction : \n{:#?}", transactions);
+            for conn in connections.values_mut() {
+                let ids = conn.known_txs.announce(&transactions);
+                if !ids.is_empty() {
+                    send_payload(transport, conn, &Payload::new(PayloadType::Inventory, &ids));
+                }
+            }
+        }
+        _ => {}
     }
-    // If we reach here, it means the client got disconnected.
-    tx.send(BroadcastEvents::Quit(peer.clone())).unwrap();
 }
 
-async fn connect(
-    blockchain: Arc<RwLock<Vec<Block>>>,
+/// Handle payloads of a peer in the order they arrived, until its connection is gone.
+async fn receive_all(
+    blockchain: Arc<ChainState>,
     unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>>,
     transaction_pool: Arc<RwLock<Vec<Transaction>>>,
     wallet: Arc<RwLock<Wallet>>,
+    address_index: Arc<RwLock<AddressIndex>>,
+    validation_cache: Arc<RwLock<ValidationCache>>,
+    sync_tracker: Arc<SyncTracker>,
+    mempool_policy: MempoolPolicy,
+    consensus_rules: ConsensusRules,
     tx: UnboundedSender<BroadcastEvents>,
-    ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
     peer: String,
+    mut inbox: UnboundedReceiver<Payload>,
 ) {
-    let (sender, mut receiver) = ws_stream.split();
-    let conn = Connection::new(peer.clone(), None, Some(sender));
-    let _ = tx.send(BroadcastEvents::Join(conn));
-
-    while let Some(msg) = receiver.next().await {
-        println!("Receive connect message");
-        if let Ok(msg) = msg {
-            println!("Receive connect message : {:#?}", msg);
-            if msg.is_text() {
-                let b = Arc::clone(&blockchain);
-                let u = Arc::clone(&unspent_tx_outs);
-                let t = Arc::clone(&transaction_pool);
-                let w = Arc::clone(&wallet);
-                receive(b, u, t, w, &tx, peer.clone(), msg);
-            } else if msg.is_close() {
-                break; // When we break, we disconnect.
-            }
-        } else {
-            break; // When we break, we disconnect.
-        }
+    while let Some(payload) = inbox.recv().await {
+        println!("Receive message : {} {:?}", peer, payload.r#type);
+        let b = Arc::clone(&blockchain);
+        let u = Arc::clone(&unspent_tx_outs);
+        let t = Arc::clone(&transaction_pool);
+        let w = Arc::clone(&wallet);
+        let a = Arc::clone(&address_index);
+        let v = Arc::clone(&validation_cache);
+        let s = Arc::clone(&sync_tracker);
+        receive(b, u, t, w, a, v, s, &mempool_policy, &consensus_rules, &tx, peer.clone(), payload).await;
     }
-    // If we reach here, it means the client got disconnected.
-    tx.send(BroadcastEvents::Quit(peer.clone())).unwrap();
 }
 
-fn receive(
-    blockchain: Arc<RwLock<Vec<Block>>>,
+/// Handle a payload of peer, replies and chain updates are sent as events.
+pub(crate) async fn receive(
+    blockchain: Arc<ChainState>,
     unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>>,
     transaction_pool: Arc<RwLock<Vec<Transaction>>>,
     _wallet: Arc<RwLock<Wallet>>,
+    address_index: Arc<RwLock<AddressIndex>>,
+    validation_cache: Arc<RwLock<ValidationCache>>,
+    sync_tracker: Arc<SyncTracker>,
+    mempool_policy: &MempoolPolicy,
+    consensus_rules: &ConsensusRules,
     tx: &UnboundedSender<BroadcastEvents>,
     peer: String,
-    message: Message,
+    payload: Payload,
 ) {
-    let payload = Payload::deserialize(message);
     match payload.r#type {
         PayloadType::Blockchain => {
             println!("Receive Blockchain");
-            let b_guard = blockchain.read().unwrap().clone();
-            let new_blockchain = serde_json::from_str::<Vec<Block>>(payload.data.as_str()).unwrap();
+            let new_blockchain = match parse_model::<Vec<Block>>(&payload, tx, &peer) {
+                Some(new_blockchain) => new_blockchain,
+                None => return,
+            };
             println!("Receive Blockchain: \nnew_blockchain {:#?}", new_blockchain);
+            if let Some(latest) = new_blockchain.last() {
+                sync_tracker.observe_tip(&peer, &latest.hash, latest.index, blockchain.load().len() - 1);
+            }
 
-            if get_is_replace_chain(&b_guard, &new_blockchain) {
-                let mut b_guard = blockchain.write().unwrap();
-                let mut u_guard = unspent_tx_outs.write().unwrap();
+            // Peers relaying the same chain in quick succession get the verdict on the first one.
+            let hash = new_blockchain.last().map(|block| block.hash.clone()).unwrap_or_default();
+            let digest = get_chain_digest(&payload.data);
+            let cached = validation_cache.read().await.get_chain_verdict(&hash, &digest, get_adjusted_timestamp());
+            let validated = match cached {
+                Some(verdict) => verdict,
+                None => {
+                    let verdict = validate_chain(&blockchain.load(), &new_blockchain, consensus_rules, &NetworkClock);
+                    validation_cache.write().await.record_chain_verdict(&hash, &digest, &verdict, get_adjusted_timestamp());
+                    verdict
+                }
+            };
+            if let Err(error) = validated {
+                println!("{:#?}", error);
+                reject(tx, &peer, PayloadType::Blockchain, &hash, &error);
+                return;
+            }
+            sync_tracker.confirm(&peer, new_blockchain.len() - 1);
 
+            if get_is_heavier_chain(&blockchain.load(), &new_blockchain) {
                 match get_unspent_tx_outs(&new_blockchain) {
                     Ok(new_unspent_tx_outs) => {
-                        let _ = mem::replace(&mut *b_guard, new_blockchain);
+                        let b_writer = blockchain.write().await;
+                        if !get_is_replace_chain(&b_writer.load(), &new_blockchain, consensus_rules, &NetworkClock) {
+                            return;
+                        }
+                        if blockchain.get_has_invalidated(&new_blockchain) {
+                            println!("Receive Blockchain: {} includes an invalidated block", hash);
+                            return;
+                        }
+
+                        let mut u_guard = unspent_tx_outs.write().await;
+                        let new_blockchain = b_writer.store(new_blockchain, Some(peer.as_str()));
+                        sync_tracker.progress(new_blockchain.len() - 1);
                         let _ = mem::replace(&mut *u_guard, new_unspent_tx_outs);
-                        println!("Receive Blockchain: \nadded_blockchain {:#?}, \nnew_unspent_tx_outs {:#?}", b_guard, u_guard);
-                        tx.send(BroadcastEvents::Blockchain(b_guard.to_vec(), Some(peer.clone()))).unwrap();
+                        address_index.write().await.rebuild(&new_blockchain);
+                        validation_cache.write().await.clear();
+                        println!("Receive Blockchain: \nadded_blockchain {:#?}, \nnew_unspent_tx_outs {:#?}", new_blockchain, u_guard);
+                        tx.send(BroadcastEvents::Blockchain(new_blockchain, Some(peer.clone()))).unwrap();
                     }
                     Err(error) => {
                         println!("{:#?}", error);
+                        validation_cache.write().await.record_chain_verdict(&hash, &digest, &Err(AppError::new(error.code)), get_adjusted_timestamp());
+                        reject(tx, &peer, PayloadType::Blockchain, &hash, &error);
                     }
                 }
             }
         }
+        PayloadType::Reject => {
+            if let Some(reject) = parse_data::<Reject>(&payload, tx, &peer) {
+                tracing::warn!(peer = %peer, r#type = ?reject.r#type, code = reject.code, hash = %reject.hash, reason = %reject.reason, "peer rejected payload");
+                tx.send(BroadcastEvents::Rejected(peer, reject)).unwrap();
+            }
+        }
+        PayloadType::Handshake => {
+            let handshake = match parse_data::<Handshake>(&payload, tx, &peer) {
+                Some(handshake) => handshake,
+                None => return,
+            };
+            sync_tracker.observe(handshake.height, blockchain.load().len() - 1);
+            tx.send(BroadcastEvents::Handshake(peer, handshake)).unwrap();
+        }
+        PayloadType::Ping => {
+            if let Some(ping) = parse_data::<Heartbeat>(&payload, tx, &peer) {
+                tx.send(BroadcastEvents::Reply(peer, Payload::new(PayloadType::Pong, &ping.pong()))).unwrap();
+            }
+        }
+        PayloadType::Pong => {
+            if let Some(pong) = parse_data::<Heartbeat>(&payload, tx, &peer) {
+                let clock = pong.get_clock(Utc::now().timestamp_millis() as usize);
+                tx.send(BroadcastEvents::Clock(peer, clock)).unwrap();
+            }
+        }
+        PayloadType::QueryLatest => {
+            let latest = blockchain.load().last().cloned();
+            tx.send(BroadcastEvents::Reply(peer, Payload::with_model(PayloadType::Latest, &latest))).unwrap();
+        }
+        PayloadType::Latest => {
+            if let Some(latest) = parse_model::<Option<Block>>(&payload, tx, &peer).flatten() {
+                let current_height = blockchain.load().len() - 1;
+                sync_tracker.observe_tip(&peer, &latest.hash, latest.index, current_height);
+                if latest.index > current_height && sync_tracker.get_is_fast_sync(current_height) {
+                    println!("Receive Latest: {} is behind {} at {}, query snapshot", current_height, peer, latest.index);
+                    sync_tracker.query_snapshot(&peer);
+                    tx.send(BroadcastEvents::Reply(peer, Payload::new(PayloadType::QuerySnapshot, &()))).unwrap();
+                } else if latest.index > current_height {
+                    println!("Receive Latest: {} is behind {} at {}, query all", current_height, peer, latest.index);
+                    tx.send(BroadcastEvents::Reply(peer, Payload::new(PayloadType::QueryAll, &()))).unwrap();
+                }
+            }
+        }
+        PayloadType::QueryAll => {
+            // Headers of a utxo snapshot carry no data, so a peer could not replay the chain.
+            let blockchain = blockchain.load();
+            if get_is_pruned(&blockchain) {
+                reject(tx, &peer, PayloadType::QueryAll, &get_latest_block(&blockchain).hash, &AppError::new(1014));
+                return;
+            }
+            tx.send(BroadcastEvents::Reply(peer, Payload::with_model(PayloadType::Blockchain, &*blockchain))).unwrap();
+        }
+        PayloadType::QuerySnapshot => {
+            let blockchain = blockchain.load();
+            let payload = match UtxoSnapshot::new(&blockchain, UTXO_SNAPSHOT_DEPTH) {
+                Ok(snapshot) => Payload::with_model(PayloadType::Snapshot, &snapshot),
+                // Without a commitment deep enough the chain is sent whole, unless it is pruned itself.
+                Err(_) if !get_is_pruned(&blockchain) => Payload::with_model(PayloadType::Blockchain, &*blockchain),
+                Err(error) => {
+                    reject(tx, &peer, PayloadType::QuerySnapshot, &get_latest_block(&blockchain).hash, &error);
+                    return;
+                }
+            };
+            tx.send(BroadcastEvents::Reply(peer, payload)).unwrap();
+        }
+        PayloadType::Snapshot => {
+            println!("Receive Snapshot");
+            if !sync_tracker.take_snapshot_query(&peer) {
+                println!("Receive Snapshot: {} was not queried", peer);
+                return;
+            }
+            let snapshot = match parse_model::<UtxoSnapshot>(&payload, tx, &peer) {
+                Some(snapshot) => snapshot,
+                None => return,
+            };
+            if !sync_tracker.get_is_fast_sync(blockchain.load().len() - 1) {
+                return;
+            }
+            let (snapshot_unspent_tx_outs, snapshot_height) = (snapshot.unspent_tx_outs.clone(), snapshot.headers.len());
+
+            let hash = snapshot.blocks.last().or(snapshot.headers.last()).map(|block| block.hash.clone()).unwrap_or_default();
+            let verified = snapshot.verify(&blockchain.load(), consensus_rules, &NetworkClock);
+            let (new_blockchain, new_unspent_tx_outs) = match verified {
+                Ok(verified) => verified,
+                Err(error) => {
+                    println!("{:#?}", error);
+                    reject(tx, &peer, PayloadType::Snapshot, &hash, &error);
+                    return;
+                }
+            };
+            sync_tracker.confirm(&peer, new_blockchain.len() - 1);
+
+            let b_writer = blockchain.write().await;
+            if !sync_tracker.get_is_fast_sync(b_writer.load().len() - 1) || !get_is_heavier_chain(&b_writer.load(), &new_blockchain) {
+                return;
+            }
+            if blockchain.get_has_invalidated(&new_blockchain) {
+                println!("Receive Snapshot: {} includes an invalidated block", hash);
+                return;
+            }
+
+            // Headers carry no data, so the chain is not announced to peers, which could not validate it.
+            let mut u_guard = unspent_tx_outs.write().await;
+            let new_blockchain = b_writer.store(new_blockchain, Some(peer.as_str()));
+            sync_tracker.progress(new_blockchain.len() - 1);
+            let _ = mem::replace(&mut *u_guard, new_unspent_tx_outs);
+            address_index.write().await.rebuild_from_snapshot(&snapshot_unspent_tx_outs, &new_blockchain[snapshot_height..]);
+            validation_cache.write().await.clear();
+            println!("Receive Snapshot: synced to {} from {}", new_blockchain.len() - 1, peer);
+        }
+        PayloadType::Inventory => {
+            let ids = match parse_data::<Vec<String>>(&payload, tx, &peer) {
+                Some(ids) => ids,
+                None => return,
+            };
+            let missing_ids = get_missing_ids(&ids, &transaction_pool.read().await);
+            tx.send(BroadcastEvents::Known(peer.clone(), ids)).unwrap();
+            if !missing_ids.is_empty() {
+                tx.send(BroadcastEvents::Reply(peer, Payload::new(PayloadType::GetData, &missing_ids))).unwrap();
+            }
+        }
+        PayloadType::GetData => {
+            let ids = match parse_data::<Vec<String>>(&payload, tx, &peer) {
+                Some(ids) => ids,
+                None => return,
+            };
+            let transactions = get_transactions(&ids, &transaction_pool.read().await);
+            tx.send(BroadcastEvents::Reply(peer, Payload::with_model(PayloadType::Transaction, &transactions))).unwrap();
+        }
         PayloadType::Transaction => {
             println!("Receive Transaction");
-            let u_guard = unspent_tx_outs.read().unwrap().clone();
-            let mut t_guard = transaction_pool.write().unwrap();
-            let received_transactions = serde_json::from_str::<Vec<Transaction>>(payload.data.as_str()).unwrap();
+            let u_guard = unspent_tx_outs.read().await.clone();
+            let mut t_guard = transaction_pool.write().await;
+            let received_transactions = match parse_model::<Vec<Transaction>>(&payload, tx, &peer) {
+                Some(received_transactions) => received_transactions,
+                None => return,
+            };
             println!("Receive Transaction: \nreceived_transactions {:#?}", received_transactions);
+            tx.send(BroadcastEvents::Known(peer.clone(), received_transactions.iter().map(|transaction| transaction.id.clone()).collect())).unwrap();
 
+            let context = BlockContext::next(get_latest_block(&blockchain.load()), &NetworkClock);
+            let mut added = false;
             for transaction in received_transactions {
-                match add_to_transaction_pool(&transaction, &mut t_guard, &u_guard) {
+                match add_to_transaction_pool(&transaction, &mut t_guard, &u_guard, context, mempool_policy, &mut *validation_cache.write().await) {
                     Ok(_) => {
                         println!("Receive Transaction: \nadded_transactions {:#?}", t_guard);
-                        tx.send(BroadcastEvents::Transaction(t_guard.to_vec(), Some(peer.clone()))).unwrap();
+                        added = true;
                     }
                     Err(error) => {
                         println!("{:#?}", error);
+                        // A transaction pooled already is only late, not invalid.
+                        if !t_guard.iter().any(|pooled| pooled.id == transaction.id) {
+                            reject(tx, &peer, PayloadType::Transaction, &transaction.id, &error);
+                        }
                     }
                 }
             }
+            if added {
+                tx.send(BroadcastEvents::Transaction(t_guard.to_vec(), Some(peer.clone()))).unwrap();
+            }
         }
     }
 }
+
+/// Tell peer its block or transaction failed validation with error.
+fn reject(tx: &UnboundedSender<BroadcastEvents>, peer: &str, r#type: PayloadType, hash: &str, error: &AppError) {
+    let reject = Reject::new(r#type, hash, error);
+    let _ = tx.send(BroadcastEvents::Reply(peer.to_string(), Payload::new(PayloadType::Reject, &reject)));
+}
+
+/// Parse payload data, reporting the peer when it is malformed.
+fn parse_data<T: DeserializeOwned>(payload: &Payload, tx: &UnboundedSender<BroadcastEvents>, peer: &str) -> Option<T> {
+    match serde_json::from_str::<T>(payload.data.as_str()) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            println!("Invalid payload : {} {:?}, {:?}", peer, payload.r#type, e);
+            let _ = tx.send(BroadcastEvents::Invalid(peer.to_string()));
+            None
+        }
+    }
+}
+
+fn parse_model<T: Model>(payload: &Payload, tx: &UnboundedSender<BroadcastEvents>, peer: &str) -> Option<T> {
+    parse_data::<T::Dto>(payload, tx, peer).map(T::from_dto)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::miner::{JobStatus, MineRequest, Miner};
+    use crate::testkit::{get_genesis_block, get_wallet, Keypair, ALICE, BOB};
+    use crate::wallet::create_transaction;
+    use crate::wire::WireFormat;
+    use super::*;
+
+    const CONVERGENCE_TIMEOUT_SECS: u64 = 10;
+
+    /// Node whose broadcaster listens on an ephemeral port, with the state it shares with its miner.
+    struct Node {
+        address: String,
+        blockchain: Arc<ChainState>,
+        unspent_tx_outs: Arc<RwLock<Vec<UnspentTxOut>>>,
+        transaction_pool: Arc<RwLock<Vec<Transaction>>>,
+        validation_cache: Arc<RwLock<ValidationCache>>,
+        peer_registry: Arc<PeerRegistry>,
+        miner: Arc<Miner>,
+        sender: UnboundedSender<BroadcastEvents>,
+    }
+
+    /// Boot a node on the genesis block with the wallet of keypair.
+    async fn spawn_node(keypair: Keypair) -> Node {
+        let blockchain = Arc::new(ChainState::new(vec![get_genesis_block()]));
+        let unspent_tx_outs = Arc::new(RwLock::new(get_unspent_tx_outs(&blockchain.load()).unwrap()));
+        let transaction_pool = Arc::new(RwLock::new(vec![]));
+        let wallet = Arc::new(RwLock::new(get_wallet(keypair)));
+        let address_index = Arc::new(RwLock::new(AddressIndex::new(&blockchain.load())));
+        let validation_cache = Arc::new(RwLock::new(ValidationCache::default()));
+        let peer_registry = Arc::new(PeerRegistry::default());
+        let mempool_policy = MempoolPolicy::new(1);
+
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let transport = WebSocketTransport::listen(&["127.0.0.1:0".parse().unwrap()], WireFormat::Json, event_sender).await.unwrap();
+        let address = format!("ws://{}", transport.get_local_addrs()[0]);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let miner = Arc::new(Miner::launch(
+            &blockchain, &unspent_tx_outs, &transaction_pool, &wallet, &address_index, &validation_cache,
+            None, None, mempool_policy, ConsensusRules::default(), sender.clone(),
+        ));
+        tokio::spawn(broadcast(
+            Box::new(transport), None, None, Arc::clone(&blockchain), Arc::clone(&unspent_tx_outs), Arc::clone(&transaction_pool),
+            wallet, address_index, Arc::new(TipNotifier::default()), Arc::new(InvoiceBook::default()), Arc::clone(&validation_cache),
+            Arc::new(SyncTracker::default()), Arc::clone(&peer_registry), mempool_policy, ConsensusRules::default(), 8, 8, sender.clone(), receiver, event_receiver,
+        ));
+        Node { address, blockchain, unspent_tx_outs, transaction_pool, validation_cache, peer_registry, miner, sender }
+    }
+
+    /// Boot two nodes and connect the second to the first, returning once both see the connection.
+    async fn spawn_pair() -> (Node, Node) {
+        let a = spawn_node(ALICE).await;
+        let b = spawn_node(BOB).await;
+        b.sender.send(BroadcastEvents::Peer(a.address.clone())).unwrap();
+        wait_until(|| a.peer_registry.get_peers().len() == 1 && b.peer_registry.get_peers().len() == 1).await;
+        (a, b)
+    }
+
+    /// Wait until condition holds, failing the test past the convergence timeout.
+    async fn wait_until(condition: impl Fn() -> bool) {
+        let waited = tokio::time::timeout(Duration::from_secs(CONVERGENCE_TIMEOUT_SECS), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }).await;
+        assert!(waited.is_ok(), "nodes did not converge in {} seconds", CONVERGENCE_TIMEOUT_SECS);
+    }
+
+    /// Mine a block with the coinbase and pool of node, returning it once appended.
+    async fn mine(node: &Node) -> Block {
+        let miner = Arc::clone(&node.miner);
+        let status = tokio::task::spawn_blocking(move || {
+            let job = miner.submit(MineRequest::Coinbase { payout_address: None });
+            miner.wait_job(&job.id).unwrap().status
+        }).await.unwrap();
+        match status {
+            JobStatus::Mined { block } => block,
+            status => panic!("unexpected status {:?}", status),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_chain_convergence() {
+        let (a, b) = spawn_pair().await;
+        assert_eq!(b.peer_registry.get_peers()[0].peer, a.address);
+        assert!(b.peer_registry.get_dials().is_empty());
+
+        let block = mine(&a).await;
+        wait_until(|| b.blockchain.load().last().map(|latest| latest.hash.clone()) == Some(block.hash.clone())).await;
+        assert_eq!(b.blockchain.get_arrival(&block.hash).unwrap().source, Some(a.address.clone()));
+        assert_eq!(b.unspent_tx_outs.read().await.len(), a.unspent_tx_outs.read().await.len());
+
+        let block = mine(&b).await;
+        wait_until(|| a.blockchain.load().len() == 3).await;
+        assert_eq!(a.blockchain.load()[2].hash, block.hash);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pool_relay() {
+        let (a, b) = spawn_pair().await;
+
+        let transaction = {
+            let u_guard = a.unspent_tx_outs.read().await;
+            let wallet = get_wallet(ALICE);
+            create_transaction(BOB.address, 10, &wallet, &u_guard, 1).unwrap()
+        };
+        {
+            let u_guard = a.unspent_tx_outs.read().await;
+            let mut t_guard = a.transaction_pool.write().await;
+            let context = BlockContext::next(get_latest_block(&a.blockchain.load()), &NetworkClock);
+            add_to_transaction_pool(&transaction, &mut t_guard, &u_guard, context, &MempoolPolicy::new(1), &mut *a.validation_cache.write().await).unwrap();
+            a.sender.send(BroadcastEvents::Transaction(t_guard.to_vec(), None)).unwrap();
+        }
+        wait_until(|| b.transaction_pool.try_read().map_or(false, |pool| pool.iter().any(|pooled| pooled.id == transaction.id))).await;
+
+        let block = mine(&b).await;
+        assert!(block.data.iter().any(|mined| mined.id == transaction.id));
+        wait_until(|| a.blockchain.load().len() == 2).await;
+        assert_eq!(a.blockchain.load()[1].hash, block.hash);
+    }
+}
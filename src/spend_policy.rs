@@ -0,0 +1,187 @@
+use std::fmt;
+use std::sync::Mutex;
+use serde::Serialize;
+
+use crate::Transaction;
+
+const SECONDS_PER_DAY: usize = 24 * 60 * 60;
+
+/// Rule of the spend policy a transaction broke.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PolicyViolation {
+    /// Transaction pays more than allowed at once
+    MaxAmount { amount: usize, max_amount: usize },
+
+    /// Transaction pays more than left of the limit of the day
+    DailyLimit { amount: usize, spent: usize, daily_limit: usize },
+
+    /// Transaction pays an address which is not allowed
+    Destination { address: String },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyViolation::MaxAmount { amount, max_amount } => {
+                write!(f, "amount {} exceeds max amount {}", amount, max_amount)
+            }
+            PolicyViolation::DailyLimit { amount, spent, daily_limit } => {
+                write!(f, "amount {} with {} spent today exceeds daily limit {}", amount, spent, daily_limit)
+            }
+            PolicyViolation::Destination { address } => write!(f, "address {} is not allowed", address),
+        }
+    }
+}
+
+/// Rules approving what the wallet pays to other addresses, checked before a transaction is broadcast.
+///
+/// Only coins count toward amounts, every address paid must be allowed whatever it receives.
+#[derive(Debug, Default)]
+pub struct SpendPolicy {
+    /// Most coins paid by one transaction
+    pub max_amount: Option<usize>,
+
+    /// Most coins paid in a UTC day
+    pub daily_limit: Option<usize>,
+
+    /// Addresses which may be paid, any when empty
+    pub allowed_addresses: Vec<String>,
+
+    /// Day and coins paid on it
+    spent: Mutex<(usize, usize)>,
+}
+
+impl SpendPolicy {
+    /// Returns a policy of rules, none spent yet
+    pub fn new(max_amount: Option<usize>, daily_limit: Option<usize>, allowed_addresses: Vec<String>) -> SpendPolicy {
+        SpendPolicy { max_amount, daily_limit, allowed_addresses, spent: Mutex::new((0, 0)) }
+    }
+
+    /// Check payment of amount to address at timestamp without spending it, as a preflight of a payment.
+    pub fn check_payment(&self, address: &str, amount: usize, timestamp: usize) -> Result<usize, PolicyViolation> {
+        self.check_payments(&[(address, amount)], &self.spent.lock().unwrap(), timestamp)
+    }
+
+    /// Approve transaction of a wallet owning wallet addresses at timestamp, returning the coins it pays to others
+    /// once they count toward the limit of the day.
+    ///
+    /// Change and data outputs are not payments.
+    pub fn try_spend(&self, transaction: &Transaction, wallet_addresses: &[String], timestamp: usize) -> Result<usize, PolicyViolation> {
+        let payments: Vec<(&str, usize)> = transaction.tx_outs
+            .iter()
+            .filter(|tx_out| !tx_out.get_is_unspendable() && !wallet_addresses.contains(&tx_out.address))
+            .map(|tx_out| (tx_out.address.as_str(), if tx_out.asset.is_none() { tx_out.amount } else { 0 }))
+            .collect();
+        self.try_spend_payments(&payments, timestamp)
+    }
+
+    /// Approve payment of amount to address at timestamp like `try_spend`, before the transaction paying it is created.
+    pub fn try_spend_payment(&self, address: &str, amount: usize, timestamp: usize) -> Result<usize, PolicyViolation> {
+        self.try_spend_payments(&[(address, amount)], timestamp)
+    }
+
+    /// Give back amount approved by `try_spend` at timestamp for a payment which was not made after all.
+    pub fn refund(&self, amount: usize, timestamp: usize) {
+        let mut spent = self.spent.lock().unwrap();
+        if spent.0 == timestamp / SECONDS_PER_DAY {
+            spent.1 = spent.1.saturating_sub(amount);
+        }
+    }
+
+    /// Check payments and record them under one lock, so concurrent payments are never approved over the limit.
+    fn try_spend_payments(&self, payments: &[(&str, usize)], timestamp: usize) -> Result<usize, PolicyViolation> {
+        let mut spent = self.spent.lock().unwrap();
+        let amount = self.check_payments(payments, &spent, timestamp)?;
+        let day = timestamp / SECONDS_PER_DAY;
+        if spent.0 != day {
+            *spent = (day, 0);
+        }
+        spent.1 += amount;
+        Ok(amount)
+    }
+
+    fn check_payments(&self, payments: &[(&str, usize)], spent: &(usize, usize), timestamp: usize) -> Result<usize, PolicyViolation> {
+        if !self.allowed_addresses.is_empty() {
+            let denied = payments.iter().find(|(address, _)| !self.allowed_addresses.iter().any(|allowed| allowed == address));
+            if let Some((address, _)) = denied {
+                return Err(PolicyViolation::Destination { address: address.to_string() });
+            }
+        }
+
+        let amount = payments.iter().map(|(_, amount)| amount).sum();
+        if let Some(max_amount) = self.max_amount {
+            if amount > max_amount {
+                return Err(PolicyViolation::MaxAmount { amount, max_amount });
+            }
+        }
+        if let Some(daily_limit) = self.daily_limit {
+            let (day, spent) = *spent;
+            let spent = if day == timestamp / SECONDS_PER_DAY { spent } else { 0 };
+            if spent + amount > daily_limit {
+                return Err(PolicyViolation::DailyLimit { amount, spent, daily_limit });
+            }
+        }
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{ALICE, BOB, RECEIVER_ADDRESS};
+    use crate::transaction::TxOut;
+    use super::*;
+
+    #[test]
+    fn test_try_spend() {
+        let transaction = Transaction::generate(&vec![], &vec![
+            TxOut::new(RECEIVER_ADDRESS.to_string(), 10),
+            TxOut::new(ALICE.address.to_string(), 40),
+        ]);
+        let wallet_addresses = vec![ALICE.address.to_string()];
+
+        assert_eq!(SpendPolicy::default().try_spend(&transaction, &wallet_addresses, 0), Ok(10));
+        assert_eq!(
+            SpendPolicy::new(Some(9), None, vec![]).try_spend(&transaction, &wallet_addresses, 0),
+            Err(PolicyViolation::MaxAmount { amount: 10, max_amount: 9 }),
+        );
+        assert_eq!(
+            SpendPolicy::new(None, None, vec![BOB.address.to_string()]).try_spend(&transaction, &wallet_addresses, 0),
+            Err(PolicyViolation::Destination { address: RECEIVER_ADDRESS.to_string() }),
+        );
+        assert_eq!(
+            SpendPolicy::new(Some(10), None, vec![RECEIVER_ADDRESS.to_string()]).try_spend(&transaction, &wallet_addresses, 0),
+            Ok(10),
+        );
+    }
+
+    #[test]
+    fn test_try_spend_payment() {
+        let spend_policy = SpendPolicy::new(None, Some(100), vec![]);
+        let day = 19_000 * SECONDS_PER_DAY;
+        assert_eq!(spend_policy.try_spend_payment(BOB.address, 60, day), Ok(60));
+        assert_eq!(
+            spend_policy.try_spend_payment(BOB.address, 50, day + 10),
+            Err(PolicyViolation::DailyLimit { amount: 50, spent: 60, daily_limit: 100 }),
+        );
+        assert_eq!(
+            spend_policy.check_payment(BOB.address, 50, day + 10),
+            Err(PolicyViolation::DailyLimit { amount: 50, spent: 60, daily_limit: 100 }),
+        );
+        assert_eq!(spend_policy.check_payment(BOB.address, 40, day + 10), Ok(40));
+        assert_eq!(spend_policy.check_payment(BOB.address, 100, day + SECONDS_PER_DAY), Ok(100));
+
+        spend_policy.refund(20, day);
+        assert_eq!(spend_policy.try_spend_payment(BOB.address, 60, day + 10), Ok(60));
+        assert_eq!(
+            spend_policy.check_payment(BOB.address, 1, day + 10),
+            Err(PolicyViolation::DailyLimit { amount: 1, spent: 100, daily_limit: 100 }),
+        );
+
+        assert_eq!(spend_policy.try_spend_payment(BOB.address, 10, day + SECONDS_PER_DAY), Ok(10));
+        assert_eq!(
+            spend_policy.check_payment(BOB.address, 95, day + SECONDS_PER_DAY),
+            Err(PolicyViolation::DailyLimit { amount: 95, spent: 10, daily_limit: 100 }),
+        );
+    }
+}
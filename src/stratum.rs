@@ -0,0 +1,436 @@
+//! Mining protocol for external workers, lines of JSON over TCP in the manner of stratum.
+//!
+//! A worker sends `{"id":1,"method":"subscribe","params":{"worker":"alice"}}` and is notified of jobs with
+//! `{"method":"notify","params":{...}}`. It appends a nonce as 8 big-endian bytes to the header of the job, hashes it
+//! with the pow algorithm of the job and submits `{"id":2,"method":"submit","params":{"job_id":"...","nonce":7}}`
+//! once the hash has the leading zero bits of the share difficulty. Shares which also match the block difficulty
+//! are appended as blocks paying the node.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{Block, Transaction, UnspentTxOut, Wallet};
+use crate::activation::{get_block_version, get_is_active, Rule};
+use crate::block::{get_coinbase_template, get_difficulty, get_latest_block};
+use crate::chain_state::ChainState;
+use crate::codec::encode_block_content;
+use crate::consensus_rules::ConsensusRules;
+use crate::errors::AppError;
+use crate::miner::{get_consensus_pool, JobStatus, MineRequest, Miner};
+use crate::network_time::{Clock, NetworkClock};
+use crate::pow::{get_pow_algorithm, PowAlgorithm};
+use crate::tx_root::get_tx_root_at;
+use crate::utxo_commitment::get_next_utxo_commitment;
+
+const TEMPLATE_POLL_MILLIS: u64 = 1_000;
+
+/// Seconds a template is worked on before it is renewed with a fresh timestamp and pool transactions.
+const TEMPLATE_INTERVAL_SECS: u64 = 30;
+
+/// Workers connected at once, later ones are closed until others leave.
+const MAX_WORKERS: usize = 64;
+
+/// Lines queued for a worker, which is dropped from notifications when it falls this far behind.
+const WORKER_QUEUE: usize = 16;
+
+/// Seconds a write to a worker may block before the worker is disconnected.
+const WRITE_TIMEOUT_SECS: u64 = 10;
+
+/// Work sent to workers.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct StratumJob {
+    pub job_id: String,
+    pub pow: PowAlgorithm,
+
+    /// Hex of the block content hashed by proof of work, without the trailing nonce
+    pub header: String,
+    pub index: usize,
+    pub previous_hash: String,
+
+    /// Leading zero bits of the hash of a share
+    pub share_difficulty: usize,
+
+    /// Leading zero bits of the hash of a block
+    pub difficulty: usize,
+}
+
+/// Shares submitted by a worker.
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+pub struct WorkerStats {
+    pub worker: String,
+
+    /// Shares matching the share difficulty
+    pub accepted: usize,
+
+    /// Shares of stale jobs, duplicated, or above the target
+    pub rejected: usize,
+
+    /// Shares appended as blocks
+    pub blocks: usize,
+
+    /// Time of the last accepted share
+    pub last_share_at: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Template {
+    job: StratumJob,
+    block: Block,
+    created_at: Instant,
+    nonces: HashSet<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subscribe {
+    worker: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Submit {
+    job_id: String,
+    nonce: usize,
+}
+
+/// Pool of workers mining templates of the node, counting their shares.
+#[derive(Debug)]
+pub struct Stratum {
+    share_difficulty: usize,
+    template: Mutex<Option<Template>>,
+    workers: Mutex<BTreeMap<String, WorkerStats>>,
+
+    /// Queues of the writers of subscribed workers
+    subscribers: Mutex<Vec<SyncSender<String>>>,
+    connections: AtomicUsize,
+}
+
+impl Stratum {
+    /// Returns a pool accepting shares of share difficulty, without a template yet
+    pub fn new(share_difficulty: usize) -> Stratum {
+        Stratum {
+            share_difficulty,
+            template: Mutex::new(None),
+            workers: Mutex::new(BTreeMap::new()),
+            subscribers: Mutex::new(vec![]),
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    /// Replace the template by a block of data on top of blockchain and its unspent tx outs at the time of clock,
+    /// returning its job.
+    ///
+    /// Shares of previous jobs are stale from then on.
+    pub fn set_template(&self, blockchain: &Vec<Block>, unspent_tx_outs: &Vec<UnspentTxOut>, data: Vec<Transaction>, clock: &dyn Clock) -> StratumJob {
+        let latest = get_latest_block(blockchain);
+        let index = latest.index + 1;
+        let difficulty = get_difficulty(blockchain);
+        let utxo_commitment = if get_is_active(Rule::UtxoCommitment, index) {
+            get_next_utxo_commitment(&data, unspent_tx_outs, index)
+        } else {
+            None
+        };
+        let block = Block {
+            version: get_block_version(index),
+            pow: get_pow_algorithm(),
+            tx_root: get_tx_root_at(index, &data),
+            utxo_commitment,
+            ..Block::new(index, "".to_string(), latest.hash.clone(), clock.now().max(latest.timestamp), data, difficulty, 0)
+        };
+        let mut header = encode_block_content(
+            block.version, block.pow, block.index, &block.previous_hash, block.timestamp, &block.data,
+            block.tx_root.as_deref(), block.utxo_commitment.as_deref(), block.difficulty, 0,
+        );
+        header.truncate(header.len() - 8);
+
+        let job = StratumJob {
+            job_id: format!("{}", Uuid::new_v4()),
+            pow: block.pow,
+            header: hex::encode(header),
+            index,
+            previous_hash: block.previous_hash.clone(),
+            share_difficulty: self.share_difficulty.min(difficulty),
+            difficulty,
+        };
+        *self.template.lock().unwrap() = Some(Template { job: job.clone(), block, created_at: Instant::now(), nonces: HashSet::new() });
+        job
+    }
+
+    /// Get job of the current template
+    pub fn get_job(&self) -> Option<StratumJob> {
+        self.template.lock().unwrap().as_ref().map(|template| template.job.clone())
+    }
+
+    /// Submit share of worker for job at timestamp, returning the block if it matches the block difficulty.
+    ///
+    /// # Errors
+    /// If job is not the current one, it returns error 11002, if nonce was already submitted error 11003,
+    /// and if hash does not match the share difficulty error 11004.
+    pub fn submit(&self, worker: &str, job_id: &str, nonce: usize, timestamp: usize) -> Result<Option<Block>, AppError> {
+        let result = self.check_share(job_id, nonce);
+        let mut workers = self.workers.lock().unwrap();
+        let stats = workers.entry(worker.to_string()).or_insert_with(|| WorkerStats { worker: worker.to_string(), ..WorkerStats::default() });
+        match &result {
+            Ok(_) => {
+                stats.accepted += 1;
+                stats.last_share_at = Some(timestamp);
+            }
+            Err(_) => stats.rejected += 1,
+        }
+        result
+    }
+
+    /// Count block of worker appended to the blockchain
+    pub fn record_block(&self, worker: &str) {
+        if let Some(stats) = self.workers.lock().unwrap().get_mut(worker) {
+            stats.blocks += 1;
+        }
+    }
+
+    /// Get shares of every worker which submitted any
+    pub fn get_workers(&self) -> Vec<WorkerStats> {
+        self.workers.lock().unwrap().values().cloned().collect()
+    }
+
+    fn check_share(&self, job_id: &str, nonce: usize) -> Result<Option<Block>, AppError> {
+        let mut template = self.template.lock().unwrap();
+        let template = template.as_mut().filter(|template| template.job.job_id == job_id).ok_or(AppError::new(11002))?;
+        if !template.nonces.insert(nonce) {
+            return Err(AppError::new(11003));
+        }
+
+        let mut block = Block { nonce, ..template.block.clone() };
+        block.hash = block.get_calculated_hash();
+        let hasher = block.pow.get_hasher();
+        if !hasher.get_is_hash_matches_difficulty(&block.hash, template.job.share_difficulty) {
+            return Err(AppError::new(11004));
+        }
+        Ok(Some(block).filter(|block| hasher.get_is_hash_matches_difficulty(&block.hash, block.difficulty)))
+    }
+
+    /// Listen for workers on port, and spawn a thread renewing the template whenever the tip changes or it ages,
+    /// with coinbase paying payout address or the wallet when none and pool transactions consensus rules accept.
+    ///
+    /// At most `MAX_WORKERS` workers are served at once.
+    ///
+    /// # Errors
+    /// If port cannot be bound, it returns error 11000.
+    pub fn launch(
+        self: &Arc<Self>,
+        port: u16,
+        blockchain: &Arc<ChainState>,
+        unspent_tx_outs: &Arc<RwLock<Vec<UnspentTxOut>>>,
+        transaction_pool: &Arc<RwLock<Vec<Transaction>>>,
+        wallet: &Arc<RwLock<Wallet>>,
+        payout_address: Option<String>,
+        miner_tag: Option<String>,
+        consensus_rules: ConsensusRules,
+        miner: &Arc<Miner>,
+    ) -> Result<(), AppError> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|_| AppError::new(11000))?;
+
+        let stratum = Arc::clone(self);
+        let b = Arc::clone(blockchain);
+        let u = Arc::clone(unspent_tx_outs);
+        let t = Arc::clone(transaction_pool);
+        let w = Arc::clone(wallet);
+        thread::spawn(move || loop {
+            let job = {
+                // The unspent tx outs are updated with the chain while their lock is held, so both match.
+                let u_guard = u.blocking_read();
+                let chain_snapshot = b.load();
+                let is_stale = stratum.template.lock().unwrap().as_ref().map_or(true, |template| {
+                    template.job.previous_hash != get_latest_block(&chain_snapshot).hash ||
+                        template.created_at.elapsed() >= Duration::from_secs(TEMPLATE_INTERVAL_SECS)
+                });
+                is_stale.then(|| {
+                    let pool_snapshot = get_consensus_pool(&t.blocking_read(), &consensus_rules);
+                    let payout_address = payout_address.clone().unwrap_or_else(|| w.blocking_read().public_key.clone());
+                    let name_registry = &b.read_chain_index().name_registry;
+                    let data = get_coinbase_template(&chain_snapshot, name_registry, &pool_snapshot, &payout_address, miner_tag.as_deref().unwrap_or_default());
+                    stratum.set_template(&chain_snapshot, &u_guard, data, &NetworkClock)
+                })
+            };
+            if let Some(job) = job {
+                stratum.notify(&job);
+            }
+            thread::sleep(Duration::from_millis(TEMPLATE_POLL_MILLIS));
+        });
+
+        let stratum = Arc::clone(self);
+        let m = Arc::clone(miner);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let is_admitted = stratum.connections
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |connections| (connections < MAX_WORKERS).then(|| connections + 1))
+                    .is_ok();
+                if !is_admitted {
+                    continue;
+                }
+                let stratum = Arc::clone(&stratum);
+                let miner = Arc::clone(&m);
+                thread::spawn(move || {
+                    serve(&stratum, miner, stream);
+                    stratum.connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Queue job for every subscribed worker, forgetting those which disconnected or fell behind
+    fn notify(&self, job: &StratumJob) {
+        let line = format!("{}\n", json!({ "method": "notify", "params": job }));
+        self.subscribers.lock().unwrap().retain(|sender| sender.try_send(line.clone()).is_ok());
+    }
+}
+
+/// Write lines queued for a worker until the queue closes or a write fails or times out.
+fn write_lines(mut stream: TcpStream, receiver: Receiver<String>) {
+    for line in receiver {
+        if stream.write_all(line.as_bytes()).is_err() {
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+/// Answer requests of a worker until it disconnects.
+///
+/// Answers and jobs are queued for a writer thread of the worker, so a slow worker never blocks others.
+fn serve(stratum: &Stratum, miner: Arc<Miner>, stream: TcpStream) {
+    let writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    if writer.set_write_timeout(Some(Duration::from_secs(WRITE_TIMEOUT_SECS))).is_err() {
+        return;
+    }
+    let (sender, receiver) = sync_channel(WORKER_QUEUE);
+    thread::spawn(move || write_lines(writer, receiver));
+
+    let mut worker: Option<String> = None;
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = serde_json::from_str::<Request>(&line);
+        let is_subscribe = matches!(&request, Ok(request) if request.method == "subscribe");
+        let (id, result) = match request {
+            Ok(request) => (request.id.clone(), handle(stratum, &miner, &mut worker, &sender, request)),
+            Err(_) => (Value::Null, Err(AppError::new(11001))),
+        };
+        let is_subscribed = is_subscribe && result.is_ok();
+        let response = match result {
+            Ok(result) => json!({ "id": id, "result": result, "error": null }),
+            Err(e) => json!({ "id": id, "result": null, "error": { "code": e.code, "message": e.to_string() } }),
+        };
+        if sender.send(format!("{}\n", response)).is_err() {
+            return;
+        }
+
+        // The current job follows the answer to subscribe, later jobs are sent on renewal of the template.
+        if let Some(job) = stratum.get_job().filter(|_| is_subscribed) {
+            if sender.send(format!("{}\n", json!({ "method": "notify", "params": job }))).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn handle(stratum: &Stratum, miner: &Miner, worker: &mut Option<String>, sender: &SyncSender<String>, request: Request) -> Result<Value, AppError> {
+    match request.method.as_str() {
+        "subscribe" => {
+            let subscribe: Subscribe = serde_json::from_value(request.params).map_err(|_| AppError::new(11001))?;
+            if worker.is_none() {
+                stratum.subscribers.lock().unwrap().push(sender.clone());
+            }
+            *worker = Some(subscribe.worker.clone());
+            Ok(json!({ "worker": subscribe.worker }))
+        }
+        "submit" => {
+            let worker = worker.as_deref().ok_or(AppError::new(11005))?;
+            let submit: Submit = serde_json::from_value(request.params).map_err(|_| AppError::new(11001))?;
+            let block = match stratum.submit(worker, &submit.job_id, submit.nonce, Utc::now().timestamp() as usize)? {
+                Some(block) => block,
+                None => return Ok(json!({ "accepted": true, "block": null })),
+            };
+
+            let job = miner.submit(MineRequest::Solved(block));
+            match miner.wait_job(&job.id).map(|job| job.status) {
+                Some(JobStatus::Mined { block }) => {
+                    stratum.record_block(worker);
+                    Ok(json!({ "accepted": true, "block": block.hash }))
+                }
+                Some(JobStatus::Failed { code }) => Err(AppError::new(code)),
+                _ => Ok(json!({ "accepted": true, "block": null })),
+            }
+        }
+        _ => Err(AppError::new(11001)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::network_time::MockClock;
+    use crate::testkit::{get_genesis_block, RECEIVER_ADDRESS};
+    use crate::transaction::get_coinbase_transaction;
+    use super::*;
+
+    #[test]
+    fn test_submit() {
+        let blockchain = vec![get_genesis_block()];
+        let stratum = Stratum::new(4);
+        let data = vec![get_coinbase_transaction(RECEIVER_ADDRESS, 1, None)];
+        let clock = MockClock::new(blockchain[0].timestamp + 10);
+        let job = stratum.set_template(&blockchain, &vec![], data.clone(), &clock);
+        assert_eq!(job.index, 1);
+        assert_eq!(job.difficulty, 0);
+        assert_eq!(job.share_difficulty, 0);
+        assert_eq!(stratum.get_job(), Some(job.clone()));
+
+        let mut header = hex::decode(&job.header).unwrap();
+        header.extend_from_slice(&7u64.to_be_bytes());
+        let block = stratum.submit("alice", &job.job_id, 7, 100).unwrap().unwrap();
+        assert_eq!(block.hash, job.pow.get_hasher().hash(&header));
+        assert_eq!(*block.data, data);
+        assert_eq!(block.timestamp, clock.now());
+        assert!(block.get_is_valid_hash());
+
+        assert_eq!(stratum.submit("alice", &job.job_id, 7, 101).unwrap_err().code, 11003);
+        assert_eq!(stratum.submit("bob", "stale", 8, 102).unwrap_err().code, 11002);
+        stratum.record_block("alice");
+
+        let workers = stratum.get_workers();
+        assert_eq!(workers.len(), 2);
+        assert_eq!(workers[0], WorkerStats { worker: "alice".to_string(), accepted: 1, rejected: 1, blocks: 1, last_share_at: Some(100) });
+        assert_eq!(workers[1].rejected, 1);
+
+        let next = stratum.set_template(&blockchain, &vec![], data, &clock);
+        assert_ne!(next.job_id, job.job_id);
+        assert_eq!(stratum.submit("alice", &job.job_id, 9, 103).unwrap_err().code, 11002);
+    }
+}
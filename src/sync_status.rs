@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::Serialize;
+
+/// Progress of catching up with the longest chain announced by peers.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct SyncStatus {
+    /// Local chain is behind a peer
+    pub syncing: bool,
+
+    /// Index of local latest block
+    pub current_height: usize,
+
+    /// Highest index of a chain verified from peers
+    pub target_height: usize,
+
+    /// Blocks added since the sync started
+    pub downloaded: usize,
+
+    /// Estimated seconds left, once progress was made
+    pub eta_seconds: Option<u64>,
+}
+
+/// Latest block a peer announced.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PeerTip {
+    pub hash: String,
+    pub height: usize,
+}
+
+#[derive(Debug, Default)]
+struct SyncState {
+    /// Heights of the chains verified from peers, unlike the heights they claim
+    peer_heights: HashMap<String, usize>,
+    peer_tips: HashMap<String, PeerTip>,
+    started: Option<(Instant, usize)>,
+
+    /// Peers a utxo snapshot was queried from and not received yet
+    snapshot_queries: HashSet<String>,
+}
+
+/// Tracks heights announced by peers against the local chain.
+#[derive(Debug, Default)]
+pub struct SyncTracker {
+    state: Mutex<SyncState>,
+    fast_sync: bool,
+}
+
+impl SyncTracker {
+    /// Returns tracker, fast-syncing from a utxo snapshot of a peer instead of its whole chain if fast sync
+    pub fn new(fast_sync: bool) -> SyncTracker {
+        SyncTracker { fast_sync, ..SyncTracker::default() }
+    }
+
+    /// Get flag to query a utxo snapshot rather than the whole chain, only while the local chain is just genesis
+    pub fn get_is_fast_sync(&self, current_height: usize) -> bool {
+        self.fast_sync && current_height == 0
+    }
+
+    /// Record a utxo snapshot is queried from peer
+    pub fn query_snapshot(&self, peer: &str) {
+        self.state.lock().unwrap().snapshot_queries.insert(peer.to_string());
+    }
+
+    /// Get flag a utxo snapshot was queried from peer, consuming the query so a peer sends one snapshot per query
+    pub fn take_snapshot_query(&self, peer: &str) -> bool {
+        self.state.lock().unwrap().snapshot_queries.remove(peer)
+    }
+
+    /// Record height claimed by a peer, starting a sync if it is ahead.
+    ///
+    /// The claim is not checked, so it does not raise the target height until `confirm`.
+    pub fn observe(&self, height: usize, current_height: usize) {
+        let mut state = self.state.lock().unwrap();
+        if height > current_height && state.started.is_none() {
+            state.started = Some((Instant::now(), current_height));
+        }
+    }
+
+    /// Record height of peer once the headers it sent up to it are verified
+    pub fn confirm(&self, peer: &str, height: usize) {
+        let mut state = self.state.lock().unwrap();
+        let peer_height = state.peer_heights.entry(peer.to_string()).or_insert(0);
+        *peer_height = height.max(*peer_height);
+    }
+
+    /// Forget heights, tip and snapshot query of peer once it disconnected
+    pub fn forget(&self, peer: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.peer_heights.remove(peer);
+        state.peer_tips.remove(peer);
+        state.snapshot_queries.remove(peer);
+    }
+
+    /// Record latest block announced by peer, starting a sync if it is ahead
+    pub fn observe_tip(&self, peer: &str, hash: &str, height: usize, current_height: usize) {
+        self.observe(height, current_height);
+        let tip = PeerTip { hash: hash.to_string(), height };
+        self.state.lock().unwrap().peer_tips.insert(peer.to_string(), tip);
+    }
+
+    /// Get latest blocks announced by peers, by peer
+    pub fn get_tips(&self) -> HashMap<String, PeerTip> {
+        self.state.lock().unwrap().peer_tips.clone()
+    }
+
+    /// Record local height change, logging progress while syncing
+    pub fn progress(&self, current_height: usize) {
+        let status = self.get_status(current_height);
+        if status.downloaded == 0 && !status.syncing {
+            return;
+        }
+
+        tracing::info!(
+            current_height = status.current_height,
+            target_height = status.target_height,
+            downloaded = status.downloaded,
+            eta_seconds = status.eta_seconds,
+            "sync progress"
+        );
+        if !status.syncing {
+            self.state.lock().unwrap().started = None;
+        }
+    }
+
+    /// Get sync status against the local height
+    pub fn get_status(&self, current_height: usize) -> SyncStatus {
+        let state = self.state.lock().unwrap();
+        let target_height = state.peer_heights.values().copied().max().unwrap_or(0).max(current_height);
+        let (downloaded, eta_seconds) = match state.started {
+            Some((started, start_height)) => {
+                let downloaded = current_height.saturating_sub(start_height);
+                let elapsed = started.elapsed().as_secs_f64();
+                let eta_seconds = if downloaded > 0 && elapsed > 0.0 {
+                    Some(((target_height - current_height) as f64 * elapsed / downloaded as f64).ceil() as u64)
+                } else {
+                    None
+                };
+                (downloaded, eta_seconds)
+            }
+            None => (0, None),
+        };
+
+        SyncStatus {
+            syncing: target_height > current_height,
+            current_height,
+            target_height,
+            downloaded,
+            eta_seconds,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sync_tracker() {
+        let sync_tracker = SyncTracker::default();
+        assert!(!sync_tracker.get_status(0).syncing);
+
+        sync_tracker.observe(10, 0);
+        assert_eq!(sync_tracker.get_status(0).target_height, 0);
+        sync_tracker.confirm("a", 10);
+        sync_tracker.confirm("b", 4);
+        let status = sync_tracker.get_status(0);
+        assert!(status.syncing);
+        assert_eq!(status.target_height, 10);
+        assert_eq!(status.eta_seconds, None);
+        assert!(sync_tracker.get_tips().is_empty());
+
+        sync_tracker.progress(5);
+        let status = sync_tracker.get_status(5);
+        assert_eq!(status.downloaded, 5);
+        assert!(status.eta_seconds.is_some());
+
+        sync_tracker.progress(10);
+        let status = sync_tracker.get_status(10);
+        assert!(!status.syncing);
+        assert_eq!(status.downloaded, 0);
+
+        sync_tracker.observe_tip("a", "hash", 12, 10);
+        assert_eq!(sync_tracker.get_tips()["a"], PeerTip { hash: "hash".to_string(), height: 12 });
+        assert_eq!(sync_tracker.get_status(10).target_height, 10);
+        sync_tracker.confirm("a", 12);
+        assert_eq!(sync_tracker.get_status(10).target_height, 12);
+
+        sync_tracker.forget("a");
+        assert!(sync_tracker.get_tips().is_empty());
+        assert_eq!(sync_tracker.get_status(10).target_height, 10);
+        assert!(!sync_tracker.get_is_fast_sync(0));
+
+        let sync_tracker = SyncTracker::new(true);
+        assert!(sync_tracker.get_is_fast_sync(0));
+        assert!(!sync_tracker.get_is_fast_sync(1));
+
+        assert!(!sync_tracker.take_snapshot_query("a"));
+        sync_tracker.query_snapshot("a");
+        assert!(!sync_tracker.take_snapshot_query("b"));
+        assert!(sync_tracker.take_snapshot_query("a"));
+        assert!(!sync_tracker.take_snapshot_query("a"));
+    }
+}
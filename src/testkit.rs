@@ -0,0 +1,111 @@
+//! Canonical fixtures shared by unit tests, integration tests and downstream crates.
+//!
+//! Enabled with the `testkit` feature, every value is fixed so results are reproducible.
+
+pub use crate::block::{get_genesis_block, Block, GENESIS_BLOCK_HASH, GENESIS_TRANSACTION_ID};
+pub use crate::transaction::{Transaction, TxIn, TxOut, UnspentTxOut};
+
+use crate::transaction::sign_tx_in;
+use crate::wallet::Wallet;
+
+/// Fixed private key and the address derived from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keypair {
+    pub private_key: &'static str,
+    pub address: &'static str,
+}
+
+/// Secp256k1 key paid by the genesis block.
+pub const ALICE: Keypair = Keypair {
+    private_key: "27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b",
+    address: "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b",
+};
+
+/// Secp256k1 key used as wallet in tests.
+pub const BOB: Keypair = Keypair {
+    private_key: "eb35a95c6c1bcd1164e5f23629797131bd24aae3995b831be94c8e8fa37ee2d8",
+    address: "03196c144d93ba0ca200221b507312a41c67eafb9b0d9b9348b286a693969b8192",
+};
+
+/// Secp256k1 key with an even public key.
+pub const CAROL: Keypair = Keypair {
+    private_key: "607b5d1e4e91558db295a7541307f16d135152a826cc851fb2283d696ac8e931",
+    address: "02f893b966666dd482c3ffb23062a4cf7034114ce2363c2ee65f67f9b5d65decee",
+};
+
+/// Ed25519 key of RFC 8032 test vector 1.
+pub const DAVE: Keypair = Keypair {
+    private_key: "ed9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+    address: "edd75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+};
+
+/// Address without a known private key, used as receiver.
+pub const RECEIVER_ADDRESS: &'static str = "03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40";
+
+/// Id of the transaction funding `ALICE` in `get_funding_unspent_tx_outs`.
+pub const FUNDING_TX_OUT_ID: &'static str = "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645";
+
+/// Signature of `ALICE` in `get_signed_transaction`, with trailing sighash type.
+pub const SIGNED_TX_IN_SIGNATURE: &'static str = "304402205ed315edcadea42e895e715f37a46bb9c4cc5145bfe384b7f8f97aadd26be30c022062f8ac9dd8700df8981a07f8580e8d5982924e303f27e532ef36194de8cbadfc01";
+
+/// Get unspent tx outs holding 50 for `ALICE`.
+pub fn get_funding_unspent_tx_outs() -> Vec<UnspentTxOut> {
+    vec![UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, ALICE.address.to_string(), 50)]
+}
+
+/// Get transaction of `ALICE` spending the funding output to herself, signed.
+pub fn get_signed_transaction() -> Transaction {
+    let tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, "".to_string())];
+    let tx_outs = vec![TxOut::new(ALICE.address.to_string(), 50)];
+    let mut transaction = Transaction::generate(&tx_ins, &tx_outs);
+    let signature = sign_tx_in(&transaction.id, tx_ins.get(0).unwrap(), ALICE.private_key, &get_funding_unspent_tx_outs()).unwrap();
+    transaction.tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, signature)];
+    transaction
+}
+
+/// Get wallet of keypair, without imported keys or remote signer.
+pub fn get_wallet(keypair: Keypair) -> Wallet {
+    Wallet {
+        private_key: keypair.private_key.to_string(),
+        public_key: keypair.address.to_string(),
+        remote_signer: None,
+        imported_keys: vec![],
+    }
+}
+
+/// Get block of data at index, without links or proof of work, for checks reading only its data.
+pub fn get_block(index: usize, data: Vec<Transaction>) -> Block {
+    Block::new(index, "".to_string(), "".to_string(), 0, data, 0, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::get_unspent_tx_outs;
+    use crate::signer::get_signer;
+    use crate::transaction::{get_is_valid_transaction, BlockContext};
+    use super::*;
+
+    #[test]
+    fn test_keypairs() {
+        for keypair in vec![ALICE, BOB, CAROL, DAVE] {
+            assert_eq!(get_signer(keypair.private_key).unwrap().get_address(), keypair.address);
+        }
+    }
+
+    #[test]
+    fn test_get_signed_transaction() {
+        let transaction = get_signed_transaction();
+        assert_eq!(transaction.tx_ins.get(0).unwrap().signature, SIGNED_TX_IN_SIGNATURE);
+        assert!(get_is_valid_transaction(&transaction, &get_funding_unspent_tx_outs(), BlockContext::default()));
+    }
+
+    #[test]
+    fn test_get_genesis_block() {
+        let genesis_block = get_genesis_block();
+        assert_eq!(genesis_block.hash, GENESIS_BLOCK_HASH);
+        assert_eq!(genesis_block.data.get(0).unwrap().id, GENESIS_TRANSACTION_ID);
+
+        let unspent_tx_outs = get_unspent_tx_outs(&vec![genesis_block]).unwrap();
+        assert_eq!(unspent_tx_outs.get(0).unwrap().address, ALICE.address);
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use serde::Serialize;
+
+use crate::{Block, Transaction};
+use crate::constants::DEFAULT_MAX_STREAMS;
+use crate::dto::serialize_model;
+use crate::invoice::Invoice;
+use crate::transaction::get_touched_addresses;
+
+const KEEP_ALIVE_INTERVAL: u64 = 15;
+
+/// Latest block of the chain.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Tip {
+    /// Index of block
+    pub height: usize,
+
+    /// Hash of block
+    pub hash: String,
+
+    /// Address paid by coinbase transaction
+    pub miner: Option<String>,
+
+    /// Count of transactions in block
+    pub tx_count: usize,
+}
+
+impl Tip {
+    /// Returns a tip from block
+    pub fn from_block(block: &Block) -> Tip {
+        Tip {
+            height: block.index,
+            hash: block.hash.clone(),
+            miner: block.data.get(0).and_then(|tx| tx.tx_outs.get(0)).map(|tx_out| tx_out.address.clone()),
+            tx_count: block.data.len(),
+        }
+    }
+}
+
+/// Transaction of a block which became part of the chain.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Confirmation {
+    #[serde(serialize_with = "serialize_model")]
+    pub transaction: Transaction,
+
+    /// Hash of containing block
+    pub block_hash: String,
+
+    /// Index of containing block
+    pub height: usize,
+}
+
+/// Addresses a subscriber follows pool and confirmation events of, every event without addresses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamFilter {
+    addresses: Option<HashSet<String>>,
+}
+
+impl StreamFilter {
+    /// Returns a filter of events touching addresses
+    pub fn addresses<I: IntoIterator<Item = String>>(addresses: I) -> StreamFilter {
+        StreamFilter { addresses: Some(addresses.into_iter().collect()) }
+    }
+
+    /// Returns a filter of comma separated addresses, none if there is no address
+    pub fn parse(addresses: &str) -> Option<StreamFilter> {
+        let addresses: HashSet<String> = addresses
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(str::to_string)
+            .collect();
+        if addresses.is_empty() {
+            return None;
+        }
+        Some(StreamFilter { addresses: Some(addresses) })
+    }
+
+    fn get_is_matched(&self, touched: &HashSet<&str>) -> bool {
+        self.addresses.as_ref().map_or(true, |addresses| touched.iter().any(|address| addresses.contains(*address)))
+    }
+}
+
+/// Event sent to stream subscribers.
+#[derive(Debug, Clone)]
+enum StreamEvent {
+    Tip(Tip),
+    Invoice(Invoice),
+    Pool(Vec<Transaction>),
+    Confirmation(Confirmation),
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    sender: Sender<StreamEvent>,
+    filter: StreamFilter,
+
+    /// Ids of pool transactions last sent
+    pool: Vec<String>,
+}
+
+/// Fans out tip changes, invoice updates, pool changes and confirmations to stream subscribers.
+///
+/// Each stream blocks an http worker while its client is connected, so at most max streams are open at once.
+#[derive(Debug)]
+pub struct TipNotifier {
+    subscribers: Mutex<Vec<Subscriber>>,
+    latest: Mutex<Option<Tip>>,
+    max_streams: usize,
+
+    /// Count of streams not dropped yet
+    streams: Arc<AtomicUsize>,
+}
+
+impl Default for TipNotifier {
+    fn default() -> Self {
+        TipNotifier::new(DEFAULT_MAX_STREAMS)
+    }
+}
+
+impl TipNotifier {
+    /// Returns a notifier serving at most max streams at once
+    pub fn new(max_streams: usize) -> TipNotifier {
+        TipNotifier {
+            subscribers: Mutex::new(vec![]),
+            latest: Mutex::new(None),
+            max_streams,
+            streams: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Subscribe to tip changes, and to pool changes and confirmations matching filter, none if max streams are open.
+    pub fn subscribe(&self, filter: StreamFilter) -> Option<TipStream> {
+        self.streams
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |streams| (streams < self.max_streams).then(|| streams + 1))
+            .ok()?;
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(Subscriber { sender, filter, pool: vec![] });
+        Some(TipStream { receiver, buffer: vec![], position: 0, flush: false, streams: Arc::clone(&self.streams) })
+    }
+
+    /// Notify tip to subscribers if it changed, dropping closed ones.
+    pub fn notify(&self, tip: Tip) {
+        let mut latest = self.latest.lock().unwrap();
+        if latest.as_ref() == Some(&tip) {
+            return;
+        }
+        self.send(StreamEvent::Tip(tip.clone()));
+        *latest = Some(tip);
+    }
+
+    /// Notify tip of blockchain if it changed, and transactions of blocks on top of the previous tip as confirmed.
+    ///
+    /// After a reorg the previous tip is not in blockchain, so only transactions of the new tip are confirmed.
+    pub fn notify_blocks(&self, blockchain: &[Block]) {
+        let tip = match blockchain.last() {
+            Some(block) => Tip::from_block(block),
+            None => return,
+        };
+        let start = {
+            let latest = self.latest.lock().unwrap();
+            if latest.as_ref() == Some(&tip) {
+                return;
+            }
+            latest
+                .as_ref()
+                .and_then(|latest| blockchain.iter().position(|block| block.hash == latest.hash))
+                .map_or(blockchain.len() - 1, |position| position + 1)
+        };
+        self.notify(tip);
+
+        let transactions = blockchain.iter().flat_map(|block| block.data.iter()).map(|tx| (tx.id.as_str(), tx)).collect();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        for block in &blockchain[start..] {
+            for transaction in block.data.iter() {
+                let touched = get_touched_addresses(transaction, &transactions);
+                let confirmation = Confirmation { transaction: transaction.clone(), block_hash: block.hash.clone(), height: block.index };
+                subscribers.retain(|subscriber| {
+                    !subscriber.filter.get_is_matched(&touched) ||
+                        subscriber.sender.send(StreamEvent::Confirmation(confirmation.clone())).is_ok()
+                });
+            }
+        }
+    }
+
+    /// Notify pool transactions matching filter of each subscriber, if they changed since last sent.
+    pub fn notify_pool(&self, transaction_pool: &[Transaction], blockchain: &[Block]) {
+        let transactions = blockchain
+            .iter()
+            .flat_map(|block| block.data.iter())
+            .chain(transaction_pool)
+            .map(|tx| (tx.id.as_str(), tx))
+            .collect();
+        let touched: Vec<HashSet<&str>> = transaction_pool.iter().map(|tx| get_touched_addresses(tx, &transactions)).collect();
+
+        self.subscribers.lock().unwrap().retain_mut(|subscriber| {
+            let pool: Vec<Transaction> = transaction_pool
+                .iter()
+                .zip(&touched)
+                .filter(|(_, touched)| subscriber.filter.get_is_matched(touched))
+                .map(|(tx, _)| tx.clone())
+                .collect();
+            let ids: Vec<String> = pool.iter().map(|tx| tx.id.clone()).collect();
+            if ids == subscriber.pool {
+                return true;
+            }
+            subscriber.pool = ids;
+            subscriber.sender.send(StreamEvent::Pool(pool)).is_ok()
+        });
+    }
+
+    /// Notify invoice whose status changed to subscribers.
+    pub fn notify_invoice(&self, invoice: Invoice) {
+        self.send(StreamEvent::Invoice(invoice));
+    }
+
+    fn send(&self, event: StreamEvent) {
+        self.subscribers.lock().unwrap().retain(|subscriber| subscriber.sender.send(event.clone()).is_ok());
+    }
+}
+
+/// Server-sent events body emitting a `tip` event per change, an `invoice` event per invoice status change,
+/// a `pool` event with the matching pool transactions when they change and a `confirmation` event per matching
+/// transaction of a new block.
+pub struct TipStream {
+    receiver: Receiver<StreamEvent>,
+    buffer: Vec<u8>,
+    position: usize,
+    flush: bool,
+    streams: Arc<AtomicUsize>,
+}
+
+impl Drop for TipStream {
+    fn drop(&mut self) {
+        self.streams.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Read for TipStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.buffer.len() {
+            // Rocket flushes the chunk when the body would block after data.
+            if self.flush {
+                self.flush = false;
+                return Err(io::Error::from(io::ErrorKind::WouldBlock));
+            }
+
+            let event = match self.receiver.recv_timeout(Duration::from_secs(KEEP_ALIVE_INTERVAL)) {
+                Ok(StreamEvent::Tip(tip)) => format!("event: tip\ndata: {}\n\n", serde_json::to_string(&tip).unwrap()),
+                Ok(StreamEvent::Invoice(invoice)) => {
+                    format!("event: invoice\ndata: {}\n\n", serde_json::to_string(&invoice).unwrap())
+                }
+                Ok(StreamEvent::Pool(pool)) => format!("event: pool\ndata: {}\n\n", serde_json::to_string(&pool).unwrap()),
+                Ok(StreamEvent::Confirmation(confirmation)) => {
+                    format!("event: confirmation\ndata: {}\n\n", serde_json::to_string(&confirmation).unwrap())
+                }
+                Err(RecvTimeoutError::Timeout) => ": keep-alive\n\n".to_string(),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            };
+            self.buffer = event.into_bytes();
+            self.position = 0;
+            self.flush = true;
+        }
+
+        let size = buf.len().min(self.buffer.len() - self.position);
+        buf[..size].copy_from_slice(&self.buffer[self.position..self.position + size]);
+        self.position += size;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::get_genesis_block;
+    use crate::testkit::{ALICE, BOB, CAROL, RECEIVER_ADDRESS};
+    use crate::transaction::{get_coinbase_transaction, TxIn, TxOut};
+    use super::*;
+
+    /// Get names of events sent to stream and not read yet.
+    fn get_events(stream: &TipStream) -> Vec<&'static str> {
+        stream.receiver.try_iter().map(|event| match event {
+            StreamEvent::Tip(_) => "tip",
+            StreamEvent::Invoice(_) => "invoice",
+            StreamEvent::Pool(_) => "pool",
+            StreamEvent::Confirmation(_) => "confirmation",
+        }).collect()
+    }
+
+    #[test]
+    fn test_tip_notifier() {
+        let notifier = TipNotifier::default();
+        let mut stream = notifier.subscribe(StreamFilter::default()).unwrap();
+        let tip = Tip {
+            height: 1,
+            hash: "41cdda1f3f0f6bd2497997a6bbab3188090b0404c1da5fc854c174dd42cefd2d".to_string(),
+            miner: None,
+            tx_count: 1,
+        };
+        notifier.notify(tip.clone());
+        notifier.notify(tip.clone());
+
+        let mut buf = [0u8; 1024];
+        let size = stream.read(&mut buf).unwrap();
+        let event = String::from_utf8(buf[..size].to_vec()).unwrap();
+        assert_eq!(event, format!("event: tip\ndata: {}\n\n", serde_json::to_string(&tip).unwrap()));
+        assert_eq!(stream.read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        drop(notifier);
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_max_streams() {
+        let notifier = TipNotifier::new(2);
+        let first = notifier.subscribe(StreamFilter::default()).unwrap();
+        let _second = notifier.subscribe(StreamFilter::default()).unwrap();
+        assert!(notifier.subscribe(StreamFilter::default()).is_none());
+
+        drop(first);
+        assert!(notifier.subscribe(StreamFilter::default()).is_some());
+    }
+
+    #[test]
+    fn test_stream_filter() {
+        let genesis_block = get_genesis_block();
+        let notifier = TipNotifier::default();
+        notifier.notify_blocks(&[genesis_block.clone()]);
+
+        let all = notifier.subscribe(StreamFilter::default()).unwrap();
+        let alice = notifier.subscribe(StreamFilter::parse(&format!(" {},", ALICE.address)).unwrap()).unwrap();
+        let bob = notifier.subscribe(StreamFilter::addresses(vec![BOB.address.to_string()])).unwrap();
+        let carol = notifier.subscribe(StreamFilter::addresses(vec![CAROL.address.to_string()])).unwrap();
+        assert_eq!(StreamFilter::parse(" , "), None);
+
+        let transaction = Transaction::generate(
+            &vec![TxIn::new(genesis_block.data[0].id.clone(), 0, "".to_string())],
+            &vec![TxOut::new(BOB.address.to_string(), 10)],
+        );
+        notifier.notify_pool(&[transaction.clone()], &[genesis_block.clone()]);
+        notifier.notify_pool(&[transaction.clone()], &[genesis_block.clone()]);
+        assert_eq!(get_events(&all), vec!["pool"]);
+        assert_eq!(get_events(&alice), vec!["pool"]);
+        assert_eq!(get_events(&bob), vec!["pool"]);
+        assert_eq!(get_events(&carol), Vec::<&str>::new());
+
+        let block = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 1, None), transaction.clone()], &genesis_block, 0);
+        let blockchain = vec![genesis_block, block.clone()];
+        notifier.notify_blocks(&blockchain);
+        notifier.notify_pool(&[], &blockchain);
+        assert_eq!(get_events(&all), vec!["tip", "confirmation", "confirmation", "pool"]);
+        assert_eq!(get_events(&bob), vec!["tip", "confirmation", "pool"]);
+        assert_eq!(get_events(&carol), vec!["tip"]);
+
+        match alice.receiver.try_iter().nth(1) {
+            Some(StreamEvent::Confirmation(confirmation)) => {
+                assert_eq!(confirmation, Confirmation { transaction, block_hash: block.hash, height: 1 });
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
@@ -1,11 +1,120 @@
 use std::collections::HashMap;
-use std::str::FromStr;
+#[cfg(feature = "node")]
+use std::collections::HashSet;
 use sha2::{Sha256, Digest};
 use serde::{Serialize, Deserialize};
-use secp256k1::{Secp256k1, ecdsa, PublicKey, SecretKey};
-use crate::constants::COINBASE_AMOUNT;
+use crate::Block;
+use crate::asset::{get_is_conserved, get_is_valid_asset_id};
+use crate::activation::{get_is_active, Rule, INITIAL_VERSION};
+use crate::codec::{encode, encode_transaction_content, Encode, Encoder};
+use crate::consensus_rules::CONSENSUS_TRANSACTION_LIMITS;
+use crate::constants::{COINBASE_AMOUNT, MAX_COINBASE_EXTRA_DATA};
 use crate::errors::AppError;
-use crate::secp256k1::{message_from_str};
+use crate::network_time::Clock;
+use crate::script::Script;
+use crate::signer::{get_signer, get_verifier, KeyType};
+use crate::validation_cache::ValidationCache;
+
+/// Sighash type committing to the whole transaction, appended to signatures as a trailing byte.
+pub const SIGHASH_ALL: u8 = 0x01;
+
+/// Bytes of block height leading coinbase extra data once `Rule::CoinbaseHeight` is active, so coinbase ids never
+/// repeat across heights whatever follows, such as an extra nonce.
+pub const COINBASE_HEIGHT_COMMITMENT: usize = 8;
+
+/// Reason a transaction is invalid, found without panicking on any data of a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Invalidity {
+    /// Id does not match content
+    Id,
+
+    /// Extra data outside a coinbase transaction
+    ExtraData,
+
+    /// No tx in, which would mint coins outside a coinbase transaction
+    NoTxIns,
+
+    /// No tx out, which would burn every input as fee
+    NoTxOuts,
+
+    /// Tx out to the empty address, which no key can spend
+    EmptyAddress,
+
+    /// Tx ins spending the same outpoint twice, which would count its amount twice
+    DuplicateTxIn,
+
+    /// Tx in spends no unspent tx out
+    MissingTxOut,
+
+    /// Tx in does not satisfy the script of its tx out, malformed keys and signatures included
+    Unlocking,
+
+    /// Script of a tx out or of a spent tx out uses an operation whose rule is not active at the height of the block
+    InactiveScript,
+
+    /// Amounts of tx outs do not match tx ins, or overflow
+    Unconserved,
+}
+
+/// Block transactions are validated for, so their validity depends on the block and not on when it is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BlockContext {
+    /// Height of the block, the rules active at it apply
+    pub index: usize,
+
+    /// Timestamp of the block, time locks are checked against it
+    pub timestamp: usize,
+}
+
+impl BlockContext {
+    /// Returns the context of block
+    pub fn of(block: &Block) -> BlockContext {
+        BlockContext { index: block.index, timestamp: block.timestamp }
+    }
+
+    /// Returns the context of the block generated next on top of latest at the time of clock, the one pooled
+    /// transactions are validated for
+    pub fn next(latest: &Block, clock: &dyn Clock) -> BlockContext {
+        BlockContext { index: latest.index + 1, timestamp: clock.now().max(latest.timestamp) }
+    }
+}
+
+/// Limits on size and input/output count of a transaction, bounding validation cost.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransactionLimits {
+    /// Largest serialized size in bytes
+    pub max_size: usize,
+
+    /// Largest count of tx ins
+    pub max_tx_ins: usize,
+
+    /// Largest count of tx outs
+    pub max_tx_outs: usize,
+}
+
+impl TransactionLimits {
+    /// Get transaction is within limits
+    pub fn get_is_within(&self, transaction: &Transaction) -> bool {
+        transaction.tx_ins.len() <= self.max_tx_ins &&
+            transaction.tx_outs.len() <= self.max_tx_outs &&
+            transaction.get_size() <= self.max_size
+    }
+}
+
+/// Signs tx ins spending outputs of one address, locally or by an external process.
+pub trait TransactionSigner {
+    /// Get address of the signing key
+    fn get_address(&self) -> String;
+
+    /// Get addresses whose outputs can be signed, change goes to `get_address`
+    fn get_addresses(&self) -> Vec<String> {
+        vec![self.get_address()]
+    }
+
+    /// Sign tx in of transaction, returning signature with sighash type
+    fn sign_tx_in(&self, transaction_id: &str, tx_in: &TxIn, unspent_tx_outs: &Vec<UnspentTxOut>) -> Result<String, AppError>;
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UnspentTxOut {
@@ -13,6 +122,14 @@ pub struct UnspentTxOut {
     pub tx_out_index: usize,
     pub address: String,
     pub amount: usize,
+
+    /// Conditions to spend, paying to address when none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+
+    /// Id of the asset held, coins when none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
 }
 
 impl UnspentTxOut {
@@ -22,8 +139,15 @@ impl UnspentTxOut {
             tx_out_index,
             address,
             amount,
+            script: None,
+            asset: None,
         }
     }
+
+    /// Get conditions to spend, pay to public key of address without script
+    pub fn get_script(&self) -> Script {
+        self.script.clone().unwrap_or_else(|| Script::pay_to_public_key(&self.address))
+    }
 }
 
 impl Clone for UnspentTxOut {
@@ -33,6 +157,8 @@ impl Clone for UnspentTxOut {
             tx_out_index: self.tx_out_index.clone(),
             address: self.address.clone(),
             amount: self.amount,
+            script: self.script.clone(),
+            asset: self.asset.clone(),
         }
     }
 }
@@ -41,6 +167,8 @@ impl Clone for UnspentTxOut {
 pub struct TxIn {
     pub tx_out_id: String,
     pub tx_out_index: usize,
+
+    /// Unlocking data pushed before the script of the referenced output, signatures separated by spaces
     pub signature: String,
 }
 
@@ -76,8 +204,17 @@ impl PartialEq for TxIn {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TxOut {
+    /// Owner listed by balances and wallets
     pub address: String,
     pub amount: usize,
+
+    /// Conditions to spend, paying to public key of address when none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<Script>,
+
+    /// Id of the asset held, coins when none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
 }
 
 impl TxOut {
@@ -85,14 +222,45 @@ impl TxOut {
         TxOut {
             address,
             amount,
+            script: None,
+            asset: None,
+        }
+    }
+
+    /// Returns an output holding amount of an asset instead of coins
+    pub fn with_asset(address: String, amount: usize, asset: String) -> TxOut {
+        TxOut {
+            asset: Some(asset),
+            ..TxOut::new(address, amount)
         }
     }
 
+    /// Returns an output spendable under script, listed for address
+    pub fn with_script(address: String, amount: usize, script: Script) -> TxOut {
+        TxOut {
+            script: Some(script),
+            ..TxOut::new(address, amount)
+        }
+    }
+
+    /// Get flag the output only carries data and never enters unspent tx outs
+    pub fn get_is_unspendable(&self) -> bool {
+        self.script.as_ref().map_or(false, |script| script.get_is_unspendable())
+    }
+
     pub fn get_is_valid_structure(&self) -> bool {
         if self.address.len() != 66 {
             return false;
         }
 
+        if !self.script.as_ref().map_or(true, |script| script.get_is_valid_structure()) {
+            return false;
+        }
+
+        if !self.asset.as_ref().map_or(true, |asset| get_is_valid_asset_id(asset)) {
+            return false;
+        }
+
         true
     }
 }
@@ -102,45 +270,75 @@ impl Clone for TxOut {
         Self {
             address: self.address.clone(),
             amount: self.amount,
+            script: self.script.clone(),
+            asset: self.asset.clone(),
         }
     }
 }
 
 impl PartialEq for TxOut {
     fn eq(&self, other: &Self) -> bool {
-        self.address.eq(&other.address) && self.amount == other.amount
+        self.address.eq(&other.address) && self.amount == other.amount && self.script == other.script && self.asset == other.asset
     }
 }
 
+/// Get script content committed to by signatures, empty without script so existing signatures stay the same.
+fn get_script_content(script: &Option<Script>) -> String {
+    script.as_ref().map(|script| hex::encode(encode(script))).unwrap_or_default()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Transaction {
+    /// Version of consensus rules, left out for the initial version so serialized transactions stay the same
+    #[serde(default = "get_initial_version", skip_serializing_if = "get_is_initial_version")]
+    pub version: u32,
     pub id: String,
     pub tx_ins: Vec<TxIn>,
     pub tx_outs: Vec<TxOut>,
+
+    /// Bytes in hex a miner embeds in a coinbase transaction, committed to by the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra_data: Option<String>,
 }
 
 impl Transaction {
     pub fn generate(tx_ins: &Vec<TxIn>, tx_outs: &Vec<TxOut>) -> Transaction {
         Transaction {
-            id: get_transaction_id(tx_ins, tx_outs),
+            version: INITIAL_VERSION,
+            id: get_transaction_id(INITIAL_VERSION, tx_ins, tx_outs, &None),
             tx_ins: tx_ins.to_vec(),
             tx_outs: tx_outs.to_vec(),
+            extra_data: None,
         }
     }
 
     pub fn new(id: String, tx_ins: &Vec<TxIn>, tx_outs: &Vec<TxOut>) -> Transaction {
         Transaction {
+            version: INITIAL_VERSION,
             id,
             tx_ins: tx_ins.to_vec(),
             tx_outs: tx_outs.to_vec(),
+            extra_data: None,
         }
     }
 
+    /// Get size in bytes of the canonical encoding, with the extra data the id commits to
+    pub fn get_size(&self) -> usize {
+        let mut encoder = Encoder::new();
+        self.encode(&mut encoder);
+        encoder.put_option(&self.extra_data);
+        encoder.into_bytes().len()
+    }
+
     pub fn get_transaction_id(&self) -> String {
-        get_transaction_id(&self.tx_ins, &self.tx_outs)
+        get_transaction_id(self.version, &self.tx_ins, &self.tx_outs, &self.extra_data)
     }
 
     pub fn get_is_valid_structure(&self) -> bool {
+        if self.version < INITIAL_VERSION {
+            return false;
+        }
+
         let ref_tx_ins = &self.tx_ins;
 
         if ref_tx_ins.into_iter().any(|tx_in| !tx_in.get_is_valid_structure()) {
@@ -160,9 +358,11 @@ impl Transaction {
 impl Clone for Transaction {
     fn clone(&self) -> Self {
         Self {
+            version: self.version,
             id: self.id.clone(),
             tx_ins: self.tx_ins.clone(),
             tx_outs: self.tx_outs.clone(),
+            extra_data: self.extra_data.clone(),
         }
     }
 }
@@ -174,7 +374,9 @@ impl PartialEq for Transaction {
         let ref_self_tx_outs = &self.tx_outs;
         let ref_other_tx_outs = &other.tx_outs;
 
-        self.id == other.id &&
+        self.version == other.version &&
+            self.id == other.id &&
+            self.extra_data == other.extra_data &&
             ref_self_tx_ins
                 .into_iter()
                 .zip(ref_other_tx_ins)
@@ -186,77 +388,160 @@ impl PartialEq for Transaction {
     }
 }
 
-fn get_transaction_id(tx_ins: &Vec<TxIn>, tx_outs: &Vec<TxOut>) -> String {
-    let tx_in_content = tx_ins.into_iter()
-        .map(|tx_in: &TxIn| format!("{}{}", tx_in.tx_out_id.to_string(), tx_in.tx_out_index))
-        .fold("".to_string(), |total: String, content: String| format!("{}{}", total, content));
+pub(crate) fn get_initial_version() -> u32 {
+    INITIAL_VERSION
+}
+
+pub(crate) fn get_is_initial_version(version: &u32) -> bool {
+    *version == INITIAL_VERSION
+}
 
-    let tx_out_content = tx_outs.into_iter()
-        .map(|tx_out: &TxOut| format!("{}{}", tx_out.address.to_string(), tx_out.amount))
-        .fold("".to_string(), |total: String, content: String| format!("{}{}", total, content));
+/// Get id from canonical bytes of a transaction content.
+fn get_transaction_id(version: u32, tx_ins: &Vec<TxIn>, tx_outs: &Vec<TxOut>, extra_data: &Option<String>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(encode_transaction_content(version, tx_ins, tx_outs, extra_data.as_deref()));
+    format!("{:x}", hasher.finalize())
+}
 
+/// Get hash signed by a tx in, committing to the referenced output and sighash type.
+pub(crate) fn get_sighash(transaction_id: &str, tx_in: &TxIn, referenced_unspent_tx_out: &UnspentTxOut, sighash_type: u8) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(format!("{}{}", tx_in_content, tx_out_content).as_bytes());
+    hasher.update(format!(
+        "{}{}{}{}{}{}{}{}",
+        transaction_id,
+        tx_in.tx_out_id,
+        tx_in.tx_out_index,
+        referenced_unspent_tx_out.address,
+        referenced_unspent_tx_out.amount,
+        get_script_content(&referenced_unspent_tx_out.script),
+        referenced_unspent_tx_out.asset.as_deref().unwrap_or_default(),
+        sighash_type,
+    ).as_bytes());
     format!("{:x}", hasher.finalize())
 }
 
-fn get_is_valid_tx_in(tx_in: &TxIn, transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> bool {
-    let u_tx_out =
-        unspent_tx_outs.into_iter().find(|u_tx_o| u_tx_o.tx_out_id.eq(&tx_in.tx_out_id));
-    return if let Some(referenced_utx_out) = u_tx_out {
-        let secp = Secp256k1::verification_only();
-        let public_key = PublicKey::from_str(&referenced_utx_out.address).unwrap();
-        let message = message_from_str(&transaction.id).unwrap();
-        let sig = ecdsa::Signature::from_str(&tx_in.signature).unwrap();
-        secp.verify_ecdsa(&message, &sig, &public_key).is_ok()
-    } else {
-        false
+/// Split a signature into the signature and its trailing sighash type byte.
+fn split_sighash_type(signature: &str) -> Option<(&str, u8)> {
+    if signature.len() < 2 || !signature.is_char_boundary(signature.len() - 2) {
+        return None;
+    }
+    let (signature, sighash_type) = signature.split_at(signature.len() - 2);
+    Some((signature, u8::from_str_radix(sighash_type, 16).ok()?))
+}
+
+/// Check tx in satisfies the script of the referenced output, run after the data of its signature field at the
+/// timestamp of the block.
+fn check_tx_in(tx_in: &TxIn, transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>, context: BlockContext) -> Result<(), Invalidity> {
+    let referenced_utx_out = find_unspent_tx_out(&tx_in.tx_out_id, tx_in.tx_out_index, unspent_tx_outs)
+        .ok_or(Invalidity::MissingTxOut)?;
+    if !get_is_active_script(&referenced_utx_out.get_script(), context.index) {
+        return Err(Invalidity::InactiveScript);
+    }
+    let unlocking_data: Vec<&str> = tx_in.signature.split_whitespace().collect();
+
+    let is_unlocked = referenced_utx_out.get_script().evaluate(&unlocking_data, context.timestamp, |public_key, signature| {
+        get_is_valid_signature(public_key, signature, tx_in, transaction, referenced_utx_out, context.index)
+    });
+    match is_unlocked {
+        true => Ok(()),
+        false => Err(Invalidity::Unlocking),
+    }
+}
+
+/// Get rules of every operation of script are active at block index.
+fn get_is_active_script(script: &Script, block_index: usize) -> bool {
+    script.get_rules().into_iter().all(|rule| get_is_active(rule, block_index))
+}
+
+/// Get signature of tx in is made by the public key.
+///
+/// Legacy secp256k1 signatures are plain DER and sign the transaction id only, newer ones
+/// carry a trailing sighash type byte and sign `get_sighash`. Legacy signatures are rejected
+/// in blocks from the activation of `Rule::Sighash`.
+fn get_is_valid_signature(
+    public_key: &str,
+    signature: &str,
+    tx_in: &TxIn,
+    transaction: &Transaction,
+    referenced_utx_out: &UnspentTxOut,
+    block_index: usize,
+) -> bool {
+    let verifier = match get_verifier(public_key) {
+        Some(verifier) => verifier,
+        None => return false,
     };
+
+    if verifier.get_key_type() == KeyType::Secp256k1 &&
+        !get_is_active(Rule::Sighash, block_index) &&
+        verifier.verify(&transaction.id, signature) {
+        return true;
+    }
+
+    match split_sighash_type(signature) {
+        Some((signature, SIGHASH_ALL)) => {
+            let sighash = get_sighash(&transaction.id, tx_in, referenced_utx_out, SIGHASH_ALL);
+            verifier.verify(&sighash, signature)
+        }
+        _ => false,
+    }
 }
 
-fn find_unspent_tx_out<'a>(transaction_id: &'a str, index: usize, unspent_tx_outs: &'a Vec<UnspentTxOut>) -> Option<&'a UnspentTxOut> {
+pub(crate) fn find_unspent_tx_out<'a>(transaction_id: &'a str, index: usize, unspent_tx_outs: &'a Vec<UnspentTxOut>) -> Option<&'a UnspentTxOut> {
     unspent_tx_outs.into_iter().find(|u_tx_o| u_tx_o.tx_out_id.eq(transaction_id) && u_tx_o.tx_out_index == index)
 }
 
-fn get_tx_in_amount(tx_in: &TxIn, unspent_tx_outs: &Vec<UnspentTxOut>) -> usize {
-    return if let Some(u_tx_o) = find_unspent_tx_out(tx_in.tx_out_id.as_str(), tx_in.tx_out_index, unspent_tx_outs) {
-        u_tx_o.amount
-    } else {
-        0
-    };
+/// Get addresses transaction pays or spends from, inputs resolved against transactions by id.
+#[cfg(feature = "node")]
+pub(crate) fn get_touched_addresses<'a>(transaction: &'a Transaction, transactions: &HashMap<&str, &'a Transaction>) -> HashSet<&'a str> {
+    let spent = transaction.tx_ins
+        .iter()
+        .filter_map(|tx_in| transactions.get(tx_in.tx_out_id.as_str())?.tx_outs.get(tx_in.tx_out_index));
+    transaction.tx_outs.iter().chain(spent).map(|tx_out| tx_out.address.as_str()).collect()
+}
+
+pub fn get_is_valid_transaction(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>, context: BlockContext) -> bool {
+    get_invalidity(transaction, unspent_tx_outs, context).is_none()
 }
 
-pub fn get_is_valid_transaction(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> bool {
+/// Get why transaction is invalid against unspent tx outs in the block of context, none if it is valid.
+pub fn get_invalidity(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>, context: BlockContext) -> Option<Invalidity> {
     if !transaction.get_transaction_id().eq(&transaction.id) {
-        return false;
+        return Some(Invalidity::Id);
     }
 
-    let ref_tx_ins = &transaction.tx_ins;
+    if transaction.extra_data.is_some() {
+        return Some(Invalidity::ExtraData);
+    }
 
-    let has_invalid_tx_ins = ref_tx_ins
-        .into_iter()
-        .any(|tx_in| !get_is_valid_tx_in(&tx_in, transaction, unspent_tx_outs));
+    if transaction.tx_ins.is_empty() {
+        return Some(Invalidity::NoTxIns);
+    }
 
-    if has_invalid_tx_ins {
-        return false;
+    if transaction.tx_outs.is_empty() {
+        return Some(Invalidity::NoTxOuts);
     }
 
-    let total_tx_in_values = ref_tx_ins
-        .into_iter()
-        .map(|tx_in| get_tx_in_amount(&tx_in, unspent_tx_outs))
-        .fold(0, |sum, amount| sum + amount);
+    if transaction.tx_outs.iter().any(|tx_out| tx_out.address.is_empty()) {
+        return Some(Invalidity::EmptyAddress);
+    }
 
-    let ref_tx_outs = &transaction.tx_outs;
-    let total_tx_out_values = ref_tx_outs
-        .into_iter()
-        .map(|tx_out| tx_out.amount)
-        .fold(0, |sum, amount| sum + amount);
+    if has_duplicates(&transaction.tx_ins.iter().collect()) {
+        return Some(Invalidity::DuplicateTxIn);
+    }
 
-    if total_tx_out_values != total_tx_in_values {
-        return false;
+    if !transaction.tx_outs.iter().all(|tx_out| tx_out.script.as_ref().map_or(true, |script| get_is_active_script(script, context.index))) {
+        return Some(Invalidity::InactiveScript);
     }
 
-    true
+    if let Err(invalidity) = transaction.tx_ins.iter().try_for_each(|tx_in| check_tx_in(tx_in, transaction, unspent_tx_outs, context)) {
+        return Some(invalidity);
+    }
+
+    if !get_is_conserved(transaction, unspent_tx_outs) {
+        return Some(Invalidity::Unconserved);
+    }
+
+    None
 }
 
 fn get_is_valid_coinbase_tx(transaction: Option<&Transaction>, block_index: usize) -> bool {
@@ -286,13 +571,47 @@ fn get_is_valid_coinbase_tx(transaction: Option<&Transaction>, block_index: usiz
 
     let tx_out = transaction.tx_outs.get(0).unwrap();
 
-    if tx_out.amount != COINBASE_AMOUNT {
+    if tx_out.amount != get_block_reward(block_index) || tx_out.asset.is_some() {
+        return false;
+    }
+
+    if !tx_out.script.as_ref().map_or(true, |script| get_is_active_script(script, block_index)) {
+        return false;
+    }
+
+    if !transaction.extra_data.as_ref().map_or(true, |extra_data| get_is_valid_extra_data(extra_data)) {
+        return false;
+    }
+
+    if get_is_active(Rule::CoinbaseHeight, block_index) && !get_is_committed_height(transaction, block_index) {
         return false;
     }
 
     true
 }
 
+/// Get bytes coinbase extra data of block index starts with, the big-endian height once `Rule::CoinbaseHeight` is
+/// active and none before.
+pub fn get_coinbase_prefix(block_index: usize) -> Vec<u8> {
+    if !get_is_active(Rule::CoinbaseHeight, block_index) {
+        return vec![];
+    }
+    (block_index as u64).to_be_bytes().to_vec()
+}
+
+/// Get extra data of coinbase transaction starts with the commitment to block index.
+fn get_is_committed_height(transaction: &Transaction, block_index: usize) -> bool {
+    transaction.extra_data
+        .as_ref()
+        .and_then(|extra_data| hex::decode(extra_data).ok())
+        .is_some_and(|extra_data| extra_data.starts_with(&(block_index as u64).to_be_bytes()))
+}
+
+/// Get extra data is hex of at most `MAX_COINBASE_EXTRA_DATA` bytes.
+fn get_is_valid_extra_data(extra_data: &str) -> bool {
+    extra_data.len() <= MAX_COINBASE_EXTRA_DATA * 2 && hex::decode(extra_data).is_ok()
+}
+
 fn has_duplicates(tx_ins: &Vec<&TxIn>) -> bool {
     tx_ins
         .into_iter()
@@ -303,9 +622,18 @@ fn has_duplicates(tx_ins: &Vec<&TxIn>) -> bool {
         }).values().any(|count| *count > 1)
 }
 
-fn get_is_valid_block_transactions(transactions: &Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>, block_index: usize) -> bool {
+fn get_is_valid_block_transactions(
+    transactions: &Vec<Transaction>,
+    unspent_tx_outs: &Vec<UnspentTxOut>,
+    context: BlockContext,
+    validation_cache: &mut ValidationCache,
+) -> bool {
     let coinbase_tx = transactions.get(0);
-    if !get_is_valid_coinbase_tx(coinbase_tx, block_index) {
+    if !get_is_valid_coinbase_tx(coinbase_tx, context.index) {
+        return false;
+    }
+
+    if !transactions.iter().all(|tx| CONSENSUS_TRANSACTION_LIMITS.get_is_within(tx)) {
         return false;
     }
 
@@ -321,11 +649,10 @@ fn get_is_valid_block_transactions(transactions: &Vec<Transaction>, unspent_tx_o
 
     transactions.into_iter()
         .skip(1)
-        .map(|tx| get_is_valid_transaction(tx, unspent_tx_outs))
-        .all(|valid| valid)
+        .all(|tx| validation_cache.get_is_valid_transaction(tx, unspent_tx_outs, context))
 }
 
-fn update_unspent_tx_outs(new_transactions: &Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>) -> Vec<UnspentTxOut> {
+pub(crate) fn update_unspent_tx_outs(new_transactions: &Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>) -> Vec<UnspentTxOut> {
     let new_unspent_tx_outs: Vec<UnspentTxOut> = new_transactions
         .into_iter()
         .map(|t| {
@@ -333,7 +660,12 @@ fn update_unspent_tx_outs(new_transactions: &Vec<Transaction>, unspent_tx_outs:
             ref_tx_outs
                 .into_iter()
                 .enumerate()
-                .map(|(index, tx_out)| UnspentTxOut::new(t.id.clone(), index, tx_out.address.clone(), tx_out.amount))
+                .filter(|(_, tx_out)| !tx_out.get_is_unspendable())
+                .map(|(index, tx_out)| UnspentTxOut {
+                    script: tx_out.script.clone(),
+                    asset: tx_out.asset.clone(),
+                    ..UnspentTxOut::new(t.id.clone(), index, tx_out.address.clone(), tx_out.amount)
+                })
         })
         .flatten()
         .collect();
@@ -357,17 +689,36 @@ fn get_is_valid_transactions_structure(transactions: &Vec<Transaction>) -> bool
     transactions.into_iter().all(|transactions| transactions.get_is_valid_structure())
 }
 
-pub fn get_coinbase_transaction(address: &str, block_index: usize) -> Transaction {
-    return Transaction::generate(
-        &vec![TxIn::new("".to_string(), block_index, "".to_string())],
-        &vec![TxOut::new(address.to_string(), COINBASE_AMOUNT)],
-    );
+/// Get coins issued by coinbase transaction of block index, the emission schedule.
+pub fn get_block_reward(_block_index: usize) -> usize {
+    COINBASE_AMOUNT
 }
 
-pub fn get_public_key(private_key: &str) -> String {
-    let secp = Secp256k1::new();
-    let secret_key = SecretKey::from_str(private_key).unwrap();
-    PublicKey::from_secret_key(&secp, &secret_key).to_string()
+/// Get coins issued by coinbase transactions of blocks up to block index, the supply at it.
+pub fn get_supply(block_index: usize) -> usize {
+    (0..=block_index).map(get_block_reward).fold(0, usize::saturating_add)
+}
+
+/// Get coinbase transaction of block index paying address, embedding extra data if any.
+pub fn get_coinbase_transaction(address: &str, block_index: usize, extra_data: Option<&[u8]>) -> Transaction {
+    let tx_ins = vec![TxIn::new("".to_string(), block_index, "".to_string())];
+    let tx_outs = vec![TxOut::new(address.to_string(), get_block_reward(block_index))];
+    let extra_data = extra_data.map(hex::encode);
+    Transaction {
+        version: INITIAL_VERSION,
+        id: get_transaction_id(INITIAL_VERSION, &tx_ins, &tx_outs, &extra_data),
+        tx_ins,
+        tx_outs,
+        extra_data,
+    }
+}
+
+/// Get address of private key.
+///
+/// # Errors
+/// If private key is not a key of one of the key types, it returns the error of `get_signer`.
+pub fn get_public_key(private_key: &str) -> Result<String, AppError> {
+    Ok(get_signer(private_key)?.get_address())
 }
 
 pub fn sign_tx_in(
@@ -381,22 +732,31 @@ pub fn sign_tx_in(
         return Err(AppError::new(2000));
     }
 
-    if !get_public_key(private_key).eq(&referenced_unspent_tx_out.unwrap().address) {
+    let referenced_unspent_tx_out = referenced_unspent_tx_out.unwrap();
+    let signer = get_signer(private_key)?;
+    if !referenced_unspent_tx_out.get_script().get_public_keys().contains(&signer.get_address()) {
         return Err(AppError::new(2000));
     }
 
-    let secp = Secp256k1::new();
-    let secret_key = SecretKey::from_str(private_key).unwrap();
-    let message = message_from_str(&transaction_id).unwrap();
-    Ok(secp.sign_ecdsa(&message, &secret_key).to_string())
+    let sighash = get_sighash(transaction_id, tx_in, referenced_unspent_tx_out, SIGHASH_ALL);
+    Ok(format!("{}{:02x}", signer.sign(&sighash)?, SIGHASH_ALL))
 }
 
-pub fn process_transactions(transactions: &Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>, block_index: usize) -> Result<Vec<UnspentTxOut>, AppError> {
+/// Process transactions of the block of context on top of unspent tx outs, getting unspent tx outs after them.
+///
+/// # Errors
+/// If a transaction is malformed, it returns error 2001, and if the transactions are invalid in the block, error 2002.
+pub fn process_transactions(
+    transactions: &Vec<Transaction>,
+    unspent_tx_outs: &Vec<UnspentTxOut>,
+    context: BlockContext,
+    validation_cache: &mut ValidationCache,
+) -> Result<Vec<UnspentTxOut>, AppError> {
     if !get_is_valid_transactions_structure(transactions) {
         return Err(AppError::new(2001));
     }
 
-    if !get_is_valid_block_transactions(transactions, unspent_tx_outs, block_index) {
+    if !get_is_valid_block_transactions(transactions, unspent_tx_outs, context, validation_cache) {
         return Err(AppError::new(2002));
     }
 
@@ -405,6 +765,12 @@ pub fn process_transactions(transactions: &Vec<Transaction>, unspent_tx_outs: &V
 
 #[cfg(test)]
 mod test {
+    use crate::chain_params::{with_chain_params, REGTEST};
+    use crate::mempool_policy::STANDARD_TRANSACTION_LIMITS;
+    use crate::testkit::{
+        get_funding_unspent_tx_outs, get_signed_transaction, Keypair, ALICE, BOB, CAROL, DAVE, FUNDING_TX_OUT_ID, RECEIVER_ADDRESS,
+        SIGNED_TX_IN_SIGNATURE,
+    };
     use super::*;
 
     #[test]
@@ -416,7 +782,9 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
 
-        assert_eq!(get_transaction_id(&tx_ins, &tx_outs), "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea");
+        assert_eq!(get_transaction_id(INITIAL_VERSION, &tx_ins, &tx_outs, &None), "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645");
+        assert_ne!(get_transaction_id(2, &tx_ins, &tx_outs, &None), "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645");
+        assert_ne!(get_transaction_id(INITIAL_VERSION, &tx_ins, &tx_outs, &Some("00".to_string())), "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645");
     }
 
     #[test]
@@ -427,17 +795,17 @@ mod test {
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction = Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs);
 
-        assert_eq!(transaction.id, get_transaction_id(&tx_ins, &tx_outs));
+        assert_eq!(transaction.id, get_transaction_id(INITIAL_VERSION, &tx_ins, &tx_outs, &None));
     }
 
     #[test]
-    fn test_get_is_valid_tx_in() {
+    fn test_check_tx_in() {
         let tx_in = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         let tx_ins = vec![tx_in.clone()];
         let tx_outs = vec![
@@ -445,58 +813,45 @@ mod test {
         ];
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
             )
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
-
-        assert!(get_is_valid_tx_in(&tx_in, &transaction, &unspent_tx_outs));
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
+
+        assert!(check_tx_in(&tx_in, &transaction, &unspent_tx_outs, BlockContext::default()).is_ok());
+        with_chain_params(REGTEST, || {
+            assert!(check_tx_in(&tx_in, &transaction, &unspent_tx_outs, BlockContext::default()).is_ok());
+            assert_eq!(
+                check_tx_in(&tx_in, &transaction, &unspent_tx_outs, BlockContext { index: 1, timestamp: 0 }),
+                Err(Invalidity::Unlocking),
+            );
+        });
     }
 
     #[test]
     fn test_find_unspent_tx_out() {
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
-                0,
-                "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
-                50,
-            )
-        ];
-        assert!(find_unspent_tx_out("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea", 0, &unspent_tx_outs).is_some());
-        assert!(find_unspent_tx_out("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea", 1, &unspent_tx_outs).is_none());
-    }
-
-    #[test]
-    fn test_get_tx_in_amount() {
-        let tx_in = TxIn::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), 0, "".to_string());
-        let unspent_tx_outs = vec![
-            UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
             )
         ];
-        assert_eq!(get_tx_in_amount(&tx_in, &unspent_tx_outs), 50);
-
-        let tx_in = TxIn::new("".to_string(), 0, "".to_string());
-        assert_eq!(get_tx_in_amount(&tx_in, &unspent_tx_outs), 0);
-
-        let tx_in = TxIn::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), 1, "".to_string());
-        assert_eq!(get_tx_in_amount(&tx_in, &unspent_tx_outs), 0);
+        assert!(find_unspent_tx_out("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645", 0, &unspent_tx_outs).is_some());
+        assert!(find_unspent_tx_out("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645", 1, &unspent_tx_outs).is_none());
     }
 
     #[test]
     fn test_get_is_valid_transaction() {
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             )
         ];
         let tx_outs = vec![
@@ -504,113 +859,171 @@ mod test {
         ];
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
             )
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
-        assert!(get_is_valid_transaction(&transaction, &unspent_tx_outs));
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
+        assert!(get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
 
         let tx_ins = vec![
             TxIn::new(
                 "invalid".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             )
         ];
-        let transaction = Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs);
-        assert!(!get_is_valid_transaction(&transaction, &unspent_tx_outs));
+        let transaction = Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs);
+        assert!(!get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             )
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 0)
         ];
-        let transaction = Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs);
-        assert!(!get_is_valid_transaction(&transaction, &unspent_tx_outs));
+        let transaction = Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs);
+        assert!(!get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
+    }
+
+    #[test]
+    fn test_get_invalidity() {
+        let transaction = get_signed_transaction();
+        let unspent_tx_outs = get_funding_unspent_tx_outs();
+        assert_eq!(get_invalidity(&transaction, &unspent_tx_outs, BlockContext::default()), None);
+        assert_eq!(get_invalidity(&Transaction { id: "00".to_string(), ..transaction.clone() }, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::Id));
+        assert_eq!(get_invalidity(&transaction, &vec![], BlockContext::default()), Some(Invalidity::MissingTxOut));
+
+        // Malformed keys and signatures of a peer only make the transaction invalid.
+        for signature in ["", "zz", "0", "30440220", &"ff".repeat(100), &format!("{}0", SIGNED_TX_IN_SIGNATURE)] {
+            let forged = Transaction { tx_ins: vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, signature.to_string())], ..transaction.clone() };
+            assert_eq!(get_invalidity(&forged, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::Unlocking));
+        }
+        for address in ["", "zz", &format!("03{}", "zz".repeat(32)), &format!("ed{}", "ff".repeat(32))] {
+            let malformed_unspent_tx_outs = vec![UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, address.to_string(), 50)];
+            assert_eq!(get_invalidity(&transaction, &malformed_unspent_tx_outs, BlockContext::default()), Some(Invalidity::Unlocking));
+        }
+
+        // Outputs wrapping around to the inputs do not conserve them.
+        let tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, "".to_string())];
+        let mut overflowing = Transaction::generate(&tx_ins, &vec![
+            TxOut::new(ALICE.address.to_string(), usize::MAX),
+            TxOut::new(ALICE.address.to_string(), 51),
+        ]);
+        let signature = sign_tx_in(&overflowing.id, &tx_ins[0], ALICE.private_key, &unspent_tx_outs).unwrap();
+        overflowing.tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, signature)];
+        assert_eq!(get_invalidity(&overflowing, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::Unconserved));
+    }
+
+    #[test]
+    fn test_get_invalidity_of_degenerate_transactions() {
+        let unspent_tx_outs = get_funding_unspent_tx_outs();
+        let get_signed = |tx_ins: &Vec<TxIn>, tx_outs: &Vec<TxOut>| {
+            let mut transaction = Transaction::generate(tx_ins, tx_outs);
+            transaction.tx_ins = tx_ins.iter()
+                .map(|tx_in| {
+                    let signature = sign_tx_in(&transaction.id, tx_in, ALICE.private_key, &unspent_tx_outs).unwrap();
+                    TxIn::new(tx_in.tx_out_id.clone(), tx_in.tx_out_index, signature)
+                })
+                .collect();
+            transaction
+        };
+        let tx_in = TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, "".to_string());
+
+        // Sending back to the spending address is a plain transaction.
+        let self_transfer = get_signed(&vec![tx_in.clone()], &vec![TxOut::new(ALICE.address.to_string(), 50)]);
+        assert_eq!(get_invalidity(&self_transfer, &unspent_tx_outs, BlockContext::default()), None);
+
+        let no_tx_ins = Transaction::generate(&vec![], &vec![TxOut::new(ALICE.address.to_string(), 0)]);
+        assert_eq!(get_invalidity(&no_tx_ins, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::NoTxIns));
+        let no_tx_outs = get_signed(&vec![tx_in.clone()], &vec![]);
+        assert_eq!(get_invalidity(&no_tx_outs, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::NoTxOuts));
+        let empty_address = get_signed(&vec![tx_in.clone()], &vec![TxOut::new("".to_string(), 50)]);
+        assert_eq!(get_invalidity(&empty_address, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::EmptyAddress));
+        let duplicate_tx_in = get_signed(&vec![tx_in.clone(), tx_in], &vec![TxOut::new(ALICE.address.to_string(), 100)]);
+        assert_eq!(get_invalidity(&duplicate_tx_in, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::DuplicateTxIn));
     }
 
     #[test]
     fn test_get_is_valid_coinbase_tx() {
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             )
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         assert!(get_is_valid_coinbase_tx(Some(&transaction), 0));
 
         assert!(!get_is_valid_coinbase_tx(None, 0));
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         assert!(!get_is_valid_coinbase_tx(Some(&transaction), 0));
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         assert!(!get_is_valid_coinbase_tx(Some(&transaction), 1));
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50),
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50),
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         assert!(!get_is_valid_coinbase_tx(Some(&transaction), 0));
 
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 0)
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         assert!(!get_is_valid_coinbase_tx(Some(&transaction), 0));
     }
 
     #[test]
     fn test_has_duplicates() {
         let a = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         let b = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         let tx_ins = vec![
             &a,
@@ -619,9 +1032,9 @@ mod test {
         assert!(has_duplicates(&tx_ins));
 
         let a = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         let tx_ins = vec![
             &a,
@@ -642,10 +1055,10 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         let unspent_tx_outs = vec![];
-        assert!(get_is_valid_block_transactions(&transactions, &unspent_tx_outs, 1));
+        assert!(get_is_valid_block_transactions(&transactions, &unspent_tx_outs, BlockContext { index: 1, timestamp: 0 }, &mut ValidationCache::default()));
 
         let tx_ins = vec![
             TxIn::new(
@@ -658,17 +1071,17 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("05f756fca4edb257e7ba26a4377246fcbef6de9e948886dad91355cdbfc32d9e".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("815b62d7386b68071998931112d7c1ee9acc874c780a49388d2386517ad6109b".to_string(), &tx_ins, &tx_outs)
         ];
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
             )
         ];
-        assert!(get_is_valid_block_transactions(&transactions, &unspent_tx_outs, 2));
+        assert!(get_is_valid_block_transactions(&transactions, &unspent_tx_outs, BlockContext { index: 2, timestamp: 0 }, &mut ValidationCache::default()));
     }
 
     #[test]
@@ -684,12 +1097,12 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         let unspent_tx_outs = vec![];
         let updated_unspent_tx_outs = update_unspent_tx_outs(&transactions, &unspent_tx_outs);
         let expect = updated_unspent_tx_outs.get(0).unwrap();
-        assert_eq!(expect.tx_out_id, "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea");
+        assert_eq!(expect.tx_out_id, "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645");
         assert_eq!(expect.tx_out_index, 0);
         assert_eq!(expect.address, "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b");
         assert_eq!(expect.amount, 50);
@@ -705,11 +1118,11 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("05f756fca4edb257e7ba26a4377246fcbef6de9e948886dad91355cdbfc32d9e".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("815b62d7386b68071998931112d7c1ee9acc874c780a49388d2386517ad6109b".to_string(), &tx_ins, &tx_outs)
         ];
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
@@ -717,7 +1130,7 @@ mod test {
         ];
         let updated_unspent_tx_outs = update_unspent_tx_outs(&transactions, &unspent_tx_outs);
         let expect = updated_unspent_tx_outs.get(1).unwrap();
-        assert_eq!(expect.tx_out_id, "05f756fca4edb257e7ba26a4377246fcbef6de9e948886dad91355cdbfc32d9e");
+        assert_eq!(expect.tx_out_id, "815b62d7386b68071998931112d7c1ee9acc874c780a49388d2386517ad6109b");
         assert_eq!(expect.tx_out_index, 0);
         assert_eq!(expect.address, "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b");
         assert_eq!(expect.amount, 50);
@@ -736,22 +1149,12 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         assert!(get_is_valid_transactions_structure(&transactions));
 
-        let tx_ins = vec![
-            TxIn::new(
-                "".to_string(),
-                0,
-                "".to_string(),
-            )
-        ];
-        let tx_outs = vec![
-            TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
-        ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction { version: INITIAL_VERSION - 1, ..transactions[0].clone() }
         ];
         assert!(!get_is_valid_transactions_structure(&transactions));
 
@@ -766,7 +1169,7 @@ mod test {
             TxOut::new("".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         assert!(!get_is_valid_transactions_structure(&transactions));
     }
@@ -775,8 +1178,8 @@ mod test {
     fn test_get_coinbase_transaction() {
         let block_index: usize = 1;
         let address = "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b";
-        let transaction = get_coinbase_transaction(address, block_index);
-        assert_eq!(transaction.id, get_transaction_id(&transaction.tx_ins, &transaction.tx_outs));
+        let transaction = get_coinbase_transaction(address, block_index, None);
+        assert_eq!(transaction.id, get_transaction_id(transaction.version, &transaction.tx_ins, &transaction.tx_outs, &None));
 
         let tx_in = transaction.tx_ins.get(0).unwrap();
         assert_eq!(tx_in.tx_out_id, "");
@@ -786,26 +1189,62 @@ mod test {
         let tx_out = transaction.tx_outs.get(0).unwrap();
         assert_eq!(tx_out.address, address);
         assert_eq!(tx_out.amount, COINBASE_AMOUNT);
+        assert!(get_is_valid_coinbase_tx(Some(&transaction), block_index));
+
+        let tagged = get_coinbase_transaction(address, block_index, Some(b"pool"));
+        assert_eq!(tagged.extra_data, Some("706f6f6c".to_string()));
+        assert_ne!(tagged.id, transaction.id);
+        assert!(get_is_valid_coinbase_tx(Some(&tagged), block_index));
+
+        let too_long = get_coinbase_transaction(address, block_index, Some(&[0; MAX_COINBASE_EXTRA_DATA + 1]));
+        assert!(!get_is_valid_coinbase_tx(Some(&too_long), block_index));
+        let mut tampered = tagged.clone();
+        tampered.extra_data = Some("6d696e65".to_string());
+        assert!(!get_is_valid_coinbase_tx(Some(&tampered), block_index));
+
+        let committed = get_coinbase_transaction(address, block_index, Some(&[&(block_index as u64).to_be_bytes()[..], b"pool"].concat()));
+        assert!(get_is_committed_height(&committed, block_index));
+        assert!(!get_is_committed_height(&committed, block_index + 1));
+        assert!(!get_is_committed_height(&tagged, block_index));
+        assert!(!get_is_committed_height(&transaction, block_index));
+        assert!(get_coinbase_prefix(block_index).is_empty());
+
+        with_chain_params(REGTEST, || {
+            assert!(get_coinbase_prefix(0).is_empty());
+            assert_eq!(get_coinbase_prefix(block_index), (block_index as u64).to_be_bytes());
+            assert!(!get_is_valid_coinbase_tx(Some(&tagged), block_index));
+            assert!(get_is_valid_coinbase_tx(Some(&committed), block_index));
+
+            let genesis_tagged = get_coinbase_transaction(address, 0, Some(b"pool"));
+            assert!(get_is_valid_coinbase_tx(Some(&genesis_tagged), 0));
+        });
+    }
+
+    #[test]
+    fn test_get_supply() {
+        assert_eq!(get_supply(0), COINBASE_AMOUNT);
+        assert_eq!(get_supply(9), 10 * COINBASE_AMOUNT);
     }
 
     #[test]
     fn test_get_public_key() {
-        assert_eq!(get_public_key("27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b"), "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b");
-        assert_eq!(get_public_key("607b5d1e4e91558db295a7541307f16d135152a826cc851fb2283d696ac8e931"), "02f893b966666dd482c3ffb23062a4cf7034114ce2363c2ee65f67f9b5d65decee");
+        assert_eq!(get_public_key("27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b").unwrap(), "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b");
+        assert_eq!(get_public_key("607b5d1e4e91558db295a7541307f16d135152a826cc851fb2283d696ac8e931").unwrap(), "02f893b966666dd482c3ffb23062a4cf7034114ce2363c2ee65f67f9b5d65decee");
         print!("{}", "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".len());
         print!("{}", "02f893b966666dd482c3ffb23062a4cf7034114ce2363c2ee65f67f9b5d65decee".len());
+        assert!(get_public_key("invalid").is_err());
     }
 
     #[test]
     fn test_sign_tx_in() {
-        let tx_ins = vec![TxIn::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), 0, "".to_string())];
+        let tx_ins = vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, "".to_string())];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transaction = Transaction::generate(&tx_ins, &tx_outs);
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
@@ -813,10 +1252,114 @@ mod test {
         ];
         assert_eq!(
             sign_tx_in(&transaction.id, tx_ins.get(0).unwrap(), "27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b", &unspent_tx_outs).unwrap(),
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a"
+            "304402205ed315edcadea42e895e715f37a46bb9c4cc5145bfe384b7f8f97aadd26be30c022062f8ac9dd8700df8981a07f8580e8d5982924e303f27e532ef36194de8cbadfc01"
         );
     }
 
+    #[test]
+    fn test_check_tx_in_with_sighash() {
+        let tx_ins = vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, "".to_string())];
+        let tx_outs = vec![
+            TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
+        ];
+        let mut transaction = Transaction::generate(&tx_ins, &tx_outs);
+        let unspent_tx_outs = vec![
+            UnspentTxOut::new(
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
+                0,
+                "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
+                50,
+            )
+        ];
+        let signature = sign_tx_in(&transaction.id, tx_ins.get(0).unwrap(), "27f5005f5f58f8711e99577e8b87e28ab4c2151f9289ac1203ccecdb94602a5b", &unspent_tx_outs).unwrap();
+        transaction.tx_ins = vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, signature.clone())];
+        assert!(check_tx_in(transaction.tx_ins.get(0).unwrap(), &transaction, &unspent_tx_outs, BlockContext::default()).is_ok());
+
+        let other_unspent_tx_outs = vec![
+            UnspentTxOut::new(
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
+                0,
+                "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
+                40,
+            )
+        ];
+        assert!(check_tx_in(transaction.tx_ins.get(0).unwrap(), &transaction, &other_unspent_tx_outs, BlockContext::default()).is_err());
+
+        let tx_in = TxIn::new(
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
+            0,
+            format!("{}02", &signature[..signature.len() - 2]),
+        );
+        assert!(check_tx_in(&tx_in, &transaction, &unspent_tx_outs, BlockContext::default()).is_err());
+    }
+
+    #[test]
+    fn test_check_tx_in_script() {
+        let public_keys = vec![ALICE.address.to_string(), BOB.address.to_string(), CAROL.address.to_string()];
+        let unspent_tx_outs = vec![UnspentTxOut {
+            script: Some(Script::multisig(2, &public_keys)),
+            ..UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, ALICE.address.to_string(), 50)
+        }];
+        let tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, "".to_string())];
+        let mut transaction = Transaction::generate(&tx_ins, &vec![TxOut::new(RECEIVER_ADDRESS.to_string(), 50)]);
+        let sign = |keypair: Keypair| sign_tx_in(&transaction.id, &tx_ins[0], keypair.private_key, &unspent_tx_outs).unwrap();
+        let (alice, carol) = (sign(ALICE), sign(CAROL));
+        assert!(sign_tx_in(&transaction.id, &tx_ins[0], DAVE.private_key, &unspent_tx_outs).is_err());
+
+        transaction.tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, format!("{} {}", alice, carol))];
+        let context = BlockContext { index: 1, timestamp: 0 };
+        assert_eq!(get_invalidity(&transaction, &unspent_tx_outs, context), Some(Invalidity::InactiveScript));
+        with_chain_params(REGTEST, || {
+            assert_eq!(get_invalidity(&transaction, &unspent_tx_outs, BlockContext::default()), Some(Invalidity::InactiveScript));
+            assert!(get_is_valid_transaction(&transaction, &unspent_tx_outs, context));
+        });
+
+        transaction.tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, alice)];
+        with_chain_params(REGTEST, || assert!(!get_is_valid_transaction(&transaction, &unspent_tx_outs, context)));
+
+        // Time locks are checked against the timestamp of the block, not the clock of the validating node.
+        let locked_unspent_tx_outs = vec![UnspentTxOut {
+            script: Some(Script::locktime(100, Script::pay_to_public_key(ALICE.address))),
+            ..UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, ALICE.address.to_string(), 50)
+        }];
+        let signature = sign_tx_in(&transaction.id, &tx_ins[0], ALICE.private_key, &locked_unspent_tx_outs).unwrap();
+        transaction.tx_ins = vec![TxIn::new(FUNDING_TX_OUT_ID.to_string(), 0, signature)];
+        assert!(!get_is_valid_transaction(&transaction, &locked_unspent_tx_outs, BlockContext { index: 1, timestamp: 100 }));
+        with_chain_params(REGTEST, || {
+            assert!(!get_is_valid_transaction(&transaction, &locked_unspent_tx_outs, BlockContext { index: 1, timestamp: 99 }));
+            assert!(get_is_valid_transaction(&transaction, &locked_unspent_tx_outs, BlockContext { index: 1, timestamp: 100 }));
+        });
+    }
+
+    #[test]
+    fn test_inactive_script_tx_out() {
+        let mut transaction = get_signed_transaction();
+        transaction.tx_outs[0].script = Some(Script::multisig(1, &vec![ALICE.address.to_string()]));
+        transaction.id = transaction.get_transaction_id();
+        let signature = sign_tx_in(&transaction.id, &transaction.tx_ins[0], ALICE.private_key, &get_funding_unspent_tx_outs()).unwrap();
+        transaction.tx_ins[0].signature = signature;
+
+        let context = BlockContext { index: 1, timestamp: 0 };
+        assert_eq!(get_invalidity(&transaction, &get_funding_unspent_tx_outs(), context), Some(Invalidity::InactiveScript));
+        with_chain_params(REGTEST, || assert!(get_is_valid_transaction(&transaction, &get_funding_unspent_tx_outs(), context)));
+    }
+
+    #[test]
+    fn test_transaction_limits() {
+        let tx_outs: Vec<TxOut> = (0..201)
+            .map(|_| TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 1))
+            .collect();
+        let transaction = Transaction::generate(&vec![], &tx_outs);
+        assert!(CONSENSUS_TRANSACTION_LIMITS.get_is_within(&transaction));
+        assert!(!STANDARD_TRANSACTION_LIMITS.get_is_within(&transaction));
+
+        let limits = TransactionLimits { max_size: transaction.get_size() - 1, max_tx_ins: 1, max_tx_outs: 1_000 };
+        assert!(!limits.get_is_within(&transaction));
+        assert_eq!(transaction.get_size(), encode(&transaction).len() + 1);
+        let with_data = Transaction { extra_data: Some("a".repeat(10)), ..transaction.clone() };
+        assert_eq!(with_data.get_size(), transaction.get_size() + 18);
+    }
+
     #[test]
     fn test_process_transactions() {
         let tx_ins = vec![
@@ -830,11 +1373,11 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         let unspent_tx_outs = vec![];
-        assert!(process_transactions(&transactions, &unspent_tx_outs, 1).is_ok());
-        assert!(process_transactions(&transactions, &unspent_tx_outs, 0).is_err());
+        assert!(process_transactions(&transactions, &unspent_tx_outs, BlockContext { index: 1, timestamp: 0 }, &mut ValidationCache::default()).is_ok());
+        assert!(process_transactions(&transactions, &unspent_tx_outs, BlockContext::default(), &mut ValidationCache::default()).is_err());
 
         let tx_ins = vec![
             TxIn::new(
@@ -847,9 +1390,9 @@ mod test {
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
         let transactions = vec![
-            Transaction::new("f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(), &tx_ins, &tx_outs)
+            Transaction::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), &tx_ins, &tx_outs)
         ];
         let unspent_tx_outs = vec![];
-        assert!(process_transactions(&transactions, &unspent_tx_outs, 1).is_err());
+        assert!(process_transactions(&transactions, &unspent_tx_outs, BlockContext { index: 1, timestamp: 0 }, &mut ValidationCache::default()).is_err());
     }
 }
@@ -1,5 +1,12 @@
+#[cfg(any(test, feature = "node"))]
 use crate::errors::AppError;
-use crate::transaction::{get_is_valid_transaction, Transaction, TxIn};
+#[cfg(any(test, feature = "node"))]
+use crate::mempool_policy::MempoolPolicy;
+use crate::transaction::{Transaction, TxIn};
+#[cfg(any(test, feature = "node"))]
+use crate::transaction::{BlockContext, Invalidity};
+#[cfg(any(test, feature = "node"))]
+use crate::validation_cache::ValidationCache;
 use crate::UnspentTxOut;
 
 pub fn get_tx_pool_ins(transaction_pool: &Vec<Transaction>) -> Vec<&TxIn> {
@@ -10,12 +17,14 @@ pub fn get_tx_pool_ins(transaction_pool: &Vec<Transaction>) -> Vec<&TxIn> {
         .collect()
 }
 
+#[cfg(any(test, feature = "node"))]
 fn contains_tx_in(tx_pool_ins: &Vec<&TxIn>, tx_in: &TxIn) -> bool {
     tx_pool_ins
         .into_iter()
         .any(|&tx_pool_in| tx_pool_in.tx_out_index == tx_in.tx_out_index && tx_pool_in.tx_out_id.eq(&tx_in.tx_out_id))
 }
 
+#[cfg(any(test, feature = "node"))]
 fn get_is_valid_tx_for_pool(tx: &Transaction, transaction_pool: &Vec<Transaction>) -> bool {
     let tx_pool_ins = get_tx_pool_ins(transaction_pool);
     let ref_tx_ins = &tx.tx_ins;
@@ -30,13 +39,72 @@ fn has_tx_in(tx_in: &TxIn, unspent_tx_outs: &Vec<UnspentTxOut>) -> bool {
         .any(|u_tx_o| u_tx_o.tx_out_id.eq(&tx_in.tx_out_id) && u_tx_o.tx_out_index == tx_in.tx_out_index)
 }
 
-pub fn add_to_transaction_pool(tx: &Transaction, transaction_pool: &mut Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>) -> Result<(), AppError> {
-    if !get_is_valid_transaction(tx, unspent_tx_outs) {
-        return Err(AppError::new(4000));
+/// Outcome of every check of pool admission on a transaction.
+#[cfg(any(test, feature = "node"))]
+#[derive(Debug)]
+pub struct Admission {
+    /// Consensus rule the transaction breaks, if any
+    pub invalidity: Option<Invalidity>,
+
+    /// Errors of failed checks, in the order `add_to_transaction_pool` runs them
+    pub errors: Vec<AppError>,
+}
+
+/// Run every check of pool admission on transaction without adding it to transaction pool, validating it for the
+/// block of context, the next one to be generated.
+///
+/// A transaction over the limits of mempool policy is rejected before it is validated, so its size never costs a
+/// signature check.
+#[cfg(any(test, feature = "node"))]
+pub fn check_admission(
+    tx: &Transaction,
+    transaction_pool: &Vec<Transaction>,
+    unspent_tx_outs: &Vec<UnspentTxOut>,
+    context: BlockContext,
+    mempool_policy: &MempoolPolicy,
+    validation_cache: &mut ValidationCache,
+) -> Admission {
+    if !mempool_policy.limits.get_is_within(tx) {
+        return Admission { invalidity: None, errors: vec![AppError::new(4003)] };
     }
 
+    let invalidity = validation_cache.get_invalidity(tx, unspent_tx_outs, context);
+    let mut errors = vec![];
+    if invalidity.is_some() {
+        errors.push(AppError::new(4000));
+    }
+
+    errors.extend(mempool_policy.get_violations(tx, unspent_tx_outs));
+
     if !get_is_valid_tx_for_pool(tx, transaction_pool) {
-        return Err(AppError::new(4001));
+        errors.push(AppError::new(4001));
+    }
+
+    Admission { invalidity, errors }
+}
+
+/// Add transaction valid under consensus in the block of context and following mempool policy to transaction pool.
+///
+/// # Errors
+/// If it is over the limits of mempool policy it returns error 4003 before it is validated.
+/// If it does not spend unspent tx outs it returns error 4000, else one of the errors of `MempoolPolicy::check`, and
+/// if it conflicts with the pool error 4001.
+#[cfg(any(test, feature = "node"))]
+pub fn add_to_transaction_pool(
+    tx: &Transaction,
+    transaction_pool: &mut Vec<Transaction>,
+    unspent_tx_outs: &Vec<UnspentTxOut>,
+    context: BlockContext,
+    mempool_policy: &MempoolPolicy,
+    validation_cache: &mut ValidationCache,
+) -> Result<(), AppError> {
+    let admission = check_admission(tx, transaction_pool, unspent_tx_outs, context, mempool_policy, validation_cache);
+    if let Some(invalidity) = admission.invalidity {
+        println!("Invalid transaction {}: {:?}", tx.id, invalidity);
+    }
+
+    if let Some(e) = admission.errors.into_iter().next() {
+        return Err(e);
     }
 
     transaction_pool.push(tx.clone());
@@ -44,6 +112,29 @@ pub fn add_to_transaction_pool(tx: &Transaction, transaction_pool: &mut Vec<Tran
     Ok(())
 }
 
+/// Get fee of transaction, which is inputs minus outputs.
+#[cfg(any(test, feature = "node"))]
+pub fn get_transaction_fee(tx: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> usize {
+    let total_tx_in_values: usize = tx.tx_ins
+        .iter()
+        .filter_map(|tx_in| unspent_tx_outs.iter().find(|u_tx_o| u_tx_o.tx_out_id.eq(&tx_in.tx_out_id) && u_tx_o.tx_out_index == tx_in.tx_out_index))
+        .map(|u_tx_o| u_tx_o.amount)
+        .sum();
+    let total_tx_out_values: usize = tx.tx_outs.iter().map(|tx_out| tx_out.amount).sum();
+    total_tx_in_values.saturating_sub(total_tx_out_values)
+}
+
+/// Remove transactions paying less fee than min fee, or all of them without min fee.
+#[cfg(any(test, feature = "node"))]
+pub fn trim_transaction_pool(transaction_pool: &mut Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>, min_fee: Option<usize>) -> usize {
+    let size = transaction_pool.len();
+    match min_fee {
+        Some(min_fee) => transaction_pool.retain(|tx| get_transaction_fee(tx, unspent_tx_outs) >= min_fee),
+        None => transaction_pool.clear(),
+    }
+    size - transaction_pool.len()
+}
+
 pub fn update_transaction_pool(transaction_pool: &Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>) -> Vec<Transaction> {
     let invalid_txs = transaction_pool
         .into_iter()
@@ -64,41 +155,43 @@ pub fn update_transaction_pool(transaction_pool: &Vec<Transaction>, unspent_tx_o
 
 #[cfg(test)]
 mod test {
-    use crate::transaction::TxOut;
+    use crate::constants::DEFAULT_DUST_LIMIT;
+    use crate::mempool_policy::STANDARD_TRANSACTION_LIMITS;
+    use crate::transaction::{TransactionLimits, TxOut};
     use super::*;
 
     #[test]
     fn test_get_tx_pool_ins() {
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction_pool = vec![Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs)];
+        let transaction_pool = vec![Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs)];
         let tx_pool_ins = get_tx_pool_ins(&transaction_pool);
         assert_eq!(tx_pool_ins.len(), 1);
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 1,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction_pool = vec![Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs)];
+        let transaction_pool = vec![Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs)];
         let tx_pool_ins = get_tx_pool_ins(&transaction_pool);
         assert_eq!(tx_pool_ins.len(), 2);
     }
@@ -106,17 +199,17 @@ mod test {
     #[test]
     fn test_contains_tx_in() {
         let tx_in = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         let tx_ins = vec![&tx_in];
         assert!(contains_tx_in(&tx_ins, &tx_in));
 
         let other = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         assert!(contains_tx_in(&tx_ins, &other));
     }
@@ -125,42 +218,42 @@ mod test {
     fn test_get_is_valid_tx_for_pool() {
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         let transaction_pool = vec![transaction.clone()];
         assert!(!get_is_valid_tx_for_pool(&transaction, &transaction_pool));
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 1,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let other_transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
+        let other_transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
         assert!(get_is_valid_tx_for_pool(&other_transaction, &transaction_pool));
     }
 
     #[test]
     fn test_has_tx_in() {
         let tx_in = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             0,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
@@ -169,9 +262,9 @@ mod test {
         assert!(has_tx_in(&tx_in, &unspent_tx_outs));
 
         let tx_in = TxIn::new(
-            "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+            "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
             1,
-            "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+            "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
         );
         assert!(!has_tx_in(&tx_in, &unspent_tx_outs));
     }
@@ -180,9 +273,9 @@ mod test {
     fn test_add_to_transaction_pool() {
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 1,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
@@ -190,36 +283,49 @@ mod test {
         ];
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
             )
         ];
-        let mut transaction_pool = vec![Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs)];
+        let mut transaction_pool = vec![Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs)];
 
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
             TxOut::new("03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(), 50)
         ];
-        let transaction = Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs);
-        add_to_transaction_pool(&transaction, &mut transaction_pool, &unspent_tx_outs).unwrap();
+        let transaction = Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs);
+        let error = add_to_transaction_pool(&transaction, &mut transaction_pool, &unspent_tx_outs, BlockContext::default(), &MempoolPolicy::new(60), &mut ValidationCache::default()).unwrap_err();
+        assert_eq!(error.code, 4002);
+
+        add_to_transaction_pool(&transaction, &mut transaction_pool, &unspent_tx_outs, BlockContext::default(), &MempoolPolicy::new(DEFAULT_DUST_LIMIT), &mut ValidationCache::default()).unwrap();
+        assert_eq!(transaction_pool.len(), 2);
+
+        let admission = check_admission(&transaction, &transaction_pool, &vec![], BlockContext::default(), &MempoolPolicy::new(60), &mut ValidationCache::default());
+        assert_eq!(admission.invalidity, Some(Invalidity::MissingTxOut));
+        assert_eq!(admission.errors.iter().map(|e| e.code).collect::<Vec<usize>>(), vec![4000, 4002, 4001]);
         assert_eq!(transaction_pool.len(), 2);
+
+        let limits = TransactionLimits { max_size: transaction.get_size() - 1, ..STANDARD_TRANSACTION_LIMITS };
+        let admission = check_admission(&transaction, &vec![], &vec![], BlockContext::default(), &MempoolPolicy { limits, ..MempoolPolicy::new(60) }, &mut ValidationCache::default());
+        assert_eq!(admission.invalidity, None);
+        assert_eq!(admission.errors.iter().map(|e| e.code).collect::<Vec<usize>>(), vec![4003]);
     }
 
     #[test]
     fn test_update_transaction_pool() {
         let tx_ins = vec![
             TxIn::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
-                "3045022100d73a8f9c7ce7fd44517ff0db38733af84a0ee1bc3ec89ed2c82dad412374057602203eac06b3c11dcb004991f39f9f23e46d3354ea6de8bfa73da8ca77adbb57988a".to_string(),
+                "304402206927040502de5d3fa352b69380954f76ffe2be934cecc2314ccf11cf8a95987002205ba27cb660e01807708a9062a33987be9379f6007fce814f604c36a040d15ba4".to_string(),
             ),
         ];
         let tx_outs = vec![
@@ -227,17 +333,58 @@ mod test {
         ];
         let unspent_tx_outs = vec![
             UnspentTxOut::new(
-                "f0ab1700e79b5f4c120062a791e7e69150577fea3ba9da15179025b3d2c061ea".to_string(),
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
                 0,
                 "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
                 50,
             )
         ];
-        let transaction_pool = vec![Transaction::new("2ffbf11ad81702d9a4b07b4a869b0ef304cdaebc7efcbb79e80942cdfef7cd0d".to_string(), &tx_ins, &tx_outs)];
+        let transaction_pool = vec![Transaction::new("e9e440cb59a3deca96ac3a2c846a787b030c73e8ef423a66df4604959d1f19ea".to_string(), &tx_ins, &tx_outs)];
         let new_transaction_pool = update_transaction_pool(&transaction_pool, &unspent_tx_outs);
         assert_eq!(new_transaction_pool.len(), 1);
 
         let new_transaction_pool = update_transaction_pool(&transaction_pool, &vec![]);
         assert_eq!(new_transaction_pool.len(), 0);
     }
+
+    #[test]
+    fn test_trim_transaction_pool() {
+        let unspent_tx_outs = vec![
+            UnspentTxOut::new(
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
+                0,
+                "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
+                50,
+            ),
+            UnspentTxOut::new(
+                "b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(),
+                1,
+                "03cbad07a30fa3c44cf3709e005149c5b41464070c15e783589d937a071f62930b".to_string(),
+                50,
+            ),
+        ];
+        let tx_outs = vec![
+            TxOut::new("03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40".to_string(), 40)
+        ];
+        let paying = Transaction::generate(
+            &vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 0, "".to_string())],
+            &tx_outs,
+        );
+        let tx_outs = vec![
+            TxOut::new("03b375875391f1dcd5af49e64a477d1be23ccbd0c7765bdde1b46072fb3703ec40".to_string(), 50)
+        ];
+        let free = Transaction::generate(
+            &vec![TxIn::new("b6c6a9436676b5ae1098bb94ca70b4be2fac9c953085c804f5901d42e7b72645".to_string(), 1, "".to_string())],
+            &tx_outs,
+        );
+        assert_eq!(get_transaction_fee(&paying, &unspent_tx_outs), 10);
+        assert_eq!(get_transaction_fee(&free, &unspent_tx_outs), 0);
+
+        let mut transaction_pool = vec![paying.clone(), free.clone()];
+        assert_eq!(trim_transaction_pool(&mut transaction_pool, &unspent_tx_outs, Some(1)), 1);
+        assert_eq!(transaction_pool, vec![paying]);
+
+        assert_eq!(trim_transaction_pool(&mut transaction_pool, &unspent_tx_outs, None), 1);
+        assert!(transaction_pool.is_empty());
+    }
 }
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use crate::errors::AppError;
+use crate::payload::Payload;
+use crate::peers::Direction;
+
+/// Backend carrying payloads between nodes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportKind {
+    WebSocket,
+    Libp2p,
+}
+
+impl FromStr for TransportKind {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "websocket" => Ok(TransportKind::WebSocket),
+            "libp2p" => Ok(TransportKind::Libp2p),
+            _ => Err(AppError::new(5001)),
+        }
+    }
+}
+
+impl TransportKind {
+    /// Returns whether a normalized peer address can be dialed with this transport, libp2p dialing multiaddrs only
+    pub fn get_is_dialable(&self, peer: &str) -> bool {
+        match self {
+            TransportKind::WebSocket => !peer.starts_with('/'),
+            TransportKind::Libp2p => peer.starts_with('/'),
+        }
+    }
+}
+
+/// Event raised by a transport, with the id it gave the peer.
+#[derive(Debug)]
+pub enum TransportEvent {
+    /// Dial of an address is up, with the id of the peer raised as `Connected` next
+    Dialed(String, String),
+    /// Dial of an address failed, with the error
+    DialFailed(String, String),
+    Connected(String, Direction),
+    /// Connection is down, with the reason the peer gave if any
+    Disconnected(String, Option<String>),
+    Received(String, Payload),
+}
+
+/// Connections to peers, reporting them as `TransportEvent`s.
+///
+/// Methods only queue work, so the broadcaster never waits on a slow peer.
+pub trait PeerTransport: Send {
+    /// Open a connection to address, raising `Dialed` and `Connected` once it is up or `DialFailed`
+    fn dial(&mut self, address: &str);
+
+    /// Send payload to a connected peer
+    fn send(&mut self, peer: &str, payload: &Payload);
+
+    /// Relay payload through the network, starting with peers
+    fn announce(&mut self, peers: &[String], payload: &Payload) {
+        for peer in peers {
+            self.send(peer, payload);
+        }
+    }
+
+    /// Close connection to peer, raising `Disconnected` once it is down
+    fn close(&mut self, peer: &str, reason: &str);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transport_kind() {
+        assert_eq!("websocket".parse::<TransportKind>().unwrap(), TransportKind::WebSocket);
+        assert_eq!("libp2p".parse::<TransportKind>().unwrap(), TransportKind::Libp2p);
+        assert_eq!("quic".parse::<TransportKind>().unwrap_err().code, 5001);
+    }
+
+    #[test]
+    fn test_get_is_dialable() {
+        assert!(TransportKind::WebSocket.get_is_dialable("ws://127.0.0.1:6001"));
+        assert!(!TransportKind::WebSocket.get_is_dialable("/ip4/127.0.0.1/tcp/6001"));
+        assert!(TransportKind::Libp2p.get_is_dialable("/ip4/127.0.0.1/tcp/6001"));
+        assert!(!TransportKind::Libp2p.get_is_dialable("ws://127.0.0.1:6001"));
+    }
+}
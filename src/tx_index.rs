@@ -0,0 +1,115 @@
+//! Replay protection of the chain.
+//!
+//! A block is rejected if one of its transactions:
+//! - has the id of a transaction already confirmed, or of another transaction of the block, since the outputs of
+//!   both would be keyed by the same outpoints;
+//! - spends an outpoint already spent on the chain, or by another transaction of the block.
+//!
+//! Coinbase inputs spend no outpoint and are only kept unique through their ids. Blocks after a utxo snapshot are
+//! only checked against the blocks the snapshot carries with data.
+
+use std::collections::HashSet;
+
+use crate::Block;
+use crate::transaction::Transaction;
+
+/// Ids of transactions and outpoints they spent on the chain.
+#[derive(Debug, Default)]
+pub struct TxIndex {
+    tx_ids: HashSet<String>,
+    spent_outpoints: HashSet<(String, usize)>,
+}
+
+impl TxIndex {
+    /// Returns an index replaying blockchain
+    pub fn new(blockchain: &[Block]) -> TxIndex {
+        let mut index = TxIndex::default();
+        for block in blockchain {
+            index.apply_block(block);
+        }
+        index
+    }
+
+    /// Get transaction of id is on the chain
+    pub fn contains(&self, tx_id: &str) -> bool {
+        self.tx_ids.contains(tx_id)
+    }
+
+    /// Get outpoint is spent on the chain
+    pub fn get_is_spent(&self, tx_out_id: &str, tx_out_index: usize) -> bool {
+        self.spent_outpoints.contains(&(tx_out_id.to_string(), tx_out_index))
+    }
+
+    /// Record transactions of a block and outpoints they spend
+    pub fn apply_block(&mut self, block: &Block) {
+        for transaction in block.data.iter() {
+            self.tx_ids.insert(transaction.id.clone());
+            self.spent_outpoints.extend(get_spent_outpoints(transaction));
+        }
+    }
+
+    /// Get transactions replay none on the chain or among themselves
+    pub fn get_is_valid_transactions(&self, transactions: &[Transaction]) -> bool {
+        let mut tx_ids = HashSet::new();
+        let mut spent_outpoints = HashSet::new();
+        transactions.iter().all(|transaction| {
+            !self.tx_ids.contains(&transaction.id) && tx_ids.insert(&transaction.id) &&
+                get_spent_outpoints(transaction).all(|outpoint| {
+                    !self.spent_outpoints.contains(&outpoint) && spent_outpoints.insert(outpoint)
+                })
+        })
+    }
+}
+
+fn get_spent_outpoints(transaction: &Transaction) -> impl Iterator<Item = (String, usize)> + '_ {
+    transaction.tx_ins
+        .iter()
+        .filter(|tx_in| !tx_in.tx_out_id.is_empty())
+        .map(|tx_in| (tx_in.tx_out_id.clone(), tx_in.tx_out_index))
+}
+
+/// Get no block of blockchain replays a transaction before it.
+pub fn get_is_valid_tx_ids(blockchain: &[Block]) -> bool {
+    let mut index = TxIndex::default();
+    blockchain.iter().all(|block| {
+        let is_valid = index.get_is_valid_transactions(&block.data);
+        index.apply_block(block);
+        is_valid
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{get_block, ALICE, BOB};
+    use crate::transaction::{get_coinbase_transaction, TxIn, TxOut};
+    use super::*;
+
+    #[test]
+    fn test_tx_index() {
+        let coinbase = get_coinbase_transaction(ALICE.address, 1, None);
+        let spend = Transaction::generate(
+            &vec![TxIn::new(coinbase.id.clone(), 0, "".to_string())],
+            &vec![TxOut::new(BOB.address.to_string(), 50)],
+        );
+        let blockchain = vec![get_block(0, vec![]), get_block(1, vec![coinbase.clone(), spend.clone()])];
+        let index = TxIndex::new(&blockchain);
+        assert!(index.contains(&coinbase.id));
+        assert!(index.get_is_spent(&coinbase.id, 0));
+        assert!(!index.get_is_spent("", 1));
+
+        let double_spend = Transaction::generate(
+            &vec![TxIn::new(coinbase.id.clone(), 0, "".to_string())],
+            &vec![TxOut::new(ALICE.address.to_string(), 50)],
+        );
+        let next_coinbase = get_coinbase_transaction(ALICE.address, 2, None);
+        assert!(!index.get_is_valid_transactions(&vec![coinbase.clone()]));
+        assert!(!index.get_is_valid_transactions(&vec![double_spend]));
+        assert!(!index.get_is_valid_transactions(&vec![next_coinbase.clone(), next_coinbase.clone()]));
+        assert!(index.get_is_valid_transactions(&vec![next_coinbase]));
+
+        assert!(get_is_valid_tx_ids(&blockchain));
+        let mut blockchain = blockchain;
+        blockchain.push(get_block(2, vec![spend]));
+        assert!(!get_is_valid_tx_ids(&blockchain));
+    }
+}
@@ -0,0 +1,77 @@
+use sha2::{Digest, Sha256};
+
+use crate::activation::{get_is_active, Rule};
+use crate::codec::{Encode, Encoder};
+use crate::transaction::Transaction;
+
+/// Prefix of leaf hashes, so a transaction never hashes like a node of the tree.
+const LEAF_PREFIX: u8 = 0;
+
+/// Prefix of node hashes.
+const NODE_PREFIX: u8 = 1;
+
+/// Get root of the merkle tree of transactions, leaves hashing their canonical bytes so signatures are covered. The
+/// last node of a level with an odd count is carried up unpaired.
+pub fn get_tx_root(data: &[Transaction]) -> String {
+    let mut level: Vec<[u8; 32]> = data
+        .iter()
+        .map(|transaction| {
+            let mut encoder = Encoder::new();
+            transaction.encode(&mut encoder);
+            Sha256::new().chain_update([LEAF_PREFIX]).chain_update(encoder.into_bytes()).finalize().into()
+        })
+        .collect();
+    if level.is_empty() {
+        return format!("{:x}", Sha256::digest([]));
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => Sha256::new().chain_update([NODE_PREFIX]).chain_update(left).chain_update(right).finalize().into(),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    hex::encode(level[0])
+}
+
+/// Get root of transactions a block of data at height carries, none before the rule is active.
+pub fn get_tx_root_at(height: usize, data: &[Transaction]) -> Option<String> {
+    if !get_is_active(Rule::TxRoot, height) {
+        return None;
+    }
+    Some(get_tx_root(data))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::chain_params::{with_chain_params, REGTEST};
+    use crate::testkit::{get_signed_transaction, ALICE};
+    use crate::transaction::get_coinbase_transaction;
+    use super::*;
+
+    #[test]
+    fn test_get_tx_root() {
+        let coinbase = get_coinbase_transaction(ALICE.address, 1, None);
+        let transaction = get_signed_transaction();
+        let root = get_tx_root(&[coinbase.clone(), transaction.clone()]);
+        assert_eq!(root.len(), 64);
+        assert_ne!(root, get_tx_root(&[transaction.clone(), coinbase.clone()]));
+        assert_ne!(root, get_tx_root(&[coinbase.clone(), transaction.clone(), transaction.clone()]));
+        assert_ne!(get_tx_root(&[coinbase.clone()]), get_tx_root(&[]));
+
+        // Signatures are not covered by transaction ids, but are by the root.
+        let mut forged = transaction.clone();
+        forged.tx_ins[0].signature = "00".to_string();
+        assert_ne!(root, get_tx_root(&[coinbase.clone(), forged]));
+
+        assert_eq!(get_tx_root_at(usize::MAX, &[coinbase.clone()]), None);
+        with_chain_params(REGTEST, || {
+            assert_eq!(get_tx_root_at(0, &[coinbase.clone()]), None);
+            assert_eq!(get_tx_root_at(1, &[coinbase.clone()]), Some(get_tx_root(&[coinbase])));
+        });
+    }
+}
@@ -0,0 +1,69 @@
+use sha2::{Digest, Sha256};
+
+use crate::Block;
+use crate::activation::{get_is_active, Rule};
+use crate::codec::{Encode, Encoder};
+use crate::transaction::UnspentTxOut;
+#[cfg(any(test, feature = "node"))]
+use crate::transaction::{update_unspent_tx_outs, Transaction};
+
+/// Get commitment to unspent tx outs, the hash of their canonical bytes in order of outpoint, so it does not depend
+/// on the order they were added in.
+pub fn get_utxo_commitment(unspent_tx_outs: &[UnspentTxOut]) -> String {
+    let mut sorted: Vec<&UnspentTxOut> = unspent_tx_outs.iter().collect();
+    sorted.sort_by(|a, b| (&a.tx_out_id, a.tx_out_index).cmp(&(&b.tx_out_id, b.tx_out_index)));
+
+    let mut encoder = Encoder::new();
+    encoder.put_usize(sorted.len());
+    for unspent_tx_out in sorted {
+        unspent_tx_out.encode(&mut encoder);
+    }
+    format!("{:x}", Sha256::digest(encoder.into_bytes()))
+}
+
+/// Get commitment a block at height carries to unspent tx outs after it, none before the rule is active.
+pub fn get_utxo_commitment_at(height: usize, unspent_tx_outs: &[UnspentTxOut]) -> Option<String> {
+    if !get_is_active(Rule::UtxoCommitment, height) {
+        return None;
+    }
+    Some(get_utxo_commitment(unspent_tx_outs))
+}
+
+/// Get commitment a block of data at height carries on top of unspent tx outs, none before the rule is active.
+#[cfg(any(test, feature = "node"))]
+pub fn get_next_utxo_commitment(data: &Vec<Transaction>, unspent_tx_outs: &Vec<UnspentTxOut>, height: usize) -> Option<String> {
+    if !get_is_active(Rule::UtxoCommitment, height) {
+        return None;
+    }
+    Some(get_utxo_commitment(&update_unspent_tx_outs(data, unspent_tx_outs)))
+}
+
+/// Get flag block carries the commitment to unspent tx outs after it due at its height.
+pub fn get_is_valid_utxo_commitment(block: &Block, unspent_tx_outs: &[UnspentTxOut]) -> bool {
+    block.utxo_commitment == get_utxo_commitment_at(block.index, unspent_tx_outs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{ALICE, BOB, FUNDING_TX_OUT_ID};
+    use super::*;
+
+    #[test]
+    fn test_get_utxo_commitment() {
+        let a = UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 0, ALICE.address.to_string(), 50);
+        let b = UnspentTxOut::new(FUNDING_TX_OUT_ID.to_string(), 1, BOB.address.to_string(), 10);
+        let commitment = get_utxo_commitment(&[a.clone(), b.clone()]);
+        assert_eq!(commitment.len(), 64);
+        assert_eq!(commitment, get_utxo_commitment(&[b.clone(), a.clone()]));
+        assert_ne!(commitment, get_utxo_commitment(&[a.clone()]));
+        assert_ne!(commitment, get_utxo_commitment(&[a.clone(), UnspentTxOut { amount: 11, ..b.clone() }]));
+
+        assert_eq!(get_utxo_commitment_at(usize::MAX, &[a.clone()]), None);
+        assert_eq!(get_next_utxo_commitment(&vec![], &vec![a.clone()], usize::MAX), None);
+
+        let block = Block::new(1, "".to_string(), "".to_string(), 0, vec![], 0, 0);
+        assert!(get_is_valid_utxo_commitment(&block, &[a.clone()]));
+        let block = Block { utxo_commitment: Some(commitment), ..block };
+        assert!(!get_is_valid_utxo_commitment(&block, &[a, b]));
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+
+use crate::Block;
+use crate::activation::{get_is_active, Rule};
+use crate::block::{add_block, get_is_valid_new_header, get_latest_block, ChainIndex};
+use crate::consensus_rules::ConsensusRules;
+use crate::errors::AppError;
+use crate::network_time::Clock;
+use crate::transaction::{update_unspent_tx_outs, UnspentTxOut};
+use crate::utxo_commitment::get_utxo_commitment;
+use crate::validation_cache::ValidationCache;
+
+/// Unspent tx outs at a block committing to them, with headers of the chain up to it and blocks after it, for a node
+/// to fast-sync from instead of replaying every block.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UtxoSnapshot {
+    /// Blocks up to the snapshot, without data once they carry a tx root
+    pub headers: Vec<Block>,
+
+    /// Unspent tx outs after the last header
+    pub unspent_tx_outs: Vec<UnspentTxOut>,
+
+    /// Blocks after the snapshot, with data
+    pub blocks: Vec<Block>,
+}
+
+impl UtxoSnapshot {
+    /// Returns snapshot of blockchain at the latest block committing to unspent tx outs at least depth blocks below
+    /// its tip.
+    ///
+    /// # Errors
+    /// If no such block exists, it returns error 1008.
+    pub fn new(blockchain: &Vec<Block>, depth: usize) -> Result<UtxoSnapshot, AppError> {
+        let height = get_latest_block(blockchain).index
+            .checked_sub(depth)
+            .and_then(|top| (0..=top).rev().find(|height| blockchain[*height].utxo_commitment.is_some()))
+            .ok_or(AppError::new(1008))?;
+        let unspent_tx_outs = blockchain[..=height]
+            .iter()
+            .fold(vec![], |unspent_tx_outs, block| update_unspent_tx_outs(&block.data, &unspent_tx_outs));
+
+        Ok(UtxoSnapshot {
+            headers: blockchain[..=height].iter().map(get_header).collect(),
+            unspent_tx_outs,
+            blocks: blockchain[height + 1..].to_vec(),
+        })
+    }
+
+    /// Verify snapshot starts from the genesis block of blockchain at the time of clock, and get the chain it leads
+    /// to, blocks up to the snapshot without data, with its unspent tx outs.
+    ///
+    /// Headers are checked to link up with hashes recalculated from them matching their difficulty, and the last one
+    /// to commit to the unspent tx outs, blocks after it are replayed.
+    ///
+    /// # Errors
+    /// If headers do not link up from the genesis block, it returns error 1000.
+    /// If the utxo commitment rule is not active at the last header or unspent tx outs do not match its commitment, it
+    /// returns error 1007.
+    /// If a block after it cannot be added under consensus rules, it returns one of the errors of `add_block`.
+    pub fn verify(self, blockchain: &Vec<Block>, consensus_rules: &ConsensusRules, clock: &dyn Clock) -> Result<(Vec<Block>, Vec<UnspentTxOut>), AppError> {
+        let is_valid_headers = self.headers.first() == Some(&get_header(&blockchain[0]))
+            && self.headers.windows(2).all(|window| get_is_valid_new_header(&window[1], &window[0], clock));
+        if !is_valid_headers {
+            return Err(AppError::new(1000));
+        }
+
+        let latest = get_latest_block(&self.headers);
+        let utxo_commitment = get_utxo_commitment(&self.unspent_tx_outs);
+        if !get_is_active(Rule::UtxoCommitment, latest.index) || latest.utxo_commitment.as_deref() != Some(utxo_commitment.as_str()) {
+            return Err(AppError::new(1007));
+        }
+
+        let mut new_blockchain = self.headers;
+        let mut unspent_tx_outs = self.unspent_tx_outs;
+        let mut validation_cache = ValidationCache::new(0);
+        let mut chain_index = ChainIndex::new(&new_blockchain);
+        for block in &self.blocks {
+            add_block(&mut new_blockchain, &mut chain_index, &mut unspent_tx_outs, &mut vec![], block, &mut validation_cache, consensus_rules, clock)?;
+        }
+        Ok((new_blockchain, unspent_tx_outs))
+    }
+}
+
+/// Get flag blockchain holds headers of a utxo snapshot without data, which peers cannot replay.
+pub fn get_is_pruned(blockchain: &[Block]) -> bool {
+    blockchain.iter().any(|block| block.data.is_empty())
+}
+
+/// Get block without its data, which its hash still commits to by tx root, or with it before the block carries one.
+pub fn get_header(block: &Block) -> Block {
+    match block.tx_root {
+        Some(_) => Block { data: Arc::new(vec![]), ..block.clone() },
+        None => block.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::{get_genesis_block, MiningProgress};
+    use crate::chain_params::{with_chain_params, REGTEST};
+    use crate::network_time::MockClock;
+    use crate::testkit::ALICE;
+    use crate::transaction::{get_coinbase_prefix, get_coinbase_transaction};
+    use crate::utxo_commitment::get_utxo_commitment_at;
+    use super::*;
+
+    /// Get chain of blocks paying `ALICE` up to height, committing to unspent tx outs where `commit` holds.
+    fn get_blockchain(height: usize, commit: impl Fn(usize) -> bool, clock: &MockClock) -> (Vec<Block>, Vec<UnspentTxOut>) {
+        let mut blockchain = vec![get_genesis_block()];
+        let mut unspent_tx_outs = update_unspent_tx_outs(&blockchain[0].data, &vec![]);
+        for index in 1..=height {
+            let data = vec![get_coinbase_transaction(ALICE.address, index, Some(&get_coinbase_prefix(index)))];
+            unspent_tx_outs = update_unspent_tx_outs(&data, &unspent_tx_outs);
+            let utxo_commitment = Some(get_utxo_commitment(&unspent_tx_outs)).filter(|_| commit(index));
+            let block = Block::generate_with_progress(&data, get_latest_block(&blockchain), 0, utxo_commitment, &MiningProgress::default(), clock);
+            blockchain.push(block.unwrap());
+        }
+        (blockchain, unspent_tx_outs)
+    }
+
+    #[test]
+    fn test_utxo_snapshot() {
+        let clock = MockClock::new(get_genesis_block().timestamp + 100);
+        with_chain_params(REGTEST, || {
+            let (blockchain, unspent_tx_outs) = get_blockchain(4, |index| get_utxo_commitment_at(index, &[]).is_some(), &clock);
+
+            assert_eq!(UtxoSnapshot::new(&blockchain, 5).unwrap_err().code, 1008);
+            let snapshot = UtxoSnapshot::new(&blockchain, 2).unwrap();
+            assert_eq!((snapshot.headers.len(), snapshot.blocks.len()), (3, 2));
+            assert!(snapshot.headers[1..].iter().all(|header| header.data.is_empty()));
+
+            let (new_blockchain, new_unspent_tx_outs) = snapshot.clone().verify(&vec![get_genesis_block()], &ConsensusRules::default(), &clock).unwrap();
+            assert_eq!(get_latest_block(&new_blockchain), get_latest_block(&blockchain));
+            assert_eq!(new_blockchain.len(), blockchain.len());
+            assert!(get_is_pruned(&new_blockchain));
+            assert!(!get_is_pruned(&blockchain));
+            assert_eq!(get_utxo_commitment(&new_unspent_tx_outs), get_utxo_commitment(&unspent_tx_outs));
+
+            let tampered = UtxoSnapshot { unspent_tx_outs: vec![], ..snapshot.clone() };
+            assert_eq!(tampered.verify(&vec![get_genesis_block()], &ConsensusRules::default(), &clock).unwrap_err().code, 1007);
+            let mut headers = snapshot.headers.clone();
+            headers.remove(1);
+            let unlinked = UtxoSnapshot { headers: headers.clone(), ..snapshot.clone() };
+            assert_eq!(unlinked.verify(&vec![get_genesis_block()], &ConsensusRules::default(), &clock).unwrap_err().code, 1000);
+
+            // Hashes matching difficulty 0 are not enough, they are recalculated from the headers.
+            let mut headers = snapshot.headers.clone();
+            headers[2].utxo_commitment = Some(get_utxo_commitment(&[]));
+            let forged = UtxoSnapshot { headers, unspent_tx_outs: vec![], blocks: vec![] };
+            assert_eq!(forged.verify(&vec![get_genesis_block()], &ConsensusRules::default(), &clock).unwrap_err().code, 1000);
+            let mut headers = snapshot.headers.clone();
+            headers[1].tx_root = None;
+            let rootless = UtxoSnapshot { headers, ..snapshot };
+            assert_eq!(rootless.verify(&vec![get_genesis_block()], &ConsensusRules::default(), &clock).unwrap_err().code, 1000);
+        });
+
+        // Commitments only bind unspent tx outs once the rule is active.
+        let (blockchain, _) = get_blockchain(3, |index| index == 2, &clock);
+        let snapshot = UtxoSnapshot::new(&blockchain, 1).unwrap();
+        assert!(snapshot.headers.iter().all(|header| header.tx_root.is_none()));
+        assert_eq!(snapshot.verify(&vec![get_genesis_block()], &ConsensusRules::default(), &clock).unwrap_err().code, 1007);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::{BTreeMap, HashMap};
+use sha2::{Digest, Sha256};
+
+use crate::activation::get_block_version;
+use crate::block::TIMESTAMP_INTERVAL;
+use crate::constants::{CHAIN_VERDICT_CACHE_SIZE, VALIDATION_CACHE_SIZE};
+use crate::errors::AppError;
+use crate::transaction::{find_unspent_tx_out, get_invalidity, BlockContext, Invalidity, Transaction, UnspentTxOut};
+
+#[derive(Debug)]
+struct Entry {
+    snapshot_hash: String,
+    invalidity: Option<Invalidity>,
+    last_used: u64,
+}
+
+/// Verdict on a chain received from a peer, with the error code it was rejected with.
+#[derive(Debug)]
+struct ChainVerdict {
+    digest: String,
+    error: Option<usize>,
+    checked_at: usize,
+}
+
+/// Least recently used cache of transaction validation results.
+///
+/// Results are keyed by transaction id and only reused while the transaction, the outputs it spends and the rules
+/// active at the height of the block are the same, so a changed snapshot of unspent tx outs revalidates it.
+#[derive(Debug)]
+pub struct ValidationCache {
+    capacity: usize,
+    entries: HashMap<String, Entry>,
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    chain_verdicts: HashMap<String, ChainVerdict>,
+}
+
+impl Default for ValidationCache {
+    fn default() -> Self {
+        ValidationCache::new(VALIDATION_CACHE_SIZE)
+    }
+}
+
+impl ValidationCache {
+    /// Returns an empty cache holding up to capacity results
+    pub fn new(capacity: usize) -> ValidationCache {
+        ValidationCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            chain_verdicts: HashMap::new(),
+        }
+    }
+
+    /// Get count of cached results
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drop all cached results of transactions, e.g. when the chain is reorganized.
+    ///
+    /// Verdicts on chains do not depend on the local chain and are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Get transaction is valid in the block of context, reusing the cached result against the same spent outputs.
+    pub fn get_is_valid_transaction(&mut self, transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>, context: BlockContext) -> bool {
+        self.get_invalidity(transaction, unspent_tx_outs, context).is_none()
+    }
+
+    /// Get why transaction is invalid in the block of context, reusing the cached reason against the same spent outputs.
+    ///
+    /// Transactions spending time locked outputs are validated every time, as their result changes with the timestamp
+    /// of the block.
+    pub fn get_invalidity(&mut self, transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>, context: BlockContext) -> Option<Invalidity> {
+        if get_is_time_locked(transaction, unspent_tx_outs) {
+            return get_invalidity(transaction, unspent_tx_outs, context);
+        }
+
+        let snapshot_hash = get_snapshot_hash(transaction, unspent_tx_outs, get_block_version(context.index));
+        self.tick += 1;
+
+        if let Some(entry) = self.entries.get_mut(&transaction.id) {
+            if entry.snapshot_hash == snapshot_hash {
+                self.recency.remove(&entry.last_used);
+                self.recency.insert(self.tick, transaction.id.clone());
+                entry.last_used = self.tick;
+                return entry.invalidity;
+            }
+        }
+
+        let invalidity = get_invalidity(transaction, unspent_tx_outs, context);
+        self.insert(transaction.id.clone(), Entry { snapshot_hash, invalidity, last_used: self.tick });
+        invalidity
+    }
+
+    /// Get verdict on a chain of tip hash received as payload data of digest, reusing it if it was checked before.
+    ///
+    /// A rejection is only reused for `TIMESTAMP_INTERVAL` seconds, as blocks ahead of the clock become valid later.
+    pub fn get_chain_verdict(&self, tip_hash: &str, digest: &str, now: usize) -> Option<Result<(), AppError>> {
+        let verdict = self.chain_verdicts.get(tip_hash).filter(|verdict| verdict.digest == digest)?;
+        match verdict.error {
+            None => Some(Ok(())),
+            Some(code) if now < verdict.checked_at + TIMESTAMP_INTERVAL => Some(Err(AppError::new(code))),
+            Some(_) => None,
+        }
+    }
+
+    /// Record verdict on a chain of tip hash received as payload data of digest, dropping the oldest one when full
+    pub fn record_chain_verdict(&mut self, tip_hash: &str, digest: &str, verdict: &Result<(), AppError>, now: usize) {
+        if !self.chain_verdicts.contains_key(tip_hash) && self.chain_verdicts.len() >= CHAIN_VERDICT_CACHE_SIZE {
+            if let Some(oldest) = self.chain_verdicts.iter().min_by_key(|(_, verdict)| verdict.checked_at).map(|(hash, _)| hash.clone()) {
+                self.chain_verdicts.remove(&oldest);
+            }
+        }
+        self.chain_verdicts.insert(tip_hash.to_string(), ChainVerdict {
+            digest: digest.to_string(),
+            error: verdict.as_ref().err().map(|error| error.code),
+            checked_at: now,
+        });
+    }
+
+    fn insert(&mut self, id: String, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some(previous) = self.entries.remove(&id) {
+            self.recency.remove(&previous.last_used);
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest = *self.recency.keys().next().unwrap();
+            let oldest_id = self.recency.remove(&oldest).unwrap();
+            self.entries.remove(&oldest_id);
+        }
+
+        self.recency.insert(entry.last_used, id.clone());
+        self.entries.insert(id, entry);
+    }
+}
+
+/// Get hash of a transaction with the unspent tx outs it spends, missing ones included as absent, under the rules of
+/// block version.
+///
+/// Signatures are not covered by transaction id, so the whole transaction is hashed.
+fn get_snapshot_hash(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>, block_version: u32) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(block_version.to_be_bytes());
+    hasher.update(serde_json::to_string(transaction).unwrap().as_bytes());
+    for tx_in in &transaction.tx_ins {
+        match find_unspent_tx_out(&tx_in.tx_out_id, tx_in.tx_out_index, unspent_tx_outs) {
+            Some(u_tx_o) => hasher.update(format!(
+                "{}{}{}{}{}{};",
+                u_tx_o.tx_out_id,
+                u_tx_o.tx_out_index,
+                u_tx_o.address,
+                u_tx_o.amount,
+                serde_json::to_string(&u_tx_o.script).unwrap(),
+                u_tx_o.asset.as_deref().unwrap_or_default(),
+            ).as_bytes()),
+            None => hasher.update(format!("{}{}-;", tx_in.tx_out_id, tx_in.tx_out_index).as_bytes()),
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Get digest of payload data carrying a chain, so a verdict is only reused for the same chain.
+#[cfg(any(test, feature = "node"))]
+pub fn get_chain_digest(data: &str) -> String {
+    format!("{:x}", Sha256::digest(data.as_bytes()))
+}
+
+/// Get transaction spends an output with a time locked script.
+fn get_is_time_locked(transaction: &Transaction, unspent_tx_outs: &Vec<UnspentTxOut>) -> bool {
+    transaction.tx_ins.iter().any(|tx_in| {
+        find_unspent_tx_out(&tx_in.tx_out_id, tx_in.tx_out_index, unspent_tx_outs)
+            .and_then(|u_tx_o| u_tx_o.script.as_ref())
+            .map_or(false, |script| script.get_has_locktime())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testkit::{get_funding_unspent_tx_outs, get_signed_transaction};
+    use super::*;
+
+    #[test]
+    fn test_get_is_valid_transaction() {
+        let mut cache = ValidationCache::new(1);
+        let transaction = get_signed_transaction();
+        let unspent_tx_outs = get_funding_unspent_tx_outs();
+
+        assert!(cache.get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
+        assert!(cache.get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
+        assert_eq!(cache.len(), 1);
+
+        assert!(!cache.get_is_valid_transaction(&transaction, &vec![], BlockContext::default()));
+        assert!(cache.get_is_valid_transaction(&transaction, &unspent_tx_outs, BlockContext::default()));
+
+        let mut forged = get_signed_transaction();
+        forged.tx_ins[0].signature = "00".to_string();
+        assert!(!cache.get_is_valid_transaction(&forged, &unspent_tx_outs, BlockContext::default()));
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = ValidationCache::new(2);
+        let unspent_tx_outs = vec![];
+        let transactions: Vec<Transaction> = (0..3)
+            .map(|index| Transaction::new(format!("{}", index), &vec![], &vec![]))
+            .collect();
+
+        cache.get_is_valid_transaction(&transactions[0], &unspent_tx_outs, BlockContext::default());
+        cache.get_is_valid_transaction(&transactions[1], &unspent_tx_outs, BlockContext::default());
+        cache.get_is_valid_transaction(&transactions[0], &unspent_tx_outs, BlockContext::default());
+        cache.get_is_valid_transaction(&transactions[2], &unspent_tx_outs, BlockContext::default());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.entries.contains_key("0"));
+        assert!(!cache.entries.contains_key("1"));
+    }
+
+    #[test]
+    fn test_chain_verdict() {
+        let mut cache = ValidationCache::new(0);
+        let digest = get_chain_digest("[]");
+        assert!(cache.get_chain_verdict("a", &digest, 0).is_none());
+
+        cache.record_chain_verdict("a", &digest, &Ok(()), 0);
+        cache.record_chain_verdict("b", &digest, &Err(AppError::new(1000)), 0);
+        cache.clear();
+        assert!(cache.get_chain_verdict("a", &digest, usize::MAX).unwrap().is_ok());
+        assert!(cache.get_chain_verdict("a", &get_chain_digest("[{}]"), 0).is_none());
+        assert_eq!(cache.get_chain_verdict("b", &digest, TIMESTAMP_INTERVAL - 1).unwrap().unwrap_err().code, 1000);
+        assert!(cache.get_chain_verdict("b", &digest, TIMESTAMP_INTERVAL).is_none());
+
+        for index in 0..CHAIN_VERDICT_CACHE_SIZE {
+            cache.record_chain_verdict(&index.to_string(), &digest, &Ok(()), index + 1);
+        }
+        assert_eq!(cache.chain_verdicts.len(), CHAIN_VERDICT_CACHE_SIZE);
+        assert!(cache.get_chain_verdict("a", &digest, 0).is_none());
+    }
+}
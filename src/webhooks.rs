@@ -0,0 +1,505 @@
+//! Callbacks to a configured url when watched transactions reach a count of confirmations or leave the chain.
+//!
+//! Watches and undelivered callbacks are saved as a JSON file, so they survive restarts.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{rename, File};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use url::Url;
+use uuid::Uuid;
+
+use crate::Block;
+use crate::chain_state::ChainState;
+use crate::constants::WEBHOOK_OUTBOX;
+use crate::errors::AppError;
+
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+const POLL_INTERVAL_SECS: u64 = 5;
+
+/// Transaction or address a watch follows.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type", content = "id")]
+pub enum WatchTarget {
+    Transaction(String),
+    Address(String),
+}
+
+/// Registration calling back when transactions of its target reach a count of confirmations.
+///
+/// A transaction watch is removed once its transaction is confirmed, an address watch follows every transaction
+/// touching the address in blocks from its start height. Transactions leaving the chain before they are confirmed are
+/// called back as reorged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Watch {
+    pub id: String,
+    pub target: WatchTarget,
+
+    /// Confirmations a transaction is called back at
+    pub confirmations: usize,
+
+    /// Index of the first block searched for transactions of an address
+    pub start_height: usize,
+
+    /// Transactions in the chain not confirmed yet, by id with the hash of their block
+    #[serde(default)]
+    pub pending: BTreeMap<String, String>,
+
+    /// Transactions already called back as confirmed
+    #[serde(default)]
+    pub confirmed: BTreeSet<String>,
+}
+
+impl Watch {
+    /// Get ids of transactions of target in the chain of index
+    fn get_transactions<'a>(&'a self, index: &'a WatchIndex) -> Vec<&'a str> {
+        match &self.target {
+            WatchTarget::Transaction(id) => index.positions.get(id).map(|_| id.as_str()).into_iter().collect(),
+            WatchTarget::Address(address) => index.touched
+                .iter()
+                .skip(self.start_height)
+                .flatten()
+                .filter(|(_, addresses)| addresses.contains(address))
+                .map(|(id, _)| id.as_str())
+                .collect(),
+        }
+    }
+
+    /// Update transactions of watch to the chain of index, returning callbacks of transactions confirmed or reorged
+    fn update(&mut self, index: &WatchIndex) -> Vec<WebhookEvent> {
+        let mut events = vec![];
+        let reorged: Vec<String> = self.pending
+            .iter()
+            .filter(|(id, hash)| index.positions.get(id.as_str()).map_or(true, |&position| index.hashes[position] != **hash))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in reorged {
+            let block_hash = self.pending.remove(&id).unwrap();
+            events.push(WebhookEvent { watch: self.id.clone(), event: WebhookKind::Reorged, transaction: id, block_hash, confirmations: 0 });
+        }
+
+        let seen: Vec<String> = self.get_transactions(index)
+            .into_iter()
+            .filter(|id| !self.confirmed.contains(*id) && !self.pending.contains_key(*id))
+            .map(str::to_string)
+            .collect();
+        for id in seen {
+            let block_hash = index.hashes[index.positions[&id]].clone();
+            self.pending.insert(id, block_hash);
+        }
+
+        let confirmed: Vec<(String, usize)> = self.pending
+            .keys()
+            .map(|id| (id.clone(), index.hashes.len() - index.positions[id]))
+            .filter(|(_, confirmations)| *confirmations >= self.confirmations)
+            .collect();
+        for (id, confirmations) in confirmed {
+            let block_hash = self.pending.remove(&id).unwrap();
+            self.confirmed.insert(id.clone());
+            events.push(WebhookEvent { watch: self.id.clone(), event: WebhookKind::Confirmed, transaction: id, block_hash, confirmations });
+        }
+        events
+    }
+
+    fn get_is_done(&self) -> bool {
+        matches!(self.target, WatchTarget::Transaction(_)) && !self.confirmed.is_empty()
+    }
+}
+
+/// Transactions of the chain watches are updated to, applied block by block instead of rebuilt on every update.
+#[derive(Debug, Default)]
+struct WatchIndex {
+    /// Hash of every block applied, by height
+    hashes: Vec<String>,
+
+    /// Height of every transaction applied, by id
+    positions: HashMap<String, usize>,
+
+    /// Addresses of the outputs of every transaction applied, by id, resolving inputs spending them
+    outputs: HashMap<String, Vec<String>>,
+
+    /// Ids of the transactions of every block applied with the addresses they pay or spend from, by height
+    touched: Vec<Vec<(String, HashSet<String>)>>,
+}
+
+impl WatchIndex {
+    /// Record transactions of a block appended to the chain
+    fn apply_block(&mut self, block: &Block) {
+        let height = self.hashes.len();
+        let mut touched = vec![];
+        for tx in block.data.iter() {
+            let spent: Vec<&String> = tx.tx_ins
+                .iter()
+                .filter_map(|tx_in| self.outputs.get(&tx_in.tx_out_id)?.get(tx_in.tx_out_index))
+                .collect();
+            let addresses: HashSet<String> = tx.tx_outs.iter().map(|tx_out| &tx_out.address).chain(spent).cloned().collect();
+            self.outputs.insert(tx.id.clone(), tx.tx_outs.iter().map(|tx_out| tx_out.address.clone()).collect());
+            self.positions.insert(tx.id.clone(), height);
+            touched.push((tx.id.clone(), addresses));
+        }
+        self.touched.push(touched);
+        self.hashes.push(block.hash.clone());
+    }
+
+    /// Update index to blockchain, applying only the blocks after the latest one applied if blockchain extends it,
+    /// or replaying blockchain if it forks off. Returns whether the chain changed.
+    fn update(&mut self, blockchain: &[Block]) -> bool {
+        let applied = self.hashes.len();
+        let is_extended = applied > 0 && blockchain.get(applied - 1).map(|block| &block.hash) == self.hashes.last();
+        if is_extended && blockchain.len() == applied {
+            return false;
+        }
+        if !is_extended {
+            *self = WatchIndex::default();
+        }
+        blockchain[self.hashes.len()..].iter().for_each(|block| self.apply_block(block));
+        true
+    }
+}
+
+/// Why a transaction is called back.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookKind {
+    Confirmed,
+    Reorged,
+}
+
+/// JSON body posted to the webhook url.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookEvent {
+    /// Id of watch
+    pub watch: String,
+    pub event: WebhookKind,
+    pub transaction: String,
+
+    /// Hash of the block containing the transaction, or which contained it when reorged
+    pub block_hash: String,
+
+    /// Confirmations of the transaction, zero when reorged
+    pub confirmations: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+struct Webhooks {
+    #[serde(default)]
+    watches: Vec<Watch>,
+
+    /// Callbacks not delivered yet, oldest first
+    #[serde(default)]
+    outbox: Vec<WebhookEvent>,
+}
+
+/// Watches calling back an http url, saved as a JSON file or kept in memory only when there is no path.
+#[derive(Debug)]
+pub struct WebhookBook {
+    url: Url,
+    path: Option<String>,
+    webhooks: Mutex<Webhooks>,
+
+    /// Chain watches were last updated to
+    index: Mutex<WatchIndex>,
+
+    /// Watches were added or not saved since the last update, so it runs even if the chain did not change
+    is_stale: AtomicBool,
+}
+
+impl WebhookBook {
+    /// Open watches saved at path calling back url, none yet if the file does not exist.
+    ///
+    /// # Errors
+    /// If url is not an http url with a host, it returns error 12002, and if file cannot be read or does not hold
+    /// watches error 12000.
+    pub fn open(url: &str, path: Option<&str>) -> Result<WebhookBook, AppError> {
+        let url = Url::parse(url).map_err(|_| AppError::new(12002))?;
+        if url.scheme() != "http" || url.host_str().is_none() {
+            return Err(AppError::new(12002));
+        }
+        let webhooks = match path {
+            Some(path) if Path::new(path).exists() => {
+                let file = File::open(path).map_err(|_| AppError::new(12000))?;
+                serde_json::from_reader(file).map_err(|_| AppError::new(12000))?
+            }
+            _ => Webhooks::default(),
+        };
+        Ok(WebhookBook {
+            url,
+            path: path.map(str::to_string),
+            webhooks: Mutex::new(webhooks),
+            index: Mutex::new(WatchIndex::default()),
+            is_stale: AtomicBool::new(true),
+        })
+    }
+
+    /// Watch transactions of target until they reach confirmations, searching blocks from height, and save watches.
+    ///
+    /// # Errors
+    /// If confirmations is zero, it returns error 12004, and if watches cannot be saved error 12001.
+    pub fn watch(&self, target: WatchTarget, confirmations: usize, height: usize) -> Result<Watch, AppError> {
+        if confirmations == 0 {
+            return Err(AppError::new(12004));
+        }
+        let watch = Watch {
+            id: format!("{}", Uuid::new_v4()),
+            target,
+            confirmations,
+            start_height: height,
+            pending: BTreeMap::new(),
+            confirmed: BTreeSet::new(),
+        };
+        self.change(|webhooks| webhooks.watches.push(watch.clone()))?;
+        self.is_stale.store(true, Ordering::Relaxed);
+        Ok(watch)
+    }
+
+    /// Remove watch of id and save watches, returning it if it exists.
+    ///
+    /// # Errors
+    /// If watches cannot be saved, it returns error 12001.
+    pub fn unwatch(&self, id: &str) -> Result<Option<Watch>, AppError> {
+        self.change(|webhooks| {
+            let position = webhooks.watches.iter().position(|watch| watch.id == id)?;
+            Some(webhooks.watches.remove(position))
+        })
+    }
+
+    /// Get every watch
+    pub fn get_watches(&self) -> Vec<Watch> {
+        self.webhooks.lock().unwrap().watches.clone()
+    }
+
+    /// Get callbacks not delivered yet, oldest first
+    pub fn get_outbox(&self) -> Vec<WebhookEvent> {
+        self.webhooks.lock().unwrap().outbox.clone()
+    }
+
+    /// Update watches to blockchain, queueing callbacks of transactions confirmed or reorged, and save watches if
+    /// they changed. Nothing is done if neither the chain nor the watches changed since the last update.
+    /// The oldest callbacks are dropped past the outbox size, with a warning.
+    ///
+    /// # Errors
+    /// If watches cannot be saved, it returns error 12001, and they are updated again on the next update.
+    pub fn update(&self, blockchain: &[Block]) -> Result<(), AppError> {
+        let mut index = self.index.lock().unwrap();
+        let is_changed = index.update(blockchain);
+        if !self.is_stale.swap(false, Ordering::Relaxed) && !is_changed {
+            return Ok(());
+        }
+
+        let result = self.change(|webhooks| {
+            for watch in webhooks.watches.iter_mut() {
+                let events = watch.update(&index);
+                webhooks.outbox.extend(events);
+            }
+            webhooks.watches.retain(|watch| !watch.get_is_done());
+            let overflow = webhooks.outbox.len().saturating_sub(WEBHOOK_OUTBOX);
+            if overflow > 0 {
+                tracing::warn!(dropped = overflow, "webhook outbox full, dropping oldest callbacks");
+            }
+            webhooks.outbox.drain(..overflow);
+        });
+        if result.is_err() {
+            self.is_stale.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Post callbacks of the outbox in order, stopping at the first failure, and save the rest.
+    ///
+    /// # Errors
+    /// If a callback cannot be delivered, it returns error 12003 after saving the callbacks delivered before it, or
+    /// error 12001 if they cannot be saved.
+    pub fn deliver(&self) -> Result<usize, AppError> {
+        let outbox = self.get_outbox();
+        let delivered = outbox.iter().take_while(|event| post_event(&self.url, event).is_ok()).count();
+        if delivered > 0 {
+            self.change(|webhooks| webhooks.outbox.drain(..delivered.min(webhooks.outbox.len())).count())?;
+        }
+        if delivered < outbox.len() {
+            return Err(AppError::new(12003));
+        }
+        Ok(delivered)
+    }
+
+    /// Spawn a thread updating watches to the chain and delivering callbacks periodically.
+    pub fn launch(self: &Arc<Self>, blockchain: &Arc<ChainState>) {
+        let webhooks = Arc::clone(self);
+        let blockchain = Arc::clone(blockchain);
+        thread::spawn(move || loop {
+            if let Err(e) = webhooks.update(&blockchain.load()) {
+                tracing::warn!(error = %e, "webhook update failed");
+            }
+            if let Err(e) = webhooks.deliver() {
+                tracing::warn!(error = %e, "webhook delivery failed");
+            }
+            thread::sleep(Duration::from_secs(POLL_INTERVAL_SECS));
+        });
+    }
+
+    /// Apply change to webhooks and save them if they changed, keeping them as they were if they cannot be saved.
+    fn change<T>(&self, change: impl FnOnce(&mut Webhooks) -> T) -> Result<T, AppError> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let mut changed = webhooks.clone();
+        let result = change(&mut changed);
+        if changed == *webhooks {
+            return Ok(result);
+        }
+
+        if let Some(path) = &self.path {
+            save_webhooks(path, &changed)?;
+        }
+        *webhooks = changed;
+        Ok(result)
+    }
+}
+
+/// Write webhooks to a temporary file renamed over path, so a crash never leaves them half written.
+fn save_webhooks(path: &str, webhooks: &Webhooks) -> Result<(), AppError> {
+    if let Some(prefix) = Path::new(path).parent() {
+        std::fs::create_dir_all(prefix).map_err(|_| AppError::new(12001))?;
+    }
+    let temp_path = format!("{}.tmp", path);
+    let mut file = File::create(&temp_path).map_err(|_| AppError::new(12001))?;
+    file.write_all(serde_json::to_string_pretty(webhooks).unwrap().as_bytes()).map_err(|_| AppError::new(12001))?;
+    file.sync_all().map_err(|_| AppError::new(12001))?;
+    rename(&temp_path, path).map_err(|_| AppError::new(12001))
+}
+
+/// Post event as JSON to url, delivered if it answers a 2xx status.
+fn post_event(url: &Url, event: &WebhookEvent) -> Result<(), AppError> {
+    let host = url.host_str().unwrap();
+    let address = (host, url.port_or_known_default().unwrap_or(80))
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or(AppError::new(12003))?;
+    let timeout = Duration::from_secs(DELIVERY_TIMEOUT_SECS);
+    let mut stream = TcpStream::connect_timeout(&address, timeout).map_err(|_| AppError::new(12003))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|_| AppError::new(12003))?;
+
+    let target = &url[url::Position::BeforePath..url::Position::AfterQuery];
+    let body = serde_json::to_string(event).unwrap();
+    let request = format!(
+        "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        target, host, body.len(), body,
+    );
+    stream.write_all(request.as_bytes()).map_err(|_| AppError::new(12003))?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|_| AppError::new(12003))?;
+
+    match response.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(AppError::new(12003)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::remove_file;
+    use std::net::TcpListener;
+    use crate::block::get_genesis_block;
+    use crate::testkit::{BOB, RECEIVER_ADDRESS};
+    use crate::transaction::{get_coinbase_transaction, Transaction, TxIn, TxOut};
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let path = "sample/webhooks.json";
+        let _ = remove_file(path);
+
+        let genesis_block = get_genesis_block();
+        let transaction = Transaction::generate(
+            &vec![TxIn::new(genesis_block.data[0].id.clone(), 0, "".to_string())],
+            &vec![TxOut::new(BOB.address.to_string(), 10)],
+        );
+        let a1 = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 1, None), transaction.clone()], &genesis_block, 0);
+        let a2 = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 2, None)], &a1, 0);
+        let b1 = Block::generate(&vec![get_coinbase_transaction(BOB.address, 1, None)], &genesis_block, 0);
+
+        let webhooks = WebhookBook::open("http://127.0.0.1:1/callback", Some(path)).unwrap();
+        let watch = webhooks.watch(WatchTarget::Transaction(transaction.id.clone()), 2, 1).unwrap();
+        let bob = webhooks.watch(WatchTarget::Address(BOB.address.to_string()), 1, 1).unwrap();
+        assert_eq!(webhooks.watch(WatchTarget::Address(BOB.address.to_string()), 0, 1).unwrap_err().code, 12004);
+
+        webhooks.update(&vec![genesis_block.clone(), a1.clone()]).unwrap();
+        let outbox = webhooks.get_outbox();
+        assert_eq!(outbox.len(), 1);
+        assert_eq!((outbox[0].watch.as_str(), outbox[0].event, outbox[0].confirmations), (bob.id.as_str(), WebhookKind::Confirmed, 1));
+
+        // Reopened watches keep the transaction pending, so the reorg is called back after a restart.
+        let webhooks = WebhookBook::open("http://127.0.0.1:1/callback", Some(path)).unwrap();
+        webhooks.update(&vec![genesis_block.clone(), b1.clone()]).unwrap();
+        let outbox = webhooks.get_outbox();
+        assert_eq!(outbox.len(), 3);
+        assert_eq!((outbox[1].watch.as_str(), outbox[1].event), (watch.id.as_str(), WebhookKind::Reorged));
+        assert_eq!(outbox[1].block_hash, a1.hash);
+        assert_eq!((outbox[2].watch.as_str(), outbox[2].event, outbox[2].block_hash.as_str()), (bob.id.as_str(), WebhookKind::Confirmed, b1.hash.as_str()));
+
+        webhooks.update(&vec![genesis_block.clone(), a1.clone(), a2.clone()]).unwrap();
+        let outbox = webhooks.get_outbox();
+        assert_eq!((outbox[3].watch.as_str(), outbox[3].event, outbox[3].confirmations), (watch.id.as_str(), WebhookKind::Confirmed, 2));
+        assert_eq!(webhooks.get_watches().iter().map(|watch| watch.id.as_str()).collect::<Vec<_>>(), vec![bob.id.as_str()]);
+        assert_eq!(webhooks.unwatch(&bob.id).unwrap().unwrap().id, bob.id);
+        assert_eq!(webhooks.unwatch(&bob.id).unwrap(), None);
+        assert!(webhooks.get_watches().is_empty());
+
+        // A watch added on an unchanged chain is updated, then nothing is done until the chain changes.
+        let late = webhooks.watch(WatchTarget::Transaction(transaction.id.clone()), 1, 1).unwrap();
+        webhooks.update(&vec![genesis_block.clone(), a1.clone(), a2.clone()]).unwrap();
+        let outbox = webhooks.get_outbox();
+        assert_eq!((outbox[4].watch.as_str(), outbox[4].confirmations), (late.id.as_str(), 2));
+        webhooks.update(&vec![genesis_block.clone(), a1.clone(), a2.clone()]).unwrap();
+        assert_eq!(webhooks.get_outbox().len(), 5);
+
+        let receiver = webhooks.watch(WatchTarget::Address(RECEIVER_ADDRESS.to_string()), 3, 1).unwrap();
+        let a3 = Block::generate(&vec![get_coinbase_transaction(RECEIVER_ADDRESS, 3, None)], &a2, 0);
+        webhooks.update(&vec![genesis_block.clone(), a1.clone(), a2.clone(), a3]).unwrap();
+        let outbox = webhooks.get_outbox();
+        assert_eq!(outbox.len(), 6);
+        assert_eq!((outbox[5].watch.as_str(), outbox[5].block_hash.as_str(), outbox[5].confirmations), (receiver.id.as_str(), a1.hash.as_str(), 3));
+        assert_eq!(webhooks.get_watches()[0].pending.len(), 2);
+
+        assert_eq!(WebhookBook::open("ftp://127.0.0.1/callback", None).unwrap_err().code, 12002);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_deliver() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            for (index, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request = [0u8; 1024];
+                let size = stream.read(&mut request).unwrap();
+                let request = String::from_utf8_lossy(&request[..size]).to_string();
+                assert!(request.starts_with("POST /callback HTTP/1.0\r\n"));
+                assert!(request.ends_with("\"event\":\"confirmed\",\"transaction\":\"b6c6a943\",\"block_hash\":\"41cdda1f\",\"confirmations\":1}"));
+                let response = match index {
+                    0 => "HTTP/1.0 204 No Content\r\n\r\n",
+                    _ => "HTTP/1.0 500 Internal Server Error\r\n\r\n",
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let webhooks = WebhookBook::open(&format!("http://127.0.0.1:{}/callback", port), None).unwrap();
+        let event = WebhookEvent {
+            watch: "watch".to_string(),
+            event: WebhookKind::Confirmed,
+            transaction: "b6c6a943".to_string(),
+            block_hash: "41cdda1f".to_string(),
+            confirmations: 1,
+        };
+        webhooks.change(|webhooks| webhooks.outbox = vec![event.clone(), event.clone(), event.clone()]).unwrap();
+        assert_eq!(webhooks.deliver().unwrap_err().code, 12003);
+        assert_eq!(webhooks.get_outbox().len(), 2);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio_tungstenite::{accept_async, connect_async, WebSocketStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use url::Url;
+
+use crate::errors::AppError;
+use crate::naivecoin::{from_naivecoin, to_naivecoin, NAIVECOIN_SCHEME_PREFIX};
+use crate::payload::Payload;
+use crate::peers::{format_socket_addr, Direction};
+use crate::transport::{PeerTransport, TransportEvent};
+use crate::wire::{decode_payload, encode_payload, WireFormat};
+
+type Writers = Arc<Mutex<HashMap<String, UnboundedSender<Message>>>>;
+type PeerFormats = Arc<Mutex<HashMap<String, WireFormat>>>;
+
+/// Transport over websocket connections, naming inbound peers by socket address and outbound peers by url.
+///
+/// It listens on every bind address, IPv6 included, so a node can be dual-stack.
+///
+/// Payloads are read from text messages as JSON and binary messages as protobuf, and written in the wire format.
+/// Peers dialed with a `naivecoin+` url or sending naivecoin messages are written in the naivecoin format instead.
+pub struct WebSocketTransport {
+    local_addrs: Vec<SocketAddr>,
+    wire_format: WireFormat,
+    peer_formats: PeerFormats,
+    writers: Writers,
+    events: UnboundedSender<TransportEvent>,
+}
+
+impl WebSocketTransport {
+    /// Listen on addresses, accepting connections in background
+    ///
+    /// # Errors
+    /// If an address cannot be bound, it returns error 5002.
+    pub async fn listen(addrs: &[SocketAddr], wire_format: WireFormat, events: UnboundedSender<TransportEvent>) -> Result<WebSocketTransport, AppError> {
+        let writers: Writers = Arc::default();
+        let peer_formats: PeerFormats = Arc::default();
+        let mut listeners = vec![];
+        for addr in addrs {
+            let listener = TcpListener::bind(addr).await.map_err(|_| AppError::new(5002))?;
+            println!("Listening on: {}", listener.local_addr().map_err(|_| AppError::new(5002))?);
+            listeners.push(listener);
+        }
+        let local_addrs = listeners
+            .iter()
+            .map(|listener| listener.local_addr().map_err(|_| AppError::new(5002)))
+            .collect::<Result<Vec<SocketAddr>, AppError>>()?;
+
+        for listener in listeners {
+            tokio::spawn(accept_all(listener, Arc::clone(&writers), Arc::clone(&peer_formats), events.clone()));
+        }
+        Ok(WebSocketTransport { local_addrs, wire_format, peer_formats, writers, events })
+    }
+
+    /// Get addresses the transport listens on
+    pub fn get_local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    fn write(&self, peer: &str, message: Message) {
+        if let Some(writer) = self.writers.lock().unwrap().get(peer) {
+            let _ = writer.send(message);
+        }
+    }
+}
+
+impl PeerTransport for WebSocketTransport {
+    fn dial(&mut self, address: &str) {
+        let peer = address.to_string();
+        let writers = Arc::clone(&self.writers);
+        let peer_formats = Arc::clone(&self.peer_formats);
+        let events = self.events.clone();
+        let url = match address.strip_prefix(NAIVECOIN_SCHEME_PREFIX) {
+            Some(url) => {
+                peer_formats.lock().unwrap().insert(peer.clone(), WireFormat::Naivecoin);
+                url.to_string()
+            }
+            None => peer.clone(),
+        };
+        tokio::spawn(async move {
+            let url = match Url::parse(url.as_str()) {
+                Ok(url) => url,
+                Err(e) => {
+                    println!("Invalid peer url : {:?}", e);
+                    let _ = events.send(TransportEvent::DialFailed(peer, e.to_string()));
+                    return;
+                }
+            };
+            match connect_async(url).await {
+                Ok((ws_stream, _)) => {
+                    let _ = events.send(TransportEvent::Dialed(peer.clone(), peer.clone()));
+                    run_connection(ws_stream, peer, Direction::Outbound, writers, peer_formats, events).await
+                }
+                Err(e) => {
+                    println!("Websocket connect error : {:?}", e);
+                    let _ = events.send(TransportEvent::DialFailed(peer, e.to_string()));
+                }
+            }
+        });
+    }
+
+    fn send(&mut self, peer: &str, payload: &Payload) {
+        let wire_format = self.peer_formats.lock().unwrap().get(peer).copied().unwrap_or(self.wire_format);
+        let message = match wire_format {
+            WireFormat::Json => Message::Text(serde_json::to_string(payload).unwrap()),
+            WireFormat::Protobuf => match encode_payload(payload) {
+                Ok(bytes) => Message::Binary(bytes),
+                Err(e) => {
+                    println!("Invalid payload to {} : {}", peer, e);
+                    return;
+                }
+            },
+            WireFormat::Naivecoin => match to_naivecoin(payload) {
+                Ok(Some(text)) => Message::Text(text),
+                Ok(None) => return,
+                Err(e) => {
+                    println!("Invalid payload to {} : {}", peer, e);
+                    return;
+                }
+            },
+        };
+        self.write(peer, message);
+    }
+
+    fn close(&mut self, peer: &str, reason: &str) {
+        self.write(peer, Message::Close(Some(CloseFrame { code: CloseCode::Normal, reason: reason.to_string().into() })));
+    }
+}
+
+/// Accept connections of listener until it fails, naming peers by their socket address.
+async fn accept_all(listener: TcpListener, writers: Writers, peer_formats: PeerFormats, events: UnboundedSender<TransportEvent>) {
+    while let Ok((stream, peer)) = listener.accept().await {
+        match accept_async(stream).await {
+            Err(e) => println!("Websocket connection error : {:?}", e),
+            Ok(ws_stream) => {
+                println!("New Connection : {:?}", peer);
+                tokio::spawn(run_connection(
+                    ws_stream,
+                    format_socket_addr(peer),
+                    Direction::Inbound,
+                    Arc::clone(&writers),
+                    Arc::clone(&peer_formats),
+                    events.clone(),
+                ));
+            }
+        }
+    }
+}
+
+/// Forward messages of a connection as events until it closes, writing queued messages meanwhile.
+async fn run_connection<S>(
+    ws_stream: WebSocketStream<S>,
+    peer: String,
+    direction: Direction,
+    writers: Writers,
+    peer_formats: PeerFormats,
+    events: UnboundedSender<TransportEvent>,
+)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, mut receiver) = ws_stream.split();
+    let (writer, mut queue) = mpsc::unbounded_channel::<Message>();
+    writers.lock().unwrap().insert(peer.clone(), writer);
+    let _ = events.send(TransportEvent::Connected(peer.clone(), direction));
+
+    let write = tokio::spawn(async move {
+        while let Some(message) = queue.recv().await {
+            let is_close = message.is_close();
+            if let Err(e) = sender.send(message).await {
+                println!("Websocket send error : {:?}", e);
+                break;
+            }
+            if is_close {
+                break;
+            }
+        }
+    });
+
+    let mut reason = None;
+    while let Some(Ok(message)) = receiver.next().await {
+        if let Message::Close(frame) = message {
+            reason = frame.map(|frame| frame.reason.to_string()).filter(|reason| !reason.is_empty());
+            break; // When we break, we disconnect.
+        }
+        let payload = match message {
+            Message::Text(text) => match serde_json::from_str::<Payload>(&text) {
+                Ok(payload) => Ok(Some(payload)),
+                Err(e) => from_naivecoin(&text)
+                    .map(|payload| {
+                        peer_formats.lock().unwrap().insert(peer.clone(), WireFormat::Naivecoin);
+                        payload
+                    })
+                    .map_err(|_| format!("{:?}", e)),
+            },
+            Message::Binary(bytes) => decode_payload(&bytes).map(Some).map_err(|e| e.to_string()),
+            _ => continue,
+        };
+        match payload {
+            Ok(Some(payload)) => {
+                let _ = events.send(TransportEvent::Received(peer.clone(), payload));
+            }
+            Ok(None) => {}
+            Err(e) => println!("Invalid payload from {} : {}", peer, e),
+        }
+    }
+    // If we reach here, it means the peer got disconnected.
+    writers.lock().unwrap().remove(&peer);
+    peer_formats.lock().unwrap().remove(&peer);
+    write.abort();
+    let _ = events.send(TransportEvent::Disconnected(peer, reason));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::payload::PayloadType;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_websocket_transport() {
+        let (a_events, mut a_receiver) = mpsc::unbounded_channel();
+        let (b_events, mut b_receiver) = mpsc::unbounded_channel();
+        let mut a = WebSocketTransport::listen(&["127.0.0.1:0".parse().unwrap()], WireFormat::Json, a_events).await.unwrap();
+        let mut b = WebSocketTransport::listen(&["127.0.0.1:0".parse().unwrap()], WireFormat::Protobuf, b_events).await.unwrap();
+
+        let address = format!("ws://{}", a.get_local_addrs()[0]);
+        b.dial(&address);
+        assert!(matches!(b_receiver.recv().await, Some(TransportEvent::Dialed(dialed, peer)) if dialed == address && peer == address));
+        let a_peer = match b_receiver.recv().await {
+            Some(TransportEvent::Connected(peer, Direction::Outbound)) => peer,
+            event => panic!("{:?}", event),
+        };
+        let b_peer = match a_receiver.recv().await {
+            Some(TransportEvent::Connected(peer, Direction::Inbound)) => peer,
+            event => panic!("{:?}", event),
+        };
+
+        b.send(&a_peer, &Payload::new(PayloadType::QueryLatest, &()));
+        assert!(matches!(
+            a_receiver.recv().await,
+            Some(TransportEvent::Received(peer, payload)) if peer == b_peer && payload.r#type == PayloadType::QueryLatest
+        ));
+
+        a.send(&b_peer, &Payload::new(PayloadType::Inventory, &vec!["a"]));
+        assert!(matches!(
+            b_receiver.recv().await,
+            Some(TransportEvent::Received(peer, payload)) if peer == a_peer && payload.data == "[\"a\"]"
+        ));
+
+        a.close(&b_peer, "Bye");
+        assert!(matches!(
+            b_receiver.recv().await,
+            Some(TransportEvent::Disconnected(peer, Some(reason))) if peer == a_peer && reason == "Bye"
+        ));
+        assert!(matches!(a_receiver.recv().await, Some(TransportEvent::Disconnected(peer, _)) if peer == b_peer));
+
+        b.dial("ws://127.0.0.1:1");
+        assert!(matches!(b_receiver.recv().await, Some(TransportEvent::DialFailed(dialed, _)) if dialed == "ws://127.0.0.1:1"));
+    }
+}
@@ -0,0 +1,117 @@
+use sha2::{Digest, Sha256};
+
+use crate::constants::WIF_PREFIX;
+use crate::errors::AppError;
+
+const BASE58_ALPHABET: &'static [u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Suffix marking the key derives a compressed public key.
+const COMPRESSED_SUFFIX: u8 = 0x01;
+
+fn get_checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(&Sha256::digest(payload));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn encode_base58(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = vec![];
+    for &byte in bytes {
+        let mut carry = byte as usize;
+        for digit in digits.iter_mut() {
+            carry += (*digit as usize) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+    std::iter::repeat('1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|&digit| BASE58_ALPHABET[digit as usize] as char))
+        .collect()
+}
+
+fn decode_base58(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    for c in encoded.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)?;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as usize) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+
+    let zeros = encoded.bytes().take_while(|&c| c == b'1').count();
+    Some(std::iter::repeat(0).take(zeros).chain(bytes.into_iter().rev()).collect())
+}
+
+/// Encode a hex secp256k1 private key as WIF, with network prefix, compressed suffix and checksum.
+pub fn encode_wif(private_key: &str) -> Result<String, AppError> {
+    let secret = hex::decode(private_key).map_err(|_| AppError::new(3008))?;
+    if secret.len() != 32 {
+        return Err(AppError::new(3008));
+    }
+
+    let mut payload = vec![WIF_PREFIX];
+    payload.extend_from_slice(&secret);
+    payload.push(COMPRESSED_SUFFIX);
+    let checksum = get_checksum(&payload);
+    payload.extend_from_slice(&checksum);
+    Ok(encode_base58(&payload))
+}
+
+/// Decode a WIF private key into a hex secp256k1 private key, checking prefix and checksum.
+pub fn decode_wif(wif: &str) -> Result<String, AppError> {
+    let decoded = decode_base58(wif.trim()).ok_or(AppError::new(3009))?;
+    if decoded.len() < 5 {
+        return Err(AppError::new(3009));
+    }
+
+    let (payload, checksum) = decoded.split_at(decoded.len() - 4);
+    if get_checksum(payload) != checksum || payload[0] != WIF_PREFIX {
+        return Err(AppError::new(3009));
+    }
+
+    match &payload[1..] {
+        [secret @ .., COMPRESSED_SUFFIX] if secret.len() == 32 => Ok(hex::encode(secret)),
+        secret if secret.len() == 32 => Ok(hex::encode(secret)),
+        _ => Err(AppError::new(3009)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_wif() {
+        assert_eq!(
+            encode_wif("0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d").unwrap(),
+            "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617",
+        );
+        assert!(encode_wif("ed9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").is_err());
+    }
+
+    #[test]
+    fn test_decode_wif() {
+        assert_eq!(
+            decode_wif("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617").unwrap(),
+            "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d",
+        );
+        assert_eq!(
+            decode_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ").unwrap(),
+            "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d",
+        );
+        assert!(decode_wif("KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98618").is_err());
+        assert!(decode_wif("invalid0").is_err());
+    }
+}
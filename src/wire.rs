@@ -0,0 +1,639 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::Block;
+use crate::dto::Model;
+use crate::errors::AppError;
+use crate::payload::{Payload, PayloadType};
+use crate::peers::{Handshake, Heartbeat, Reject};
+use crate::pow::PowAlgorithm;
+use crate::script::{Op, Script};
+use crate::transaction::{Transaction, TxIn, TxOut, UnspentTxOut};
+use crate::utxo_snapshot::UtxoSnapshot;
+
+/// Encoding of payloads written to peers, every format is read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireFormat {
+    /// JSON in text messages
+    Json,
+
+    /// Protobuf of `proto/payload.proto` in binary messages, for nodes in other languages
+    Protobuf,
+
+    /// Messages of naivecoin in text messages, to join a naivecoin network
+    Naivecoin,
+}
+
+impl FromStr for WireFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(WireFormat::Json),
+            "protobuf" => Ok(WireFormat::Protobuf),
+            "naivecoin" => Ok(WireFormat::Naivecoin),
+            _ => Err(AppError::new(5003)),
+        }
+    }
+}
+
+/// Messages of `proto/payload.proto`.
+mod proto {
+    use prost::{Enumeration, Message};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+    #[repr(i32)]
+    pub enum PayloadType {
+        Blockchain = 0,
+        Transaction = 1,
+        Handshake = 2,
+        QueryLatest = 3,
+        Latest = 4,
+        QueryAll = 5,
+        Inventory = 6,
+        GetData = 7,
+        Ping = 8,
+        Pong = 9,
+        Reject = 10,
+        QuerySnapshot = 11,
+        Snapshot = 12,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Payload {
+        #[prost(enumeration = "PayloadType", tag = "1")]
+        pub r#type: i32,
+        #[prost(bytes = "vec", tag = "2")]
+        pub data: Vec<u8>,
+        #[prost(string, optional, tag = "3")]
+        pub mac: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Block {
+        #[prost(uint32, tag = "1")]
+        pub version: u32,
+        #[prost(int32, tag = "2")]
+        pub pow: i32,
+        #[prost(uint64, tag = "3")]
+        pub index: u64,
+        #[prost(string, tag = "4")]
+        pub hash: String,
+        #[prost(string, tag = "5")]
+        pub previous_hash: String,
+        #[prost(uint64, tag = "6")]
+        pub timestamp: u64,
+        #[prost(message, repeated, tag = "7")]
+        pub data: Vec<Transaction>,
+        #[prost(uint64, tag = "8")]
+        pub difficulty: u64,
+        #[prost(uint64, tag = "9")]
+        pub nonce: u64,
+        #[prost(string, optional, tag = "10")]
+        pub utxo_commitment: Option<String>,
+        #[prost(string, optional, tag = "11")]
+        pub tx_root: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Transaction {
+        #[prost(uint32, tag = "1")]
+        pub version: u32,
+        #[prost(string, tag = "2")]
+        pub id: String,
+        #[prost(message, repeated, tag = "3")]
+        pub tx_ins: Vec<TxIn>,
+        #[prost(message, repeated, tag = "4")]
+        pub tx_outs: Vec<TxOut>,
+        #[prost(string, optional, tag = "5")]
+        pub extra_data: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TxIn {
+        #[prost(string, tag = "1")]
+        pub tx_out_id: String,
+        #[prost(uint64, tag = "2")]
+        pub tx_out_index: u64,
+        #[prost(string, tag = "3")]
+        pub signature: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct TxOut {
+        #[prost(string, tag = "1")]
+        pub address: String,
+        #[prost(uint64, tag = "2")]
+        pub amount: u64,
+        #[prost(message, optional, tag = "3")]
+        pub script: Option<Script>,
+        #[prost(string, optional, tag = "4")]
+        pub asset: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Script {
+        #[prost(message, repeated, tag = "1")]
+        pub ops: Vec<Op>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Op {
+        #[prost(oneof = "op::Op", tags = "1, 2, 3, 4, 5")]
+        pub op: Option<op::Op>,
+    }
+
+    pub mod op {
+        use prost::{Message, Oneof};
+
+        #[derive(Clone, PartialEq, Message)]
+        pub struct CheckSig {}
+
+        #[derive(Clone, PartialEq, Message)]
+        pub struct CheckMultisig {
+            #[prost(uint64, tag = "1")]
+            pub required: u64,
+            #[prost(uint64, tag = "2")]
+            pub count: u64,
+        }
+
+        #[derive(Clone, PartialEq, Oneof)]
+        pub enum Op {
+            #[prost(string, tag = "1")]
+            Push(String),
+            #[prost(message, tag = "2")]
+            CheckSig(CheckSig),
+            #[prost(message, tag = "3")]
+            CheckMultisig(CheckMultisig),
+            #[prost(uint64, tag = "4")]
+            CheckLocktime(u64),
+            #[prost(string, tag = "5")]
+            Data(String),
+        }
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Handshake {
+        #[prost(string, tag = "1")]
+        pub user_agent: String,
+        #[prost(uint64, tag = "2")]
+        pub height: u64,
+        #[prost(uint64, tag = "3")]
+        pub services: u64,
+        #[prost(uint64, tag = "4")]
+        pub timestamp: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Heartbeat {
+        #[prost(uint64, tag = "1")]
+        pub sent_at: u64,
+        #[prost(uint64, tag = "2")]
+        pub timestamp: u64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Reject {
+        #[prost(enumeration = "PayloadType", tag = "1")]
+        pub r#type: i32,
+        #[prost(uint64, tag = "2")]
+        pub code: u64,
+        #[prost(string, tag = "3")]
+        pub reason: String,
+        #[prost(string, tag = "4")]
+        pub hash: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Blocks {
+        #[prost(message, repeated, tag = "1")]
+        pub blocks: Vec<Block>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Latest {
+        #[prost(message, optional, tag = "1")]
+        pub block: Option<Block>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Transactions {
+        #[prost(message, repeated, tag = "1")]
+        pub transactions: Vec<Transaction>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Ids {
+        #[prost(string, repeated, tag = "1")]
+        pub ids: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct UnspentTxOut {
+        #[prost(string, tag = "1")]
+        pub tx_out_id: String,
+        #[prost(uint64, tag = "2")]
+        pub tx_out_index: u64,
+        #[prost(string, tag = "3")]
+        pub address: String,
+        #[prost(uint64, tag = "4")]
+        pub amount: u64,
+        #[prost(message, optional, tag = "5")]
+        pub script: Option<Script>,
+        #[prost(string, optional, tag = "6")]
+        pub asset: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    pub struct UtxoSnapshot {
+        #[prost(message, repeated, tag = "1")]
+        pub headers: Vec<Block>,
+        #[prost(message, repeated, tag = "2")]
+        pub unspent_tx_outs: Vec<UnspentTxOut>,
+        #[prost(message, repeated, tag = "3")]
+        pub blocks: Vec<Block>,
+    }
+}
+
+/// Encode payload as protobuf, its JSON data re-encoded as the message of its type.
+///
+/// # Errors
+/// If data does not match its type, it returns error 5004.
+pub fn encode_payload(payload: &Payload) -> Result<Vec<u8>, AppError> {
+    let data = match payload.r#type {
+        PayloadType::Blockchain => proto::Blocks {
+            blocks: parse_model::<Vec<Block>>(payload)?.iter().map(to_proto_block).collect(),
+        }.encode_to_vec(),
+        PayloadType::Latest => proto::Latest {
+            block: parse_model::<Option<Block>>(payload)?.as_ref().map(to_proto_block),
+        }.encode_to_vec(),
+        PayloadType::Transaction => proto::Transactions {
+            transactions: parse_model::<Vec<Transaction>>(payload)?.iter().map(to_proto_transaction).collect(),
+        }.encode_to_vec(),
+        PayloadType::Handshake => {
+            let handshake = parse_data::<Handshake>(payload)?;
+            proto::Handshake {
+                user_agent: handshake.user_agent,
+                height: handshake.height as u64,
+                services: handshake.services,
+                timestamp: handshake.timestamp as u64,
+            }.encode_to_vec()
+        }
+        PayloadType::Inventory | PayloadType::GetData => proto::Ids { ids: parse_data(payload)? }.encode_to_vec(),
+        PayloadType::Ping | PayloadType::Pong => {
+            let heartbeat = parse_data::<Heartbeat>(payload)?;
+            proto::Heartbeat { sent_at: heartbeat.sent_at as u64, timestamp: heartbeat.timestamp as u64 }.encode_to_vec()
+        }
+        PayloadType::Reject => {
+            let reject = parse_data::<Reject>(payload)?;
+            proto::Reject {
+                r#type: to_proto_payload_type(reject.r#type) as i32,
+                code: reject.code as u64,
+                reason: reject.reason,
+                hash: reject.hash,
+            }.encode_to_vec()
+        }
+        PayloadType::Snapshot => {
+            let snapshot = parse_model::<UtxoSnapshot>(payload)?;
+            proto::UtxoSnapshot {
+                headers: snapshot.headers.iter().map(to_proto_block).collect(),
+                unspent_tx_outs: snapshot.unspent_tx_outs.iter().map(to_proto_unspent_tx_out).collect(),
+                blocks: snapshot.blocks.iter().map(to_proto_block).collect(),
+            }.encode_to_vec()
+        }
+        PayloadType::QueryLatest | PayloadType::QueryAll | PayloadType::QuerySnapshot => vec![],
+    };
+
+    Ok(proto::Payload {
+        r#type: to_proto_payload_type(payload.r#type) as i32,
+        data,
+        mac: payload.mac.clone(),
+    }.encode_to_vec())
+}
+
+/// Decode payload from protobuf, its data re-encoded as JSON so the node handles it like a text message.
+///
+/// # Errors
+/// If bytes are not a valid payload, it returns error 5005.
+pub fn decode_payload(bytes: &[u8]) -> Result<Payload, AppError> {
+    let payload = proto::Payload::decode(bytes).map_err(|_| AppError::new(5005))?;
+    let r#type = from_proto_payload_type(proto::PayloadType::try_from(payload.r#type).map_err(|_| AppError::new(5005))?);
+    let data = match r#type {
+        PayloadType::Blockchain => {
+            let blocks = decode_data::<proto::Blocks>(&payload.data)?.blocks
+                .into_iter()
+                .map(from_proto_block)
+                .collect::<Result<Vec<Block>, AppError>>()?;
+            to_json(&blocks.to_dto())
+        }
+        PayloadType::Latest => {
+            let block = decode_data::<proto::Latest>(&payload.data)?.block.map(from_proto_block).transpose()?;
+            to_json(&block.to_dto())
+        }
+        PayloadType::Transaction => {
+            let transactions = decode_data::<proto::Transactions>(&payload.data)?.transactions
+                .into_iter()
+                .map(from_proto_transaction)
+                .collect::<Result<Vec<Transaction>, AppError>>()?;
+            to_json(&transactions.to_dto())
+        }
+        PayloadType::Handshake => {
+            let handshake = decode_data::<proto::Handshake>(&payload.data)?;
+            to_json(&Handshake {
+                user_agent: handshake.user_agent,
+                height: handshake.height as usize,
+                services: handshake.services,
+                timestamp: handshake.timestamp as usize,
+            })
+        }
+        PayloadType::Inventory | PayloadType::GetData => to_json(&decode_data::<proto::Ids>(&payload.data)?.ids),
+        PayloadType::Ping | PayloadType::Pong => {
+            let heartbeat = decode_data::<proto::Heartbeat>(&payload.data)?;
+            to_json(&Heartbeat { sent_at: heartbeat.sent_at as usize, timestamp: heartbeat.timestamp as usize })
+        }
+        PayloadType::Reject => {
+            let reject = decode_data::<proto::Reject>(&payload.data)?;
+            to_json(&Reject {
+                r#type: from_proto_payload_type(proto::PayloadType::try_from(reject.r#type).map_err(|_| AppError::new(5005))?),
+                code: reject.code as usize,
+                reason: reject.reason,
+                hash: reject.hash,
+            })
+        }
+        PayloadType::Snapshot => {
+            let snapshot = decode_data::<proto::UtxoSnapshot>(&payload.data)?;
+            to_json(&UtxoSnapshot {
+                headers: snapshot.headers.into_iter().map(from_proto_block).collect::<Result<Vec<Block>, AppError>>()?,
+                unspent_tx_outs: snapshot.unspent_tx_outs
+                    .into_iter()
+                    .map(from_proto_unspent_tx_out)
+                    .collect::<Result<Vec<UnspentTxOut>, AppError>>()?,
+                blocks: snapshot.blocks.into_iter().map(from_proto_block).collect::<Result<Vec<Block>, AppError>>()?,
+            }.to_dto())
+        }
+        PayloadType::QueryLatest | PayloadType::QueryAll | PayloadType::QuerySnapshot => to_json(&()),
+    };
+
+    Ok(Payload { r#type, data, mac: payload.mac })
+}
+
+fn parse_data<T: DeserializeOwned>(payload: &Payload) -> Result<T, AppError> {
+    serde_json::from_str(&payload.data).map_err(|_| AppError::new(5004))
+}
+
+fn parse_model<T: Model>(payload: &Payload) -> Result<T, AppError> {
+    parse_data::<T::Dto>(payload).map(T::from_dto)
+}
+
+fn decode_data<T: Message + Default>(bytes: &[u8]) -> Result<T, AppError> {
+    T::decode(bytes).map_err(|_| AppError::new(5005))
+}
+
+fn to_json<T: Serialize>(data: &T) -> String {
+    serde_json::to_string(data).unwrap()
+}
+
+fn to_proto_payload_type(r#type: PayloadType) -> proto::PayloadType {
+    match r#type {
+        PayloadType::Blockchain => proto::PayloadType::Blockchain,
+        PayloadType::Transaction => proto::PayloadType::Transaction,
+        PayloadType::Handshake => proto::PayloadType::Handshake,
+        PayloadType::QueryLatest => proto::PayloadType::QueryLatest,
+        PayloadType::Latest => proto::PayloadType::Latest,
+        PayloadType::QueryAll => proto::PayloadType::QueryAll,
+        PayloadType::Inventory => proto::PayloadType::Inventory,
+        PayloadType::GetData => proto::PayloadType::GetData,
+        PayloadType::Ping => proto::PayloadType::Ping,
+        PayloadType::Pong => proto::PayloadType::Pong,
+        PayloadType::Reject => proto::PayloadType::Reject,
+        PayloadType::QuerySnapshot => proto::PayloadType::QuerySnapshot,
+        PayloadType::Snapshot => proto::PayloadType::Snapshot,
+    }
+}
+
+fn from_proto_payload_type(r#type: proto::PayloadType) -> PayloadType {
+    match r#type {
+        proto::PayloadType::Blockchain => PayloadType::Blockchain,
+        proto::PayloadType::Transaction => PayloadType::Transaction,
+        proto::PayloadType::Handshake => PayloadType::Handshake,
+        proto::PayloadType::QueryLatest => PayloadType::QueryLatest,
+        proto::PayloadType::Latest => PayloadType::Latest,
+        proto::PayloadType::QueryAll => PayloadType::QueryAll,
+        proto::PayloadType::Inventory => PayloadType::Inventory,
+        proto::PayloadType::GetData => PayloadType::GetData,
+        proto::PayloadType::Ping => PayloadType::Ping,
+        proto::PayloadType::Pong => PayloadType::Pong,
+        proto::PayloadType::Reject => PayloadType::Reject,
+        proto::PayloadType::QuerySnapshot => PayloadType::QuerySnapshot,
+        proto::PayloadType::Snapshot => PayloadType::Snapshot,
+    }
+}
+
+fn to_proto_block(block: &Block) -> proto::Block {
+    proto::Block {
+        version: block.version,
+        pow: block.pow as i32,
+        index: block.index as u64,
+        hash: block.hash.clone(),
+        previous_hash: block.previous_hash.clone(),
+        timestamp: block.timestamp as u64,
+        data: block.data.iter().map(to_proto_transaction).collect(),
+        difficulty: block.difficulty as u64,
+        nonce: block.nonce as u64,
+        utxo_commitment: block.utxo_commitment.clone(),
+        tx_root: block.tx_root.clone(),
+    }
+}
+
+fn from_proto_block(block: proto::Block) -> Result<Block, AppError> {
+    let pow = match block.pow {
+        0 => PowAlgorithm::Sha256,
+        1 => PowAlgorithm::Sha256d,
+        2 => PowAlgorithm::Blake3,
+        3 => PowAlgorithm::Argon2,
+        _ => return Err(AppError::new(5005)),
+    };
+    let data = block.data
+        .into_iter()
+        .map(from_proto_transaction)
+        .collect::<Result<Vec<Transaction>, AppError>>()?;
+
+    Ok(Block {
+        version: block.version,
+        pow,
+        index: block.index as usize,
+        hash: block.hash,
+        previous_hash: block.previous_hash,
+        timestamp: block.timestamp as usize,
+        data: Arc::new(data),
+        difficulty: block.difficulty as usize,
+        nonce: block.nonce as usize,
+        tx_root: block.tx_root,
+        utxo_commitment: block.utxo_commitment,
+        chain_work: Default::default(),
+    })
+}
+
+fn to_proto_transaction(transaction: &Transaction) -> proto::Transaction {
+    proto::Transaction {
+        version: transaction.version,
+        id: transaction.id.clone(),
+        tx_ins: transaction.tx_ins
+            .iter()
+            .map(|tx_in| proto::TxIn {
+                tx_out_id: tx_in.tx_out_id.clone(),
+                tx_out_index: tx_in.tx_out_index as u64,
+                signature: tx_in.signature.clone(),
+            })
+            .collect(),
+        tx_outs: transaction.tx_outs
+            .iter()
+            .map(|tx_out| proto::TxOut {
+                address: tx_out.address.clone(),
+                amount: tx_out.amount as u64,
+                script: tx_out.script.as_ref().map(to_proto_script),
+                asset: tx_out.asset.clone(),
+            })
+            .collect(),
+        extra_data: transaction.extra_data.clone(),
+    }
+}
+
+fn from_proto_transaction(transaction: proto::Transaction) -> Result<Transaction, AppError> {
+    let tx_outs = transaction.tx_outs
+        .into_iter()
+        .map(|tx_out| {
+            Ok(TxOut {
+                address: tx_out.address,
+                amount: tx_out.amount as usize,
+                script: tx_out.script.map(from_proto_script).transpose()?,
+                asset: tx_out.asset,
+            })
+        })
+        .collect::<Result<Vec<TxOut>, AppError>>()?;
+
+    Ok(Transaction {
+        version: transaction.version,
+        id: transaction.id,
+        tx_ins: transaction.tx_ins
+            .into_iter()
+            .map(|tx_in| TxIn::new(tx_in.tx_out_id, tx_in.tx_out_index as usize, tx_in.signature))
+            .collect(),
+        tx_outs,
+        extra_data: transaction.extra_data,
+    })
+}
+
+fn to_proto_unspent_tx_out(unspent_tx_out: &UnspentTxOut) -> proto::UnspentTxOut {
+    proto::UnspentTxOut {
+        tx_out_id: unspent_tx_out.tx_out_id.clone(),
+        tx_out_index: unspent_tx_out.tx_out_index as u64,
+        address: unspent_tx_out.address.clone(),
+        amount: unspent_tx_out.amount as u64,
+        script: unspent_tx_out.script.as_ref().map(to_proto_script),
+        asset: unspent_tx_out.asset.clone(),
+    }
+}
+
+fn from_proto_unspent_tx_out(unspent_tx_out: proto::UnspentTxOut) -> Result<UnspentTxOut, AppError> {
+    Ok(UnspentTxOut {
+        tx_out_id: unspent_tx_out.tx_out_id,
+        tx_out_index: unspent_tx_out.tx_out_index as usize,
+        address: unspent_tx_out.address,
+        amount: unspent_tx_out.amount as usize,
+        script: unspent_tx_out.script.map(from_proto_script).transpose()?,
+        asset: unspent_tx_out.asset,
+    })
+}
+
+fn to_proto_script(script: &Script) -> proto::Script {
+    proto::Script {
+        ops: script.0
+            .iter()
+            .map(|op| proto::Op {
+                op: Some(match op {
+                    Op::Push(data) => proto::op::Op::Push(data.clone()),
+                    Op::CheckSig => proto::op::Op::CheckSig(proto::op::CheckSig {}),
+                    Op::CheckMultisig(required, count) => proto::op::Op::CheckMultisig(proto::op::CheckMultisig {
+                        required: *required as u64,
+                        count: *count as u64,
+                    }),
+                    Op::CheckLocktime(timestamp) => proto::op::Op::CheckLocktime(*timestamp as u64),
+                    Op::Data(data) => proto::op::Op::Data(data.clone()),
+                }),
+            })
+            .collect(),
+    }
+}
+
+fn from_proto_script(script: proto::Script) -> Result<Script, AppError> {
+    let ops = script.ops
+        .into_iter()
+        .map(|op| match op.op {
+            Some(proto::op::Op::Push(data)) => Ok(Op::Push(data)),
+            Some(proto::op::Op::CheckSig(_)) => Ok(Op::CheckSig),
+            Some(proto::op::Op::CheckMultisig(multisig)) => Ok(Op::CheckMultisig(multisig.required as usize, multisig.count as usize)),
+            Some(proto::op::Op::CheckLocktime(timestamp)) => Ok(Op::CheckLocktime(timestamp as usize)),
+            Some(proto::op::Op::Data(data)) => Ok(Op::Data(data)),
+            None => Err(AppError::new(5005)),
+        })
+        .collect::<Result<Vec<Op>, AppError>>()?;
+    Ok(Script(ops))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::block::get_genesis_block;
+    use crate::testkit::{get_signed_transaction, ALICE};
+    use crate::utxo_snapshot::get_header;
+    use super::*;
+
+    #[test]
+    fn test_wire_format() {
+        assert_eq!("json".parse::<WireFormat>().unwrap(), WireFormat::Json);
+        assert_eq!("protobuf".parse::<WireFormat>().unwrap(), WireFormat::Protobuf);
+        assert_eq!("naivecoin".parse::<WireFormat>().unwrap(), WireFormat::Naivecoin);
+        assert_eq!("xml".parse::<WireFormat>().unwrap_err().code, 5003);
+    }
+
+    #[test]
+    fn test_encode_payload() {
+        let mut transaction = get_signed_transaction();
+        transaction.tx_outs.push(TxOut::with_script(
+            ALICE.address.to_string(),
+            0,
+            Script::locktime(100, Script::multisig(1, &vec![ALICE.address.to_string()])),
+        ));
+        let block = Block { pow: PowAlgorithm::Blake3, ..get_genesis_block() };
+        let snapshot = UtxoSnapshot {
+            headers: vec![Block { utxo_commitment: Some("c0ffee".to_string()), ..get_header(&get_genesis_block()) }],
+            unspent_tx_outs: vec![UnspentTxOut {
+                script: Some(Script::multisig(1, &vec![ALICE.address.to_string()])),
+                ..UnspentTxOut::new("b6c6a943".to_string(), 1, ALICE.address.to_string(), 50)
+            }],
+            blocks: vec![block.clone()],
+        };
+        let payloads = vec![
+            Payload::new(PayloadType::Blockchain, &vec![get_genesis_block(), block.clone()]),
+            Payload::new(PayloadType::Latest, &Some(block)),
+            Payload::new(PayloadType::Latest, &None::<Block>),
+            Payload::new(PayloadType::Transaction, &vec![transaction]),
+            Payload::new(PayloadType::Handshake, &Handshake::local(3, false)),
+            Payload::new(PayloadType::Inventory, &vec!["a", "b"]),
+            Payload::new(PayloadType::Pong, &Heartbeat::ping().pong()),
+            Payload::new(PayloadType::Reject, &Reject::new(PayloadType::Transaction, "b6c6a943", &AppError::new(4001))),
+            Payload { mac: Some("mac".to_string()), ..Payload::new(PayloadType::QueryAll, &()) },
+            Payload::new(PayloadType::QuerySnapshot, &()),
+            Payload::new(PayloadType::Snapshot, &snapshot),
+        ];
+        for payload in payloads {
+            assert_eq!(decode_payload(&encode_payload(&payload).unwrap()).unwrap(), payload);
+        }
+
+        assert_eq!(encode_payload(&Payload::new(PayloadType::Blockchain, &vec!["a"])).unwrap_err().code, 5004);
+        assert_eq!(decode_payload(b"invalid").unwrap_err().code, 5005);
+    }
+}